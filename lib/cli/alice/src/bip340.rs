@@ -0,0 +1,107 @@
+//! BIP-340 Schnorr signatures.
+//!
+//! The points are expected to be encoded as SEC1-compressed, and the scalars as big-endian (as is
+//! the case for secp256k1).
+
+use digest::Digest;
+use ff::PrimeField;
+use group::GroupEncoding;
+use sha2::Sha256;
+
+const TAG_AUX: &str = "BIP0340/aux";
+const TAG_NONCE: &str = "BIP0340/nonce";
+const TAG_CHALLENGE: &str = "BIP0340/challenge";
+
+const SEC1_EVEN: u8 = 0x02;
+const SEC1_ODD: u8 = 0x03;
+
+pub fn is_odd<G: GroupEncoding>(p: &G) -> bool {
+    p.to_bytes().as_ref()[0] == SEC1_ODD
+}
+
+pub fn x_only<G: GroupEncoding>(p: &G) -> Vec<u8> {
+    p.to_bytes().as_ref()[1..].to_vec()
+}
+
+/// The point with the given x-coordinate and an even y-coordinate.
+pub fn lift_x<G: GroupEncoding>(x: &[u8]) -> Option<G> {
+    let mut repr = G::Repr::default();
+    if repr.as_ref().len() != x.len() + 1 {
+        return None
+    }
+    repr.as_mut()[0] = SEC1_EVEN;
+    repr.as_mut()[1..].copy_from_slice(x);
+
+    G::from_bytes(&repr).into()
+}
+
+/// `e = int(hash_BIP0340/challenge(bytes(R) || bytes(P) || m)) mod n`
+pub fn challenge<F, G>(y: &G, r: &G, message: &[u8]) -> F
+where
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    utils::reduce_be(&tagged_hash(TAG_CHALLENGE, &[&x_only(r), &x_only(y), message]))
+}
+
+/// The instance key as per the default signing algorithm: `x` is the secret key already negated
+/// to match the even-Y public key `y`. Returns `None` if the derived key is zero.
+pub fn instance_key<F, G>(x: &F, y: &G, message: &[u8], aux_rand: &[u8]) -> Option<F>
+where
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    let t = x
+        .to_repr()
+        .as_ref()
+        .iter()
+        .zip(tagged_hash(TAG_AUX, &[aux_rand]))
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>();
+    let k = utils::reduce_be::<F>(&tagged_hash(TAG_NONCE, &[&t, &x_only(y), message]));
+
+    (!bool::from(k.is_zero())).then_some(k)
+}
+
+/// The 64-byte encoding of the signature: `bytes(R) || bytes(s)`.
+pub fn signature_bytes<F, G>(r: &G, s: &F) -> Vec<u8>
+where
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    let mut out = x_only(r);
+    out.extend_from_slice(s.to_repr().as_ref());
+    out
+}
+
+pub fn verify<F, G>(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool
+where
+    F: PrimeField,
+    G: group::Group<Scalar = F> + GroupEncoding,
+{
+    let Some(y) = lift_x::<G>(public_key) else { return false };
+    if signature.len() != 2 * public_key.len() {
+        return false
+    }
+    let (r_x, s) = signature.split_at(public_key.len());
+    let Some(r) = lift_x::<G>(r_x) else { return false };
+
+    let mut s_repr = F::Repr::default();
+    if s_repr.as_ref().len() != s.len() {
+        return false
+    }
+    s_repr.as_mut().copy_from_slice(s);
+    let Some(s) = Option::<F>::from(F::from_repr(s_repr)) else { return false };
+
+    let e = challenge::<F, G>(&y, &r, message);
+    let r_derived = G::generator() * s - y * e;
+
+    !bool::from(r_derived.is_identity()) && r_derived == r
+}
+
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new().chain_update(tag_hash).chain_update(tag_hash);
+    parts.iter().for_each(|part| hasher.update(part));
+    hasher.finalize().into()
+}
@@ -0,0 +1,113 @@
+use std::cell::Cell;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::transcript::{Input, Transcript};
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use structopt::StructOpt;
+
+use crate::{bip340, ed25519, transcript, AnyError};
+
+#[derive(Debug, StructOpt)]
+pub struct ChallengeOpts {
+    /// Sign a plain `message` with the challenge computed as per RFC 8032 (Ed25519 only).
+    #[structopt(long, conflicts_with = "bip340")]
+    pub rfc8032: bool,
+
+    /// Sign a plain `message` as per BIP-340 (secp256k1 only).
+    #[structopt(long)]
+    pub bip340: bool,
+}
+
+/// The way the Schnorr-challenge is produced.
+pub enum Challenge {
+    Transcript(Transcript),
+    Rfc8032(Vec<u8>),
+    Bip340(Vec<u8>),
+}
+
+impl Challenge {
+    pub fn new(
+        opts: &ChallengeOpts,
+        curve: CurveSelect,
+        transcript: Option<Transcript>,
+        message: Option<Vec<Input>>,
+    ) -> Result<Self, AnyError> {
+        let (name, required_curve) = match (opts.rfc8032, opts.bip340) {
+            (false, false) =>
+                return match (transcript, message) {
+                    (Some(transcript), None) => Ok(Self::Transcript(transcript)),
+                    _ => Err("the input should contain a `transcript` (and no `message`)".into()),
+                },
+            (true, _) => ("RFC 8032", CurveSelect::Ed25519),
+            (false, true) => ("BIP-340", CurveSelect::Secp256k1),
+        };
+        if curve != required_curve {
+            return Err(
+                format!("{} signatures are not supported for the curve: {}", name, curve).into()
+            )
+        }
+        let (None, Some(message)) = (transcript, message) else {
+            return Err("the input should contain a `message` (and no `transcript`)".into())
+        };
+        let message = transcript::produce_message(&message)?;
+
+        Ok(if opts.rfc8032 { Self::Rfc8032(message) } else { Self::Bip340(message) })
+    }
+
+    pub fn produce<F, G>(&self, y: &G, r: &G) -> Result<F, AnyError>
+    where
+        F: PrimeField,
+        G: Group<Scalar = F> + GroupEncoding,
+    {
+        match self {
+            Self::Transcript(t) => transcript::produce_challenge(t, y, r),
+            Self::Rfc8032(message) => Ok(ed25519::challenge(y, r, message)),
+            Self::Bip340(message) => Ok(bip340::challenge(y, r, message)),
+        }
+    }
+
+    /// Same as [`Self::produce`], for the callers that can't fail (e.g. the callbacks of
+    /// `frost_tss`): the challenge is zero on failure, and the (first) error is kept in `error`.
+    pub fn produce_or_keep<F, G>(&self, y: &G, r: &G, error: &Cell<Option<AnyError>>) -> F
+    where
+        F: PrimeField,
+        G: Group<Scalar = F> + GroupEncoding,
+    {
+        self.produce(y, r).unwrap_or_else(|reason| {
+            let first = error.take().unwrap_or(reason);
+            error.set(Some(first));
+            F::ZERO
+        })
+    }
+
+    /// Whether the point should be negated before use (BIP-340 requires even-Y points).
+    pub fn is_odd<G: GroupEncoding>(&self, p: &G) -> bool {
+        match self {
+            Self::Bip340(_) => bip340::is_odd(p),
+            Self::Transcript(_) | Self::Rfc8032(_) => false,
+        }
+    }
+
+    /// The standard encoding of the public key, if there is one for this kind of challenge.
+    pub fn public_key_bytes<G: GroupEncoding>(&self, y: &G) -> Option<Vec<u8>> {
+        match self {
+            Self::Transcript(_) => None,
+            Self::Rfc8032(_) => Some(y.to_bytes().as_ref().to_vec()),
+            Self::Bip340(_) => Some(bip340::x_only(y)),
+        }
+    }
+
+    /// The standard encoding of the signature, if there is one for this kind of challenge.
+    pub fn signature_bytes<F, G>(&self, r: &G, s: &F) -> Option<Vec<u8>>
+    where
+        F: PrimeField,
+        G: GroupEncoding,
+    {
+        match self {
+            Self::Transcript(_) => None,
+            Self::Rfc8032(_) => Some(ed25519::signature_bytes(r, s)),
+            Self::Bip340(_) => Some(bip340::signature_bytes(r, s)),
+        }
+    }
+}
@@ -12,14 +12,25 @@ mod dkg;
 mod keys;
 mod s4;
 mod sign;
+mod storage;
 mod tss;
 mod verify;
 
 #[derive(Debug, StructOpt)]
 pub struct Cli {
+    /// The storage: a path, or a URI (`sled://PATH`, `dir://PATH`, `mem://NAME`).
     #[structopt(long, short, env = "ALICE_STORAGE")]
     storage_path: Option<PathBuf>,
 
+    /// The backend of a storage given by its path [default: sled].
+    #[structopt(long, env = "ALICE_STORAGE_BACKEND", possible_values = &["sled", "dir", "mem"])]
+    storage_backend: Option<String>,
+
+    /// The file holding the passphrase of an encrypted storage [otherwise: $ALICE_PASSPHRASE,
+    /// or asked for].
+    #[structopt(long, env = "ALICE_PASSPHRASE_FILE")]
+    passphrase_file: Option<PathBuf>,
+
     #[structopt(subcommand)]
     cmd: Sub,
 }
@@ -30,6 +41,7 @@ enum Sub {
     Keys(keys::CmdKeys),
     S4(s4::CmdS4),
     Sign(sign::CmdSign),
+    Storage(storage::CmdStorage),
     Tss(tss::CmdTss),
     Verify(verify::CmdVerify),
 }
@@ -50,19 +62,45 @@ where
     R: RngCore,
     I: IO,
 {
-    let open_storage = || Storage::open(cli.storage_path()?.to_str().ok_or("invalid path")?);
+    let open_storage_as_is = || {
+        let passphrase = || storage::read_passphrase(cli.passphrase_file.as_deref(), false);
+        Storage::open_with(&cli.storage_location()?, passphrase)
+    };
+    let open_storage = || {
+        let storage = open_storage_as_is()?;
+        storage::ensure_migrated(&storage)?;
+        Ok::<_, AnyError>(storage)
+    };
 
     match &cli.cmd {
         Sub::Dkg(sub) => dkg::run(sub, rng, io, open_storage()?),
         Sub::Keys(sub) => keys::run(sub, rng, io, open_storage()?),
         Sub::S4(sub) => s4::run(sub, rng, io, open_storage()?),
         Sub::Sign(sub) => sign::run(sub, rng, io, open_storage()?),
+        Sub::Storage(sub) =>
+            storage::run(sub, io, open_storage_as_is()?, cli.passphrase_file.as_deref()),
         Sub::Tss(sub) => tss::run(sub, rng, io, open_storage()?),
         Sub::Verify(sub) => verify::run(sub, io),
     }
 }
 
 impl Cli {
+    /// The storage-path, prefixed with the scheme of the storage-backend (unless it has one).
+    fn storage_location(&self) -> Result<String, AnyError> {
+        let path = self.storage_path()?;
+        let path = path.to_str().ok_or("invalid path")?;
+        match (path.split_once("://"), self.storage_backend.as_deref()) {
+            (_, None) => Ok(path.to_owned()),
+            (None, Some(backend)) => Ok(format!("{}://{}", backend, path)),
+            (Some((scheme, _)), Some(backend)) if scheme == backend => Ok(path.to_owned()),
+            (Some((scheme, _)), Some(backend)) => Err(format!(
+                "The storage {:?} is not of the storage-backend {:?} (but {:?})",
+                path, backend, scheme
+            )
+            .into()),
+        }
+    }
+
     fn storage_path(&self) -> Result<PathBuf, AnyError> {
         if let Some(path) = self.storage_path.as_ref() {
             Ok(path.to_owned())
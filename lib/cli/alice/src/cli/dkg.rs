@@ -7,6 +7,7 @@ use crate::caps::IO;
 use crate::{AnyError, RetCode};
 
 mod csi_rashi;
+mod gjkr;
 
 #[derive(Debug, StructOpt)]
 pub struct CmdDkg {
@@ -17,6 +18,7 @@ pub struct CmdDkg {
 #[derive(Debug, StructOpt)]
 enum Cmd {
     CsiRashi(csi_rashi::CmdCsiRashi),
+    Gjkr(gjkr::CmdGjkr),
 }
 
 pub fn run(
@@ -27,5 +29,6 @@ pub fn run(
 ) -> Result<RetCode, AnyError> {
     match &dkg.cmd {
         Cmd::CsiRashi(sub) => csi_rashi::run(sub, rng, io, storage),
+        Cmd::Gjkr(sub) => gjkr::run(sub, rng, io, storage),
     }
 }
@@ -2,21 +2,20 @@ use std::collections::HashMap;
 use std::io::Write;
 
 use common_interop::curve_select::CurveSelect;
-use common_interop::types::{Point, Scalar};
+use common_interop::types::{Point, Scalar, SecretScalar};
+use digest::Digest;
 use ff::PrimeField;
 use group::{Group, GroupEncoding};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
-use cli_storage::{Storage, Table};
+use cli_storage::{Record, Storage, Table};
 
 use crate::caps::IO;
-use crate::data::{Key, S4Share};
+use crate::data::{Key, S4Share, Threshold};
 use crate::{AnyError, RetCode};
 
-const MAX_THRESHOLD: usize = 32;
-
 #[derive(Debug, StructOpt)]
 pub struct CmdCsiRashi {
     #[structopt(subcommand)]
@@ -27,7 +26,12 @@ pub struct CmdCsiRashi {
 enum Cmd {
     Reset(CmdReset),
     Deal(CmdDeal),
-    Aggregate(CmdAggregate),
+    /// Verify the deals received: outputs the `x` of the dealers whose deals are invalid.
+    Complain(CmdSession),
+    /// Reveal the deals that were complained about.
+    Respond(CmdSession),
+    /// Produce the key-share: the dealers that failed to respond to the complaints are excluded.
+    Aggregate(CmdSession),
 }
 
 #[derive(Debug, StructOpt)]
@@ -46,7 +50,7 @@ struct CmdDeal {
 }
 
 #[derive(Debug, StructOpt)]
-struct CmdAggregate {
+struct CmdSession {
     #[structopt(name = "KEY-ID")]
     key_id: String,
 }
@@ -54,12 +58,39 @@ struct CmdAggregate {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Session {
     curve: CurveSelect,
-    threshold: usize,
+    /// The identifier of the DKG session agreed upon by the parties: the proofs are bound to it.
+    #[serde(default)]
+    session_id: String,
+    threshold: Threshold,
     s4_xs: Vec<Scalar>,
     this: usize,
 
-    s4_y: Scalar,
+    s4_y: SecretScalar,
     commitment: Vec<Point>,
+    proof: Proof,
+
+    /// The deals issued to the other parties, kept to respond to the complaints.
+    #[serde(default)]
+    deals: HashMap<Scalar, SecretScalar>,
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "dkg/csi-rashi/sessions";
+    const VERSION: u32 = 1;
+}
+
+/// The proof of the knowledge of the dealer's secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Proof {
+    s: Scalar,
+    r: Point,
+}
+
+/// The commitments and the deals received from the other dealers, keyed by their `x`.
+#[derive(Debug, Deserialize)]
+struct Received {
+    commitments: HashMap<Scalar, Vec<Point>>,
+    deals: HashMap<Scalar, SecretScalar>,
 }
 
 pub fn run(
@@ -76,6 +107,10 @@ pub fn run(
                     (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
                     (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
                 ]).ok_or("Unsupported curve")?,
+            Cmd::Complain(sub) =>
+                run_complain(io, storage, sub),
+            Cmd::Respond(sub) =>
+                run_respond(io, storage, sub),
             Cmd::Aggregate(sub) =>
                 run_aggregate(io, storage, sub),
         }
@@ -116,6 +151,7 @@ fn run_deal<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
 
     #[derive(Debug, Deserialize)]
     struct Input {
+        session_id: String,
         threshold: usize,
         this: usize,
         shamir_xs: Vec<Scalar>,
@@ -123,65 +159,132 @@ fn run_deal<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
     #[derive(Serialize)]
     struct Output {
         commitment: Vec<Point>,
-        deals: HashMap<Scalar, Scalar>,
+        proof: Proof,
+        deals: HashMap<Scalar, SecretScalar>,
     }
 
     let input: Input = serde_yaml::from_reader(io.stdin())?;
 
-    let threshold = input.threshold;
+    let threshold = Threshold { t: input.threshold, n: input.shamir_xs.len() };
+    if !threshold.is_valid() {
+        writeln!(io.stderr(), "Invalid threshold: {}", threshold)?;
+        return Ok(1)
+    }
 
     let s4_xs = input
         .shamir_xs
         .iter()
         .map(|x| x.restore::<F>(curve))
         .collect::<Result<Vec<_>, _>>()?;
+    // a repeated `x` would be dealt twice, and counted twice in `n`
+    if (1..s4_xs.len()).any(|j| s4_xs[..j].contains(&s4_xs[j])) {
+        writeln!(io.stderr(), "`shamir_xs` should be distinct")?;
+        return Ok(1)
+    }
 
     let mut s4_ys = vec![F::ZERO; s4_xs.len()];
 
     let secret = F::random(&mut rng);
-    let commitment = csi_rashi_dkg::deal::<F, G, MAX_THRESHOLD>(
+    let this_s4_x = s4_xs.get(input.this).ok_or("`this` is out of range")?;
+    let (commitment, (s, r)) = csi_rashi_dkg::deal_vec::<F, G>(
         &mut rng,
-        threshold,
+        threshold.t,
         &secret,
         s4_xs.as_ref(),
         s4_ys.as_mut(),
+        |y, r| proof_challenge(&input.session_id, threshold.t, &s4_xs, this_s4_x, y, r),
     )?;
+    let proof = Proof { s: Scalar::from_value(curve, s), r: Point::from_value(curve, r) };
 
-    let commitment = commitment
+    let commitment =
+        commitment.into_iter().map(|p| Point::from_value(curve, p)).collect::<Vec<_>>();
+    let s4_xs = s4_xs.into_iter().map(|s| Scalar::from_value(curve, s)).collect::<Vec<_>>();
+    let mut s4_ys = s4_ys
         .into_iter()
-        .map(|p| Point::from_value(curve, p))
-        .take(threshold)
+        .map(|s| SecretScalar::from_value(curve, s))
         .collect::<Vec<_>>();
-    let mut s4_xs = s4_xs.into_iter().map(|s| Scalar::from_value(curve, s)).collect::<Vec<_>>();
-    let mut s4_ys = s4_ys.into_iter().map(|s| Scalar::from_value(curve, s)).collect::<Vec<_>>();
 
     let s4_y = s4_ys.remove(input.this);
-    assert!(tab_sessions
-        .insert(
-            key_id,
-            &Session {
-                curve,
-                threshold,
-                s4_xs: s4_xs.clone(),
-                this: input.this,
-                s4_y,
-                commitment: commitment.clone()
-            }
-        )?
-        .is_none());
-    let _s4_x = s4_xs.remove(input.this);
+    let mut other_s4_xs = s4_xs.clone();
+    let _s4_x = other_s4_xs.remove(input.this);
+    let deals = other_s4_xs.into_iter().zip(s4_ys).collect::<HashMap<_, _>>();
+    if !tab_sessions.insert_new(
+        key_id,
+        &Session {
+            curve,
+            session_id: input.session_id.clone(),
+            threshold,
+            s4_xs,
+            this: input.this,
+            s4_y,
+            commitment: commitment.clone(),
+            proof: proof.clone(),
+            deals: deals.clone(),
+        },
+    )? {
+        writeln!(io.stderr(), "The session is already dealt: {}", key_id)?;
+        return Ok(1)
+    }
 
-    let output = Output { commitment, deals: s4_xs.into_iter().zip(s4_ys).collect() };
+    let output = Output { commitment, proof, deals };
 
     serde_yaml::to_writer(io.stdout(), &output)?;
 
     Ok(0)
 }
 
+fn run_complain(io: impl IO, storage: Storage, complain: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = complain.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+
+    specialize_call!(run_complain_typed, (io, &session), session.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+                ]).ok_or("Unsupported curve")?
+}
+
+fn run_respond(io: impl IO, storage: Storage, respond: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = respond.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        complaints: HashMap<Scalar, Vec<Scalar>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let this_x = &session.s4_xs[session.this];
+
+    let mut responses = HashMap::new();
+    for (accuser, dealers) in input.complaints {
+        if !dealers.contains(this_x) {
+            continue
+        }
+        let Some(deal) = session.deals.get(&accuser) else {
+            writeln!(io.stderr(), "No deal was issued to: {}", accuser)?;
+            return Ok(1)
+        };
+        responses.insert(accuser, deal.clone());
+    }
+
+    serde_yaml::to_writer(io.stdout(), &responses)?;
+
+    Ok(0)
+}
+
 fn run_aggregate(
     io: impl IO,
     storage: Storage,
-    aggregate: &CmdAggregate,
+    aggregate: &CmdSession,
 ) -> Result<RetCode, AnyError> {
     let key_id = aggregate.key_id.as_str();
     let tab_sessions = sessions_table(&storage)?;
@@ -197,10 +300,42 @@ fn run_aggregate(
                 ]).ok_or("Unsupported curve")?
 }
 
+fn run_complain_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    io: impl IO,
+    session: &Session,
+) -> Result<RetCode, AnyError> {
+    #[derive(Serialize)]
+    struct Output {
+        complaints: Vec<Scalar>,
+    }
+
+    let input: Received = serde_yaml::from_reader(io.stdin())?;
+    let (dealers, vss_commitments, shamir_ys, malformed) = received::<F, G>(session, &input)?;
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(session.curve)?;
+    let mut complaints = vec![false; dealers.len()];
+    csi_rashi_dkg::complain::<F, G>(
+        vss_commitments.as_ref(),
+        &own_s4_x,
+        shamir_ys.as_ref(),
+        &mut complaints,
+    );
+
+    let complaints = dealers
+        .into_iter()
+        .zip(complaints.into_iter().zip(malformed))
+        .filter_map(|(dealer, (complaint, malformed))| (complaint || malformed).then_some(dealer))
+        .collect();
+
+    serde_yaml::to_writer(io.stdout(), &Output { complaints })?;
+
+    Ok(0)
+}
+
 fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
     io: impl IO,
     storage: Storage,
-    aggregate: &CmdAggregate,
+    aggregate: &CmdSession,
     session: &Session,
 ) -> Result<RetCode, AnyError> {
     let key_id = aggregate.key_id.as_str();
@@ -217,13 +352,160 @@ fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
 
     #[derive(Debug, Deserialize)]
     struct Input {
-        commitments: HashMap<Scalar, Vec<Point>>,
-        deals: HashMap<Scalar, Scalar>,
+        #[serde(flatten)]
+        received: Received,
+
+        /// The proofs of the other dealers, keyed by their `x`.
+        proofs: HashMap<Scalar, Proof>,
+
+        /// The complaints published by the parties: the `x` of the accused dealers, keyed by the
+        /// `x` of the accuser.
+        #[serde(default)]
+        complaints: HashMap<Scalar, Vec<Scalar>>,
+
+        /// The responses published by the accused dealers: the revealed deals keyed by the `x` of
+        /// the accuser, keyed by the `x` of the dealer.
+        #[serde(default)]
+        responses: HashMap<Scalar, HashMap<Scalar, Scalar>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        disqualified: Vec<Scalar>,
     }
 
     let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let (dealers, vss_commitments, mut shamir_ys, malformed) =
+        received::<F, G>(session, &input.received)?;
+
+    if let Some(accuser) = input.complaints.keys().find(|x| !session.s4_xs.contains(x)) {
+        return Err(format!("complaint from an unknown party: {}", accuser).into())
+    }
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(curve)?;
+    let s4_xs = session
+        .s4_xs
+        .iter()
+        .map(|x| x.restore::<F>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dealer_xs = dealers.iter().map(|x| x.restore::<F>(curve)).collect::<Result<Vec<_>, _>>()?;
+    let produce_challenge = |dealer: usize, y: &G, r: &G| {
+        proof_challenge(&session.session_id, threshold.t, &s4_xs, &dealer_xs[dealer], y, r)
+    };
+
+    let proofs = dealers
+        .iter()
+        .map(|dealer| {
+            let proof = if dealer == &session.s4_xs[session.this] {
+                &session.proof
+            } else {
+                input.proofs.get(dealer).ok_or(format!("missing proof (from {:?})", dealer))?
+            };
+            Ok((proof.s.restore::<F>(curve)?, proof.r.restore::<G>(curve)?))
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?;
+
+    // the commitments, the proofs, the complaints and the responses are public: every party
+    // disqualifies the same dealers
+    let mut disqualified = (0..dealers.len())
+        .map(|dealer| {
+            malformed[dealer] ||
+                !csi_rashi_dkg::verify_proof(
+                    &vss_commitments[dealer],
+                    &proofs[dealer],
+                    |y, r| produce_challenge(dealer, y, r),
+                )
+        })
+        .collect::<Vec<_>>();
+    for (((dealer, vss_commitment), shamir_y), disqualified) in
+        dealers.iter().zip(&vss_commitments).zip(&mut shamir_ys).zip(&mut disqualified)
+    {
+        let responses = input.responses.get(dealer);
+        for (accuser, _) in input.complaints.iter().filter(|(_, accused)| accused.contains(dealer))
+        {
+            let Some(response) = responses.and_then(|r| r.get(accuser)) else {
+                *disqualified = true;
+                break
+            };
+            let revealed = response.restore::<F>(curve)?;
+            if !csi_rashi_dkg::verify_response(
+                vss_commitment,
+                &accuser.restore::<F>(curve)?,
+                &revealed,
+            ) {
+                *disqualified = true;
+                break
+            }
+            if accuser == &session.s4_xs[session.this] {
+                *shamir_y = revealed;
+            }
+        }
+    }
+
+    let (s4_y, public_key) = match csi_rashi_dkg::aggregate::<F, G>(
+        vss_commitments.as_ref(),
+        &proofs,
+        &own_s4_x,
+        shamir_ys.as_ref(),
+        &disqualified,
+        produce_challenge,
+    ) {
+        Ok(out) => out,
+        Err(csi_rashi_dkg::Error::InvalidShare(dealer)) => {
+            writeln!(
+                io.stderr(),
+                "Invalid deal (should be complained about): {}",
+                dealers[dealer]
+            )?;
+            return Ok(1)
+        },
+        Err(reason) => return Err(reason.into()),
+    };
+
+    let public_key = Point::from_value(curve, public_key);
+
+    let key = Key::S4Share(S4Share {
+        curve,
+        threshold,
+        public_key,
+        x: Scalar::from_value(curve, own_s4_x),
+        y: SecretScalar::from_value(curve, s4_y),
+    });
+    // the key-share replaces the session: both or neither
+    let stored = storage.transaction(&[&tab_keys, &tab_sessions], |tx| {
+        if !tx.table(&tab_keys)?.insert_new(key_id, &key)? {
+            return Ok(Err("Key already exists"))
+        }
+        if tx.table(&tab_sessions)?.remove(key_id)?.is_none() {
+            return Ok(Err("No such session"))
+        }
+        Ok(Ok(()))
+    })?;
+    if let Err(reason) = stored {
+        writeln!(io.stderr(), "{}: {}", reason, key_id)?;
+        return Ok(1)
+    }
+
+    let disqualified = dealers
+        .into_iter()
+        .zip(disqualified)
+        .filter_map(|(dealer, disqualified)| disqualified.then_some(dealer))
+        .collect();
+    serde_yaml::to_writer(io.stdout(), &Output { disqualified })?;
 
-    let mut complaints = vec![false; session.s4_xs.len()];
+    Ok(0)
+}
+
+/// The `x`, the commitments and the deals of all the dealers, this party being the first one,
+/// along with whether the commitment of the dealer is malformed (not of `threshold.t` points):
+/// such a dealer is to be disqualified.
+#[allow(clippy::type_complexity)]
+fn received<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    session: &Session,
+    received: &Received,
+) -> Result<(Vec<Scalar>, Vec<Vec<G>>, Vec<F>, Vec<bool>), AnyError> {
+    let curve = session.curve;
+
+    let mut dealers = vec![session.s4_xs[session.this].clone()];
     let mut vss_commitments = vec![];
     let mut shamir_ys = vec![];
 
@@ -235,18 +517,21 @@ fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
     let own_s4_y = session.s4_y.restore::<F>(curve)?;
     vss_commitments.push(own_commitment);
     shamir_ys.push(own_s4_y);
+    let mut malformed = vec![false];
 
     for s4_x in session.s4_xs.iter() {
         if s4_x == &session.s4_xs[session.this] {
             continue
         }
 
-        let vss_commitment = input
+        let vss_commitment = received
             .commitments
             .get(s4_x)
             .ok_or(format!("missing commitment (from {:?})", s4_x))?;
-        let deal = input.deals.get(s4_x).ok_or(format!("missing deal (from {:?})", s4_x))?;
+        let deal = received.deals.get(s4_x).ok_or(format!("missing deal (from {:?})", s4_x))?;
 
+        dealers.push(s4_x.clone());
+        malformed.push(vss_commitment.len() != session.threshold.t);
         vss_commitments.push(
             vss_commitment
                 .iter()
@@ -256,32 +541,30 @@ fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
         shamir_ys.push(deal.restore::<F>(curve)?);
     }
 
-    let own_s4_x = session.s4_xs[session.this].restore::<F>(curve)?;
-
-    let (s4_y, public_key) = csi_rashi_dkg::aggregate::<F, G>(
-        vss_commitments.as_ref(),
-        &own_s4_x,
-        shamir_ys.as_ref(),
-        &mut complaints,
-    )?;
-
-    let public_key = Point::from_value(curve, public_key);
-
-    assert!(tab_keys
-        .insert(
-            key_id,
-            &Key::S4Share(S4Share {
-                curve,
-                threshold,
-                public_key,
-                x: Scalar::from_value(curve, own_s4_x),
-                y: Scalar::from_value(curve, s4_y),
-            })
-        )?
-        .is_none());
-    assert!(tab_sessions.remove(key_id)?.is_some());
+    Ok((dealers, vss_commitments, shamir_ys, malformed))
+}
 
-    Ok(0)
+/// The challenge of the proof of the knowledge of the secret of the dealer `dealer_x`: bound to
+/// the session (its identifier, the threshold and the `x` of all the parties) and to the dealer.
+fn proof_challenge<F: PrimeField, G: GroupEncoding>(
+    session_id: &str,
+    threshold: usize,
+    s4_xs: &[F],
+    dealer_x: &F,
+    y: &G,
+    r: &G,
+) -> F {
+    let mut hasher = sha3::Sha3_256::new()
+        .chain_update(b"csi-rashi-dkg/proof")
+        .chain_update((session_id.len() as u64).to_be_bytes())
+        .chain_update(session_id.as_bytes())
+        .chain_update((threshold as u64).to_be_bytes());
+    s4_xs.iter().for_each(|x| hasher.update(x.to_repr()));
+    let digest = hasher
+        .chain_update(dealer_x.to_repr())
+        .chain_update(y.to_bytes())
+        .chain_update(r.to_bytes());
+    utils::bytes_to_scalar(digest.finalize().as_ref())
 }
 
 fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
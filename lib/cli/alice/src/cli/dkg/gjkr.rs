@@ -0,0 +1,802 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+use feldman_vsss::CommitmentVerifyShare;
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use pedersen_vsss::second_generator::{Ed25519, Ristretto255, SecondGenerator, Secp256k1};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+
+use crate::caps::IO;
+use crate::data::{Key, S4Share, Threshold};
+use crate::{AnyError, RetCode};
+
+#[derive(Debug, StructOpt)]
+pub struct CmdGjkr {
+    #[structopt(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    Reset(CmdReset),
+    Deal(CmdDeal),
+    /// Verify the deals received: outputs the `x` of the dealers whose deals are invalid.
+    Complain(CmdSession),
+    /// Reveal the deals that were complained about.
+    Respond(CmdSession),
+    /// Fix the set of the qualified dealers: outputs the Feldman commitment of this dealer.
+    Qualify(CmdSession),
+    /// Reveal the deals of the qualified dealers whose Feldman commitments are invalid.
+    Reveal(CmdSession),
+    /// Produce the key-share and the public key from the Feldman commitments of the qualified
+    /// dealers.
+    Aggregate(CmdSession),
+}
+
+#[derive(Debug, StructOpt)]
+struct CmdReset {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct CmdDeal {
+    #[structopt(long, short, env = "ALICE_CURVE")]
+    curve: CurveSelect,
+
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct CmdSession {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    curve: CurveSelect,
+    threshold: Threshold,
+    s4_xs: Vec<Scalar>,
+    this: usize,
+
+    s4_y: Deal,
+    commitment: Vec<Point>,
+
+    /// The Feldman commitment of this dealer, withheld until the qualified dealers are fixed.
+    feldman_commitment: Vec<Point>,
+
+    /// The deals issued to the other parties, kept to respond to the complaints.
+    deals: HashMap<Scalar, Deal>,
+
+    #[serde(default)]
+    qualified: Option<Qualified>,
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "dkg/gjkr/sessions";
+    const VERSION: u32 = 1;
+}
+
+/// The qualified dealers, along with their Pedersen commitments and their deals to this party.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Qualified {
+    dealers: Vec<Scalar>,
+    commitments: HashMap<Scalar, Vec<Point>>,
+    deals: HashMap<Scalar, Deal>,
+}
+
+/// The share of the secret polynomial, and the share of the blinding one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Deal {
+    y: SecretScalar,
+    y_blind: SecretScalar,
+}
+
+/// The Pedersen commitments and the deals received from the other dealers, keyed by their `x`.
+#[derive(Debug, Deserialize)]
+struct Received {
+    commitments: HashMap<Scalar, Vec<Point>>,
+    deals: HashMap<Scalar, Deal>,
+}
+
+pub fn run(
+    gjkr: &CmdGjkr,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match &gjkr.cmd {
+            Cmd::Reset(sub) => run_reset(io, storage, sub),
+            Cmd::Deal(sub) =>
+                specialize_call!(run_deal, (rng, io, storage, sub), sub.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint, Secp256k1),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint, Ed25519),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint, Ristretto255),
+                ]).ok_or("Unsupported curve")?,
+            Cmd::Complain(sub) =>
+                run_complain(io, storage, sub),
+            Cmd::Respond(sub) =>
+                run_respond(io, storage, sub),
+            Cmd::Qualify(sub) =>
+                run_qualify(io, storage, sub),
+            Cmd::Reveal(sub) =>
+                run_reveal(io, storage, sub),
+            Cmd::Aggregate(sub) =>
+                run_aggregate(io, storage, sub),
+        }
+}
+
+fn run_reset(io: impl IO, storage: Storage, reset: &CmdReset) -> Result<RetCode, AnyError> {
+    let table = sessions_table(&storage)?;
+
+    if table.remove(&reset.key_id)?.is_some() {
+        writeln!(io.stderr(), "Session reset: {}", reset.key_id)?;
+        Ok(0)
+    } else {
+        writeln!(io.stderr(), "No such session: {}", reset.key_id)?;
+        Ok(1)
+    }
+}
+
+fn run_deal<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: SecondGenerator<Group = G>>(
+    mut rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+    deal: &CmdDeal,
+) -> Result<RetCode, AnyError> {
+    let key_id = deal.key_id.as_str();
+    let curve = deal.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let tab_keys = keys_table(&storage)?;
+
+    if tab_sessions.get(key_id)?.is_some() {
+        writeln!(io.stderr(), "The session is already dealt: {}", key_id)?;
+        return Ok(1)
+    }
+    if tab_keys.get(key_id)?.is_some() {
+        writeln!(io.stderr(), "Key already exists: {}", key_id)?;
+        return Ok(1)
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        threshold: usize,
+        this: usize,
+        shamir_xs: Vec<Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        commitment: Vec<Point>,
+        deals: HashMap<Scalar, Deal>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let threshold = Threshold { t: input.threshold, n: input.shamir_xs.len() };
+    if !threshold.is_valid() {
+        writeln!(io.stderr(), "Invalid threshold: {}", threshold)?;
+        return Ok(1)
+    }
+
+    let s4_xs = input
+        .shamir_xs
+        .iter()
+        .map(|x| x.restore::<F>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    // a repeated `x` would be dealt twice, and counted twice in `n`
+    if (1..s4_xs.len()).any(|j| s4_xs[..j].contains(&s4_xs[j])) {
+        writeln!(io.stderr(), "`shamir_xs` should be distinct")?;
+        return Ok(1)
+    }
+    if input.this >= s4_xs.len() {
+        return Err("`this` is out of range".into())
+    }
+
+    let mut s4_ys = vec![(F::ZERO, F::ZERO); s4_xs.len()];
+
+    let secret = F::random(&mut rng);
+    let (commitment, feldman_commitment) = pedersen_dkg::deal_vec(
+        &mut rng,
+        threshold.t,
+        &secret,
+        &H::second_generator(),
+        s4_xs.as_ref(),
+        s4_ys.as_mut(),
+    )?;
+
+    let commitment =
+        commitment.into_iter().map(|p| Point::from_value(curve, p)).collect::<Vec<_>>();
+    let feldman_commitment = feldman_commitment
+        .into_iter()
+        .map(|p| Point::from_value(curve, p))
+        .collect::<Vec<_>>();
+    let s4_xs = s4_xs.into_iter().map(|s| Scalar::from_value(curve, s)).collect::<Vec<_>>();
+    let mut s4_ys = s4_ys.into_iter().map(|y| Deal::from_value(curve, y)).collect::<Vec<_>>();
+
+    let s4_y = s4_ys.remove(input.this);
+    let mut other_s4_xs = s4_xs.clone();
+    let _s4_x = other_s4_xs.remove(input.this);
+    let deals = other_s4_xs.into_iter().zip(s4_ys).collect::<HashMap<_, _>>();
+    if !tab_sessions.insert_new(
+        key_id,
+        &Session {
+            curve,
+            threshold,
+            s4_xs,
+            this: input.this,
+            s4_y,
+            commitment: commitment.clone(),
+            feldman_commitment,
+            deals: deals.clone(),
+            qualified: None,
+        },
+    )? {
+        writeln!(io.stderr(), "The session is already dealt: {}", key_id)?;
+        return Ok(1)
+    }
+
+    let output = Output { commitment, deals };
+
+    serde_yaml::to_writer(io.stdout(), &output)?;
+
+    Ok(0)
+}
+
+fn run_complain(io: impl IO, storage: Storage, complain: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = complain.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+
+    specialize_call!(run_complain_typed, (io, &session), session.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint, Secp256k1),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint, Ed25519),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint, Ristretto255),
+                ]).ok_or("Unsupported curve")?
+}
+
+fn run_respond(io: impl IO, storage: Storage, respond: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = respond.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        complaints: HashMap<Scalar, Vec<Scalar>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let this_x = &session.s4_xs[session.this];
+
+    let mut responses = HashMap::new();
+    for (accuser, dealers) in input.complaints {
+        if !dealers.contains(this_x) {
+            continue
+        }
+        let Some(deal) = session.deals.get(&accuser) else {
+            writeln!(io.stderr(), "No deal was issued to: {}", accuser)?;
+            return Ok(1)
+        };
+        responses.insert(accuser, deal.clone());
+    }
+
+    serde_yaml::to_writer(io.stdout(), &responses)?;
+
+    Ok(0)
+}
+
+fn run_qualify(io: impl IO, storage: Storage, qualify: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = qualify.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+    if session.qualified.is_some() {
+        writeln!(io.stderr(), "The session is already qualified: {}", key_id)?;
+        return Ok(1)
+    }
+
+    specialize_call!(run_qualify_typed, (io, storage, qualify, session), session.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint, Secp256k1),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint, Ed25519),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint, Ristretto255),
+                ]).ok_or("Unsupported curve")?
+}
+
+fn run_reveal(io: impl IO, storage: Storage, reveal: &CmdSession) -> Result<RetCode, AnyError> {
+    let key_id = reveal.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+    let Some(qualified) = session.qualified.as_ref() else {
+        writeln!(io.stderr(), "The session isn't qualified: {}", key_id)?;
+        return Ok(1)
+    };
+
+    specialize_call!(run_reveal_typed, (io, &session, qualified), session.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint, Secp256k1),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint, Ed25519),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint, Ristretto255),
+                ]).ok_or("Unsupported curve")?
+}
+
+fn run_aggregate(
+    io: impl IO,
+    storage: Storage,
+    aggregate: &CmdSession,
+) -> Result<RetCode, AnyError> {
+    let key_id = aggregate.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+    let Some(qualified) = session.qualified.as_ref() else {
+        writeln!(io.stderr(), "The session isn't qualified: {}", key_id)?;
+        return Ok(1)
+    };
+
+    specialize_call!(run_aggregate_typed, (io, storage, aggregate, &session, qualified), session.curve, [
+                    (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint, Secp256k1),
+                    (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint, Ed25519),
+                    (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint, Ristretto255),
+                ]).ok_or("Unsupported curve")?
+}
+
+fn run_complain_typed<
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: SecondGenerator<Group = G>,
+>(
+    io: impl IO,
+    session: &Session,
+) -> Result<RetCode, AnyError> {
+    #[derive(Serialize)]
+    struct Output {
+        complaints: Vec<Scalar>,
+    }
+
+    let input: Received = serde_yaml::from_reader(io.stdin())?;
+    let (dealers, commitments, shamir_ys) = received::<F, G>(session, &input)?;
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(session.curve)?;
+    let mut complaints = vec![false; dealers.len()];
+    pedersen_dkg::complain::<F, G>(
+        &H::second_generator(),
+        commitments.as_ref(),
+        &own_s4_x,
+        shamir_ys.as_ref(),
+        &mut complaints,
+    );
+
+    let complaints = dealers
+        .into_iter()
+        .zip(complaints)
+        .filter_map(|(dealer, complaint)| complaint.then_some(dealer))
+        .collect();
+
+    serde_yaml::to_writer(io.stdout(), &Output { complaints })?;
+
+    Ok(0)
+}
+
+fn run_qualify_typed<
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: SecondGenerator<Group = G>,
+>(
+    io: impl IO,
+    storage: Storage,
+    qualify: &CmdSession,
+    mut session: Session,
+) -> Result<RetCode, AnyError> {
+    let key_id = qualify.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+
+    let curve = session.curve;
+    let h = H::second_generator();
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        #[serde(flatten)]
+        received: Received,
+
+        /// The complaints published by the parties: the `x` of the accused dealers, keyed by the
+        /// `x` of the accuser.
+        #[serde(default)]
+        complaints: HashMap<Scalar, Vec<Scalar>>,
+
+        /// The responses published by the accused dealers: the revealed deals keyed by the `x` of
+        /// the accuser, keyed by the `x` of the dealer.
+        #[serde(default)]
+        responses: HashMap<Scalar, HashMap<Scalar, Deal>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        disqualified: Vec<Scalar>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        feldman_commitment: Option<Vec<Point>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let (dealers, commitments, mut shamir_ys) = received::<F, G>(&session, &input.received)?;
+
+    if let Some(accuser) = input.complaints.keys().find(|x| !session.s4_xs.contains(x)) {
+        return Err(format!("complaint from an unknown party: {}", accuser).into())
+    }
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(curve)?;
+
+    // the commitments, the complaints and the responses are public: every party disqualifies the
+    // same dealers
+    let mut disqualified =
+        commitments.iter().map(|c| c.len() != session.threshold.t).collect::<Vec<_>>();
+    for (((dealer, commitment), shamir_y), disqualified) in
+        dealers.iter().zip(&commitments).zip(&mut shamir_ys).zip(&mut disqualified)
+    {
+        let responses = input.responses.get(dealer);
+        for (accuser, _) in input.complaints.iter().filter(|(_, accused)| accused.contains(dealer))
+        {
+            let Some(response) = responses.and_then(|r| r.get(accuser)) else {
+                *disqualified = true;
+                break
+            };
+            let revealed = response.restore::<F>(curve)?;
+            if !pedersen_dkg::verify_share(&h, commitment, &accuser.restore::<F>(curve)?, &revealed)
+            {
+                *disqualified = true;
+                break
+            }
+            if accuser == &session.s4_xs[session.this] {
+                *shamir_y = revealed;
+            }
+        }
+    }
+
+    match pedersen_dkg::key_share::<F, G>(
+        &h,
+        commitments.as_ref(),
+        &own_s4_x,
+        shamir_ys.as_ref(),
+        &disqualified,
+    ) {
+        Ok(_) => (),
+        Err(pedersen_dkg::Error::InvalidShare(dealer)) => {
+            writeln!(
+                io.stderr(),
+                "Invalid deal (should be complained about): {}",
+                dealers[dealer]
+            )?;
+            return Ok(1)
+        },
+        Err(reason) => return Err(reason.into()),
+    };
+
+    let mut qualified =
+        Qualified { dealers: vec![], commitments: HashMap::new(), deals: HashMap::new() };
+    let mut output = Output { disqualified: vec![], feldman_commitment: None };
+    for (((dealer, commitment), shamir_y), disqualified) in
+        dealers.into_iter().zip(commitments).zip(shamir_ys).zip(disqualified)
+    {
+        if disqualified {
+            output.disqualified.push(dealer);
+            continue
+        }
+        if dealer == session.s4_xs[session.this] {
+            output.feldman_commitment = Some(session.feldman_commitment.clone());
+        }
+        qualified.commitments.insert(
+            dealer.clone(),
+            commitment.into_iter().map(|p| Point::from_value(curve, p)).collect(),
+        );
+        qualified.deals.insert(dealer.clone(), Deal::from_value(curve, shamir_y));
+        qualified.dealers.push(dealer);
+    }
+
+    session.qualified = Some(qualified);
+    tab_sessions.insert(key_id, &session)?;
+
+    serde_yaml::to_writer(io.stdout(), &output)?;
+
+    Ok(0)
+}
+
+fn run_reveal_typed<
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: SecondGenerator<Group = G>,
+>(
+    io: impl IO,
+    session: &Session,
+    qualified: &Qualified,
+) -> Result<RetCode, AnyError> {
+    let curve = session.curve;
+    let threshold = session.threshold;
+    let h = H::second_generator();
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        /// The Feldman commitments published by the dealers whose deals are to be revealed, keyed
+        /// by their `x`.
+        feldman_commitments: HashMap<Scalar, Vec<Point>>,
+
+        /// The deals already revealed by the other parties, as the proof that a Feldman
+        /// commitment is invalid: keyed by the `x` of the party, keyed by the `x` of the dealer.
+        #[serde(default)]
+        reveals: HashMap<Scalar, HashMap<Scalar, Deal>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(curve)?;
+
+    // a deal is revealed only for a Feldman commitment that fails the check of this party, or of
+    // another party that revealed its own valid deal: the deals of the honest dealers stay secret
+    let mut revealed = HashMap::new();
+    for (dealer, feldman_commitment) in input.feldman_commitments {
+        let Some(deal) = qualified.deals.get(&dealer) else {
+            writeln!(io.stderr(), "Not a qualified dealer: {}", dealer)?;
+            return Ok(1)
+        };
+        let commitment = qualified
+            .commitments
+            .get(&dealer)
+            .ok_or("corrupt session")?
+            .iter()
+            .map(|p| p.restore::<G>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+        let feldman_commitment = feldman_commitment
+            .iter()
+            .map(|p| p.restore::<G>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+        let is_invalid_for = |x: &F, y: &F| {
+            feldman_commitment.len() != threshold.t || !feldman_commitment.verify_share(x, y)
+        };
+
+        let mut is_invalid = is_invalid_for(&own_s4_x, &deal.restore::<F>(curve)?.0);
+        for (x, revealed) in input.reveals.get(&dealer).into_iter().flatten() {
+            if is_invalid {
+                break
+            }
+            if !session.s4_xs.contains(x) {
+                continue
+            }
+            let x = x.restore::<F>(curve)?;
+            let revealed = revealed.restore::<F>(curve)?;
+            is_invalid = pedersen_dkg::verify_share(&h, &commitment, &x, &revealed) &&
+                is_invalid_for(&x, &revealed.0);
+        }
+        if !is_invalid {
+            writeln!(io.stderr(), "The Feldman commitment is valid: {}", dealer)?;
+            return Ok(1)
+        }
+
+        revealed.insert(dealer, deal.clone());
+    }
+
+    serde_yaml::to_writer(io.stdout(), &revealed)?;
+
+    Ok(0)
+}
+
+fn run_aggregate_typed<
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: SecondGenerator<Group = G>,
+>(
+    io: impl IO,
+    storage: Storage,
+    aggregate: &CmdSession,
+    session: &Session,
+    qualified: &Qualified,
+) -> Result<RetCode, AnyError> {
+    let key_id = aggregate.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+    let tab_keys = keys_table(&storage)?;
+
+    let threshold = session.threshold;
+    let curve = session.curve;
+    let h = H::second_generator();
+
+    if tab_keys.get(key_id)?.is_some() {
+        writeln!(io.stderr(), "Key already exists: {}", key_id)?;
+        return Ok(1)
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        /// The Feldman commitments of the other qualified dealers, keyed by their `x`.
+        feldman_commitments: HashMap<Scalar, Vec<Point>>,
+
+        /// The deals of the dealers whose Feldman commitments are invalid, revealed by the
+        /// parties: keyed by the `x` of the party, keyed by the `x` of the dealer.
+        #[serde(default)]
+        reveals: HashMap<Scalar, HashMap<Scalar, Deal>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        public_key: Point,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let own_s4_x = session.s4_xs[session.this].restore::<F>(curve)?;
+
+    let mut feldman_commitments = vec![];
+    let mut shamir_ys = vec![];
+    for dealer in qualified.dealers.iter() {
+        let deal = qualified.deals.get(dealer).ok_or("corrupt session")?;
+        shamir_ys.push(deal.restore::<F>(curve)?);
+
+        let feldman_commitment = if let Some(reveals) = input.reveals.get(dealer) {
+            let commitment = qualified
+                .commitments
+                .get(dealer)
+                .ok_or("corrupt session")?
+                .iter()
+                .map(|p| p.restore::<G>(curve))
+                .collect::<Result<Vec<_>, _>>()?;
+            let shares = reveals
+                .iter()
+                .map(|(x, deal)| Ok((x.restore::<F>(curve)?, deal.restore::<F>(curve)?)))
+                .collect::<Result<Vec<_>, AnyError>>()?;
+            match pedersen_dkg::reconstruct_vec(&h, &commitment, threshold.t, &shares) {
+                Ok(feldman_commitment) => feldman_commitment,
+                Err(pedersen_dkg::Error::NotEnoughShares) => {
+                    writeln!(
+                        io.stderr(),
+                        "Not enough valid deals revealed to reconstruct: {}",
+                        dealer
+                    )?;
+                    return Ok(1)
+                },
+                Err(reason) => return Err(reason.into()),
+            }
+        } else {
+            let feldman_commitment = if dealer == &session.s4_xs[session.this] {
+                &session.feldman_commitment
+            } else {
+                input
+                    .feldman_commitments
+                    .get(dealer)
+                    .ok_or(format!("missing Feldman commitment (from {:?})", dealer))?
+            };
+            feldman_commitment
+                .iter()
+                .map(|p| p.restore::<G>(curve))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        feldman_commitments.push(feldman_commitment);
+    }
+
+    let (s4_y, public_key) = match pedersen_dkg::aggregate::<F, G>(
+        threshold.t,
+        feldman_commitments.as_ref(),
+        &own_s4_x,
+        shamir_ys.as_ref(),
+        &vec![false; qualified.dealers.len()],
+    ) {
+        Ok(out) => out,
+        Err(pedersen_dkg::Error::InvalidFeldmanCommitment(dealer)) => {
+            writeln!(
+                io.stderr(),
+                "Invalid Feldman commitment (the deals should be revealed): {}",
+                qualified.dealers[dealer]
+            )?;
+            return Ok(1)
+        },
+        Err(reason) => return Err(reason.into()),
+    };
+
+    let public_key = Point::from_value(curve, public_key);
+
+    let key = Key::S4Share(S4Share {
+        curve,
+        threshold,
+        public_key: public_key.clone(),
+        x: Scalar::from_value(curve, own_s4_x),
+        y: SecretScalar::from_value(curve, s4_y),
+    });
+    // the key-share replaces the session: both or neither
+    let stored = storage.transaction(&[&tab_keys, &tab_sessions], |tx| {
+        if !tx.table(&tab_keys)?.insert_new(key_id, &key)? {
+            return Ok(Err("Key already exists"))
+        }
+        if tx.table(&tab_sessions)?.remove(key_id)?.is_none() {
+            return Ok(Err("No such session"))
+        }
+        Ok(Ok(()))
+    })?;
+    if let Err(reason) = stored {
+        writeln!(io.stderr(), "{}: {}", reason, key_id)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(io.stdout(), &Output { public_key })?;
+
+    Ok(0)
+}
+
+/// The `x`, the Pedersen commitments and the deals of all the dealers, this party being the first
+/// one.
+#[allow(clippy::type_complexity)]
+fn received<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    session: &Session,
+    received: &Received,
+) -> Result<(Vec<Scalar>, Vec<Vec<G>>, Vec<(F, F)>), AnyError> {
+    let curve = session.curve;
+
+    let mut dealers = vec![session.s4_xs[session.this].clone()];
+    let mut commitments = vec![];
+    let mut shamir_ys = vec![];
+
+    let own_commitment = session
+        .commitment
+        .iter()
+        .map(|s| s.restore::<G>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    commitments.push(own_commitment);
+    shamir_ys.push(session.s4_y.restore::<F>(curve)?);
+
+    for s4_x in session.s4_xs.iter() {
+        if s4_x == &session.s4_xs[session.this] {
+            continue
+        }
+
+        let commitment = received
+            .commitments
+            .get(s4_x)
+            .ok_or(format!("missing commitment (from {:?})", s4_x))?;
+        let deal = received.deals.get(s4_x).ok_or(format!("missing deal (from {:?})", s4_x))?;
+
+        dealers.push(s4_x.clone());
+        commitments.push(
+            commitment
+                .iter()
+                .map(|p| p.restore::<G>(curve))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        shamir_ys.push(deal.restore::<F>(curve)?);
+    }
+
+    Ok((dealers, commitments, shamir_ys))
+}
+
+impl Deal {
+    fn from_value<F: PrimeField>(curve: CurveSelect, (y, y_blind): (F, F)) -> Self {
+        Self {
+            y: SecretScalar::from_value(curve, y),
+            y_blind: SecretScalar::from_value(curve, y_blind),
+        }
+    }
+
+    fn restore<F: PrimeField>(&self, curve: CurveSelect) -> Result<(F, F), AnyError> {
+        Ok((self.y.restore::<F>(curve)?, self.y_blind.restore::<F>(curve)?))
+    }
+}
+
+fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
+    Table::open(storage)
+}
+
+fn keys_table(storage: &Storage) -> Result<Table<Key>, AnyError> {
+    Table::open(storage)
+}
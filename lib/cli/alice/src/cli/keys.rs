@@ -1,7 +1,7 @@
 use std::io::Write;
 
 use common_interop::curve_select::CurveSelect;
-use common_interop::types::Scalar;
+use common_interop::types::SecretScalar;
 use ff::PrimeField;
 use rand::RngCore;
 use structopt::StructOpt;
@@ -156,6 +156,6 @@ fn keys_table(storage: &Storage) -> Result<Table<Key>, AnyError> {
     Table::open(storage)
 }
 
-fn random_scalar<F: PrimeField>(curve: CurveSelect, rng: impl RngCore) -> Scalar {
-    Scalar::from_value(curve, F::random(rng))
+fn random_scalar<F: PrimeField>(curve: CurveSelect, rng: impl RngCore) -> SecretScalar {
+    SecretScalar::from_value(curve, F::random(rng))
 }
@@ -7,29 +7,49 @@ use structopt::StructOpt;
 
 use cli_storage::{Storage, Table};
 use common_interop::curve_select::CurveSelect;
-use common_interop::types::{Point, Scalar};
+use common_interop::types::{Point, Scalar, SecretScalar};
 
 use crate::caps::IO;
-use crate::data::{Key, S4Share, S4};
+use crate::data::{FullKey, Key, S4Share, Threshold, S4};
 use crate::{AnyError, RetCode};
 
+mod refresh;
+mod repair;
+mod reshare;
+
 #[derive(Debug, StructOpt)]
 pub enum CmdS4 {
+    Combine(CmdS4Combine),
     Export(CmdS4Export),
     Gen(CmdS4Gen),
     Import(CmdS4Import),
     IssueShare(CmdS4IssueShare),
     List(CmdS4List),
+    Refresh(refresh::CmdRefresh),
+    Repair(repair::CmdRepair),
+    Reshare(reshare::CmdReshare),
     Rm(CmdS4Rm),
 }
 
+#[derive(Debug, StructOpt)]
+pub struct CmdS4Combine {
+    /// Store the secret as a full-key, rather than print it.
+    #[structopt(long, short)]
+    key_id: Option<String>,
+
+    /// The key-shares to combine: if none, the exported key-shares are read from the stdin.
+    #[structopt(name = "SHARE-KEY-ID")]
+    share_key_ids: Vec<String>,
+}
+
 #[derive(Debug, StructOpt)]
 pub struct CmdS4Gen {
     #[structopt(long, short)]
     key_id: String,
 
+    /// "T-of-N": any `T` of the `N` key-shares are needed to use the key.
     #[structopt(long, short)]
-    threshold: usize,
+    threshold: Threshold,
 
     #[structopt(name = "SCHEME-ID")]
     s4_id: String,
@@ -78,36 +98,175 @@ pub fn run(
     storage: Storage,
 ) -> Result<RetCode, AnyError> {
     match cmd {
+        CmdS4::Combine(sub) => run_combine(sub, io, storage),
         CmdS4::Export(sub) => run_export(sub, io, storage),
         CmdS4::Gen(sub) => run_gen(sub, rng, storage),
         CmdS4::Import(sub) => run_import(sub, io, storage),
-        CmdS4::IssueShare(sub) => run_issue_share(sub, storage),
+        CmdS4::IssueShare(sub) => run_issue_share(sub, io, storage),
         CmdS4::List(sub) => run_list(sub, io, storage),
+        CmdS4::Refresh(sub) => refresh::run(sub, rng, io, storage),
+        CmdS4::Repair(sub) => repair::run(sub, rng, io, storage),
+        CmdS4::Reshare(sub) => reshare::run(sub, rng, io, storage),
         CmdS4::Rm(sub) => run_rm(sub, io, storage),
     }
 }
 
-fn run_issue_share(cmd: &CmdS4IssueShare, storage: Storage) -> Result<RetCode, AnyError> {
+fn run_combine(cmd: &CmdS4Combine, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let tab_keys = keys_table(&storage)?;
+
+    if let Some(key_id) = cmd.key_id.as_ref() {
+        if tab_keys.get(key_id)?.is_some() {
+            writeln!(io.stderr(), "The key already exists: {:?}", key_id)?;
+            return Ok(1)
+        }
+    }
+
+    let keys = if cmd.share_key_ids.is_empty() {
+        serde_yaml::from_reader::<_, Vec<Key>>(io.stdin())?
+    } else {
+        let mut keys = vec![];
+        for key_id in cmd.share_key_ids.iter() {
+            let Some(key) = tab_keys.get(key_id)? else {
+                writeln!(io.stderr(), "No such key: {:?}", key_id)?;
+                return Ok(1)
+            };
+            keys.push(key);
+        }
+        keys
+    };
+    let mut s4_shares = vec![];
+    for key in keys {
+        let Key::S4Share(s4_share) = key else {
+            writeln!(io.stderr(), "Not an s4-share")?;
+            return Ok(1)
+        };
+        s4_shares.push(s4_share);
+    }
+
+    let Some(first) = s4_shares.first() else {
+        writeln!(io.stderr(), "No key-shares to combine")?;
+        return Ok(1)
+    };
+    let (curve, threshold, public_key) = (first.curve, first.threshold, first.public_key.clone());
+    if s4_shares
+        .iter()
+        .any(|s| s.curve != curve || s.threshold != threshold || s.public_key != public_key)
+    {
+        writeln!(
+            io.stderr(),
+            "The key-shares disagree on the curve, the threshold or the public key"
+        )?;
+        return Ok(1)
+    }
+    if s4_shares.len() < threshold.t {
+        writeln!(
+            io.stderr(),
+            "Not enough key-shares [threshold: {}; shares-count: {}]",
+            threshold,
+            s4_shares.len()
+        )?;
+        return Ok(1)
+    }
+
+    fn reconstruct_secret<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+        curve: CurveSelect,
+        s4_shares: &[S4Share],
+        public_key: &Point,
+        io: impl IO,
+    ) -> Result<Option<SecretScalar>, AnyError> {
+        let xs = s4_shares
+            .iter()
+            .map(|s| s.x.restore::<F>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ys = s4_shares
+            .iter()
+            .map(|s| s.y.restore::<F>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the same `x` may be encoded differently: the restored values are compared
+        let Some(secret) = shamir_sss::reconstruct_at(&xs, &ys, F::ZERO) else {
+            writeln!(io.stderr(), "The key-shares are not distinct")?;
+            return Ok(None)
+        };
+        if G::generator() * secret != public_key.restore::<G>(curve)? {
+            writeln!(
+                io.stderr(),
+                "The key-shares do not add up to the public key: {}",
+                public_key
+            )?;
+            return Ok(None)
+        }
+
+        Ok(Some(SecretScalar::from_value(curve, secret)))
+    }
+
+    let value = specialize_call!(reconstruct_secret, (curve, &s4_shares, &public_key, &io), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint)
+    ]).ok_or(format!("Unsupported curve: {}", curve))??;
+    let Some(value) = value else { return Ok(1) };
+
+    let full_key = Key::FullKey(FullKey { curve, value });
+    if let Some(key_id) = cmd.key_id.as_ref() {
+        let stored = storage.transaction(&[&tab_keys], |tx| {
+            Ok(tx.table(&tab_keys)?.insert_new(key_id, &full_key)?.then_some(()).ok_or(()))
+        })?;
+        if stored.is_err() {
+            writeln!(io.stderr(), "The key already exists: {:?}", key_id)?;
+            return Ok(1)
+        }
+        storage.flush()?;
+    } else {
+        serde_yaml::to_writer(io.stdout(), &full_key)?;
+    }
+
+    Ok(0)
+}
+
+fn run_issue_share(
+    cmd: &CmdS4IssueShare,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
     let tab_s4 = s4_table(&storage)?;
     let tab_keys = keys_table(&storage)?;
 
     if tab_keys.get(&cmd.key_id)?.is_some() {
-        return Err(format!("Key already exists: {}", cmd.key_id).into())
+        writeln!(io.stderr(), "The key already exists: {:?}", cmd.key_id)?;
+        return Ok(1)
+    }
+    let Some(s4) = tab_s4.get(&cmd.s4_id)? else {
+        writeln!(io.stderr(), "No such scheme: {:?}", cmd.s4_id)?;
+        return Ok(1)
+    };
+    // the same `x` can be issued again: it is the same key-share
+    if !s4.issued.contains(&cmd.shamir_x) && s4.issued.len() >= s4.threshold.n {
+        writeln!(
+            io.stderr(),
+            "All the key-shares are issued [threshold: {}; scheme: {:?}]",
+            s4.threshold,
+            cmd.s4_id
+        )?;
+        return Ok(1)
     }
-    let Some(s4) = tab_s4.get(&cmd.s4_id)? else { return Err(format!("No such scheme: {}", cmd.key_id).into()) };
 
     let curve = s4.curve;
 
     fn calculate_shamir_y<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
         curve: CurveSelect,
-        polynomial: &[Scalar],
+        polynomial: &[SecretScalar],
         shamir_x: &Scalar,
-    ) -> Result<(Scalar, Point), AnyError> {
+    ) -> Result<Option<(SecretScalar, Point)>, AnyError> {
         let polynomial = polynomial
             .iter()
             .map(|s| s.restore::<F>(curve))
             .collect::<Result<Vec<_>, AnyError>>()?;
         let shamir_x = shamir_x.restore::<F>(curve)?;
+        // the key-share at zero is the secret
+        if bool::from(shamir_x.is_zero()) {
+            return Ok(None)
+        }
 
         let mut acc = F::ZERO;
         let mut x_to_n = F::ONE;
@@ -118,18 +277,52 @@ fn run_issue_share(cmd: &CmdS4IssueShare, storage: Storage) -> Result<RetCode, A
             x_to_n *= shamir_x;
         }
 
-        Ok((Scalar::from_value(curve, acc), Point::from_value(curve, public_key)))
+        Ok(Some((SecretScalar::from_value(curve, acc), Point::from_value(curve, public_key))))
     }
 
-    let threshold = s4.polynomial.len().saturating_sub(1);
-    let (shamir_y, public_key) = specialize_call!(calculate_shamir_y, (curve, s4.polynomial.as_ref(), &cmd.shamir_x), curve, [
+    let threshold = s4.threshold;
+    let calculated = specialize_call!(calculate_shamir_y, (curve, s4.polynomial.as_ref(), &cmd.shamir_x), curve, [
         (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
         (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
         (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint)
     ]).ok_or(format!("Unsupported curve: {}", curve))??;
-
-    let key_share = S4Share { curve, threshold, public_key, x: cmd.shamir_x.clone(), y: shamir_y };
-    tab_keys.insert(&cmd.key_id, &Key::S4Share(key_share))?;
+    let Some((shamir_y, public_key)) = calculated else {
+        writeln!(io.stderr(), "Invalid shamir-x: {}", cmd.shamir_x)?;
+        return Ok(1)
+    };
+
+    let key_share = Key::S4Share(S4Share {
+        curve,
+        threshold,
+        public_key,
+        x: cmd.shamir_x.clone(),
+        y: shamir_y,
+    });
+    // the scheme is checked again along with issuing the key-share: both are written, or neither
+    let issued = storage.transaction(&[&tab_s4, &tab_keys], |tx| {
+        let (tx_s4, tx_keys) = (tx.table(&tab_s4)?, tx.table(&tab_keys)?);
+        let Some(mut s4) = tx_s4.get(&cmd.s4_id)? else {
+            return Ok(Err(format!("No such scheme: {:?}", cmd.s4_id)))
+        };
+        if !s4.issued.contains(&cmd.shamir_x) {
+            if s4.issued.len() >= s4.threshold.n {
+                return Ok(Err(format!(
+                    "All the key-shares are issued [threshold: {}; scheme: {:?}]",
+                    s4.threshold, cmd.s4_id
+                )))
+            }
+            s4.issued.push(cmd.shamir_x.clone());
+            tx_s4.insert(&cmd.s4_id, &s4)?;
+        }
+        if !tx_keys.insert_new(&cmd.key_id, &key_share)? {
+            return Ok(Err(format!("The key already exists: {:?}", cmd.key_id)))
+        }
+        Ok(Ok(()))
+    })?;
+    if let Err(reason) = issued {
+        writeln!(io.stderr(), "{}", reason)?;
+        return Ok(1)
+    }
 
     Ok(0)
 }
@@ -138,10 +331,11 @@ fn run_gen(cmd: &CmdS4Gen, mut rng: impl RngCore, storage: Storage) -> Result<Re
     let tab_s4 = s4_table(&storage)?;
     let tab_keys = keys_table(&storage)?;
 
-    let Key::FullKey(full_key) = tab_keys
-        .get(&cmd.key_id)?
-        .ok_or(format!("No such key: {}", cmd.key_id))? 
-        else { return Err("Should be a full-key".into()) };
+    let Key::FullKey(full_key) =
+        tab_keys.get(&cmd.key_id)?.ok_or(format!("No such key: {}", cmd.key_id))?
+    else {
+        return Err("Should be a full-key".into())
+    };
 
     let curve = full_key.curve;
 
@@ -150,16 +344,16 @@ fn run_gen(cmd: &CmdS4Gen, mut rng: impl RngCore, storage: Storage) -> Result<Re
     }
 
     let polynomial = std::iter::once(Some(full_key.value))
-        .chain((0..cmd.threshold).map(|_| {
+        .chain((1..cmd.threshold.t).map(|_| {
             specialize_call!(random_scalar, (curve, &mut rng), curve, [
-            (CurveSelect::Secp256k1 => k256::Scalar),
-            (CurveSelect::Ed25519 | CurveSelect::Ristretto25519 => curve25519::scalar::Scalar),
-        ])
+                (CurveSelect::Secp256k1 => k256::Scalar),
+                (CurveSelect::Ed25519 | CurveSelect::Ristretto25519 => curve25519::scalar::Scalar),
+            ])
         }))
         .collect::<Option<Vec<_>>>()
         .ok_or(format!("Unsupported curve: {}", curve))?;
 
-    let s4 = S4 { curve, polynomial };
+    let s4 = S4 { curve, threshold: cmd.threshold, polynomial, issued: vec![] };
 
     tab_s4.insert(&cmd.s4_id, &s4)?;
 
@@ -171,6 +365,24 @@ fn run_import(cmd: &CmdS4Import, io: impl IO, storage: Storage) -> Result<RetCod
 
     if table.get(&cmd.s4_id)?.is_none() {
         let s4: S4 = serde_yaml::from_reader(io.stdin())?;
+        if !s4.threshold.is_valid() || s4.threshold.t != s4.polynomial.len() {
+            writeln!(
+                io.stderr(),
+                "Invalid threshold [threshold: {}; coefficients-count: {}]",
+                s4.threshold,
+                s4.polynomial.len()
+            )?;
+            return Ok(1)
+        }
+        if s4.issued.len() > s4.threshold.n {
+            writeln!(
+                io.stderr(),
+                "More key-shares issued than the threshold allows [threshold: {}; issued-count: {}]",
+                s4.threshold,
+                s4.issued.len()
+            )?;
+            return Ok(1)
+        }
         assert!(table.insert(&cmd.s4_id, &s4)?.is_none());
         Ok(0)
     } else {
@@ -225,6 +437,21 @@ fn keys_table(storage: &Storage) -> Result<Table<Key>, AnyError> {
     Table::open(storage)
 }
 
-fn random_scalar<F: PrimeField>(curve: CurveSelect, rng: impl RngCore) -> Scalar {
-    Scalar::from_value(curve, F::random(rng))
+/// The key-share `key_id`: `None` if there is no such key-share (reported to `stderr`).
+fn s4_share(key_id: &str, io: &impl IO, storage: &Storage) -> Result<Option<S4Share>, AnyError> {
+    match keys_table(storage)?.get(key_id)? {
+        Some(Key::S4Share(s4_share)) => Ok(Some(s4_share)),
+        Some(_) => {
+            writeln!(io.stderr(), "Not an s4-share: {}", key_id)?;
+            Ok(None)
+        },
+        None => {
+            writeln!(io.stderr(), "No such key: {}", key_id)?;
+            Ok(None)
+        },
+    }
+}
+
+fn random_scalar<F: PrimeField>(curve: CurveSelect, rng: impl RngCore) -> SecretScalar {
+    SecretScalar::from_value(curve, F::random(rng))
 }
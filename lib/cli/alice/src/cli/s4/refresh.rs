@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+use feldman_vsss::{CommitmentInitFromScheme, CommitmentVerifyShare};
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use shamir_sss::{LagrangeCoefficientAt, SchemeInitFromSecret, SchemeIssueShare};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+
+use crate::caps::IO;
+use crate::data::{Key, S4Share};
+use crate::{AnyError, RetCode};
+
+use super::{keys_table, s4_share};
+
+#[derive(Debug, StructOpt)]
+pub enum CmdRefresh {
+    Reset(CmdKey),
+    /// Deal the zero-secret polynomial to the holders of the key.
+    Deal(CmdKey),
+    /// Add the deals of all the holders to the key-share: the old key-share is replaced.
+    Aggregate(CmdKey),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdKey {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    shamir_xs: Vec<Scalar>,
+
+    /// The deal of this holder to itself.
+    shamir_y: SecretScalar,
+    commitment: Vec<Point>,
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "s4/refresh/sessions";
+    const VERSION: u32 = 1;
+}
+
+pub fn run(
+    cmd: &CmdRefresh,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdRefresh::Reset(sub) => run_reset(sub, io, storage),
+        CmdRefresh::Deal(sub) => run_deal(sub, rng, io, storage),
+        CmdRefresh::Aggregate(sub) => run_aggregate(sub, io, storage),
+    }
+}
+
+fn run_reset(reset: &CmdKey, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let table = sessions_table(&storage)?;
+
+    if table.remove(&reset.key_id)?.is_some() {
+        writeln!(io.stderr(), "Session reset: {}", reset.key_id)?;
+        Ok(0)
+    } else {
+        writeln!(io.stderr(), "No such session: {}", reset.key_id)?;
+        Ok(1)
+    }
+}
+
+fn run_deal(
+    deal: &CmdKey,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let key_id = deal.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+
+    if tab_sessions.get(key_id)?.is_some() {
+        writeln!(io.stderr(), "The session is already dealt: {}", key_id)?;
+        return Ok(1)
+    }
+    let Some(s4_share) = s4_share(key_id, &io, &storage)? else { return Ok(1) };
+
+    specialize_call!(run_deal_typed, (rng, io, storage, key_id, &s4_share), s4_share.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_aggregate(aggregate: &CmdKey, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let key_id = aggregate.key_id.as_str();
+    let tab_sessions = sessions_table(&storage)?;
+
+    let Some(session) = tab_sessions.get(key_id)? else {
+        writeln!(io.stderr(), "The session isn't dealt: {}", key_id)?;
+        return Ok(1)
+    };
+    let Some(s4_share) = s4_share(key_id, &io, &storage)? else { return Ok(1) };
+
+    specialize_call!(run_aggregate_typed, (io, storage, key_id, s4_share, &session), s4_share.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_deal_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    mut rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+    key_id: &str,
+    s4_share: &S4Share,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+    let tab_sessions = sessions_table(&storage)?;
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        /// The `x` of all the holders of the key, this one included.
+        shamir_xs: Vec<Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        commitment: Vec<Point>,
+        deals: HashMap<Scalar, SecretScalar>,
+        /// `g * y` of the key-share of this holder before the refresh.
+        verification_share: Point,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    // the same `x` may be encoded differently: the restored values are compared
+    let shamir_xs = input
+        .shamir_xs
+        .iter()
+        .map(|x| x.restore::<F>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    let this_x = s4_share.x.restore::<F>(curve)?;
+    if !shamir_xs.contains(&this_x) {
+        writeln!(io.stderr(), "This holder is not in `shamir_xs`: {}", s4_share.x)?;
+        return Ok(1)
+    }
+    // the holders left out would end up with the shares that don't match the others
+    if shamir_xs.len() != s4_share.threshold.n ||
+        shamir_xs.iter().any(|x| bool::from(x.is_zero())) ||
+        (1..shamir_xs.len()).any(|j| shamir_xs[..j].contains(&shamir_xs[j]))
+    {
+        writeln!(
+            io.stderr(),
+            "`shamir_xs` should be the {} distinct non-zero `x` of all the holders",
+            s4_share.threshold.n
+        )?;
+        return Ok(1)
+    }
+
+    let mut scheme = vec![F::ZERO; s4_share.threshold.t];
+    scheme.init_from_secret(&F::ZERO, &mut rng);
+    let mut commitment = vec![G::identity(); s4_share.threshold.t];
+    commitment.init_from_scheme(&scheme);
+
+    let mut deals = HashMap::new();
+    let mut shamir_y = None;
+    for (x, x_value) in input.shamir_xs.iter().zip(shamir_xs) {
+        let y = SecretScalar::from_value(curve, scheme.issue_share(x_value));
+        if x_value == this_x {
+            shamir_y = Some(y);
+        } else {
+            deals.insert(x.clone(), y);
+        }
+    }
+    let commitment =
+        commitment.into_iter().map(|c| Point::from_value(curve, c)).collect::<Vec<_>>();
+    let verification_share =
+        Point::from_value(curve, G::generator() * s4_share.y.restore::<F>(curve)?);
+
+    let shamir_y = shamir_y.expect("`this` is in `shamir_xs`");
+    if !tab_sessions.insert_new(
+        key_id,
+        &Session { shamir_xs: input.shamir_xs, shamir_y, commitment: commitment.clone() },
+    )? {
+        writeln!(io.stderr(), "The session is already dealt: {}", key_id)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(io.stdout(), &Output { commitment, deals, verification_share })?;
+
+    Ok(0)
+}
+
+fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    io: impl IO,
+    storage: Storage,
+    key_id: &str,
+    s4_share: S4Share,
+    session: &Session,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+    let tab_sessions = sessions_table(&storage)?;
+    let tab_keys = keys_table(&storage)?;
+
+    /// The commitments, the deals and the verification shares received from the other holders,
+    /// keyed by their `x`.
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        commitments: HashMap<Scalar, Vec<Point>>,
+        deals: HashMap<Scalar, SecretScalar>,
+        verification_shares: HashMap<Scalar, Point>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        public_key: Point,
+        /// `g * y` of the refreshed key-shares of all the holders.
+        verification_shares: BTreeMap<Scalar, Point>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let x = s4_share.x.restore::<F>(curve)?;
+    let mut combined_commitment = vec![G::identity(); s4_share.threshold.t];
+    let mut delta = F::ZERO;
+    for dealer in session.shamir_xs.iter() {
+        let (commitment, y) = if dealer.restore::<F>(curve)? == x {
+            (&session.commitment, &session.shamir_y)
+        } else {
+            let commitment = input
+                .commitments
+                .get(dealer)
+                .ok_or(format!("missing commitment (from {:?})", dealer))?;
+            let y = input.deals.get(dealer).ok_or(format!("missing deal (from {:?})", dealer))?;
+            (commitment, y)
+        };
+        let commitment = commitment
+            .iter()
+            .map(|c| c.restore::<G>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+        let y = y.restore::<F>(curve)?;
+
+        // the zero secret keeps the public key
+        if commitment.len() != s4_share.threshold.t ||
+            !commitment.first().is_some_and(|c| bool::from(c.is_identity()))
+        {
+            writeln!(io.stderr(), "Not a zero-secret commitment: {}", dealer)?;
+            return Ok(1)
+        }
+        if !commitment.verify_share(&x, &y) {
+            writeln!(io.stderr(), "Invalid deal: {}", dealer)?;
+            return Ok(1)
+        }
+
+        combined_commitment.iter_mut().zip(&commitment).for_each(|(acc, c)| *acc += c);
+        delta += y;
+    }
+    let y = s4_share.y.restore::<F>(curve)? + delta;
+
+    // the refreshed key-shares of all the holders should still be of the public key
+    let mut xs = vec![];
+    let mut points = vec![];
+    for holder in session.shamir_xs.iter() {
+        let point = if holder.restore::<F>(curve)? == x {
+            G::generator() * y
+        } else {
+            let verification_share = input
+                .verification_shares
+                .get(holder)
+                .ok_or(format!("missing verification share (of {:?})", holder))?
+                .restore::<G>(curve)?;
+            verification_share + commitment_at(&combined_commitment, &holder.restore::<F>(curve)?)
+        };
+        xs.push(holder.restore::<F>(curve)?);
+        points.push(point);
+    }
+    let public_key = s4_share.public_key.restore::<G>(curve)?;
+    let (base_xs, base_points) = (&xs[..s4_share.threshold.t], &points[..s4_share.threshold.t]);
+    let interpolate_at = |x: F| {
+        base_points
+            .iter()
+            .enumerate()
+            .map(|(k, p)| *p * base_xs.lagrange_coefficient_at(k, x))
+            .sum::<G>()
+    };
+    if interpolate_at(F::ZERO) != public_key ||
+        xs.iter()
+            .zip(&points)
+            .skip(s4_share.threshold.t)
+            .any(|(x, p)| interpolate_at(*x) != *p)
+    {
+        writeln!(io.stderr(), "The refreshed key-shares don't match the public key: {}", key_id)?;
+        return Ok(1)
+    }
+    let verification_shares = session
+        .shamir_xs
+        .iter()
+        .cloned()
+        .zip(points.into_iter().map(|p| Point::from_value(curve, p)))
+        .collect();
+
+    let public_key = s4_share.public_key.clone();
+    let shamir_x = s4_share.x.clone();
+    let key = Key::S4Share(S4Share { y: SecretScalar::from_value(curve, y), ..s4_share });
+    // the refreshed key-share replaces the old one and the session: all or nothing
+    let stored = storage.transaction(&[&tab_sessions, &tab_keys], |tx| {
+        if tx.table(&tab_sessions)?.remove(key_id)?.is_none() {
+            return Ok(Err("No such session"))
+        }
+        let tx_keys = tx.table(&tab_keys)?;
+        if !matches!(tx_keys.get(key_id)?, Some(Key::S4Share(old)) if old.x == shamir_x) {
+            return Ok(Err("The key-share has changed since the deal"))
+        }
+        tx_keys.insert(key_id, &key)?;
+        Ok(Ok(()))
+    })?;
+    if let Err(reason) = stored {
+        writeln!(io.stderr(), "{}: {}", reason, key_id)?;
+        return Ok(1)
+    }
+    storage.flush()?;
+
+    serde_yaml::to_writer(io.stdout(), &Output { public_key, verification_shares })?;
+
+    Ok(0)
+}
+
+/// The value committed to by `commitment` at `x`.
+fn commitment_at<F: PrimeField, G: Group<Scalar = F>>(commitment: &[G], x: &F) -> G {
+    commitment.iter().rev().fold(G::identity(), |acc, c| acc * x + c)
+}
+
+fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
+    Table::open(storage)
+}
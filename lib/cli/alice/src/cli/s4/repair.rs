@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use shamir_sss::LagrangeCoefficientAt;
+use structopt::StructOpt;
+
+use cli_storage::Storage;
+
+use crate::caps::IO;
+use crate::data::{Key, S4Share, Threshold};
+use crate::{AnyError, RetCode};
+
+use super::{keys_table, s4_share};
+
+#[derive(Debug, StructOpt)]
+pub enum CmdRepair {
+    /// Split the weighted key-share of this helper into the deltas for all the helpers.
+    Deal(CmdKey),
+    /// Add up the deltas received from all the helpers into the sigma of this helper.
+    Sum(CmdKey),
+    /// Add up the sigmas of all the helpers into the lost key-share.
+    Recover(CmdRecover),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdKey {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdRecover {
+    #[structopt(long, short, env = "ALICE_CURVE")]
+    curve: CurveSelect,
+
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+pub fn run(
+    cmd: &CmdRepair,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdRepair::Deal(sub) => run_deal(sub, rng, io, storage),
+        CmdRepair::Sum(sub) => run_sum(sub, io, storage),
+        CmdRepair::Recover(sub) => run_recover(sub, io, storage),
+    }
+}
+
+fn run_deal(
+    deal: &CmdKey,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let Some(s4_share) = s4_share(&deal.key_id, &io, &storage)? else { return Ok(1) };
+
+    specialize_call!(run_deal_typed, (rng, io, &s4_share), s4_share.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_sum(sum: &CmdKey, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let Some(s4_share) = s4_share(&sum.key_id, &io, &storage)? else { return Ok(1) };
+
+    specialize_call!(run_sum_typed, (io, &s4_share), s4_share.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar),
+        (CurveSelect::Ed25519 | CurveSelect::Ristretto25519 => curve25519::scalar::Scalar),
+    ])
+    .ok_or("Unsupported curve")?
+}
+
+fn run_recover(recover: &CmdRecover, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    if keys_table(&storage)?.get(&recover.key_id)?.is_some() {
+        writeln!(io.stderr(), "Key already exists: {}", recover.key_id)?;
+        return Ok(1)
+    }
+
+    specialize_call!(run_recover_typed, (io, storage, recover), recover.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_deal_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    rng: impl RngCore,
+    io: impl IO,
+    s4_share: &S4Share,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        /// The `x` of all the helpers, this one included.
+        helpers_xs: Vec<Scalar>,
+        lost_x: Scalar,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        /// The public share of this helper, to check the repaired key-share against.
+        public_share: Point,
+        deltas: HashMap<Scalar, Scalar>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let Some(this) = input.helpers_xs.iter().position(|x| x == &s4_share.x) else {
+        writeln!(io.stderr(), "This holder is not in `helpers_xs`: {}", s4_share.x)?;
+        return Ok(1)
+    };
+    if input.helpers_xs.contains(&input.lost_x) {
+        writeln!(io.stderr(), "The holder of the lost key-share can't help: {}", input.lost_x)?;
+        return Ok(1)
+    }
+    if input.helpers_xs.iter().collect::<HashSet<_>>().len() != input.helpers_xs.len() {
+        writeln!(io.stderr(), "`helpers_xs` should be distinct")?;
+        return Ok(1)
+    }
+    if input.helpers_xs.len() < s4_share.threshold.t {
+        writeln!(
+            io.stderr(),
+            "Not enough helpers [threshold: {}; helpers-count: {}]",
+            s4_share.threshold,
+            input.helpers_xs.len()
+        )?;
+        return Ok(1)
+    }
+
+    let helpers_xs = input
+        .helpers_xs
+        .iter()
+        .map(|x| x.restore::<F>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    let lost_x = input.lost_x.restore::<F>(curve)?;
+    // the share at zero is the secret itself
+    if helpers_xs.iter().chain([&lost_x]).any(|x| bool::from(x.is_zero())) {
+        writeln!(io.stderr(), "The `x` of the key-shares should be non-zero")?;
+        return Ok(1)
+    }
+    let y = s4_share.y.restore::<F>(curve)?;
+
+    let mut deltas = vec![F::ZERO; helpers_xs.len()];
+    shamir_sss::repair_deltas(rng, &helpers_xs, this, &y, lost_x, &mut deltas)?;
+
+    let public_share = Point::from_value(curve, G::generator() * y);
+    let deltas = input
+        .helpers_xs
+        .into_iter()
+        .zip(deltas)
+        .map(|(x, delta)| (x, Scalar::from_value(curve, delta)))
+        .collect();
+
+    serde_yaml::to_writer(io.stdout(), &Output { public_share, deltas })?;
+
+    Ok(0)
+}
+
+fn run_sum_typed<F: PrimeField>(io: impl IO, s4_share: &S4Share) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    /// The deltas received from all the helpers (this one included), keyed by their `x`.
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        deltas: HashMap<Scalar, Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        sigma: Scalar,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    if input.deltas.len() < s4_share.threshold.t {
+        writeln!(
+            io.stderr(),
+            "Not enough deltas [threshold: {}; deltas-count: {}]",
+            s4_share.threshold,
+            input.deltas.len()
+        )?;
+        return Ok(1)
+    }
+
+    let deltas = input
+        .deltas
+        .values()
+        .map(|d| d.restore::<F>(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sigma = Scalar::from_value(curve, shamir_sss::repair_sigma(&deltas)?);
+
+    serde_yaml::to_writer(io.stdout(), &Output { sigma })?;
+
+    Ok(0)
+}
+
+fn run_recover_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    io: impl IO,
+    storage: Storage,
+    recover: &CmdRecover,
+) -> Result<RetCode, AnyError> {
+    let curve = recover.curve;
+    let tab_keys = keys_table(&storage)?;
+
+    /// The public shares and the sigmas of the helpers, keyed by their `x`.
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        public_key: Point,
+        threshold: Threshold,
+        shamir_x: Scalar,
+        public_shares: HashMap<Scalar, Point>,
+        sigmas: HashMap<Scalar, Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        public_key: Point,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    if input.sigmas.len() < input.threshold.t {
+        writeln!(
+            io.stderr(),
+            "Not enough helpers [threshold: {}; helpers-count: {}]",
+            input.threshold,
+            input.sigmas.len()
+        )?;
+        return Ok(1)
+    }
+
+    let mut helpers_xs = vec![];
+    let mut public_shares = vec![];
+    let mut sigmas = vec![];
+    for (x, sigma) in input.sigmas.iter() {
+        let public_share =
+            input.public_shares.get(x).ok_or(format!("missing public share (of {:?})", x))?;
+        helpers_xs.push(x.restore::<F>(curve)?);
+        public_shares.push(public_share.restore::<G>(curve)?);
+        sigmas.push(sigma.restore::<F>(curve)?);
+    }
+    // the same `x` may be encoded differently: the restored values are compared
+    if (1..helpers_xs.len()).any(|j| helpers_xs[..j].contains(&helpers_xs[j])) {
+        writeln!(io.stderr(), "The helpers' `x` should be distinct")?;
+        return Ok(1)
+    }
+    let interpolate = |x: F| -> G {
+        public_shares
+            .iter()
+            .enumerate()
+            .map(|(i, p)| *p * helpers_xs.lagrange_coefficient_at(i, x))
+            .sum()
+    };
+
+    // the public shares of the helpers are those of the key
+    if interpolate(F::ZERO) != input.public_key.restore::<G>(curve)? {
+        writeln!(io.stderr(), "The public shares do not add up to the public key")?;
+        return Ok(1)
+    }
+
+    let x = input.shamir_x.restore::<F>(curve)?;
+    let y = shamir_sss::repair_share(&sigmas)?;
+    if G::generator() * y != interpolate(x) {
+        writeln!(io.stderr(), "The sigmas do not add up to the key-share")?;
+        return Ok(1)
+    }
+
+    let s4_share = S4Share {
+        curve,
+        threshold: input.threshold,
+        public_key: input.public_key.clone(),
+        x: input.shamir_x,
+        y: SecretScalar::from_value(curve, y),
+    };
+    // checked before, but the key might have been stored since
+    let key = Key::S4Share(s4_share);
+    let stored = storage.transaction(&[&tab_keys], |tx| {
+        Ok(tx.table(&tab_keys)?.insert_new(&recover.key_id, &key)?.then_some(()).ok_or(()))
+    })?;
+    if stored.is_err() {
+        writeln!(io.stderr(), "Key already exists: {}", recover.key_id)?;
+        return Ok(1)
+    }
+    storage.flush()?;
+
+    serde_yaml::to_writer(io.stdout(), &Output { public_key: input.public_key })?;
+
+    Ok(0)
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+use feldman_vsss::{CommitmentInitFromScheme, CommitmentVerifyShare};
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use shamir_sss::{LagrangeCoefficientAt, SchemeInitFromSecret, SchemeIssueShare};
+use structopt::StructOpt;
+
+use cli_storage::Storage;
+
+use crate::caps::IO;
+use crate::data::{Key, S4Share, Threshold};
+use crate::{AnyError, RetCode};
+
+use super::{keys_table, s4_share};
+
+#[derive(Debug, StructOpt)]
+pub enum CmdReshare {
+    /// Deal the Lagrange-weighted key-share to the new holders.
+    Deal(CmdDeal),
+    /// Add up the deals of the old holders into a new key-share.
+    Aggregate(CmdAggregate),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdDeal {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdAggregate {
+    #[structopt(long, short, env = "ALICE_CURVE")]
+    curve: CurveSelect,
+
+    #[structopt(name = "NEW-KEY-ID")]
+    key_id: String,
+}
+
+pub fn run(
+    cmd: &CmdReshare,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdReshare::Deal(sub) => run_deal(sub, rng, io, storage),
+        CmdReshare::Aggregate(sub) => run_aggregate(sub, io, storage),
+    }
+}
+
+fn run_deal(
+    deal: &CmdDeal,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let Some(s4_share) = s4_share(&deal.key_id, &io, &storage)? else { return Ok(1) };
+
+    specialize_call!(run_deal_typed, (rng, io, &s4_share), s4_share.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_aggregate(
+    aggregate: &CmdAggregate,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    if keys_table(&storage)?.get(&aggregate.key_id)?.is_some() {
+        writeln!(io.stderr(), "Key already exists: {}", aggregate.key_id)?;
+        return Ok(1)
+    }
+
+    specialize_call!(run_aggregate_typed, (io, storage, aggregate), aggregate.curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or("Unsupported curve")?
+}
+
+fn run_deal_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    mut rng: impl RngCore,
+    io: impl IO,
+    s4_share: &S4Share,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        /// The `x` of the old holders taking part in the resharing, this one included.
+        old_shamir_xs: Vec<Scalar>,
+
+        /// The threshold of the new key-shares: `n` is the count of `new_shamir_xs`.
+        threshold: Threshold,
+        new_shamir_xs: Vec<Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        commitment: Vec<Point>,
+        deals: HashMap<Scalar, Scalar>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    if input.old_shamir_xs.len() < s4_share.threshold.t {
+        writeln!(
+            io.stderr(),
+            "Not enough old holders [threshold: {}; old-holders-count: {}]",
+            s4_share.threshold,
+            input.old_shamir_xs.len()
+        )?;
+        return Ok(1)
+    }
+    let threshold = input.threshold;
+    if !threshold.is_valid() || threshold.n != input.new_shamir_xs.len() {
+        writeln!(
+            io.stderr(),
+            "Invalid threshold [threshold: {}; new-holders-count: {}]",
+            threshold,
+            input.new_shamir_xs.len()
+        )?;
+        return Ok(1)
+    }
+
+    let Some(old_xs) = distinct_non_zero::<F>(curve, &input.old_shamir_xs)? else {
+        writeln!(io.stderr(), "`old_shamir_xs` should be distinct and non-zero")?;
+        return Ok(1)
+    };
+    let Some(new_xs) = distinct_non_zero::<F>(curve, &input.new_shamir_xs)? else {
+        writeln!(io.stderr(), "`new_shamir_xs` should be distinct and non-zero")?;
+        return Ok(1)
+    };
+    let this_x = s4_share.x.restore::<F>(curve)?;
+    let Some(this) = old_xs.iter().position(|x| *x == this_x) else {
+        writeln!(io.stderr(), "This holder is not in `old_shamir_xs`: {}", s4_share.x)?;
+        return Ok(1)
+    };
+    let secret = s4_share.y.restore::<F>(curve)? * old_xs.lagrange_coefficient_at(this, F::ZERO);
+
+    let mut scheme = vec![F::ZERO; threshold.t];
+    scheme.init_from_secret(&secret, &mut rng);
+    let mut commitment = vec![G::identity(); threshold.t];
+    commitment.init_from_scheme(&scheme);
+
+    let mut deals = HashMap::new();
+    for (x, x_value) in input.new_shamir_xs.iter().zip(new_xs) {
+        let y = scheme.issue_share(x_value);
+        deals.insert(x.clone(), Scalar::from_value(curve, y));
+    }
+    let commitment = commitment.into_iter().map(|c| Point::from_value(curve, c)).collect();
+
+    serde_yaml::to_writer(io.stdout(), &Output { commitment, deals })?;
+
+    Ok(0)
+}
+
+fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    io: impl IO,
+    storage: Storage,
+    aggregate: &CmdAggregate,
+) -> Result<RetCode, AnyError> {
+    let curve = aggregate.curve;
+    let tab_keys = keys_table(&storage)?;
+
+    /// The commitments and the deals received from the old holders, keyed by their `x`.
+    ///
+    /// Being keyed by their `x`, the old holders are distinct; that there are enough of them to
+    /// meet the old threshold is checked against the public key: the Lagrange-weighted key-shares
+    /// of fewer holders don't add up to the secret.
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        public_key: Point,
+        threshold: Threshold,
+        shamir_x: Scalar,
+        commitments: HashMap<Scalar, Vec<Point>>,
+        deals: HashMap<Scalar, Scalar>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        public_key: Point,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let x = input.shamir_x.restore::<F>(curve)?;
+    if bool::from(x.is_zero()) {
+        writeln!(io.stderr(), "`shamir_x` should be non-zero")?;
+        return Ok(1)
+    }
+    if let Some(dealer) = input.deals.keys().find(|d| !input.commitments.contains_key(*d)) {
+        writeln!(io.stderr(), "A deal with no commitment: {}", dealer)?;
+        return Ok(1)
+    }
+
+    let mut combined_commitment = vec![G::identity(); input.threshold.t];
+    let mut y = F::ZERO;
+    for (dealer, commitment) in input.commitments.iter() {
+        let deal = input.deals.get(dealer).ok_or(format!("missing deal (from {:?})", dealer))?;
+        let commitment = commitment
+            .iter()
+            .map(|c| c.restore::<G>(curve))
+            .collect::<Result<Vec<_>, _>>()?;
+        let deal = deal.restore::<F>(curve)?;
+
+        if commitment.len() != input.threshold.t {
+            writeln!(io.stderr(), "The commitment doesn't match the threshold: {}", dealer)?;
+            return Ok(1)
+        }
+        if !commitment.verify_share(&x, &deal) {
+            writeln!(io.stderr(), "Invalid deal: {}", dealer)?;
+            return Ok(1)
+        }
+
+        combined_commitment.iter_mut().zip(&commitment).for_each(|(acc, c)| *acc += c);
+        y += deal;
+    }
+
+    // the weighted key-shares of the old holders add up to the same secret
+    if combined_commitment.first() != Some(&input.public_key.restore::<G>(curve)?) {
+        writeln!(io.stderr(), "The deals do not add up to the public key: {}", input.public_key)?;
+        return Ok(1)
+    }
+
+    let s4_share = S4Share {
+        curve,
+        threshold: input.threshold,
+        public_key: input.public_key.clone(),
+        x: input.shamir_x,
+        y: SecretScalar::from_value(curve, y),
+    };
+    let key = Key::S4Share(s4_share);
+    let stored = storage.transaction(&[&tab_keys], |tx| {
+        Ok(tx
+            .table(&tab_keys)?
+            .insert_new(&aggregate.key_id, &key)?
+            .then_some(())
+            .ok_or(()))
+    })?;
+    if stored.is_err() {
+        writeln!(io.stderr(), "Key already exists: {}", aggregate.key_id)?;
+        return Ok(1)
+    }
+    storage.flush()?;
+
+    serde_yaml::to_writer(io.stdout(), &Output { public_key: input.public_key })?;
+
+    Ok(0)
+}
+
+/// The `xs` restored, unless any of them is zero or repeated.
+fn distinct_non_zero<F: PrimeField>(
+    curve: CurveSelect,
+    xs: &[Scalar],
+) -> Result<Option<Vec<F>>, AnyError> {
+    let restored = xs.iter().map(|x| x.restore::<F>(curve)).collect::<Result<Vec<_>, _>>()?;
+    // the same `x` may be encoded differently: the restored values are compared
+    let is_valid = restored.iter().all(|x| !bool::from(x.is_zero())) &&
+        (1..restored.len()).all(|j| !restored[..j].contains(&restored[j]));
+    Ok(is_valid.then_some(restored))
+}
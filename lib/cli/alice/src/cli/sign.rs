@@ -1,27 +1,44 @@
+use elliptic_curve::bigint::U256;
+use elliptic_curve::ops::Reduce;
+use elliptic_curve::point::AffineCoordinates;
+use elliptic_curve::scalar::IsHigh;
 use ff::PrimeField;
-use group::{Group, GroupEncoding};
+use group::{Curve, Group, GroupEncoding};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
 use cli_storage::{Storage, Table};
 use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
 use common_interop::transcript::Transcript;
 use common_interop::types::{Point, Scalar};
 
 use crate::caps::IO;
+use crate::challenge::{Challenge, ChallengeOpts};
 use crate::data::{FullKey, Key};
-use crate::{transcript, AnyError, RetCode};
+use crate::ecdsa::rfc6979_instance_key;
+use crate::{bip340, ecdsa, ed25519, transcript, AnyError, RetCode};
 
 #[derive(Debug, StructOpt)]
 pub enum CmdSign {
     Schnorr(CmdSignSchnorr),
+    Ecdsa(CmdSignEcdsa),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct CmdSignSchnorr {
     #[structopt(long, short)]
     key_id: String,
+
+    #[structopt(flatten)]
+    challenge: ChallengeOpts,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdSignEcdsa {
+    #[structopt(long, short)]
+    key_id: String,
 }
 
 pub fn run(
@@ -32,6 +49,7 @@ pub fn run(
 ) -> Result<RetCode, AnyError> {
     match cmd {
         CmdSign::Schnorr(sub) => run_sign_schnorr(sub, rng, io, storage),
+        CmdSign::Ecdsa(sub) => run_sign_ecdsa(sub, io, storage),
     }
 }
 
@@ -46,7 +64,7 @@ pub fn run_sign_schnorr(
     let curve = full_key.curve;
 
     specialize_call!(
-        run_sign_schnorr_typed, (&full_key, rng, io),
+        run_sign_schnorr_typed, (cmd, &full_key, rng, io),
         curve,
         [
             (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
@@ -57,14 +75,17 @@ pub fn run_sign_schnorr(
 }
 
 pub fn run_sign_schnorr_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    cmd: &CmdSignSchnorr,
     full_key: &FullKey,
-    rng: impl RngCore,
+    mut rng: impl RngCore,
     io: impl IO,
 ) -> Result<RetCode, AnyError> {
     #[derive(Deserialize)]
     struct Input {
-        transcript: Transcript,
+        transcript: Option<Transcript>,
+        message: Option<Vec<common_interop::transcript::Input>>,
         instance_key: Option<Scalar>,
+        aux_rand: Option<String>,
     }
 
     #[derive(Serialize)]
@@ -72,6 +93,10 @@ pub fn run_sign_schnorr_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncodin
         y: Point,
         r: Point,
         s: Scalar,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        public_key: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
     }
 
     let curve = full_key.curve;
@@ -80,13 +105,28 @@ pub fn run_sign_schnorr_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncodin
     let y = g * x;
 
     let input: Input = serde_yaml::from_reader(io.stdin())?;
-
-    let k = input
-        .instance_key
-        .map(|s| s.restore::<F>(curve))
-        .transpose()?
-        .unwrap_or_else(|| F::random(rng));
-    let c = transcript::produce_challenge(&input.transcript, &y, &(g * k))?;
+    let challenge = Challenge::new(&cmd.challenge, curve, input.transcript, input.message)?;
+
+    let (x, y) = if challenge.is_odd(&y) { (-x, -y) } else { (x, y) };
+
+    let k = match (input.instance_key, &challenge, input.aux_rand) {
+        (Some(k), ..) => k.restore::<F>(curve)?,
+        (None, Challenge::Rfc8032(message), _) => ed25519::instance_key(&x, message),
+        (None, Challenge::Bip340(message), aux_rand) => {
+            let aux_rand = match aux_rand {
+                Some(aux_rand) => hex::decode(aux_rand)?,
+                None => {
+                    let mut aux_rand = vec![0; 32];
+                    rng.fill_bytes(&mut aux_rand);
+                    aux_rand
+                },
+            };
+            bip340::instance_key(&x, &y, message, &aux_rand).ok_or("the instance key is zero")?
+        },
+        (None, Challenge::Transcript(_), _) => F::random(rng),
+    };
+    let k = if challenge.is_odd(&(g * k)) { -k } else { k };
+    let c = challenge.produce(&y, &(g * k))?;
 
     let (s, r) = schnorr_proof::prove(g, &x, &k, c);
 
@@ -96,6 +136,90 @@ pub fn run_sign_schnorr_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncodin
             y: Point::from_value(curve, y),
             r: Point::from_value(curve, r),
             s: Scalar::from_value(curve, s),
+            public_key: challenge.public_key_bytes(&y).map(hex::encode),
+            signature: challenge.signature_bytes(&r, &s).map(hex::encode),
+        },
+    )?;
+
+    Ok(0)
+}
+
+pub fn run_sign_ecdsa(
+    cmd: &CmdSignEcdsa,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let tab_keys = keys_table(&storage)?;
+    let Key::FullKey(full_key) = tab_keys.get(&cmd.key_id)?.ok_or("No such key")? else {
+        return Err("the key should be a Full-Key".into())
+    };
+    let curve = full_key.curve;
+
+    specialize_call!(
+        run_sign_ecdsa_typed, (&full_key, io),
+        curve,
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ]
+    )
+    .ok_or(format!("Unsupported curve: {}", curve))?
+}
+
+pub fn run_sign_ecdsa_typed<F, G>(full_key: &FullKey, io: impl IO) -> Result<RetCode, AnyError>
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr> + IsHigh,
+    G: Group<Scalar = F> + GroupEncoding + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+{
+    #[derive(Deserialize)]
+    struct Input {
+        transcript: Transcript,
+        instance_key: Option<Scalar>,
+    }
+
+    #[derive(Serialize)]
+    struct Output {
+        y: Point,
+        r: Scalar,
+        s: Scalar,
+        recovery_id: u8,
+    }
+
+    let curve = full_key.curve;
+    let x = full_key.value.restore::<F>(curve)?;
+    let y = G::generator() * x;
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let digest = transcript::produce_digest(&input.transcript)?;
+    let m = ecdsa::digest_to_scalar::<F>(&digest);
+
+    let k = match input.instance_key {
+        Some(k) => k.restore::<F>(curve)?,
+        None => {
+            let hash_function = input.transcript.hash_function;
+            specialize_call!(
+                rfc6979_instance_key, (&x, &digest),
+                hash_function,
+                [
+                    (HashFunctionSelect::Sha3_256 => F, sha3::Sha3_256),
+                    (HashFunctionSelect::Sha2_256 => F, sha2::Sha256),
+                ]
+            )
+            .ok_or(format!("Unsupported hash-function: {}", hash_function))?
+        },
+    };
+
+    let (r, s, recovery_id) =
+        ecdsa::sign::<F, G>(&x, &m, &k).ok_or("the instance key is not suitable")?;
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            y: Point::from_value(curve, y),
+            r: Scalar::from_value(curve, r),
+            s: Scalar::from_value(curve, s),
+            recovery_id,
         },
     )?;
 
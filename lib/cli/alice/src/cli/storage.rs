@@ -0,0 +1,151 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+use zeroize::Zeroizing;
+
+use cli_storage::{KdfParams, Storage};
+
+use crate::caps::IO;
+use crate::{AnyError, RetCode};
+
+mod backup;
+mod migrate;
+pub(super) use migrate::ensure_migrated;
+
+#[derive(Debug, StructOpt)]
+pub enum CmdStorage {
+    Backup(backup::CmdBackup),
+    Init(CmdInit),
+    Migrate(migrate::CmdMigrate),
+    Passphrase(CmdPassphrase),
+    Restore(backup::CmdRestore),
+}
+
+/// Create the storage.
+///
+/// With `--encrypted`, the entries (including those already stored) are encrypted with a key
+/// protected by the passphrase [see `--passphrase-file`].
+#[derive(Debug, StructOpt)]
+pub struct CmdInit {
+    #[structopt(long)]
+    encrypted: bool,
+
+    #[structopt(flatten)]
+    kdf: KdfOpts,
+}
+
+/// Change the passphrase of an encrypted storage.
+#[derive(Debug, StructOpt)]
+pub struct CmdPassphrase {
+    /// The file holding the new passphrase [otherwise: $ALICE_NEW_PASSPHRASE, or asked for].
+    #[structopt(long, env = "ALICE_NEW_PASSPHRASE_FILE")]
+    new_passphrase_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    kdf: KdfOpts,
+}
+
+#[derive(Debug, StructOpt)]
+struct KdfOpts {
+    /// The memory to derive the key from the passphrase with, in KiB [at most: 1048576].
+    #[structopt(long, default_value = "65536")]
+    kdf_memory: u32,
+
+    /// The passes to derive the key from the passphrase with [at most: 16].
+    #[structopt(long, default_value = "3")]
+    kdf_iterations: u32,
+}
+
+impl KdfOpts {
+    fn params(&self) -> KdfParams {
+        KdfParams { memory_kib: self.kdf_memory, iterations: self.kdf_iterations }
+    }
+}
+
+pub fn run(
+    cmd: &CmdStorage,
+    io: impl IO,
+    storage: Storage,
+    passphrase_file: Option<&Path>,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdStorage::Backup(sub) => backup::run_backup(sub, io, storage),
+        CmdStorage::Init(sub) => run_init(sub, io, storage, passphrase_file),
+        CmdStorage::Migrate(sub) => migrate::run(sub, io, storage),
+        CmdStorage::Passphrase(sub) => run_passphrase(sub, io, storage),
+        CmdStorage::Restore(sub) => backup::run_restore(sub, io, storage),
+    }
+}
+
+/// The passphrase: read from the `file`, from `$ALICE_PASSPHRASE`, or asked for (twice if it is
+/// `new`).
+pub(super) fn read_passphrase(
+    file: Option<&Path>,
+    new: bool,
+) -> Result<Zeroizing<String>, AnyError> {
+    read_secret(file, "ALICE_PASSPHRASE", "Storage passphrase: ", new)
+}
+
+fn read_secret(
+    file: Option<&Path>,
+    env: &str,
+    prompt: &str,
+    new: bool,
+) -> Result<Zeroizing<String>, AnyError> {
+    let passphrase = if let Some(file) = file {
+        let content = Zeroizing::new(std::fs::read_to_string(file)?);
+        Zeroizing::new(content.trim_end_matches(['\n', '\r']).to_owned())
+    } else if let Ok(passphrase) = std::env::var(env) {
+        Zeroizing::new(passphrase)
+    } else {
+        let passphrase = Zeroizing::new(rpassword::prompt_password(prompt)?);
+        if new && *passphrase != *Zeroizing::new(rpassword::prompt_password("Once again: ")?) {
+            return Err("The passphrases do not match".into())
+        }
+        passphrase
+    };
+
+    if new && passphrase.is_empty() {
+        return Err("The passphrase is empty".into())
+    }
+    Ok(passphrase)
+}
+
+fn run_init(
+    cmd: &CmdInit,
+    io: impl IO,
+    mut storage: Storage,
+    passphrase_file: Option<&Path>,
+) -> Result<RetCode, AnyError> {
+    if cmd.encrypted {
+        if storage.is_encrypted() {
+            writeln!(io.stderr(), "The storage is already encrypted")?;
+            return Ok(1)
+        }
+        let passphrase = read_passphrase(passphrase_file, true)?;
+        storage.encrypt(&passphrase, cmd.kdf.params())?;
+    }
+    storage.flush()?;
+
+    Ok(0)
+}
+
+fn run_passphrase(cmd: &CmdPassphrase, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    if !storage.is_encrypted() {
+        writeln!(
+            io.stderr(),
+            "The storage is not encrypted: see `alice storage init --encrypted`"
+        )?;
+        return Ok(1)
+    }
+    let passphrase = read_secret(
+        cmd.new_passphrase_file.as_deref(),
+        "ALICE_NEW_PASSPHRASE",
+        "New storage passphrase: ",
+        true,
+    )?;
+    storage.change_passphrase(&passphrase, cmd.kdf.params())?;
+
+    Ok(0)
+}
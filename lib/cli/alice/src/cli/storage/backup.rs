@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use structopt::StructOpt;
+
+use cli_storage::{AnyTable, Backup, RawTable, Storage};
+
+use super::{read_secret, KdfOpts};
+use crate::caps::IO;
+use crate::{AnyError, RetCode};
+
+/// The tables holding the one-time nonces: restoring them might let a nonce be used twice, which
+/// reveals the key-share.
+const NONCE_TABLES: &[&str] =
+    &["tss/frost/nonces", "tss/dkls/aggregator/sessions", "tss/dkls/cosigner/sessions"];
+
+/// Write all the tables into a single archive, protected by a MAC under the backup passphrase.
+#[derive(Debug, StructOpt)]
+pub struct CmdBackup {
+    /// The file to write the archive into [otherwise: stdout].
+    #[structopt(long, short)]
+    output: Option<PathBuf>,
+
+    /// Encrypt the contents of the archive (otherwise they are only authenticated).
+    #[structopt(long)]
+    encrypt: bool,
+
+    #[structopt(flatten)]
+    passphrase: BackupPassphrase,
+
+    #[structopt(flatten)]
+    kdf: KdfOpts,
+}
+
+/// Restore the records from an archive made by `alice storage backup`.
+///
+/// The nonces (FROST nonces, DKLs sessions) are not restored unless `--with-nonces`.
+#[derive(Debug, StructOpt)]
+pub struct CmdRestore {
+    /// The file to read the archive from [otherwise: stdin].
+    #[structopt(long, short)]
+    input: Option<PathBuf>,
+
+    /// Restore only the given tables.
+    #[structopt(long = "table")]
+    tables: Vec<String>,
+
+    /// Restore only the records whose ids (e.g. key-ids) start with the prefix.
+    #[structopt(long, default_value = "")]
+    prefix: String,
+
+    /// Restore the nonces too: make sure none of them has been used since the backup.
+    #[structopt(long)]
+    with_nonces: bool,
+
+    /// Replace the records already in the storage.
+    #[structopt(long)]
+    overwrite: bool,
+
+    #[structopt(flatten)]
+    passphrase: BackupPassphrase,
+}
+
+#[derive(Debug, StructOpt)]
+struct BackupPassphrase {
+    /// The file holding the passphrase of the backup [otherwise: $ALICE_BACKUP_PASSPHRASE, or
+    /// asked for].
+    #[structopt(long, env = "ALICE_BACKUP_PASSPHRASE_FILE")]
+    backup_passphrase_file: Option<PathBuf>,
+}
+
+pub fn run_backup(cmd: &CmdBackup, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let passphrase = read_secret(
+        cmd.passphrase.backup_passphrase_file.as_deref(),
+        "ALICE_BACKUP_PASSPHRASE",
+        "Backup passphrase: ",
+        true,
+    )?;
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let archive =
+        Backup::of(&storage)?.seal(&passphrase, cmd.encrypt, cmd.kdf.params(), created_at)?;
+
+    if let Some(output) = cmd.output.as_ref() {
+        std::fs::write(output, archive)?;
+    } else {
+        io.stdout().write_all(&archive)?;
+    }
+
+    Ok(0)
+}
+
+pub fn run_restore(cmd: &CmdRestore, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let archive = if let Some(input) = cmd.input.as_ref() {
+        std::fs::read(input)?
+    } else {
+        let mut archive = vec![];
+        io.stdin().read_to_end(&mut archive)?;
+        archive
+    };
+    let passphrase = read_secret(
+        cmd.passphrase.backup_passphrase_file.as_deref(),
+        "ALICE_BACKUP_PASSPHRASE",
+        "Backup passphrase: ",
+        false,
+    )?;
+    let mut backup = Backup::open(&archive, &passphrase)?;
+
+    if !cmd.tables.is_empty() {
+        if let Some(missing) = cmd.tables.iter().find(|t| !backup.tables.contains_key(*t)) {
+            writeln!(io.stderr(), "No table {:?} in the backup", missing)?;
+            return Ok(1)
+        }
+        backup.tables.retain(|table, _| cmd.tables.contains(table));
+    }
+    if !cmd.with_nonces {
+        for table in NONCE_TABLES {
+            if cmd.tables.iter().any(|t| t == table) {
+                writeln!(io.stderr(), "The table {:?} holds nonces: see `--with-nonces`", table)?;
+                return Ok(1)
+            }
+            if backup.tables.remove(*table).is_some() {
+                writeln!(io.stderr(), "Skipped the nonces in {:?}: see `--with-nonces`", table)?;
+            }
+        }
+    }
+    for records in backup.tables.values_mut() {
+        records.retain(|id, _| id.starts_with(&cmd.prefix));
+    }
+    backup.tables.retain(|_, records| !records.is_empty());
+
+    let tables = backup
+        .tables
+        .keys()
+        .map(|table| RawTable::open(&storage, table))
+        .collect::<Result<Vec<_>, _>>()?;
+    let any_tables = tables.iter().map(|table| table as &dyn AnyTable).collect::<Vec<_>>();
+
+    // all the records are restored, or (if any is in the way) none
+    let restored = storage.transaction(&any_tables, |tx| {
+        let mut conflicts = BTreeMap::<String, Vec<&str>>::new();
+        let mut report = BTreeMap::new();
+        for (table, records) in tables.iter().zip(backup.tables.values()) {
+            let tx_table = tx.raw_table(table)?;
+            for (id, record) in records {
+                if !cmd.overwrite && tx_table.contains(id)? {
+                    conflicts.entry(table.name()).or_default().push(id);
+                }
+                tx_table.insert(id, record.version, &record.record)?;
+            }
+            report.insert(table.name(), records.len());
+        }
+        Ok(if conflicts.is_empty() { Ok(report) } else { Err(conflicts) })
+    })?;
+    let report = match restored {
+        Ok(report) => report,
+        Err(conflicts) => {
+            writeln!(io.stderr(), "Already in the storage (see `--overwrite`):")?;
+            serde_yaml::to_writer(io.stderr(), &conflicts)?;
+            return Ok(1)
+        },
+    };
+    storage.flush()?;
+
+    serde_yaml::to_writer(io.stdout(), &report)?;
+
+    Ok(0)
+}
@@ -0,0 +1,380 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::io::Write;
+use std::str::FromStr;
+
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use structopt::StructOpt;
+
+use cli_storage::{AnyTable, RawTable, Record, Storage, LEGACY_VERSION};
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar};
+
+use crate::caps::IO;
+use crate::data::{Key, Threshold, S4};
+use crate::{AnyError, RetCode};
+
+/// Bring the records up to date, by running the migration steps in turn.
+///
+/// The steps:
+/// - `stable-table-ids`: the tables were named after the types of their records, they are moved to
+///   their stable identifiers;
+/// - `explicit-thresholds`: the thresholds stored as a bare number are rewritten as "T-of-N". The
+///   DKG sessions record all the parties, so their threshold is recovered. So is that of the
+///   key-shares issued by a scheme still in the storage (it recorded the degree of its polynomial).
+///   The count of the key-shares of the other entries was never recorded: it is to be given;
+/// - `record-versions`: the records are stamped with the version of their schema.
+///
+/// Nothing is written unless all the steps succeed.
+#[derive(Debug, StructOpt)]
+pub struct CmdMigrate {
+    /// Report the records to rewrite, and leave them intact.
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// The threshold of a key-share: "KEY-ID=T-of-N".
+    #[structopt(long = "key", number_of_values = 1)]
+    keys: Vec<ThresholdOf>,
+
+    /// The threshold of a scheme: "SCHEME-ID=T-of-N".
+    #[structopt(long = "scheme", number_of_values = 1)]
+    schemes: Vec<ThresholdOf>,
+}
+
+#[derive(Debug, Clone)]
+struct ThresholdOf {
+    id: String,
+    threshold: Threshold,
+}
+
+impl FromStr for ThresholdOf {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, threshold) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected: \"ID=T-of-N\", got {:?}", s))?;
+        Ok(Self { id: id.to_owned(), threshold: threshold.parse()? })
+    }
+}
+
+/// The records of all the tables, keyed by the table and the id: the steps rewrite it.
+type Snapshot = BTreeMap<String, BTreeMap<String, Stored>>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Stored {
+    version: u32,
+    record: Value,
+}
+
+/// A migration step: rewrites the snapshot, and reports the rewrites (pushes to `unresolved` what
+/// it can't rewrite without help).
+struct Step {
+    name: &'static str,
+    run: fn(&CmdMigrate, &mut Snapshot, &mut Vec<String>) -> Result<Value, AnyError>,
+}
+
+const STEPS: &[Step] = &[
+    Step { name: "stable-table-ids", run: stable_table_ids },
+    Step { name: "explicit-thresholds", run: explicit_thresholds },
+    Step { name: "record-versions", run: record_versions },
+];
+
+/// The tables: named after the types of their records before they had stable identifiers.
+const TABLES: &[(&str, &str)] = &[
+    ("rabbit_hole_cli_alice::data::Key", "keys"),
+    ("rabbit_hole_cli_alice::data::S4", "s4/schemes"),
+    ("rabbit_hole_cli_alice::cli::dkg::csi_rashi::Session", "dkg/csi-rashi/sessions"),
+    ("rabbit_hole_cli_alice::cli::tss::frost::Nonces", "tss/frost/nonces"),
+];
+
+const CSI_RASHI_SESSIONS: &str = "dkg/csi-rashi/sessions";
+const GJKR_SESSIONS: &str = "dkg/gjkr/sessions";
+
+/// Refuse a storage with the tables to move: its records would seem to be missing.
+pub(in crate::cli) fn ensure_migrated(storage: &Storage) -> Result<(), AnyError> {
+    let table_names = storage.table_names()?;
+    if TABLES.iter().any(|(legacy, _)| table_names.iter().any(|name| name == legacy)) {
+        return Err("The storage is of an earlier version: run `alice storage migrate`".into())
+    }
+    Ok(())
+}
+
+pub fn run(cmd: &CmdMigrate, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let mut snapshot = Snapshot::new();
+    for name in storage.table_names()? {
+        let records = RawTable::open(&storage, &name)?
+            .select("")
+            .map(|item| item.map(|(id, version, record)| (id, Stored { version, record })))
+            .collect::<Result<_, AnyError>>()?;
+        snapshot.insert(name, records);
+    }
+    let stored = snapshot.clone();
+
+    let mut unresolved = vec![];
+    let mut report = BTreeMap::new();
+    for step in STEPS {
+        let rewrites = (step.run)(cmd, &mut snapshot, &mut unresolved)?;
+        if !rewrites.as_object().is_some_and(serde_json::Map::is_empty) {
+            report.insert(step.name, rewrites);
+        }
+    }
+
+    if !unresolved.is_empty() {
+        for message in unresolved {
+            writeln!(io.stderr(), "{}", message)?;
+        }
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(io.stdout(), &report)?;
+
+    if !cmd.dry_run {
+        write(&storage, &stored, &snapshot)?;
+        storage.flush()?;
+    }
+
+    Ok(0)
+}
+
+/// Write the difference between the `stored` records and the `migrated` ones.
+fn write(storage: &Storage, stored: &Snapshot, migrated: &Snapshot) -> Result<(), AnyError> {
+    let tables = stored
+        .keys()
+        .chain(migrated.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|name| RawTable::open(storage, name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let any_tables = tables.iter().map(|table| table as &dyn AnyTable).collect::<Vec<_>>();
+
+    // the records are rewritten all at once; the tables left empty are dropped afterwards
+    let written = storage.transaction(&any_tables, |tx| {
+        for table in &tables {
+            let tx_table = tx.raw_table(table)?;
+            let (before, after) = (stored.get(&table.name()), migrated.get(&table.name()));
+            for (id, record) in after.into_iter().flatten() {
+                if before.and_then(|before| before.get(id)) != Some(record) {
+                    tx_table.insert(id, record.version, &record.record)?;
+                }
+            }
+            for id in before.into_iter().flat_map(BTreeMap::keys) {
+                if !after.is_some_and(|after| after.contains_key(id)) {
+                    tx_table.remove(id)?;
+                }
+            }
+        }
+        Ok(Ok::<_, Infallible>(()))
+    })?;
+    if let Err(never) = written {
+        match never {}
+    }
+
+    for name in stored.keys() {
+        if !migrated.contains_key(name) {
+            storage.drop_table(name)?;
+        }
+    }
+    Ok(())
+}
+
+fn stable_table_ids(
+    _cmd: &CmdMigrate,
+    snapshot: &mut Snapshot,
+    unresolved: &mut Vec<String>,
+) -> Result<Value, AnyError> {
+    let mut rewrites = BTreeMap::new();
+    for (legacy, table) in TABLES {
+        let Some(records) = snapshot.remove(*legacy) else { continue };
+        let moved_to = snapshot.entry(table.to_string()).or_default();
+        for (id, record) in records {
+            if moved_to.contains_key(&id) {
+                unresolved
+                    .push(format!("The record {:?} is both in {:?} and {:?}", id, legacy, table));
+            }
+            moved_to.insert(id, record);
+        }
+        rewrites.insert(*legacy, *table);
+    }
+    Ok(serde_json::to_value(rewrites)?)
+}
+
+fn explicit_thresholds(
+    cmd: &CmdMigrate,
+    snapshot: &mut Snapshot,
+    unresolved: &mut Vec<String>,
+) -> Result<Value, AnyError> {
+    let override_of = |overrides: &[ThresholdOf], id: &str| {
+        overrides.iter().rev().find(|o| o.id == id).map(|o| o.threshold)
+    };
+
+    // the schemes: `t` is the count of the coefficients
+    let mut schemes = BTreeMap::new();
+    let mut scheme_rewrites = BTreeMap::new();
+    for (id, stored) in records_mut(snapshot, S4::TABLE) {
+        let entry = &mut stored.record;
+        let curve: CurveSelect = field(entry, "curve")?;
+        let polynomial: Vec<Scalar> = field(entry, "polynomial")?;
+        let public_key = public_key_of(curve, &polynomial)?;
+
+        let threshold = if stored.version == LEGACY_VERSION && entry["threshold"].is_null() {
+            match override_of(&cmd.schemes, id) {
+                Some(threshold) if threshold.t == polynomial.len() => {
+                    entry["threshold"] = serde_json::to_value(threshold)?;
+                    scheme_rewrites.insert(id.to_owned(), threshold.to_string());
+                    Some(threshold)
+                },
+                Some(threshold) => {
+                    unresolved.push(format!(
+                        "The threshold of the scheme {:?} does not match its polynomial \
+                         [threshold: {}; coefficients-count: {}]",
+                        id,
+                        threshold,
+                        polynomial.len()
+                    ));
+                    None
+                },
+                None => {
+                    unresolved.push(format!(
+                        "The threshold of the scheme {:?} is not recorded: pass `--scheme {}={}-of-N`",
+                        id,
+                        id,
+                        polynomial.len()
+                    ));
+                    None
+                },
+            }
+        } else {
+            Some(field(entry, "threshold")?)
+        };
+        schemes.insert(id.to_owned(), (curve, public_key, polynomial.len(), threshold));
+    }
+
+    // the key-shares: those issued by a scheme recorded the degree of its polynomial, those of the
+    // DKG recorded `t`
+    let mut key_rewrites = BTreeMap::new();
+    let mut issued: BTreeMap<String, Vec<Scalar>> = BTreeMap::new();
+    for (id, stored) in records_mut(snapshot, Key::TABLE) {
+        let Some(s4_share) = stored.record.get_mut("s4_share") else { continue };
+        let curve: CurveSelect = field(s4_share, "curve")?;
+        let public_key: Point = field(s4_share, "public_key")?;
+        let x: Scalar = field(s4_share, "x")?;
+
+        let issued_by = schemes
+            .iter()
+            .filter(|(_, (c, p, _, _))| *c == curve && *p == public_key)
+            .collect::<Vec<_>>();
+        for (scheme_id, _) in issued_by.iter() {
+            issued.entry(scheme_id.to_string()).or_default().push(x.clone());
+        }
+
+        if stored.version != LEGACY_VERSION {
+            continue
+        }
+        let Value::Number(threshold) = &s4_share["threshold"] else { continue };
+        let threshold = threshold.as_u64().ok_or("Invalid threshold")? as usize;
+        let recovered = match issued_by.as_slice() {
+            [(_, (_, _, len, Some(scheme_threshold)))] if *len == threshold + 1 =>
+                Some(Threshold { t: *len, n: scheme_threshold.n }),
+            _ => None,
+        };
+        let Some(threshold) = override_of(&cmd.keys, id).or(recovered) else {
+            unresolved.push(format!(
+                "The threshold of the key-share {:?} is not recorded: pass `--key {}=T-of-N`",
+                id, id
+            ));
+            continue
+        };
+        s4_share["threshold"] = serde_json::to_value(threshold)?;
+        key_rewrites.insert(id.to_owned(), threshold.to_string());
+    }
+
+    // the legacy schemes did not keep track of the key-shares they issued: those in the storage are
+    for (id, stored) in records_mut(snapshot, S4::TABLE) {
+        if stored.version == LEGACY_VERSION && stored.record.get("issued").is_none() {
+            stored.record["issued"] = serde_json::to_value(issued.remove(id).unwrap_or_default())?;
+        }
+    }
+
+    let rewrites = BTreeMap::from([
+        (Key::TABLE, key_rewrites),
+        (S4::TABLE, scheme_rewrites),
+        (CSI_RASHI_SESSIONS, session_thresholds(snapshot, CSI_RASHI_SESSIONS)?),
+        (GJKR_SESSIONS, session_thresholds(snapshot, GJKR_SESSIONS)?),
+    ]);
+    let rewrites = rewrites
+        .into_iter()
+        .filter(|(_, rewrites)| !rewrites.is_empty())
+        .collect::<BTreeMap<_, _>>();
+    Ok(serde_json::to_value(rewrites)?)
+}
+
+/// The DKG sessions record the `x` of all the parties: `n` is the count of these.
+fn session_thresholds(
+    snapshot: &mut Snapshot,
+    table: &str,
+) -> Result<BTreeMap<String, String>, AnyError> {
+    let mut rewrites = BTreeMap::new();
+    for (id, stored) in records_mut(snapshot, table) {
+        if stored.version != LEGACY_VERSION {
+            continue
+        }
+        let entry = &mut stored.record;
+        let Value::Number(t) = &entry["threshold"] else { continue };
+        let t = t.as_u64().ok_or("Invalid threshold")? as usize;
+        let n = entry["s4_xs"].as_array().ok_or("Invalid session")?.len();
+
+        let threshold = Threshold { t, n };
+        entry["threshold"] = serde_json::to_value(threshold)?;
+        rewrites.insert(id.to_owned(), threshold.to_string());
+    }
+    Ok(rewrites)
+}
+
+fn record_versions(
+    _cmd: &CmdMigrate,
+    snapshot: &mut Snapshot,
+    _unresolved: &mut Vec<String>,
+) -> Result<Value, AnyError> {
+    let mut rewrites = BTreeMap::new();
+    for (_, table) in TABLES {
+        for (_, stored) in records_mut(snapshot, table) {
+            if stored.version == LEGACY_VERSION {
+                stored.version = 1;
+                *rewrites.entry(*table).or_insert(0usize) += 1;
+            }
+        }
+    }
+    Ok(serde_json::to_value(rewrites)?)
+}
+
+fn records_mut<'a>(
+    snapshot: &'a mut Snapshot,
+    table: &str,
+) -> impl Iterator<Item = (&'a String, &'a mut Stored)> {
+    snapshot.get_mut(table).into_iter().flat_map(|records| records.iter_mut())
+}
+
+fn field<T: DeserializeOwned>(entry: &Value, name: &str) -> Result<T, AnyError> {
+    let value = entry.get(name).ok_or_else(|| format!("No {:?} in the entry", name))?;
+    Ok(serde_json::from_value(value.clone())?)
+}
+
+fn public_key_of(curve: CurveSelect, polynomial: &[Scalar]) -> Result<Point, AnyError> {
+    fn public_key_of_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+        curve: CurveSelect,
+        polynomial: &[Scalar],
+    ) -> Result<Point, AnyError> {
+        let secret = polynomial.first().ok_or("Empty polynomial")?.restore::<F>(curve)?;
+        Ok(Point::from_value(curve, G::generator() * secret))
+    }
+
+    specialize_call!(public_key_of_typed, (curve, polynomial), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint)
+    ]).ok_or(format!("Unsupported curve: {}", curve))?
+}
@@ -5,7 +5,7 @@ use cli_storage::Storage;
 
 use crate::caps::IO;
 
-// mod dkls;
+mod dkls;
 mod frost;
 
 #[derive(Debug, StructOpt)]
@@ -16,7 +16,7 @@ pub struct CmdTss {
 
 #[derive(Debug, StructOpt)]
 enum Cmd {
-    // Dkls(dkls::CmdDkls),
+    Dkls(dkls::CmdDkls),
     Frost(frost::CmdFrost),
 }
 
@@ -27,7 +27,7 @@ pub fn run(
     storage: Storage,
 ) -> Result<crate::RetCode, crate::AnyError> {
     match &tss.cmd {
-        // Cmd::Dkls(sub) => dkls::run(sub, rng, io, storage),
+        Cmd::Dkls(sub) => dkls::run(sub, rng, io, storage),
         Cmd::Frost(sub) => frost::run(sub, rng, io, storage),
     }
 }
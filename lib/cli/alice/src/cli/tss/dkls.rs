@@ -1,23 +1,133 @@
+use ff::PrimeField;
+use group::GroupEncoding;
 use rand::RngCore;
 use structopt::StructOpt;
 
-use cli_storage::Storage;
+use cli_storage::{Storage, Table};
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar};
+use shamir_sss::LagrangeCoefficientAt;
 
 use crate::caps::IO;
-use crate::cli::RetCode;
-use crate::AnyError;
+use crate::data::{Key, S4Share};
+use crate::{AnyError, RetCode};
 
 mod aggregator;
 mod cosigner;
 
+/// The number of OT-instances per multiplication: should not be less than the bit-size of the
+/// scalar field.
+const L: usize = 256;
+
+/// DKLs is a two-party protocol.
+const PARTIES: usize = 2;
+
 #[derive(Debug, StructOpt)]
-pub struct CmdDkls {}
+pub struct CmdDkls {
+    #[structopt(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    Aggregator(aggregator::CmdAggregator),
+    Cosigner(cosigner::CmdCosigner),
+}
 
 pub fn run(
-    _dkls: &CmdDkls,
-    _rng: impl RngCore,
-    _io: impl IO,
-    _storage: Storage,
+    dkls: &CmdDkls,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
 ) -> Result<RetCode, AnyError> {
-    unimplemented!()
+    match &dkls.cmd {
+        Cmd::Aggregator(sub) => aggregator::run(sub, rng, io, storage),
+        Cmd::Cosigner(sub) => cosigner::run(sub, rng, io, storage),
+    }
+}
+
+fn s4_share(storage: &Storage, key_id: &str) -> Result<S4Share, AnyError> {
+    let tab_keys: Table<Key> = Table::open(storage)?;
+    let Key::S4Share(s4_share) = tab_keys.get(key_id)?.ok_or("No such key")? else {
+        return Err("the key should be an S4-share".into())
+    };
+    if s4_share.threshold.t != PARTIES {
+        return Err(format!(
+            "Invalid threshold [expected: {}; actual: {}]",
+            PARTIES, s4_share.threshold
+        )
+        .into())
+    }
+    Ok(s4_share)
+}
+
+/// The additive share of the secret key held by the party at `idx` in `[aggregator_x,
+/// cosigner_x]`.
+fn additive_share<F: PrimeField>(
+    s4_share: &S4Share,
+    shamir_xs: [&Scalar; PARTIES],
+    idx: usize,
+) -> Result<F, AnyError> {
+    let curve = s4_share.curve;
+
+    if shamir_xs[0] == shamir_xs[1] {
+        return Err(format!("Both parties hold the same share: {}", shamir_xs[0]).into())
+    }
+    let shamir_xs = [shamir_xs[0].restore::<F>(curve)?, shamir_xs[1].restore::<F>(curve)?];
+    let shamir_y = s4_share.y.restore::<F>(curve)?;
+
+    Ok(shamir_xs.lagrange_coefficient_at(idx, F::ZERO) * shamir_y)
+}
+
+fn session_key(key_id: &str, session_id: &str) -> String {
+    format!("{}[{}]", key_id, session_id)
+}
+
+fn restore_thrice<T, U>(
+    items: &[Vec<T>],
+    restore: impl Fn(&T) -> Result<U, AnyError>,
+) -> Result<[Vec<U>; 3], AnyError> {
+    let [a, b, c] = items else {
+        return Err(format!("Expected three MtA-instances, got {}", items.len()).into())
+    };
+    let restore_one = |items: &Vec<T>| {
+        if items.len() != L {
+            return Err(AnyError::from(format!(
+                "Invalid MtA-instance size [expected: {}; actual: {}]",
+                L,
+                items.len()
+            )))
+        }
+        items.iter().map(&restore).collect::<Result<Vec<_>, _>>()
+    };
+    Ok([restore_one(a)?, restore_one(b)?, restore_one(c)?])
+}
+
+fn store_thrice<T, U>(items: &[Vec<T>; 3], store: impl Fn(&T) -> U) -> Vec<Vec<U>> {
+    items.iter().map(|items| items.iter().map(&store).collect()).collect()
+}
+
+fn restore_scalars<F: PrimeField>(
+    curve: CurveSelect,
+    items: &[Vec<Scalar>],
+) -> Result<[Vec<F>; 3], AnyError> {
+    restore_thrice(items, |s| s.restore::<F>(curve))
+}
+
+fn store_scalars<F: PrimeField>(curve: CurveSelect, items: &[Vec<F>; 3]) -> Vec<Vec<Scalar>> {
+    store_thrice(items, |s| Scalar::from_value(curve, *s))
+}
+
+fn restore_points<G: GroupEncoding>(
+    curve: CurveSelect,
+    items: &[Vec<Point>],
+) -> Result<[Vec<G>; 3], AnyError> {
+    restore_thrice(items, |p| p.restore::<G>(curve))
+}
+
+fn store_points<G: GroupEncoding + Copy>(
+    curve: CurveSelect,
+    items: &[Vec<G>; 3],
+) -> Vec<Vec<Point>> {
+    store_thrice(items, |p| Point::from_value(curve, *p))
 }
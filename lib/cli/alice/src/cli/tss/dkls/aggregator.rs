@@ -1 +1,427 @@
+use std::io::Write;
 
+use digest::Digest;
+use elliptic_curve::bigint::U256;
+use elliptic_curve::ops::Reduce;
+use elliptic_curve::point::AffineCoordinates;
+use ff::PrimeField;
+use group::{Curve, Group, GroupEncoding};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
+use common_interop::transcript::Transcript;
+use common_interop::types::{Point, Scalar, SecretScalar};
+
+use crate::caps::IO;
+use crate::data::S4Share;
+use crate::{ecdsa, transcript, AnyError, RetCode};
+
+use super::L;
+
+#[derive(Debug, StructOpt)]
+pub enum CmdAggregator {
+    Choose(CmdChoose),
+    Finalize(CmdFinalize),
+    Sign(CmdSign),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdChoose {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(long, short)]
+    hash_function: HashFunctionSelect,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdFinalize {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdSign {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Session {
+    Chosen(Chosen),
+    Finalized(Finalized),
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "tss/dkls/aggregator/sessions";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chosen {
+    hash_function: HashFunctionSelect,
+    phi: SecretScalar,
+    k_a: SecretScalar,
+    r: Point,
+    mta_k: Vec<Vec<Point>>,
+    mta_t: Vec<Vec<Scalar>>,
+    mta_s: Vec<Vec<Scalar>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Finalized {
+    hash_function: HashFunctionSelect,
+    phi: SecretScalar,
+    k_a: SecretScalar,
+    r: Point,
+    t_1_a: SecretScalar,
+    t_2_a: SecretScalar,
+}
+
+pub fn run(
+    cmd: &CmdAggregator,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdAggregator::Choose(sub) => run_choose(sub, rng, io, storage),
+        CmdAggregator::Finalize(sub) => run_finalize(sub, io, storage),
+        CmdAggregator::Sign(sub) => run_sign(sub, io, storage),
+    }
+}
+
+fn run_choose(
+    choose: &CmdChoose,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &choose.key_id)?;
+    let curve = s4_share.curve;
+    let hash_function = choose.hash_function;
+
+    specialize_call!(
+        run_choose_typed,
+        (choose, &s4_share, rng, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    )
+    .ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_finalize(
+    finalize: &CmdFinalize,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &finalize.key_id)?;
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&finalize.key_id, &finalize.session_id);
+    let Some(Session::Chosen(chosen)) = tab_sessions.get(&session_key)? else {
+        writeln!(io.stderr(), "No chosen session: {}", session_key)?;
+        return Ok(1)
+    };
+    let hash_function = chosen.hash_function;
+
+    specialize_call!(
+        run_finalize_typed,
+        (finalize, &s4_share, &chosen, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    )
+    .ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_sign(sign: &CmdSign, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &sign.key_id)?;
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&sign.key_id, &sign.session_id);
+    let Some(Session::Finalized(finalized)) = tab_sessions.get(&session_key)? else {
+        writeln!(io.stderr(), "No finalized session: {}", session_key)?;
+        return Ok(1)
+    };
+    let hash_function = finalized.hash_function;
+
+    specialize_call!(
+        run_sign_typed,
+        (sign, &s4_share, &finalized, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    )
+    .ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_choose_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
+    choose: &CmdChoose,
+    s4_share: &S4Share,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&choose.key_id, &choose.session_id);
+    if tab_sessions.get(&session_key)?.is_some() {
+        writeln!(io.stderr(), "The session already exists: {}", session_key)?;
+        return Ok(1)
+    }
+
+    #[derive(Deserialize)]
+    struct Input {
+        shamir_x: Scalar,
+        d_b: Point,
+        mta_pa: Vec<Vec<Point>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        shamir_x: Scalar,
+        r_seed: Point,
+        mta_pb: Vec<Vec<Point>>,
+        mta_s: Vec<Vec<Scalar>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let t_0_a = super::additive_share::<F>(s4_share, [&s4_share.x, &input.shamir_x], 0)?;
+
+    let d_b = input.d_b.restore::<G>(curve)?;
+    let mta_pa = super::restore_points::<G>(curve, &input.mta_pa)?;
+
+    if bool::from(d_b.is_identity()) {
+        return Err("The cosigner's instance point is the identity".into())
+    }
+
+    let mut phi = F::ZERO;
+    let mut k_a = F::ZERO;
+    let mut r = G::identity();
+    let mut r_seed = G::identity();
+    let mut mta_pb: [_; 3] = core::array::from_fn(|_| vec![G::identity(); L]);
+    let mut mta_k: [_; 3] = core::array::from_fn(|_| vec![G::identity(); L]);
+    let mut mta_t: [_; 3] = core::array::from_fn(|_| vec![F::ZERO; L]);
+    let mut mta_s: [_; 3] = core::array::from_fn(|_| vec![F::ZERO; L]);
+
+    dkls_tss::a::presign_choose::<F, G, H, L>(
+        rng,
+        &t_0_a,
+        &d_b,
+        &mut phi,
+        &mut k_a,
+        &mut r,
+        &mut r_seed,
+        &mta_pa,
+        &mut mta_pb,
+        &mut mta_k,
+        &mut mta_t,
+        &mut mta_s,
+    );
+
+    let mta_s = super::store_scalars(curve, &mta_s);
+    let chosen = Chosen {
+        hash_function: choose.hash_function,
+        phi: SecretScalar::from_value(curve, phi),
+        k_a: SecretScalar::from_value(curve, k_a),
+        r: Point::from_value(curve, r),
+        mta_k: super::store_points(curve, &mta_k),
+        mta_t: super::store_scalars(curve, &mta_t),
+        mta_s: mta_s.clone(),
+    };
+    if !tab_sessions.insert_new(&session_key, &Session::Chosen(chosen))? {
+        writeln!(io.stderr(), "The session already exists: {}", session_key)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            shamir_x: s4_share.x.clone(),
+            r_seed: Point::from_value(curve, r_seed),
+            mta_pb: super::store_points(curve, &mta_pb),
+            mta_s,
+        },
+    )?;
+
+    Ok(0)
+}
+
+fn run_finalize_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
+    finalize: &CmdFinalize,
+    s4_share: &S4Share,
+    chosen: &Chosen,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&finalize.key_id, &finalize.session_id);
+
+    #[derive(Deserialize)]
+    struct Input {
+        mta_e: Vec<Vec<[Scalar; 2]>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        r: Point,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let mta_e = super::restore_thrice(&input.mta_e, |[e0, e1]| {
+        Ok([e0.restore::<F>(curve)?, e1.restore::<F>(curve)?])
+    })?;
+    let mta_k = super::restore_points::<G>(curve, &chosen.mta_k)?;
+    let mta_t = super::restore_scalars::<F>(curve, &chosen.mta_t)?;
+    let mta_s = super::restore_scalars::<F>(curve, &chosen.mta_s)?;
+
+    let mut t_1_a = F::ZERO;
+    let mut t_2_a = F::ZERO;
+
+    dkls_tss::a::presign_finalize::<F, G, H, L>(
+        &mut t_1_a, &mut t_2_a, &mta_s, &mta_e, &mta_t, &mta_k,
+    );
+
+    let finalized = Finalized {
+        hash_function: chosen.hash_function,
+        phi: chosen.phi.clone(),
+        k_a: chosen.k_a.clone(),
+        r: chosen.r.clone(),
+        t_1_a: SecretScalar::from_value(curve, t_1_a),
+        t_2_a: SecretScalar::from_value(curve, t_2_a),
+    };
+    // the session is finalized once: unless finalized (or replaced) since it was read
+    let session = Session::Finalized(finalized);
+    let stored = storage.transaction(&[&tab_sessions], |tx| {
+        let tx_sessions = tx.table(&tab_sessions)?;
+        if !matches!(tx_sessions.get(&session_key)?, Some(Session::Chosen(c)) if c.r == chosen.r) {
+            return Ok(Err(()))
+        }
+        tx_sessions.insert(&session_key, &session)?;
+        Ok(Ok(()))
+    })?;
+    if stored.is_err() {
+        writeln!(io.stderr(), "No chosen session: {}", session_key)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(io.stdout(), &Output { r: chosen.r.clone() })?;
+
+    Ok(0)
+}
+
+fn run_sign_typed<F, G, H>(
+    sign: &CmdSign,
+    s4_share: &S4Share,
+    finalized: &Finalized,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError>
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr>,
+    G: Group<Scalar = F> + GroupEncoding + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+    H: Digest,
+{
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&sign.key_id, &sign.session_id);
+
+    #[derive(Deserialize)]
+    struct Input {
+        transcript: Transcript,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        eta_phi: Scalar,
+        eta_sig: Scalar,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let public_key = s4_share.public_key.restore::<G>(curve)?;
+    let m = ecdsa::digest_to_scalar::<F>(&transcript::produce_digest(&input.transcript)?);
+
+    let phi = finalized.phi.restore::<F>(curve)?;
+    let k_a = finalized.k_a.restore::<F>(curve)?;
+    let r = finalized.r.restore::<G>(curve)?;
+    let t_1_a = finalized.t_1_a.restore::<F>(curve)?;
+    let t_2_a = finalized.t_2_a.restore::<F>(curve)?;
+
+    // the presignature should never be used twice: the removal is persisted before the output
+    let removed = storage.transaction(&[&tab_sessions], |tx| {
+        match tx.table(&tab_sessions)?.remove(&session_key)? {
+            Some(Session::Finalized(f)) if f.r == finalized.r => Ok(Ok(())),
+            _ => Ok(Err(())),
+        }
+    })?;
+    if removed.is_err() {
+        return Err(format!("The session is already used: {}", session_key).into())
+    }
+    storage.flush()?;
+
+    let mut eta_phi = F::ZERO;
+    let mut eta_sig = F::ZERO;
+    dkls_tss::a::sign::<F, G, H>(
+        &public_key,
+        &k_a,
+        &t_1_a,
+        &t_2_a,
+        &phi,
+        &r,
+        &m,
+        &mut eta_phi,
+        &mut eta_sig,
+    );
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            eta_phi: Scalar::from_value(curve, eta_phi),
+            eta_sig: Scalar::from_value(curve, eta_sig),
+        },
+    )?;
+
+    Ok(0)
+}
+
+fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
+    Table::open(storage)
+}
@@ -1 +1,403 @@
+use std::io::Write;
 
+use digest::Digest;
+use elliptic_curve::bigint::U256;
+use elliptic_curve::ops::Reduce;
+use elliptic_curve::point::AffineCoordinates;
+use elliptic_curve::scalar::IsHigh;
+use ff::PrimeField;
+use group::{Curve, Group, GroupEncoding};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
+use common_interop::transcript::Transcript;
+use common_interop::types::{Point, Scalar, SecretScalar};
+
+use crate::caps::IO;
+use crate::data::S4Share;
+use crate::{ecdsa, transcript, AnyError, RetCode};
+
+use super::L;
+
+#[derive(Debug, StructOpt)]
+pub enum CmdCosigner {
+    Offer(CmdOffer),
+    Reply(CmdReply),
+    Sign(CmdSign),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdOffer {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(long, short)]
+    hash_function: HashFunctionSelect,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdReply {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdSign {
+    #[structopt(long, short)]
+    key_id: String,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Session {
+    Offered(Offered),
+    Replied(Replied),
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "tss/dkls/cosigner/sessions";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Offered {
+    hash_function: HashFunctionSelect,
+    k_b: SecretScalar,
+    d_b: Point,
+    mta_a: Vec<Vec<Scalar>>,
+    mta_d: Vec<Vec<Scalar>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Replied {
+    hash_function: HashFunctionSelect,
+    k_b: SecretScalar,
+    t_1_b: SecretScalar,
+    t_2_b: SecretScalar,
+    r: Point,
+}
+
+pub fn run(
+    cmd: &CmdCosigner,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdCosigner::Offer(sub) => run_offer(sub, rng, io, storage),
+        CmdCosigner::Reply(sub) => run_reply(sub, io, storage),
+        CmdCosigner::Sign(sub) => run_sign(sub, io, storage),
+    }
+}
+
+fn run_offer(
+    offer: &CmdOffer,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &offer.key_id)?;
+    let curve = s4_share.curve;
+
+    specialize_call!(run_offer_typed, (offer, &s4_share, rng, io, storage), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+    ])
+    .ok_or(format!("Unsupported curve: {}", curve))?
+}
+
+fn run_reply(reply: &CmdReply, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &reply.key_id)?;
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&reply.key_id, &reply.session_id);
+    let Some(Session::Offered(offered)) = tab_sessions.get(&session_key)? else {
+        writeln!(io.stderr(), "No offered session: {}", session_key)?;
+        return Ok(1)
+    };
+    let hash_function = offered.hash_function;
+
+    specialize_call!(
+        run_reply_typed,
+        (reply, &s4_share, &offered, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    )
+    .ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_sign(sign: &CmdSign, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let s4_share = super::s4_share(&storage, &sign.key_id)?;
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&sign.key_id, &sign.session_id);
+    let Some(Session::Replied(replied)) = tab_sessions.get(&session_key)? else {
+        writeln!(io.stderr(), "No replied session: {}", session_key)?;
+        return Ok(1)
+    };
+    let hash_function = replied.hash_function;
+
+    specialize_call!(
+        run_sign_typed,
+        (sign, &s4_share, &replied, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    )
+    .ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_offer_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    offer: &CmdOffer,
+    s4_share: &S4Share,
+    rng: impl RngCore,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&offer.key_id, &offer.session_id);
+    if tab_sessions.get(&session_key)?.is_some() {
+        writeln!(io.stderr(), "The session already exists: {}", session_key)?;
+        return Ok(1)
+    }
+
+    #[derive(Serialize)]
+    struct Output {
+        shamir_x: Scalar,
+        d_b: Point,
+        mta_pa: Vec<Vec<Point>>,
+    }
+
+    let mut k_b = F::ZERO;
+    let mut d_b = G::identity();
+    let mut mta_a: [_; 3] = core::array::from_fn(|_| vec![F::ZERO; L]);
+    let mut mta_d: [_; 3] = core::array::from_fn(|_| vec![F::ZERO; L]);
+    let mut mta_pa: [_; 3] = core::array::from_fn(|_| vec![G::identity(); L]);
+
+    dkls_tss::b::presign_offer::<F, G, L>(
+        rng,
+        &mut k_b,
+        &mut d_b,
+        &mut mta_a,
+        &mut mta_d,
+        &mut mta_pa,
+    );
+
+    let d_b = Point::from_value(curve, d_b);
+    let offered = Offered {
+        hash_function: offer.hash_function,
+        k_b: SecretScalar::from_value(curve, k_b),
+        d_b: d_b.clone(),
+        mta_a: super::store_scalars(curve, &mta_a),
+        mta_d: super::store_scalars(curve, &mta_d),
+    };
+    if !tab_sessions.insert_new(&session_key, &Session::Offered(offered))? {
+        writeln!(io.stderr(), "The session already exists: {}", session_key)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output { shamir_x: s4_share.x.clone(), d_b, mta_pa: super::store_points(curve, &mta_pa) },
+    )?;
+
+    Ok(0)
+}
+
+fn run_reply_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
+    reply: &CmdReply,
+    s4_share: &S4Share,
+    offered: &Offered,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&reply.key_id, &reply.session_id);
+
+    #[derive(Deserialize)]
+    struct Input {
+        shamir_x: Scalar,
+        r_seed: Point,
+        mta_pb: Vec<Vec<Point>>,
+        mta_s: Vec<Vec<Scalar>>,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        mta_e: Vec<Vec<[Scalar; 2]>>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let t_0_b = super::additive_share::<F>(s4_share, [&input.shamir_x, &s4_share.x], 1)?;
+
+    let r_seed = input.r_seed.restore::<G>(curve)?;
+    let mta_pb = super::restore_points::<G>(curve, &input.mta_pb)?;
+    let mta_s = super::restore_scalars::<F>(curve, &input.mta_s)?;
+
+    let k_b = offered.k_b.restore::<F>(curve)?;
+    let d_b = offered.d_b.restore::<G>(curve)?;
+    let mta_a = super::restore_scalars::<F>(curve, &offered.mta_a)?;
+    let mta_d = super::restore_scalars::<F>(curve, &offered.mta_d)?;
+
+    let mut t_1_b = F::ZERO;
+    let mut t_2_b = F::ZERO;
+    let mut r = G::identity();
+    let mut mta_e: [_; 3] = core::array::from_fn(|_| vec![[F::ZERO; 2]; L]);
+
+    dkls_tss::b::presign_reply::<F, G, H, L>(
+        &r_seed, &t_0_b, &k_b, &d_b, &mut t_1_b, &mut t_2_b, &mut r, &mta_d, &mta_a, &mta_pb,
+        &mta_s, &mut mta_e,
+    );
+
+    if bool::from(r.is_identity()) {
+        return Err("The instance point is the identity".into())
+    }
+
+    let replied = Replied {
+        hash_function: offered.hash_function,
+        k_b: offered.k_b.clone(),
+        t_1_b: SecretScalar::from_value(curve, t_1_b),
+        t_2_b: SecretScalar::from_value(curve, t_2_b),
+        r: Point::from_value(curve, r),
+    };
+    // the session is replied to once: unless replied to (or replaced) since it was read
+    let session = Session::Replied(replied);
+    let stored = storage.transaction(&[&tab_sessions], |tx| {
+        let tx_sessions = tx.table(&tab_sessions)?;
+        if !matches!(tx_sessions.get(&session_key)?, Some(Session::Offered(o)) if o.d_b == offered.d_b)
+        {
+            return Ok(Err(()))
+        }
+        tx_sessions.insert(&session_key, &session)?;
+        Ok(Ok(()))
+    })?;
+    if stored.is_err() {
+        writeln!(io.stderr(), "No offered session: {}", session_key)?;
+        return Ok(1)
+    }
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            mta_e: mta_e
+                .iter()
+                .map(|es| {
+                    es.iter().map(|e| e.map(|e| Scalar::from_value(curve, e))).collect::<Vec<_>>()
+                })
+                .collect(),
+        },
+    )?;
+
+    Ok(0)
+}
+
+fn run_sign_typed<F, G, H>(
+    sign: &CmdSign,
+    s4_share: &S4Share,
+    replied: &Replied,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError>
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr> + IsHigh,
+    G: Group<Scalar = F> + GroupEncoding + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+    H: Digest,
+{
+    let curve = s4_share.curve;
+
+    let tab_sessions = sessions_table(&storage)?;
+    let session_key = super::session_key(&sign.key_id, &sign.session_id);
+
+    #[derive(Deserialize)]
+    struct Input {
+        transcript: Transcript,
+        eta_phi: Scalar,
+        eta_sig: Scalar,
+    }
+    #[derive(Serialize)]
+    struct Output {
+        y: Point,
+        r: Scalar,
+        s: Scalar,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let public_key = s4_share.public_key.restore::<G>(curve)?;
+    let m = ecdsa::digest_to_scalar::<F>(&transcript::produce_digest(&input.transcript)?);
+    let eta_phi = input.eta_phi.restore::<F>(curve)?;
+    let eta_sig = input.eta_sig.restore::<F>(curve)?;
+
+    let k_b = replied.k_b.restore::<F>(curve)?;
+    let t_1_b = replied.t_1_b.restore::<F>(curve)?;
+    let t_2_b = replied.t_2_b.restore::<F>(curve)?;
+    let r = replied.r.restore::<G>(curve)?;
+
+    // the presignature should never be used twice: the removal is persisted before the output
+    let removed = storage.transaction(&[&tab_sessions], |tx| {
+        match tx.table(&tab_sessions)?.remove(&session_key)? {
+            Some(Session::Replied(r)) if r.r == replied.r => Ok(Ok(())),
+            _ => Ok(Err(())),
+        }
+    })?;
+    if removed.is_err() {
+        return Err(format!("The session is already used: {}", session_key).into())
+    }
+    storage.flush()?;
+
+    let mut s =
+        dkls_tss::b::sign::<F, G, H>(&public_key, &eta_phi, &eta_sig, &k_b, &t_1_b, &t_2_b, &r, &m);
+    let r = ecdsa::r_x::<F, G>(&r);
+    if bool::from(s.is_high()) {
+        s = -s;
+    }
+
+    if !ecdsa::verify::<F, G>(&public_key, &m, &r, &s) {
+        return Err("The aggregator's signature-share is invalid".into())
+    }
+
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            y: s4_share.public_key.clone(),
+            r: Scalar::from_value(curve, r),
+            s: Scalar::from_value(curve, s),
+        },
+    )?;
+
+    Ok(0)
+}
+
+fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
+    Table::open(storage)
+}
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use cli_storage::Table;
+use cli_storage::{Record, Table};
 use common_interop::curve_select::CurveSelect;
 use common_interop::hash_function_select::HashFunctionSelect;
-use common_interop::transcript::Transcript;
-use common_interop::types::{Point, Scalar};
+use common_interop::transcript::{Input as MessageInput, Transcript};
+use common_interop::types::{Point, Scalar, SecretScalar};
 use digest::Digest;
 use ff::PrimeField;
 use group::{Group, GroupEncoding};
@@ -15,8 +19,12 @@ use structopt::StructOpt;
 use cli_storage::Storage;
 
 use crate::caps::IO;
+use crate::challenge::{Challenge, ChallengeOpts};
 use crate::data::{Key, S4Share};
-use crate::{transcript, AnyError, RetCode};
+use crate::{AnyError, RetCode};
+
+mod coordinator;
+mod nonces;
 
 #[derive(Debug, StructOpt)]
 pub struct CmdFrost {
@@ -28,13 +36,50 @@ pub struct CmdFrost {
 enum Cmd {
     Prepare(CmdPrepare),
     Sign(CmdSign),
+    /// Aggregate the shards of the signers into the signature, or report the invalid ones.
+    ///
+    /// Takes the `signers` (as given to `sign`), the `public_key` and the `verification_shares`
+    /// from a trusted source, and the `shards` (as produced by `sign`, keyed by the `x` of the
+    /// signers). The shards of the honest signers can't be aggregated without the others (both the
+    /// binding factors and the Lagrange coefficients depend on the whole set of signers): if some
+    /// are invalid, a new signing session among the honest signers (with fresh nonces) is needed.
     Aggregate(CmdAggregate),
+    /// Print the `x`, the public key and the verification share (`g * y`) of the key-share: the
+    /// aggregators check the shards of the signer against it.
+    VerificationShare(CmdVerificationShare),
+    Coordinator(coordinator::CmdCoordinator),
+    Nonces(nonces::CmdNonces),
+}
+
+/// The output of `sign`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Shard {
+    y: Point,
+    r: Point,
+    z: Scalar,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Nonces {
-    d: Scalar,
-    e: Scalar,
+    d: SecretScalar,
+    e: SecretScalar,
+    /// Unix-time (seconds) of `prepare`.
+    #[serde(default)]
+    created_at: u64,
+    /// Unix-time (seconds) after which the nonces can not be used for signing.
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+impl Record for Nonces {
+    const TABLE: &'static str = "tss/frost/nonces";
+    const VERSION: u32 = 1;
+}
+
+impl Nonces {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
 }
 
 #[derive(Debug, StructOpt)]
@@ -44,6 +89,10 @@ struct CmdPrepare {
 
     #[structopt(long, short)]
     count: usize,
+
+    /// The number of seconds the nonces can be used for signing.
+    #[structopt(long)]
+    ttl: Option<u64>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -53,6 +102,15 @@ struct CmdSign {
 
     #[structopt(long, short)]
     hash_function: HashFunctionSelect,
+
+    #[structopt(flatten)]
+    challenge: ChallengeOpts,
+}
+
+#[derive(Debug, StructOpt)]
+struct CmdVerificationShare {
+    #[structopt(long, short)]
+    key_id: String,
 }
 
 #[derive(Debug, StructOpt)]
@@ -62,6 +120,52 @@ struct CmdAggregate {
 
     #[structopt(long, short)]
     hash_function: HashFunctionSelect,
+
+    #[structopt(flatten)]
+    challenge: ChallengeOpts,
+
+    /// The threshold of the key: if some of the shards are invalid, the report tells whether the
+    /// honest signers are enough for a new signing session (`can_retry`).
+    #[structopt(long, short)]
+    threshold: Option<usize>,
+}
+
+/// The report on the invalid shards produced by `aggregate`.
+#[derive(Debug, Serialize)]
+struct Report {
+    complaints: BTreeMap<Scalar, Complaint>,
+    honest: Vec<Scalar>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    can_retry: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Complaint {
+    VerificationShareMismatch,
+    CommitmentMismatch,
+    InvalidResponse,
+}
+
+impl fmt::Display for Complaint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::VerificationShareMismatch => "verification_share_mismatch",
+            Self::CommitmentMismatch => "commitment_mismatch",
+            Self::InvalidResponse => "invalid_response",
+        };
+        f.write_str(name)
+    }
+}
+
+impl From<frost_tss::Complaint> for Complaint {
+    fn from(complaint: frost_tss::Complaint) -> Self {
+        match complaint {
+            frost_tss::Complaint::VerificationShareMismatch => Self::VerificationShareMismatch,
+            frost_tss::Complaint::CommitmentMismatch => Self::CommitmentMismatch,
+            frost_tss::Complaint::InvalidResponse => Self::InvalidResponse,
+        }
+    }
 }
 
 pub fn run(
@@ -74,6 +178,9 @@ pub fn run(
         Cmd::Prepare(sub) => run_prepare(sub, rng, io, storage),
         Cmd::Sign(sub) => run_sign(sub, io, storage),
         Cmd::Aggregate(sub) => run_aggregate(sub, io),
+        Cmd::VerificationShare(sub) => run_verification_share(sub, io, storage),
+        Cmd::Coordinator(sub) => coordinator::run(sub, io, storage),
+        Cmd::Nonces(sub) => nonces::run(sub, io, storage),
     }
 }
 
@@ -119,6 +226,24 @@ fn run_sign(sign: &CmdSign, io: impl IO, storage: Storage) -> Result<RetCode, An
     ).ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
 }
 
+fn run_verification_share(
+    verification_share: &CmdVerificationShare,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let tab_keys = keys_table(&storage)?;
+    let Key::S4Share(s4_share) = tab_keys.get(&verification_share.key_id)?.ok_or("No such key")?
+    else {
+        return Err("the key should be an S4-share".into());
+    };
+    let curve = s4_share.curve;
+    specialize_call!(run_verification_share_typed, (&s4_share, io), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or(format!("Unsupported curve: {}", curve))?
+}
+
 fn run_aggregate(aggregate: &CmdAggregate, io: impl IO) -> Result<RetCode, AnyError> {
     let curve = aggregate.curve;
     let hash_function = aggregate.hash_function;
@@ -148,25 +273,67 @@ fn run_prepare_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
 
     let tab_nonces = nonces_table(&storage)?;
 
+    let created_at = now()?;
+    let expires_at = prepare.ttl.map(|ttl| created_at.saturating_add(ttl));
+
     let mut nonces = vec![(F::ZERO, F::ZERO); prepare.count];
     let mut commitments = vec![(G::identity(), G::identity()); prepare.count];
 
     frost_tss::preprocess(rng, nonces.as_mut(), commitments.as_mut());
 
-    let nonces = nonces
-        .into_iter()
-        .map(|(d, e)| (Scalar::from_value(curve, d), Scalar::from_value(curve, e)))
-        .collect::<Vec<_>>();
     let commitments = commitments
         .into_iter()
         .map(|(pd, pe)| (Point::from_value(curve, pd), Point::from_value(curve, pe)))
         .collect::<Vec<_>>();
+    let nonces = nonces
+        .into_iter()
+        .zip(&commitments)
+        .map(|((d, e), (pd, pe))| {
+            let d = SecretScalar::from_value(curve, d);
+            let e = SecretScalar::from_value(curve, e);
+            (nonce_key(&prepare.key_id, pd, pe), Nonces { d, e, created_at, expires_at })
+        })
+        .collect::<Vec<_>>();
 
-    for i in 0..prepare.count {
-        let (d, e) = nonces[i].clone();
-        let (pd, pe) = &commitments[i];
-        tab_nonces.insert(&nonce_key(&prepare.key_id, pd, pe), &Nonces { d, e })?;
+    // the transaction can't scan the table: it re-reads the pool as selected, so that the pool is
+    // counted along with the inserts
+    let max_pool = nonces::max_pool(&prepare.key_id, &storage)?;
+    let pool_keys = nonces::select(&prepare.key_id, &storage)?
+        .into_iter()
+        .map(|(nonce_key, _)| nonce_key)
+        .collect::<Vec<_>>();
+    let stored = storage.transaction(&[&tab_nonces], |tx| {
+        let tx_nonces = tx.table(&tab_nonces)?;
+        let mut pool = 0;
+        for nonce_key in &pool_keys {
+            match tx_nonces.get(nonce_key)? {
+                // the expired nonces can't be used anyway: they don't take up the pool
+                Some(stored) if stored.is_expired(created_at) => {
+                    tx_nonces.remove(nonce_key)?;
+                },
+                Some(_) => pool += 1,
+                None => (),
+            }
+        }
+        if pool + prepare.count > max_pool {
+            return Ok(Err(pool))
+        }
+        for (nonce_key, nonces) in &nonces {
+            tx_nonces.insert(nonce_key, nonces)?;
+        }
+        Ok(Ok(()))
+    })?;
+    if let Err(pool) = stored {
+        writeln!(
+            io.stderr(),
+            "The nonce pool is full [max-pool: {}; unused: {}; requested: {}]",
+            max_pool,
+            pool,
+            prepare.count
+        )?;
+        return Ok(1)
     }
+    storage.flush()?;
 
     serde_yaml::to_writer(io.stdout(), &commitments)?;
 
@@ -185,7 +352,8 @@ fn run_sign_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest
 
     #[derive(Deserialize)]
     struct Input {
-        transcript: Transcript,
+        transcript: Option<Transcript>,
+        message: Option<Vec<MessageInput>>,
         signers: Vec<(Scalar, Point, Point)>,
     }
     #[derive(Serialize)]
@@ -196,30 +364,47 @@ fn run_sign_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest
     }
 
     let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let challenge = Challenge::new(&sign.challenge, curve, input.transcript, input.message)?;
+
+    // the binding factors depend on the order of the commitments: the signers and the aggregator
+    // order them by the identifiers (`x`) ascending
+    let mut signers = input
+        .signers
+        .into_iter()
+        .map(|(x, cd, ce)| Ok((x.restore::<F>(curve)?, x, cd, ce)))
+        .collect::<Result<Vec<_>, AnyError>>()?;
+    signers.sort_by(|(x_a, ..), (x_b, ..)| utils::cmp_scalars(x_a, x_b));
 
-    if input.signers.len() != s4_share.threshold {
+    if signers.len() != s4_share.threshold.t {
         return Err(format!(
             "Invalid threshold [expected: {}; commitments-count: {}]",
             s4_share.threshold,
-            input.signers.len()
+            signers.len()
         )
         .into())
     }
+    // repeated or zero `x` would break the Lagrange coefficients: rejected before the nonces are
+    // burnt
+    if signers.iter().any(|(x, ..)| bool::from(x.is_zero())) {
+        return Err("the signer's `x` should not be zero".into())
+    }
+    if let Some(w) = signers.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(format!("Repeated signer's `x`: {}", w[0].1).into())
+    }
 
-    let participant_id = input.signers.iter().position(|(x, _, _)| x == &s4_share.x).ok_or(
+    let participant_id = signers.iter().position(|(_, x, _, _)| x == &s4_share.x).ok_or(
         format!("Proposed commitments do not contain this key-share's `x`: {}", s4_share.x),
     )?;
     let public_key = s4_share.public_key.restore::<G>(curve)?;
 
-    let (_, cd, ce) = &input.signers[participant_id];
+    let (_, _, cd, ce) = &signers[participant_id];
     let nonce_key = nonce_key(&sign.key_id, cd, ce);
 
     let (shamir_xs, commitments): (Vec<_>, Vec<_>) = {
-        let tmp = input
-            .signers
+        let tmp = signers
             .iter()
-            .map(|(x, cd, ce)| {
-                let x = x.restore::<F>(curve)?;
+            .map(|(x, _, cd, ce)| {
+                let x = *x;
                 let cd = cd.restore::<G>(curve)?;
                 let ce = ce.restore::<G>(curve)?;
                 Ok((x, (cd, ce)))
@@ -227,21 +412,32 @@ fn run_sign_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest
             .collect::<Result<Vec<_>, AnyError>>()?;
         tmp.into_iter().unzip()
     };
+    // the nonces are removed (and the removal is persisted) before the shard is produced: should
+    // anything fail from now on, the commitment is burnt rather than left available for reuse.
     let Some(nonces) = tab_nonces.remove(&nonce_key)? else {
         return Err(format!("Unknown commitment: {}-{}", cd, ce).into())
     };
+    storage.flush()?;
+    if nonces.is_expired(now()?) {
+        return Err(format!("Expired commitment: {}-{}", cd, ce).into())
+    }
 
     let shamir_y = s4_share.y.restore::<F>(curve)?;
 
-    let (y, r, z) = frost_tss::sign::<F, G, H>(
+    let error = Cell::new(None);
+    let (y, r, z) = frost_tss::sign_normalized::<F, G, H>(
         &public_key,
         participant_id,
         &shamir_y,
         &shamir_xs,
         &(nonces.d.restore::<F>(curve)?, nonces.e.restore::<F>(curve)?),
         &commitments,
-        |y, r| transcript::produce_challenge(&input.transcript, y, r).expect("Invalid transcript"),
+        |p| challenge.is_odd(p),
+        |y, r| challenge.produce_or_keep(y, r, &error),
     );
+    if let Some(reason) = error.take() {
+        return Err(reason)
+    }
 
     serde_yaml::to_writer(
         io.stdout(),
@@ -255,6 +451,32 @@ fn run_sign_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest
     Ok(0)
 }
 
+fn run_verification_share_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    s4_share: &S4Share,
+    io: impl IO,
+) -> Result<RetCode, AnyError> {
+    let curve = s4_share.curve;
+
+    #[derive(Serialize)]
+    struct Output<'a> {
+        x: &'a Scalar,
+        public_key: &'a Point,
+        verification_share: Point,
+    }
+
+    let shamir_y = s4_share.y.restore::<F>(curve)?;
+    serde_yaml::to_writer(
+        io.stdout(),
+        &Output {
+            x: &s4_share.x,
+            public_key: &s4_share.public_key,
+            verification_share: Point::from_value(curve, G::generator() * shamir_y),
+        },
+    )?;
+
+    Ok(0)
+}
+
 fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
     aggregate: &CmdAggregate,
     io: impl IO,
@@ -262,34 +484,112 @@ fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: D
     let curve = aggregate.curve;
 
     #[derive(Deserialize)]
-    struct Shard {
-        c: (Point, Point),
-        y: Point,
-        r: Point,
-        z: Scalar,
+    struct Input {
+        transcript: Option<Transcript>,
+        message: Option<Vec<MessageInput>>,
+        public_key: Point,
+        verification_shares: BTreeMap<Scalar, Point>,
+        signers: Vec<(Scalar, Point, Point)>,
+        shards: BTreeMap<Scalar, Shard>,
     }
 
-    #[derive(Deserialize)]
-    struct Input {
-        transcript: Transcript,
-        shards: HashMap<Scalar, Shard>,
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    let challenge = Challenge::new(&aggregate.challenge, curve, input.transcript, input.message)?;
+
+    let mut signers = BTreeMap::new();
+    for (x, cd, ce) in input.signers {
+        if signers.insert(x.clone(), (cd, ce)).is_some() {
+            writeln!(io.stderr(), "Duplicate signer: {}", x)?;
+            return Ok(1)
+        }
     }
 
-    #[derive(Serialize)]
-    struct Output {
-        y: Point,
-        r: Point,
-        s: Scalar,
+    let Some(signature) = aggregate_typed::<F, G, H>(
+        curve,
+        &challenge,
+        &input.public_key,
+        &input.verification_shares,
+        &signers,
+        input.shards,
+        aggregate.threshold,
+        &io,
+    )?
+    else {
+        return Ok(1)
+    };
+    serde_yaml::to_writer(io.stdout(), &signature)?;
+
+    Ok(0)
+}
+
+/// The signature aggregated from the shards, as output by `aggregate`.
+#[derive(Serialize)]
+struct Signature {
+    y: Point,
+    r: Point,
+    s: Scalar,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+/// Aggregates the shards into the [`Signature`]; otherwise outputs the [`Report`] on the invalid
+/// shards (or the reason the shards can't be aggregated) and returns `None`.
+///
+/// The `public_key`, the `verification_shares` (as printed by `verification-share`) and the
+/// commitments of the `signers` should come from a trusted source rather than from the signers:
+/// a signer misreporting its commitment would change the binding factors of all the others.
+#[allow(clippy::too_many_arguments)]
+fn aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
+    curve: CurveSelect,
+    challenge: &Challenge,
+    public_key: &Point,
+    verification_shares: &BTreeMap<Scalar, Point>,
+    signers: &BTreeMap<Scalar, (Point, Point)>,
+    mut shards: BTreeMap<Scalar, Shard>,
+    threshold: Option<usize>,
+    io: impl IO,
+) -> Result<Option<Signature>, AnyError> {
+    if let Some(x) = shards.keys().find(|x| !signers.contains_key(x)) {
+        writeln!(io.stderr(), "Not a signer: {}", x)?;
+        return Ok(None)
+    }
+    let missing = signers
+        .keys()
+        .filter(|x| !shards.contains_key(x))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        writeln!(io.stderr(), "Missing shards: [{}]", missing.join(", "))?;
+        return Ok(None)
     }
 
-    let input: Input = serde_yaml::from_reader(io.stdin())?;
+    // the same order as of `sign`
+    let mut input_shards = signers
+        .iter()
+        .map(|(x, c)| {
+            let shard = shards.remove(x).ok_or_else(|| format!("Missing shard: {}", x))?;
+            Ok((x.restore::<F>(curve)?, x.clone(), c, shard))
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?;
+    input_shards.sort_by(|(x_a, ..), (x_b, ..)| utils::cmp_scalars(x_a, x_b));
+    let input_xs = input_shards.iter().map(|(_, x, ..)| x.clone()).collect::<Vec<_>>();
+
+    let public_key = public_key.restore::<G>(curve)?;
+
     let mut shards: Vec<(G, G, F)> = vec![];
     let mut commitments: Vec<(G, G)> = vec![];
     let mut shamir_xs: Vec<F> = vec![];
-    let mut complaints: Vec<bool> = vec![false; input.shards.len()];
-
-    for (shamir_x, Shard { c: (cd, ce), y, r, z }) in input.shards.into_iter() {
-        let shamir_x = shamir_x.restore::<F>(curve)?;
+    let mut signer_verification_shares: Vec<G> = vec![];
+    let mut complaints = vec![None; input_shards.len()];
+
+    for (shamir_x, input_x, (cd, ce), Shard { y, r, z }) in input_shards.into_iter() {
+        let Some(verification_share) = verification_shares.get(&input_x) else {
+            writeln!(io.stderr(), "No verification share for the signer: {}", input_x)?;
+            return Ok(None)
+        };
+        signer_verification_shares.push(verification_share.restore::<G>(curve)?);
         let cd = cd.restore::<G>(curve)?;
         let ce = ce.restore::<G>(curve)?;
         let y = y.restore::<G>(curve)?;
@@ -301,30 +601,63 @@ fn run_aggregate_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: D
         shards.push((y, r, z));
     }
 
-    let (y, r, s) = frost_tss::aggregate::<F, G, H>(
+    let error = Cell::new(None);
+    let aggregated = frost_tss::aggregate_normalized::<F, G, H>(
+        &public_key,
+        signer_verification_shares.as_ref(),
         shards.as_ref(),
         shamir_xs.as_ref(),
         commitments.as_ref(),
         complaints.as_mut(),
-        |y, r| transcript::produce_challenge(&input.transcript, y, r).expect("Invalid transcript"),
-    )?;
-
-    serde_yaml::to_writer(
-        io.stdout(),
-        &Output {
-            y: Point::from_value(curve, y),
-            r: Point::from_value(curve, r),
-            s: Scalar::from_value(curve, s),
-        },
-    )?;
-
-    Ok(0)
+        |p| challenge.is_odd(p),
+        |y, r| challenge.produce_or_keep(y, r, &error),
+    );
+    if let Some(reason) = error.take() {
+        return Err(reason)
+    }
+    let Ok((y, r, s)) = aggregated else {
+        // the shards of the honest signers can't be aggregated without the others' (see `Cmd`)
+        let honest = input_xs
+            .iter()
+            .zip(&complaints)
+            .filter(|(_, complaint)| complaint.is_none())
+            .map(|(x, _)| x.clone())
+            .collect::<Vec<_>>();
+        let report = Report {
+            can_retry: threshold.map(|threshold| honest.len() >= threshold),
+            complaints: input_xs
+                .into_iter()
+                .zip(complaints)
+                .filter_map(|(x, complaint)| Some((x, complaint?.into())))
+                .collect(),
+            honest,
+        };
+
+        writeln!(io.stderr(), "Invalid shards: {}", report.complaints.len())?;
+        serde_yaml::to_writer(io.stdout(), &report)?;
+
+        return Ok(None)
+    };
+    let public_key = challenge.public_key_bytes(&y).map(hex::encode);
+    let signature = challenge.signature_bytes(&r, &s).map(hex::encode);
+
+    Ok(Some(Signature {
+        y: Point::from_value(curve, y),
+        r: Point::from_value(curve, r),
+        s: Scalar::from_value(curve, s),
+        public_key,
+        signature,
+    }))
 }
 
 fn nonce_key(key_id: &str, cd: &Point, ce: &Point) -> String {
     format!("{}[{}-{}]", key_id, cd, ce)
 }
 
+fn now() -> Result<u64, AnyError> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
 fn keys_table(storage: &Storage) -> Result<Table<Key>, AnyError> {
     Table::open(storage)
 }
@@ -0,0 +1,506 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use digest::Digest;
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
+use common_interop::transcript::{Input as MessageInput, Transcript};
+use common_interop::types::{Point, Scalar};
+
+use crate::caps::IO;
+use crate::challenge::{Challenge, ChallengeOpts};
+use crate::{AnyError, RetCode};
+
+use super::{aggregate_typed, Complaint, Shard};
+
+#[derive(Debug, StructOpt)]
+pub enum CmdCoordinator {
+    /// Start a signing session: takes the `transcript` (or the `message`) to be signed, the
+    /// `public_key` and the `verification_shares` of the signers (keyed by their `x`).
+    Start(CmdStart),
+    /// Add the commitments (as produced by `prepare`) of the signers, keyed by their `x`.
+    AddCommitments(CmdSession),
+    /// Produce the input for `sign` of each of the signers (keyed by their `x`): no commitments
+    /// can be added afterwards.
+    SignRequest(CmdSession),
+    /// Add the shards (as produced by `sign`) of the signers, keyed by their `x`: each shard is
+    /// checked against the signer's verification share.
+    AddShard(CmdSession),
+    /// Aggregate the shards and close the session.
+    Finish(CmdSession),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdStart {
+    #[structopt(long, short, env = "ALICE_CURVE")]
+    curve: CurveSelect,
+
+    #[structopt(long, short)]
+    hash_function: HashFunctionSelect,
+
+    #[structopt(flatten)]
+    challenge: ChallengeOpts,
+
+    /// The number of the signers required.
+    #[structopt(long, short)]
+    threshold: Option<usize>,
+
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdSession {
+    #[structopt(name = "SESSION-ID")]
+    session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    curve: CurveSelect,
+    hash_function: HashFunctionSelect,
+    rfc8032: bool,
+    bip340: bool,
+    threshold: Option<usize>,
+    transcript: Option<Transcript>,
+    message: Option<Vec<MessageInput>>,
+    public_key: Point,
+    verification_shares: BTreeMap<Scalar, Point>,
+    commitments: BTreeMap<Scalar, (Point, Point)>,
+    requested: bool,
+    shards: BTreeMap<Scalar, Shard>,
+}
+
+impl Record for Session {
+    const TABLE: &'static str = "tss/frost/coordinator/sessions";
+    const VERSION: u32 = 1;
+}
+
+impl Session {
+    fn challenge(&self) -> Result<Challenge, AnyError> {
+        let opts = ChallengeOpts { rfc8032: self.rfc8032, bip340: self.bip340 };
+        Challenge::new(&opts, self.curve, self.transcript.clone(), self.message.clone())
+    }
+
+    /// What the updates of the session depend on.
+    fn stage(&self) -> (bool, Vec<&Scalar>, Vec<&Scalar>) {
+        (self.requested, self.commitments.keys().collect(), self.shards.keys().collect())
+    }
+}
+
+pub fn run(cmd: &CmdCoordinator, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdCoordinator::Start(sub) => run_start(sub, io, storage),
+        CmdCoordinator::AddCommitments(sub) => run_add_commitments(sub, io, storage),
+        CmdCoordinator::SignRequest(sub) => run_sign_request(sub, io, storage),
+        CmdCoordinator::AddShard(sub) => run_add_shard(sub, io, storage),
+        CmdCoordinator::Finish(sub) => run_finish(sub, io, storage),
+    }
+}
+
+fn run_start(start: &CmdStart, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let curve = start.curve;
+    specialize_call!(run_start_typed, (start, io, storage), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or(format!("Unsupported curve: {}", curve))?
+}
+
+fn run_start_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    start: &CmdStart,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = start.curve;
+    let tab_sessions = sessions_table(&storage)?;
+    if start.threshold == Some(0) {
+        return Err("the threshold should be positive".into())
+    }
+
+    #[derive(Deserialize)]
+    struct Input {
+        transcript: Option<Transcript>,
+        message: Option<Vec<MessageInput>>,
+        public_key: Point,
+        verification_shares: BTreeMap<Scalar, Point>,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    input.public_key.restore::<G>(curve)?;
+    for (x, verification_share) in &input.verification_shares {
+        if bool::from(x.restore::<F>(curve)?.is_zero()) {
+            return Err("the signer's `x` should not be zero".into())
+        }
+        verification_share.restore::<G>(curve)?;
+    }
+
+    let session = Session {
+        curve: start.curve,
+        hash_function: start.hash_function,
+        rfc8032: start.challenge.rfc8032,
+        bip340: start.challenge.bip340,
+        threshold: start.threshold,
+        transcript: input.transcript,
+        message: input.message,
+        public_key: input.public_key,
+        verification_shares: input.verification_shares,
+        commitments: Default::default(),
+        requested: false,
+        shards: Default::default(),
+    };
+    session.challenge()?;
+
+    if !tab_sessions.insert_new(&start.session_id, &session)? {
+        writeln!(io.stderr(), "The session already exists: {}", start.session_id)?;
+        return Ok(1)
+    }
+
+    Ok(0)
+}
+
+fn run_add_commitments(
+    add: &CmdSession,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(&add.session_id)? else {
+        writeln!(io.stderr(), "No such session: {}", add.session_id)?;
+        return Ok(1)
+    };
+    let curve = session.curve;
+
+    specialize_call!(run_add_commitments_typed, (add, session, io, storage), curve, [
+        (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+        (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+    ]).ok_or(format!("Unsupported curve: {}", curve))?
+}
+
+fn run_sign_request(
+    request: &CmdSession,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(mut session) = tab_sessions.get(&request.session_id)? else {
+        writeln!(io.stderr(), "No such session: {}", request.session_id)?;
+        return Ok(1)
+    };
+
+    /// The input for `sign` of a signer.
+    #[derive(Serialize)]
+    struct Request<'a> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transcript: Option<&'a Transcript>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<&'a Vec<MessageInput>>,
+        signers: &'a [(Scalar, Point, Point)],
+    }
+
+    if session.commitments.is_empty() {
+        writeln!(io.stderr(), "No commitments: {}", request.session_id)?;
+        return Ok(1)
+    }
+    if let Some(threshold) = session.threshold.filter(|t| *t != session.commitments.len()) {
+        writeln!(
+            io.stderr(),
+            "Invalid number of signers [expected: {}; actual: {}]",
+            threshold,
+            session.commitments.len()
+        )?;
+        return Ok(1)
+    }
+
+    if !session.requested {
+        let read = session.clone();
+        session.requested = true;
+        if !update(&storage, &request.session_id, &read, Some(&session))? {
+            writeln!(io.stderr(), "The session has changed meanwhile: {}", request.session_id)?;
+            return Ok(1)
+        }
+    }
+
+    // `sign` orders the signers by `x` anyway
+    let signers = session
+        .commitments
+        .iter()
+        .map(|(x, (cd, ce))| (x.clone(), cd.clone(), ce.clone()))
+        .collect::<Vec<_>>();
+    let requests = session
+        .commitments
+        .keys()
+        .map(|x| {
+            let request = Request {
+                transcript: session.transcript.as_ref(),
+                message: session.message.as_ref(),
+                signers: &signers,
+            };
+            (x, request)
+        })
+        .collect::<BTreeMap<_, _>>();
+    serde_yaml::to_writer(io.stdout(), &requests)?;
+
+    Ok(0)
+}
+
+fn run_add_shard(add: &CmdSession, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(&add.session_id)? else {
+        writeln!(io.stderr(), "No such session: {}", add.session_id)?;
+        return Ok(1)
+    };
+    let curve = session.curve;
+    let hash_function = session.hash_function;
+
+    specialize_call!(
+        run_add_shard_typed,
+        (add, session, io, storage),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+            (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+            (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    ).ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))?
+}
+
+fn run_finish(finish: &CmdSession, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let tab_sessions = sessions_table(&storage)?;
+    let Some(session) = tab_sessions.get(&finish.session_id)? else {
+        writeln!(io.stderr(), "No such session: {}", finish.session_id)?;
+        return Ok(1)
+    };
+    let curve = session.curve;
+    let hash_function = session.hash_function;
+    let read = session.clone();
+
+    if !session.requested {
+        writeln!(io.stderr(), "The signing request is not produced yet: {}", finish.session_id)?;
+        return Ok(1)
+    }
+    let missing = session
+        .commitments
+        .keys()
+        .filter(|x| !session.shards.contains_key(x))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        writeln!(io.stderr(), "Missing shards: [{}]", missing.join(", "))?;
+        return Ok(1)
+    }
+
+    let challenge = session.challenge()?;
+    let threshold = session.threshold;
+
+    // the session (with the shards) is kept until the signature is out: the nonces of the signers
+    // are consumed, so the shards could not be produced again
+    let Some(signature) = specialize_call!(
+        aggregate_typed,
+        (
+            curve,
+            &challenge,
+            &session.public_key,
+            &session.verification_shares,
+            &session.commitments,
+            session.shards,
+            threshold,
+            &io
+        ),
+        (curve, hash_function),
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+            (CurveSelect::Ed25519 => curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint),
+            (CurveSelect::Ristretto25519 => curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint),
+        ],
+        [
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256)
+        ]
+    ).ok_or(format!("Unsupported curve or hash-function: {}/{}", curve, hash_function))??
+    else {
+        return Ok(1)
+    };
+    serde_yaml::to_writer(io.stdout(), &signature)?;
+
+    if !update(&storage, &finish.session_id, &read, None)? {
+        writeln!(io.stderr(), "The session has changed meanwhile: {}", finish.session_id)?;
+        return Ok(1)
+    }
+
+    Ok(0)
+}
+
+fn run_add_commitments_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    add: &CmdSession,
+    mut session: Session,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = session.curve;
+    let read = session.clone();
+
+    if session.requested {
+        writeln!(io.stderr(), "The signing request is already produced: {}", add.session_id)?;
+        return Ok(1)
+    }
+
+    let input: BTreeMap<Scalar, (Point, Point)> = serde_yaml::from_reader(io.stdin())?;
+
+    for (x, (cd, ce)) in input {
+        if bool::from(x.restore::<F>(curve)?.is_zero()) {
+            return Err("the signer's `x` should not be zero".into())
+        }
+        if bool::from(cd.restore::<G>(curve)?.is_identity()) ||
+            bool::from(ce.restore::<G>(curve)?.is_identity())
+        {
+            return Err(format!("The commitment of the signer is the identity: {}", x).into())
+        }
+        if !session.verification_shares.contains_key(&x) {
+            writeln!(io.stderr(), "No verification share for the signer: {}", x)?;
+            return Ok(1)
+        }
+        if session.commitments.contains_key(&x) {
+            writeln!(io.stderr(), "The signer's commitments are already added: {}", x)?;
+            return Ok(1)
+        }
+        session.commitments.insert(x, (cd, ce));
+    }
+
+    if let Some(threshold) = session.threshold.filter(|t| *t < session.commitments.len()) {
+        writeln!(
+            io.stderr(),
+            "Too many signers [expected: {}; actual: {}]",
+            threshold,
+            session.commitments.len()
+        )?;
+        return Ok(1)
+    }
+
+    if !update(&storage, &add.session_id, &read, Some(&session))? {
+        writeln!(io.stderr(), "The session has changed meanwhile: {}", add.session_id)?;
+        return Ok(1)
+    }
+
+    Ok(0)
+}
+
+fn run_add_shard_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding, H: Digest>(
+    add: &CmdSession,
+    mut session: Session,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let curve = session.curve;
+    let read = session.clone();
+
+    if !session.requested {
+        writeln!(io.stderr(), "The signing request is not produced yet: {}", add.session_id)?;
+        return Ok(1)
+    }
+
+    let input: BTreeMap<Scalar, Shard> = serde_yaml::from_reader(io.stdin())?;
+
+    let challenge = session.challenge()?;
+    let public_key = session.public_key.restore::<G>(curve)?;
+    // the same order as of `sign`
+    let mut signers = session
+        .commitments
+        .iter()
+        .map(|(x, (cd, ce))| {
+            Ok((x, x.restore::<F>(curve)?, (cd.restore(curve)?, ce.restore(curve)?)))
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?;
+    signers.sort_by(|(_, x_a, _), (_, x_b, _)| utils::cmp_scalars(x_a, x_b));
+    let shamir_xs = signers.iter().map(|(_, x, _)| *x).collect::<Vec<_>>();
+    let commitments = signers.iter().map(|(_, _, c)| *c).collect::<Vec<(G, G)>>();
+
+    for (x, shard) in input {
+        let Some(participant_id) = signers.iter().position(|(signer, ..)| *signer == &x) else {
+            writeln!(io.stderr(), "Not a signer: {}", x)?;
+            return Ok(1)
+        };
+        if session.shards.contains_key(&x) {
+            writeln!(io.stderr(), "The signer's shard is already added: {}", x)?;
+            return Ok(1)
+        }
+
+        let verification_share = session.verification_shares[&x].restore::<G>(curve)?;
+        let error = Cell::new(None);
+        let verified = frost_tss::verify_shard_normalized::<F, G, H>(
+            &public_key,
+            &verification_share,
+            participant_id,
+            &(shard.y.restore(curve)?, shard.r.restore(curve)?, shard.z.restore(curve)?),
+            &shamir_xs,
+            &commitments,
+            |p| challenge.is_odd(p),
+            |y, r| challenge.produce_or_keep(y, r, &error),
+        );
+        if let Some(reason) = error.take() {
+            return Err(reason)
+        }
+        if let Err(complaint) = verified {
+            writeln!(
+                io.stderr(),
+                "Invalid shard [signer: {}; complaint: {}]",
+                x,
+                Complaint::from(complaint)
+            )?;
+            return Ok(1)
+        }
+
+        session.shards.insert(x, shard);
+    }
+
+    if !update(&storage, &add.session_id, &read, Some(&session))? {
+        writeln!(io.stderr(), "The session has changed meanwhile: {}", add.session_id)?;
+        return Ok(1)
+    }
+
+    Ok(0)
+}
+
+/// Store the session updated from the one `read` (or remove it), unless the session has been
+/// updated (or removed) since it was read: `false` if it has.
+fn update(
+    storage: &Storage,
+    session_id: &str,
+    read: &Session,
+    session: Option<&Session>,
+) -> Result<bool, AnyError> {
+    let tab_sessions = sessions_table(storage)?;
+    let updated = storage.transaction(&[&tab_sessions], |tx| {
+        let tx_sessions = tx.table(&tab_sessions)?;
+        if tx_sessions
+            .get(session_id)?
+            .filter(|stored| stored.stage() == read.stage())
+            .is_none()
+        {
+            return Ok(Err(()))
+        }
+        match session {
+            Some(session) => tx_sessions.insert(session_id, session)?,
+            None => tx_sessions.remove(session_id)?,
+        };
+        Ok(Ok(()))
+    })?;
+    Ok(updated.is_ok())
+}
+
+fn sessions_table(storage: &Storage) -> Result<Table<Session>, AnyError> {
+    Table::open(storage)
+}
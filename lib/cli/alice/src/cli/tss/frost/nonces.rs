@@ -0,0 +1,189 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use cli_storage::{Record, Storage, Table};
+use common_interop::types::Point;
+
+use crate::caps::IO;
+use crate::data::Key;
+use crate::{AnyError, RetCode};
+
+use super::{keys_table, nonces_table, now, Nonces};
+
+/// The maximum number of the unused commitments kept for a key unless set by `set-max-pool`.
+const DEFAULT_MAX_POOL: usize = 1024;
+
+#[derive(Debug, StructOpt)]
+pub enum CmdNonces {
+    /// List the unused commitments of the key, along with their creation and expiry times.
+    List(CmdKey),
+    /// Count the unused commitments of the key.
+    Count(CmdKey),
+    /// Remove the expired commitments of the key.
+    Purge(CmdPurge),
+    /// Set the maximum number of the unused commitments kept for the key (`1024` by default).
+    SetMaxPool(CmdSetMaxPool),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdKey {
+    #[structopt(long, short)]
+    key_id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdSetMaxPool {
+    #[structopt(name = "KEY-ID")]
+    key_id: String,
+
+    #[structopt(name = "MAX-POOL")]
+    max_pool: usize,
+}
+
+/// The settings of the nonce pool of a key, keyed by the key-id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pool {
+    max_pool: usize,
+}
+
+impl Record for Pool {
+    const TABLE: &'static str = "tss/frost/nonce-pools";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CmdPurge {
+    #[structopt(long, short)]
+    key_id: String,
+
+    /// Remove all the commitments of the key, not only the expired ones.
+    #[structopt(long)]
+    all: bool,
+}
+
+pub fn run(cmd: &CmdNonces, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    match cmd {
+        CmdNonces::List(sub) => run_list(sub, io, storage),
+        CmdNonces::Count(sub) => run_count(sub, io, storage),
+        CmdNonces::Purge(sub) => run_purge(sub, io, storage),
+        CmdNonces::SetMaxPool(sub) => run_set_max_pool(sub, io, storage),
+    }
+}
+
+fn run_list(list: &CmdKey, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    #[derive(Serialize)]
+    struct Output {
+        commitment: (Point, Point),
+        created_at: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+    }
+
+    let output = select(&list.key_id, &storage)?
+        .into_iter()
+        .map(|(nonce_key, nonces)| {
+            Ok(Output {
+                commitment: commitment(&list.key_id, &nonce_key)?,
+                created_at: nonces.created_at,
+                expires_at: nonces.expires_at,
+            })
+        })
+        .collect::<Result<Vec<_>, AnyError>>()?;
+
+    serde_yaml::to_writer(io.stdout(), &output)?;
+
+    Ok(0)
+}
+
+fn run_count(count: &CmdKey, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let count = select(&count.key_id, &storage)?.len();
+    writeln!(io.stdout(), "{}", count)?;
+
+    Ok(0)
+}
+
+fn run_purge(purge: &CmdPurge, io: impl IO, storage: Storage) -> Result<RetCode, AnyError> {
+    let now = now()?;
+    let purged =
+        self::purge(&purge.key_id, &storage, |nonces| purge.all || nonces.is_expired(now))?;
+    storage.flush()?;
+
+    writeln!(io.stdout(), "{}", purged)?;
+
+    Ok(0)
+}
+
+fn run_set_max_pool(
+    set: &CmdSetMaxPool,
+    io: impl IO,
+    storage: Storage,
+) -> Result<RetCode, AnyError> {
+    let Some(key) = keys_table(&storage)?.get(&set.key_id)? else {
+        writeln!(io.stderr(), "No such key: {}", set.key_id)?;
+        return Ok(1)
+    };
+    let Key::S4Share(_) = key else {
+        return Err("the key should be an S4-share".into());
+    };
+
+    pools_table(&storage)?.insert(&set.key_id, &Pool { max_pool: set.max_pool })?;
+    storage.flush()?;
+
+    Ok(0)
+}
+
+/// The maximum number of the unused nonces kept for the key.
+pub(super) fn max_pool(key_id: &str, storage: &Storage) -> Result<usize, AnyError> {
+    let pool = pools_table(storage)?.get(key_id)?;
+    Ok(pool.map_or(DEFAULT_MAX_POOL, |pool| pool.max_pool))
+}
+
+/// Removes the unused nonces of the key `should_purge` holds for (not flushed): returns the number
+/// of the removed ones.
+fn purge(
+    key_id: &str,
+    storage: &Storage,
+    should_purge: impl Fn(&Nonces) -> bool,
+) -> Result<usize, AnyError> {
+    let tab_nonces = nonces_table(storage)?;
+
+    let mut purged = 0;
+    for (nonce_key, nonces) in select(key_id, storage)? {
+        if should_purge(&nonces) {
+            tab_nonces.remove(&nonce_key)?;
+            purged += 1;
+        }
+    }
+
+    Ok(purged)
+}
+
+/// The unused nonces of the key, keyed by their [`super::nonce_key`].
+pub(super) fn select(key_id: &str, storage: &Storage) -> Result<Vec<(String, Nonces)>, AnyError> {
+    // the prefix also matches the nonces of the key-ids starting with `{key_id}[`: their
+    // nonce-keys don't parse as those of this key
+    nonces_table(storage)?
+        .select(&format!("{}[", key_id))
+        .filter(|item| match item {
+            Ok((nonce_key, _)) => commitment(key_id, nonce_key).is_ok(),
+            Err(_) => true,
+        })
+        .collect()
+}
+
+fn commitment(key_id: &str, nonce_key: &str) -> Result<(Point, Point), AnyError> {
+    let (cd, ce) = nonce_key
+        .strip_prefix(key_id)
+        .and_then(|s| s.strip_prefix('['))
+        .and_then(|s| s.strip_suffix(']'))
+        .and_then(|s| s.split_once('-'))
+        .ok_or_else(|| format!("Malformed nonce-key: {}", nonce_key))?;
+
+    Ok((cd.parse()?, ce.parse()?))
+}
+
+fn pools_table(storage: &Storage) -> Result<Table<Pool>, AnyError> {
+    Table::open(storage)
+}
@@ -1,42 +1,67 @@
 use common_interop::curve_select::CurveSelect;
 use common_interop::transcript::Transcript;
 use common_interop::types::{Point, Scalar};
+use elliptic_curve::bigint::U256;
+use elliptic_curve::ops::Reduce;
+use elliptic_curve::point::AffineCoordinates;
+use elliptic_curve::scalar::IsHigh;
 use ff::PrimeField;
-use group::{Group, GroupEncoding};
+use group::{Curve, Group, GroupEncoding};
 use serde::Deserialize;
 use structopt::StructOpt;
 
 use crate::caps::IO;
-use crate::{transcript, AnyError, RetCode};
+use crate::{bip340, ecdsa, transcript, AnyError, RetCode};
 
 #[derive(Debug, StructOpt)]
 pub enum CmdVerify {
     Schnorr(CmdVerifySchnorr),
-    // Ecdsa(CmdVerifyEcdsa),
+    Ecdsa(CmdVerifyEcdsa),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct CmdVerifySchnorr {
     #[structopt(long, short, env = "ALICE_CURVE")]
     curve: CurveSelect,
+
+    /// Verify a BIP-340 signature: the input contains a plain `message`, and the hex-encoded
+    /// x-only `public_key` and 64-byte `signature` (secp256k1 only).
+    #[structopt(long)]
+    bip340: bool,
 }
 
-// #[derive(Debug, StructOpt)]
-// pub struct CmdVerifyEcdsa {
-//     #[structopt(long, short)]
-//     curve: CurveSelect,
-// }
+#[derive(Debug, StructOpt)]
+pub struct CmdVerifyEcdsa {
+    #[structopt(long, short, env = "ALICE_CURVE")]
+    curve: CurveSelect,
+
+    /// Reject the signatures with `s` in the upper half of the scalar field (BIP-62, SEC1
+    /// "low-s").
+    #[structopt(long)]
+    low_s: bool,
+}
 
 pub fn run(verify: &CmdVerify, io: impl IO) -> Result<RetCode, AnyError> {
     match verify {
         CmdVerify::Schnorr(sub) => run_verify_schnorr(sub, io),
-        // CmdVerify::Ecdsa(sub) => run_verify_ecdsa(sub, io),
+        CmdVerify::Ecdsa(sub) => run_verify_ecdsa(sub, io),
     }
 }
 
 fn run_verify_schnorr(cmd: &CmdVerifySchnorr, io: impl IO) -> Result<RetCode, AnyError> {
     let curve = cmd.curve;
 
+    if cmd.bip340 {
+        return specialize_call!(
+            run_verify_bip340_typed, (io),
+            curve,
+            [
+                (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+            ]
+        )
+        .ok_or(format!("BIP-340 signatures are not supported for the curve: {}", curve))?
+    }
+
     specialize_call!(
         run_verify_schnorr_typed, (cmd, io),
         curve, [
@@ -77,24 +102,79 @@ fn run_verify_schnorr_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>
     Ok(0)
 }
 
-// fn run_verify_ecdsa(cmd: &CmdVerifyEcdsa, io: impl IO) -> Result<RetCode, AnyError> {
-//     let curve = cmd.curve;
-
-//     specialize_call!(
-//         run_verify_ecdsa_typed, (cmd, io),
-//         curve,
-//         [
-//             (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
-//         ]
-//     )
-//     .ok_or(format!("Unsupported curve: {}", curve))?
-// }
-
-// fn run_verify_ecdsa_typed<F, G>(cmd: &CmdVerifyEcdsa, io: impl IO) -> Result<RetCode, AnyError>
-// where
-//     F: PrimeField,
-//     G: Group<Scalar = F> + GroupEncoding + Curve,
-//     G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
-// {
-//     unimplemented!()
-// }
+fn run_verify_bip340_typed<F: PrimeField, G: Group<Scalar = F> + GroupEncoding>(
+    io: impl IO,
+) -> Result<RetCode, AnyError> {
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        message: Vec<common_interop::transcript::Input>,
+        public_key: String,
+        signature: String,
+    }
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let message = transcript::produce_message(&input.message)?;
+    let public_key = hex::decode(input.public_key)?;
+    let signature = hex::decode(input.signature)?;
+
+    serde_yaml::to_writer(io.stdout(), &bip340::verify::<F, G>(&public_key, &message, &signature))?;
+
+    Ok(0)
+}
+
+fn run_verify_ecdsa(cmd: &CmdVerifyEcdsa, io: impl IO) -> Result<RetCode, AnyError> {
+    let curve = cmd.curve;
+
+    specialize_call!(
+        run_verify_ecdsa_typed, (cmd, io),
+        curve,
+        [
+            (CurveSelect::Secp256k1 => k256::Scalar, k256::ProjectivePoint),
+        ]
+    )
+    .ok_or(format!("Unsupported curve: {}", curve))?
+}
+
+fn run_verify_ecdsa_typed<F, G>(cmd: &CmdVerifyEcdsa, io: impl IO) -> Result<RetCode, AnyError>
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr> + IsHigh,
+    G: Group<Scalar = F> + GroupEncoding + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+{
+    #[derive(Debug, Deserialize)]
+    struct Signature {
+        y: Point,
+        r: Scalar,
+        s: Scalar,
+    }
+    #[derive(Debug, Deserialize)]
+    struct Input {
+        #[serde(default)]
+        transcript: Option<Transcript>,
+        #[serde(default)]
+        digest: Option<String>,
+        signature: Signature,
+    }
+
+    let curve = cmd.curve;
+
+    let input: Input = serde_yaml::from_reader(io.stdin())?;
+
+    let digest = match (&input.transcript, &input.digest) {
+        (Some(t), None) => transcript::produce_digest(t)?,
+        (None, Some(d)) => hex::decode(d)?,
+        _ => return Err("exactly one of `transcript` and `digest` should be specified".into()),
+    };
+    let m = ecdsa::digest_to_scalar::<F>(&digest);
+
+    let y = input.signature.y.restore::<G>(curve)?;
+    let r = input.signature.r.restore::<F>(curve)?;
+    let s = input.signature.s.restore::<F>(curve)?;
+
+    let is_valid = !(cmd.low_s && bool::from(s.is_high())) && ecdsa::verify::<F, G>(&y, &m, &r, &s);
+
+    serde_yaml::to_writer(io.stdout(), &is_valid)?;
+
+    Ok(0)
+}
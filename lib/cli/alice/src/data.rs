@@ -1,5 +1,9 @@
+use std::fmt;
+use std::str::FromStr;
+
+use cli_storage::Record;
 use common_interop::curve_select::CurveSelect;
-use common_interop::types::{Point, Scalar};
+use common_interop::types::{Point, Scalar, SecretScalar};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,23 +13,98 @@ pub enum Key {
     S4Share(S4Share),
 }
 
+impl Record for Key {
+    const TABLE: &'static str = "keys";
+    const VERSION: u32 = 1;
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FullKey {
     pub curve: CurveSelect,
-    pub value: Scalar,
+    pub value: SecretScalar,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S4Share {
     pub curve: CurveSelect,
-    pub threshold: usize,
+    pub threshold: Threshold,
     pub public_key: Point,
     pub x: Scalar,
-    pub y: Scalar,
+    pub y: SecretScalar,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S4 {
     pub curve: CurveSelect,
-    pub polynomial: Vec<Scalar>,
+    pub threshold: Threshold,
+
+    /// The `t` coefficients of the polynomial, the secret first.
+    pub polynomial: Vec<SecretScalar>,
+
+    /// The `x` of the key-shares issued so far: no more than `n`.
+    #[serde(default)]
+    pub issued: Vec<Scalar>,
+}
+
+impl Record for S4 {
+    const TABLE: &'static str = "s4/schemes";
+    const VERSION: u32 = 1;
+}
+
+/// "`t`-of-`n`": any `t` of the `n` key-shares are needed, and enough, to use the key.
+///
+/// The polynomial of the key is thus of `t` coefficients (of the degree `t - 1`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawThreshold")]
+pub struct Threshold {
+    pub t: usize,
+    pub n: usize,
+}
+
+#[derive(Deserialize)]
+struct RawThreshold {
+    t: usize,
+    n: usize,
+}
+
+impl Threshold {
+    pub fn is_valid(&self) -> bool {
+        0 < self.t && self.t <= self.n
+    }
+}
+
+impl TryFrom<RawThreshold> for Threshold {
+    type Error = String;
+
+    fn try_from(raw: RawThreshold) -> Result<Self, Self::Error> {
+        let threshold = Self { t: raw.t, n: raw.n };
+        if !threshold.is_valid() {
+            return Err(format!("Invalid threshold: {}", threshold))
+        }
+
+        Ok(threshold)
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-of-{}", self.t, self.n)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid threshold: {:?}. Expected: \"T-of-N\"", s);
+
+        let (t, n) = s.split_once("-of-").ok_or_else(invalid)?;
+        let threshold =
+            Self { t: t.parse().map_err(|_| invalid())?, n: n.parse().map_err(|_| invalid())? };
+        if !threshold.is_valid() {
+            return Err(invalid())
+        }
+
+        Ok(threshold)
+    }
 }
@@ -0,0 +1,142 @@
+use digest::core_api::BlockSizeUser;
+use digest::Digest;
+use elliptic_curve::bigint::U256;
+use elliptic_curve::ops::Reduce;
+use elliptic_curve::point::AffineCoordinates;
+use elliptic_curve::scalar::IsHigh;
+use ff::PrimeField;
+use group::{Curve, Group};
+
+/// Converts a message digest into a scalar (`bits2int` followed by the reduction modulo the group
+/// order, as in SEC1 and RFC 6979).
+pub fn digest_to_scalar<F>(digest: &[u8]) -> F
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr>,
+{
+    let mut repr: F::Repr = Default::default();
+    let dst = repr.as_mut();
+
+    let len = dst.len().min(digest.len());
+    let offset = dst.len() - len;
+    dst[offset..].copy_from_slice(&digest[..len]);
+
+    F::reduce_bytes(&repr)
+}
+
+/// The `r` component of the signature: the x-coordinate of the instance point reduced modulo the
+/// group order.
+pub fn r_x<F, G>(r: &G) -> F
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr>,
+    G: Group<Scalar = F> + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+{
+    F::reduce_bytes(&r.to_affine().x())
+}
+
+pub fn verify<F, G>(public_key: &G, m: &F, r: &F, s: &F) -> bool
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr>,
+    G: Group<Scalar = F> + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+{
+    if bool::from(r.is_zero()) || bool::from(s.is_zero()) {
+        return false
+    }
+
+    let s_inv = s.invert().unwrap();
+    let r_derived = G::generator() * (*m * s_inv) + *public_key * (*r * s_inv);
+
+    !bool::from(r_derived.is_identity()) && r_x::<F, G>(&r_derived) == *r
+}
+
+/// Signs the message `m` with the secret key `x` and the instance key `k`.
+///
+/// The signature is normalised to "low-s". Returns `(r, s, recovery_id)`, or `None` if `k` is
+/// unsuitable (`r = 0` or `s = 0`).
+pub fn sign<F, G>(x: &F, m: &F, k: &F) -> Option<(F, F, u8)>
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr> + IsHigh,
+    G: Group<Scalar = F> + Curve,
+    G::AffineRepr: AffineCoordinates<FieldRepr = F::Repr>,
+{
+    let k_inv = Option::<F>::from(k.invert())?;
+    let r_point = (G::generator() * k).to_affine();
+    let r_point_x = r_point.x();
+
+    let r = F::reduce_bytes(&r_point_x);
+    let s = k_inv * (*m + r * x);
+    if bool::from(r.is_zero()) || bool::from(s.is_zero()) {
+        return None
+    }
+
+    let mut recovery_id = u8::from(bool::from(r_point.y_is_odd()));
+    if r.to_repr().as_ref() != r_point_x.as_ref() {
+        recovery_id |= 0b10;
+    }
+
+    if bool::from(s.is_high()) {
+        Some((r, -s, recovery_id ^ 0b01))
+    } else {
+        Some((r, s, recovery_id))
+    }
+}
+
+/// Deterministic instance key as per RFC 6979 (section 3.2), with HMAC-DRBG instantiated with `H`.
+///
+/// Assumes the big-endian representation of the scalars, and the bit-size of the group order being
+/// a multiple of eight.
+pub fn rfc6979_instance_key<F, H>(x: &F, digest: &[u8]) -> F
+where
+    F: PrimeField + Reduce<U256, Bytes = F::Repr>,
+    H: Digest + BlockSizeUser,
+{
+    let x = x.to_repr();
+    let h = digest_to_scalar::<F>(digest).to_repr();
+
+    let mut v = vec![0x01; <H as Digest>::output_size()];
+    let mut k = vec![0x00; <H as Digest>::output_size()];
+
+    k = hmac::<H>(&k, &[&v, &[0x00], x.as_ref(), h.as_ref()]);
+    v = hmac::<H>(&k, &[&v]);
+    k = hmac::<H>(&k, &[&v, &[0x01], x.as_ref(), h.as_ref()]);
+    v = hmac::<H>(&k, &[&v]);
+
+    loop {
+        let mut candidate: F::Repr = Default::default();
+        let mut t = vec![];
+        while t.len() < candidate.as_ref().len() {
+            v = hmac::<H>(&k, &[&v]);
+            t.extend_from_slice(&v);
+        }
+        let len = candidate.as_ref().len();
+        candidate.as_mut().copy_from_slice(&t[..len]);
+
+        if let Some(candidate) = Option::<F>::from(F::from_repr(candidate)) {
+            if !bool::from(candidate.is_zero()) {
+                return candidate
+            }
+        }
+
+        k = hmac::<H>(&k, &[&v, &[0x00]]);
+        v = hmac::<H>(&k, &[&v]);
+    }
+}
+
+fn hmac<H>(key: &[u8], message: &[&[u8]]) -> Vec<u8>
+where
+    H: Digest + BlockSizeUser,
+{
+    let mut key = if key.len() > H::block_size() { H::digest(key).to_vec() } else { key.to_vec() };
+    key.resize(H::block_size(), 0x00);
+
+    let mut inner = H::new();
+    inner.update(key.iter().map(|b| b ^ 0x36).collect::<Vec<_>>());
+    message.iter().for_each(|part| inner.update(part));
+
+    let mut outer = H::new();
+    outer.update(key.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>());
+    outer.update(inner.finalize());
+
+    outer.finalize().to_vec()
+}
@@ -0,0 +1,45 @@
+use digest::Digest;
+use ff::PrimeField;
+use group::GroupEncoding;
+use sha2::Sha512;
+
+/// The challenge as per RFC 8032 (section 5.1.6): `SHA-512(R || A || M)` interpreted as a
+/// little-endian integer and reduced modulo the group order.
+pub fn challenge<F, G>(y: &G, r: &G, message: &[u8]) -> F
+where
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    let digest = Sha512::new()
+        .chain_update(r.to_bytes())
+        .chain_update(y.to_bytes())
+        .chain_update(message)
+        .finalize();
+
+    utils::reduce_le(digest.as_ref())
+}
+
+/// Deterministic instance key.
+///
+/// RFC 8032 hashes the message together with a prefix derived from the seed. A stored key carries
+/// only the scalar, so the prefix is derived from the scalar instead.
+pub fn instance_key<F>(x: &F, message: &[u8]) -> F
+where
+    F: PrimeField,
+{
+    let expanded = Sha512::digest(x.to_repr());
+    let prefix = &expanded[32..];
+
+    utils::reduce_le(Sha512::new().chain_update(prefix).chain_update(message).finalize().as_ref())
+}
+
+/// The 64-byte encoding of the signature: `R || S`.
+pub fn signature_bytes<F, G>(r: &G, s: &F) -> Vec<u8>
+where
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    let mut out = r.to_bytes().as_ref().to_vec();
+    out.extend_from_slice(s.to_repr().as_ref());
+    out
+}
@@ -7,7 +7,11 @@ pub type AnyError = Box<dyn std::error::Error + Send + Sync + 'static>;
 pub mod caps;
 pub mod cli;
 
+mod bip340;
+mod challenge;
 mod data;
+mod ecdsa;
+mod ed25519;
 mod transcript;
 
 #[cfg(test)]
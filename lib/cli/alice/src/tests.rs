@@ -1,4 +1,9 @@
 mod cli_utils;
 mod io_utils;
 
+mod test_data;
 mod test_dkg;
+mod test_s4;
+mod test_sign;
+mod test_storage;
+mod test_tss;
@@ -1,4 +1,20 @@
 use std::ffi::OsString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use digest::Digest;
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+use shamir_sss::LagrangeCoefficientAt;
+
+use crate::data::{Key, S4Share, Threshold};
+use crate::tests::io_utils::TestIO;
+use crate::{cli, RetCode};
 
 pub fn args(input: impl Into<String>) -> Vec<OsString> {
     let mut args: Vec<OsString> = vec!["alice".into()];
@@ -6,3 +22,142 @@ pub fn args(input: impl Into<String>) -> Vec<OsString> {
     // eprintln!("args: {:#?}", args);
     args
 }
+
+/// A storage of its own, kept in memory.
+pub fn in_memory() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    format!("mem://alice-test-{}", NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Run the `cmd` with the `input` as YAML on the stdin: it should return the `ret_code`.
+pub fn run(storage_arg: &str, cmd: &str, input: impl Serialize, ret_code: RetCode) -> TestIO {
+    let io = TestIO::from_yaml_stdin(input).expect("make io");
+    let cli = cli::Cli::create_safe(args(format!("{}{}", storage_arg, cmd))).expect("args error");
+    assert_eq!(cli::run(&cli, rand::rngs::OsRng, &io).expect("cli-run"), ret_code, "{}", cmd);
+    io
+}
+
+/// The `x` of the three holders of a 2-of-3 key.
+pub fn shamir_xs_2_of_3<F: PrimeField>(curve: CurveSelect) -> Vec<Scalar> {
+    (0..3usize)
+        .map(|idx| utils::bytes_to_scalar::<F>(sha3::Sha3_256::digest(idx.to_ne_bytes()).as_ref()))
+        .map(|x| Scalar::from_value(curve, x))
+        .collect()
+}
+
+/// The key-ids of the three holders of a 2-of-3 key: `<key_prefix>:1..=3`.
+pub fn key_ids_2_of_3(key_prefix: &str) -> Vec<String> {
+    (0..3).map(|idx| format!("{}:{}", key_prefix, idx + 1)).collect()
+}
+
+/// Import the key-shares of a random 2-of-3 key as the `key_ids`: the `x` of the holders and the
+/// public key.
+pub fn import_2_of_3<F, G>(
+    storage_arg: &str,
+    curve: CurveSelect,
+    key_ids: &[String],
+) -> (Vec<Scalar>, Point)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let polynomial = [F::random(rand::rngs::OsRng), F::random(rand::rngs::OsRng)];
+    let public_key = Point::from_value(curve, G::generator() * polynomial[0]);
+
+    let shamir_xs = shamir_xs_2_of_3::<F>(curve);
+    for (x, key_id) in shamir_xs.iter().zip(key_ids) {
+        let x = x.restore::<F>(curve).expect("x");
+        let s4_share = S4Share {
+            curve,
+            threshold: Threshold { t: 2, n: 3 },
+            public_key: public_key.clone(),
+            x: Scalar::from_value(curve, x),
+            y: SecretScalar::from_value(curve, polynomial[0] + polynomial[1] * x),
+        };
+        run(storage_arg, &format!("keys import {}", key_id), Key::S4Share(s4_share), 0);
+    }
+
+    (shamir_xs, public_key)
+}
+
+/// Deal a 2-of-3 key with the DKG `dkg` (`gjkr` or `csi-rashi`), the `input` added to that of
+/// each `deal`: the `x` of the parties, their key-ids and the outputs of their `deal`.
+pub fn dkg_deal_2_of_3<F, O>(
+    storage_arg: &str,
+    curve: CurveSelect,
+    dkg: &str,
+    key_prefix: &str,
+    input: serde_json::Value,
+) -> (Vec<Scalar>, Vec<String>, Vec<O>)
+where
+    F: PrimeField,
+    O: DeserializeOwned,
+{
+    let shamir_xs = shamir_xs_2_of_3::<F>(curve);
+    let key_ids = key_ids_2_of_3(key_prefix);
+
+    let deal_outputs = (0..3)
+        .map(|party_idx| {
+            let mut input = input.clone();
+            input["threshold"] = json!(2);
+            input["this"] = json!(party_idx);
+            input["shamir_xs"] = json!(shamir_xs);
+            let cmd = format!("dkg {} deal --curve {} {}", dkg, curve, key_ids[party_idx]);
+            run(storage_arg, &cmd, input, 0).stdout_as_yaml::<O>().expect("io:de")
+        })
+        .collect::<Vec<_>>();
+
+    (shamir_xs, key_ids, deal_outputs)
+}
+
+/// The input of the party `party_idx` received from the other dealers: their `deals` to this
+/// party, and their `broadcast` fields (`(field, input-field)`), all keyed by the `x` of the dealer.
+pub fn dkg_received<O: Serialize>(
+    shamir_xs: &[Scalar],
+    deal_outputs: &[O],
+    party_idx: usize,
+    broadcast: &[(&str, &str)],
+) -> serde_json::Value {
+    let this_party_x = shamir_xs[party_idx].to_string();
+    let mut received = json!({});
+    for dealer_idx in (0..shamir_xs.len()).filter(|&i| i != party_idx) {
+        let deal_output = serde_json::to_value(&deal_outputs[dealer_idx]).expect("ser");
+        let dealer_x = shamir_xs[dealer_idx].to_string();
+        received["deals"][&dealer_x] = deal_output["deals"][&this_party_x].clone();
+        for (output_field, input_field) in broadcast {
+            received[input_field][&dealer_x] = deal_output[output_field].clone();
+        }
+    }
+    received
+}
+
+/// Check that the key-shares `key_ids` are 2-of-3 of the `public_key`.
+pub fn assert_2_of_3_key<F, G>(
+    storage_arg: &str,
+    curve: CurveSelect,
+    key_ids: &[String],
+    public_key: &Point,
+) where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let s4_shares = key_ids
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("keys export {}", key_id);
+            let key: Key = run(storage_arg, &cmd, (), 0).stdout_as_yaml().expect("io:de");
+            let Key::S4Share(s4_share) = key else { panic!("not an s4-share") };
+            assert_eq!(s4_share.threshold, Threshold { t: 2, n: 3 });
+            assert_eq!(&s4_share.public_key, public_key);
+            s4_share
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..s4_shares.len() {
+        let j = (i + 1) % s4_shares.len();
+        let xs = [i, j].map(|k| s4_shares[k].x.restore::<F>(curve).expect("x"));
+        let ys = [i, j].map(|k| s4_shares[k].y.restore::<F>(curve).expect("y"));
+        let secret = (0..2).map(|k| ys[k] * xs.lagrange_coefficient_at(k, F::ZERO)).sum::<F>();
+        assert_eq!(G::generator() * secret, public_key.restore::<G>(curve).expect("pk"));
+    }
+}
@@ -41,6 +41,11 @@ impl TestIO {
         let output = serde_yaml::from_slice(stdout.as_ref())?;
         Ok(output)
     }
+
+    pub fn stderr_as_string(&self) -> String {
+        let stderr = self.stderr.lock().unwrap();
+        String::from_utf8_lossy(stderr.as_ref()).into_owned()
+    }
 }
 
 pub struct W<T>(T);
@@ -83,3 +88,34 @@ where
         (*self.0).read(buf)
     }
 }
+
+/// Same as the wrapped [`TestIO`], but writing into the stdout fails.
+#[derive(Debug)]
+pub struct BrokenStdout<'a>(pub &'a TestIO);
+
+pub struct Broken;
+
+impl<'a> crate::caps::IO for BrokenStdout<'a> {
+    type Stdin = W<MutexGuard<'a, Cursor<Vec<u8>>>>;
+    type Stdout = Broken;
+    type Stderr = W<MutexGuard<'a, Vec<u8>>>;
+
+    fn stdin(&self) -> Self::Stdin {
+        W(self.0.stdin.lock().unwrap())
+    }
+    fn stdout(&self) -> Self::Stdout {
+        Broken
+    }
+    fn stderr(&self) -> Self::Stderr {
+        W(self.0.stderr.lock().unwrap())
+    }
+}
+
+impl Write for Broken {
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+}
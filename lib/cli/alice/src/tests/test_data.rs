@@ -0,0 +1,35 @@
+use group::Group;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+
+use crate::data::{FullKey, Key, S4Share, Threshold, S4};
+
+#[test]
+fn debug_is_redacted() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let secret = F::from(0x5ec7e7u64);
+    let hex = Scalar::from_value(curve, secret).to_string();
+    let (_, hex) = hex.split_once(':').expect("hex");
+
+    let full_key = Key::FullKey(FullKey { curve, value: SecretScalar::from_value(curve, secret) });
+    let s4_share = Key::S4Share(S4Share {
+        curve,
+        threshold: Threshold { t: 1, n: 1 },
+        public_key: Point::from_value(curve, <G as Group>::generator() * secret),
+        x: Scalar::from_value(curve, F::ONE),
+        y: SecretScalar::from_value(curve, secret),
+    });
+    let s4 = S4 {
+        curve,
+        threshold: Threshold { t: 1, n: 1 },
+        polynomial: vec![SecretScalar::from_value(curve, secret)],
+        issued: vec![],
+    };
+    for debug in [format!("{:?}", full_key), format!("{:?}", s4_share), format!("{:#?}", s4)] {
+        assert!(!debug.contains(hex), "{}", debug);
+    }
+}
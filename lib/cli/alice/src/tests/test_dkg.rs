@@ -1 +1,2 @@
 mod test_csi_rashi;
+mod test_gjkr;
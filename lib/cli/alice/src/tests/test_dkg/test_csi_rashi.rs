@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use digest::Digest;
 use ff::PrimeField;
-use serde::Deserialize;
+use group::{Group, GroupEncoding};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use common_interop::curve_select::CurveSelect;
@@ -10,27 +11,40 @@ use common_interop::types::{Point, Scalar};
 
 use crate::cli;
 use crate::data::Key;
-use crate::tests::cli_utils::args;
+use crate::tests::cli_utils::{
+    args, assert_2_of_3_key, dkg_deal_2_of_3, dkg_received, in_memory, run,
+};
 use crate::tests::io_utils::TestIO;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct DealOutput {
     deals: HashMap<Scalar, Scalar>,
     commitment: Vec<Point>,
+    proof: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComplainOutput {
+    complaints: Vec<Scalar>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AggregateOutput {
+    disqualified: Vec<Scalar>,
 }
 
 #[test]
-fn run_secp256k1_in_tmp_dir() {
+fn run_secp256k1_in_memory() {
     run_various_configurations(CurveSelect::Secp256k1, "k1", true)
 }
 
 #[test]
-fn run_ed25519_in_tmp_dir() {
+fn run_ed25519_in_memory() {
     run_various_configurations(CurveSelect::Ed25519, "ed", true)
 }
 
 #[test]
-fn run_ristretto25519_in_tmp_dir() {
+fn run_ristretto25519_in_memory() {
     run_various_configurations(CurveSelect::Ristretto25519, "ri", true)
 }
 
@@ -52,19 +66,228 @@ fn run_ristretto25519_in_std_dir() {
     run_various_configurations(CurveSelect::Ristretto25519, "ri", false)
 }
 
+#[test]
+fn complaints_and_disqualification_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, mut deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "ri-dq");
+
+    // the dealer #0 corrupts the deal for the party #1 (and reveals the valid one in response),
+    // the dealer #2 corrupts the deal for the party #0 (and its response as well).
+    let corrupt =
+        |y: &Scalar| Scalar::from_value(curve, y.restore::<F>(curve).expect("y") + F::ONE);
+    let valid = deal_outputs[0].deals[&shamir_xs[1]].clone();
+    deal_outputs[0].deals.insert(shamir_xs[1].clone(), corrupt(&valid));
+    let valid = deal_outputs[2].deals[&shamir_xs[0]].clone();
+    deal_outputs[2].deals.insert(shamir_xs[0].clone(), corrupt(&valid));
+
+    let received = |party_idx: usize| received(&shamir_xs, &deal_outputs, party_idx);
+
+    let complaints = (0..3)
+        .map(|party_idx| {
+            let cmd = format!("dkg csi-rashi complain {}", key_ids[party_idx]);
+            let complaints: ComplainOutput =
+                run(&storage_arg, &cmd, received(party_idx), 0).stdout_as_yaml().expect("io:de");
+            (shamir_xs[party_idx].clone(), complaints.complaints)
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(complaints[&shamir_xs[0]], vec![shamir_xs[2].clone()]);
+    assert_eq!(complaints[&shamir_xs[1]], vec![shamir_xs[0].clone()]);
+    assert!(complaints[&shamir_xs[2]].is_empty());
+
+    let mut responses = (0..3)
+        .map(|party_idx| {
+            let cmd = format!("dkg csi-rashi respond {}", key_ids[party_idx]);
+            let input = json!({ "complaints": complaints });
+            let responses: HashMap<Scalar, Scalar> =
+                run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+            (shamir_xs[party_idx].clone(), responses)
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(responses[&shamir_xs[0]].len(), 1);
+    assert!(responses[&shamir_xs[1]].is_empty());
+    let response = responses.get_mut(&shamir_xs[2]).expect("responses").get_mut(&shamir_xs[0]);
+    let response = response.expect("response");
+    *response = corrupt(response);
+
+    // the party #1 can't aggregate its invalid deal without complaining about it
+    let cmd = format!("dkg csi-rashi aggregate {}", key_ids[1]);
+    run(&storage_arg, &cmd, received(1), 1);
+
+    for (party_idx, key_id) in key_ids.iter().enumerate() {
+        let mut input = received(party_idx);
+        input["complaints"] = json!(complaints);
+        input["responses"] = json!(responses);
+
+        let cmd = format!("dkg csi-rashi aggregate {}", key_id);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.disqualified, vec![shamir_xs[2].clone()]);
+    }
+
+    let public_key = qualified_public_key::<G>(curve, &deal_outputs, &[0, 1]);
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids, &public_key);
+}
+
+#[test]
+fn invalid_proof_disqualifies_the_dealer_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, mut deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "k1-pok");
+
+    // the dealer #2 replays the proof of the dealer #1: the honest parties disqualify it
+    deal_outputs[2].proof = deal_outputs[1].proof.clone();
+
+    for (party_idx, key_id) in key_ids[..2].iter().enumerate() {
+        let cmd = format!("dkg csi-rashi aggregate {}", key_id);
+        let input = received(&shamir_xs, &deal_outputs, party_idx);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.disqualified, vec![shamir_xs[2].clone()]);
+    }
+
+    let public_key = qualified_public_key::<G>(curve, &deal_outputs, &[0, 1]);
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids[..2], &public_key);
+}
+
+#[test]
+fn malformed_commitment_disqualifies_the_dealer_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::edwards::EdwardsPoint;
+    let curve = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, mut deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "ed-len");
+
+    // the dealer #2 pads its commitment: the deals are still consistent with it, yet it is not
+    // of the agreed upon threshold
+    let extra = Point::from_value(curve, G::identity());
+    deal_outputs[2].commitment.push(extra);
+
+    for (party_idx, key_id) in key_ids[..2].iter().enumerate() {
+        let cmd = format!("dkg csi-rashi complain {}", key_id);
+        let input = received(&shamir_xs, &deal_outputs, party_idx);
+        let output: ComplainOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.complaints, vec![shamir_xs[2].clone()]);
+    }
+
+    for (party_idx, key_id) in key_ids[..2].iter().enumerate() {
+        let cmd = format!("dkg csi-rashi aggregate {}", key_id);
+        let input = received(&shamir_xs, &deal_outputs, party_idx);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.disqualified, vec![shamir_xs[2].clone()]);
+    }
+
+    let public_key = qualified_public_key::<G>(curve, &deal_outputs, &[0, 1]);
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids[..2], &public_key);
+}
+
+#[test]
+fn proof_is_bound_to_the_session_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, mut deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "k1-sid");
+
+    // the dealer #2 replays its deal from another session with the same parties
+    let (_, _, other_session) = deal_2_of_3::<F>(&storage_arg, curve, "k1-sid-other");
+    deal_outputs[2] = other_session.into_iter().nth(2).expect("dealer #2");
+
+    for (party_idx, key_id) in key_ids[..2].iter().enumerate() {
+        let cmd = format!("dkg csi-rashi aggregate {}", key_id);
+        let input = received(&shamir_xs, &deal_outputs, party_idx);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.disqualified, vec![shamir_xs[2].clone()]);
+    }
+
+    let public_key = qualified_public_key::<G>(curve, &deal_outputs, &[0, 1]);
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids[..2], &public_key);
+}
+
+#[test]
+fn repeated_x_is_refused_in_memory() {
+    type F = k256::Scalar;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let x = |idx: u64| Scalar::from_value(curve, F::from(idx));
+    let deal = |shamir_xs: &[Scalar], ret_code| {
+        let input = json!({
+            "session_id": "k1-rep",
+            "threshold": 2,
+            "this": 0,
+            "shamir_xs": shamir_xs,
+        });
+        run(&storage_arg, "dkg csi-rashi deal --curve secp256k1 k1-rep:1", input, ret_code);
+    };
+
+    let upper_case = |idx: u64| {
+        let x = x(idx).to_string();
+        Scalar::from_hex(curve, x.split_once(':').expect("hex").1.to_uppercase())
+    };
+
+    // the holder #2 would get two shares, and `n` would be 3
+    deal(&[x(1), x(2), x(2)], 1);
+    // the same `x`, encoded differently
+    deal(&[x(1), x(0xab), upper_case(0xab)], 1);
+    deal(&[x(1), x(2), x(3)], 0);
+}
+
+fn deal_2_of_3<F: PrimeField>(
+    storage_arg: &str,
+    curve: CurveSelect,
+    key_prefix: &str,
+) -> (Vec<Scalar>, Vec<String>, Vec<DealOutput>) {
+    let input = json!({ "session_id": key_prefix });
+    dkg_deal_2_of_3::<F, _>(storage_arg, curve, "csi-rashi", key_prefix, input)
+}
+
+/// The input of `complain` and `aggregate` of the party `party_idx`.
+fn received(
+    shamir_xs: &[Scalar],
+    deal_outputs: &[DealOutput],
+    party_idx: usize,
+) -> serde_json::Value {
+    let broadcast = [("commitment", "commitments"), ("proof", "proofs")];
+    dkg_received(shamir_xs, deal_outputs, party_idx, &broadcast)
+}
+
+/// The public key of the key produced by the `qualified` dealers.
+fn qualified_public_key<G: Group + GroupEncoding>(
+    curve: CurveSelect,
+    deal_outputs: &[DealOutput],
+    qualified: &[usize],
+) -> Point {
+    let public_key = qualified
+        .iter()
+        .map(|&i| deal_outputs[i].commitment[0].restore::<G>(curve).expect("commitment"))
+        .fold(G::identity(), |acc, c| acc + c);
+    Point::from_value(curve, public_key)
+}
+
 const MIN_PARTIES: usize = 2;
 const MAX_PARTIES: usize = 3;
 const MIN_THRESHOLD: usize = 2;
 
-fn run_various_configurations(curve: CurveSelect, prefix: &str, use_temp_dir: bool) {
-    let tmp: tempfile::TempDir;
-    let storage_override = if use_temp_dir {
-        tmp = tempfile::tempdir().expect("Tempdir");
-        let tmp = tmp.path();
-        Some(tmp.to_str().expect("to-str"))
-    } else {
-        None
-    };
+fn run_various_configurations(curve: CurveSelect, prefix: &str, use_memory: bool) {
+    let storage = use_memory.then(in_memory);
+    let storage_override = storage.as_deref();
 
     for p in MIN_PARTIES..=MAX_PARTIES {
         for t in MIN_THRESHOLD..=p {
@@ -117,10 +340,9 @@ fn run_typed<F: PrimeField>(
 
     let mut deal_outputs: Vec<DealOutput> = vec![];
 
-    for party_idx in 0..parties_count {
-        let key_id = key_ids[party_idx].as_str();
-
+    for (party_idx, key_id) in key_ids.iter().enumerate() {
         let io = TestIO::from_yaml_stdin(json!({
+            "session_id": key_prefix,
             "threshold": threshold,
             "this": party_idx,
             "shamir_xs": shamir_xs,
@@ -144,12 +366,14 @@ fn run_typed<F: PrimeField>(
 
         let this_party_x = &shamir_xs[party_idx];
         let mut commitments = HashMap::<Scalar, Vec<Point>>::new();
+        let mut proofs = HashMap::<Scalar, serde_json::Value>::new();
         let mut deals = HashMap::<Scalar, Scalar>::new();
 
         for other_party_idx in (0..parties_count).filter(|&i| i != party_idx) {
             let other_party_x = &shamir_xs[other_party_idx];
             commitments
                 .insert(other_party_x.clone(), deal_outputs[other_party_idx].commitment.clone());
+            proofs.insert(other_party_x.clone(), deal_outputs[other_party_idx].proof.clone());
             deals.insert(
                 other_party_x.clone(),
                 deal_outputs[other_party_idx].deals.get(this_party_x).unwrap().clone(),
@@ -158,6 +382,7 @@ fn run_typed<F: PrimeField>(
 
         let io = TestIO::from_yaml_stdin(json!({
             "commitments": commitments,
+            "proofs": proofs,
             "deals": deals,
         }))
         .expect("make io");
@@ -171,9 +396,7 @@ fn run_typed<F: PrimeField>(
     }
 
     let mut public_keys = vec![];
-    for party_idx in 0..parties_count {
-        let key_id = key_ids[party_idx].as_str();
-
+    for key_id in &key_ids {
         let io = TestIO::from_empty_input();
         let cli = cli::Cli::create_safe(args(format!("{}keys export {}", storage_arg, key_id,)))
             .expect("args error");
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar};
+
+use crate::tests::cli_utils::{assert_2_of_3_key, dkg_deal_2_of_3, dkg_received, in_memory, run};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DealOutput {
+    deals: HashMap<Scalar, serde_json::Value>,
+    commitment: Vec<Point>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QualifyOutput {
+    disqualified: Vec<Scalar>,
+    feldman_commitment: Option<Vec<Point>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AggregateOutput {
+    public_key: Point,
+}
+
+#[test]
+fn run_secp256k1_in_memory() {
+    run_2_of_3::<k256::Scalar, k256::ProjectivePoint>(CurveSelect::Secp256k1, "k1")
+}
+
+#[test]
+fn run_ed25519_in_memory() {
+    run_2_of_3::<curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        CurveSelect::Ed25519,
+        "ed",
+    )
+}
+
+#[test]
+fn run_ristretto25519_in_memory() {
+    run_2_of_3::<curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint>(
+        CurveSelect::Ristretto25519,
+        "ri",
+    )
+}
+
+#[test]
+fn complaints_and_disqualification_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, mut deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "ri-dq");
+
+    // the dealer #2 corrupts the deal for the party #0, and does not respond to the complaint
+    let deal = deal_outputs[2].deals.get_mut(&shamir_xs[0]).expect("deal");
+    let y_blind = deal["y_blind"].as_str().expect("y_blind").parse::<Scalar>().expect("y_blind");
+    deal["y_blind"] =
+        json!(Scalar::from_value(curve, y_blind.restore::<F>(curve).expect("y_blind") + F::ONE));
+
+    #[derive(Debug, Deserialize)]
+    struct Complaints {
+        complaints: Vec<Scalar>,
+    }
+    let cmd = format!("dkg gjkr complain {}", key_ids[0]);
+    let complaints: Complaints = run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 0), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(complaints.complaints, vec![shamir_xs[2].clone()]);
+    let complaints = json!({ shamir_xs[0].to_string(): complaints.complaints });
+
+    // the party #0 can't qualify its invalid deal without complaining about it
+    let cmd = format!("dkg gjkr qualify {}", key_ids[0]);
+    run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 0), 1);
+
+    let mut feldman_commitments = HashMap::new();
+    for party_idx in 0..3 {
+        let mut input = received(&shamir_xs, &deal_outputs, party_idx);
+        input["complaints"] = complaints.clone();
+
+        let cmd = format!("dkg gjkr qualify {}", key_ids[party_idx]);
+        let output: QualifyOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.disqualified, vec![shamir_xs[2].clone()]);
+        if party_idx != 2 {
+            feldman_commitments.insert(
+                shamir_xs[party_idx].clone(),
+                output.feldman_commitment.expect("feldman_commitment"),
+            );
+        } else {
+            assert!(output.feldman_commitment.is_none());
+        }
+    }
+
+    let public_key = aggregate_all(&storage_arg, &key_ids[..2], &feldman_commitments, json!({}));
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids[..2], &public_key);
+}
+
+#[test]
+fn invalid_feldman_commitment_is_reconstructed_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::edwards::EdwardsPoint;
+    let curve = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "ed-rc");
+    let mut feldman_commitments = qualify_all(&storage_arg, &shamir_xs, &key_ids, &deal_outputs);
+    let expected_public_key = public_key_of::<G>(curve, &feldman_commitments);
+
+    // the dealer #2 biases the public key by revealing an inconsistent Feldman commitment
+    let commitment = feldman_commitments.get_mut(&shamir_xs[2]).expect("feldman_commitment");
+    commitment[0] = Point::from_value(
+        curve,
+        commitment[0].restore::<G>(curve).expect("commitment") + G::generator(),
+    );
+
+    for key_id in key_ids[..2].iter() {
+        let cmd = format!("dkg gjkr aggregate {}", key_id);
+        let input = json!({ "feldman_commitments": feldman_commitments });
+        run(&storage_arg, &cmd, input, 1);
+    }
+
+    // the parties reveal their deals of the dealer #2 to reconstruct its Feldman commitment
+    let mut reveals = HashMap::new();
+    for (shamir_x, key_id) in shamir_xs.iter().zip(&key_ids) {
+        let cmd = format!("dkg gjkr reveal {}", key_id);
+        let input = json!({
+            "feldman_commitments": { shamir_xs[2].to_string(): feldman_commitments[&shamir_xs[2]] },
+        });
+        let revealed: HashMap<Scalar, serde_json::Value> =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        reveals.insert(shamir_x.clone(), revealed[&shamir_xs[2]].clone());
+    }
+    let reveals = json!({ shamir_xs[2].to_string(): reveals });
+
+    let public_key = aggregate_all(&storage_arg, &key_ids, &feldman_commitments, reveals);
+    assert_eq!(public_key.restore::<G>(curve).expect("public_key"), expected_public_key);
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids, &public_key);
+}
+
+#[test]
+fn reveal_requires_an_invalid_feldman_commitment_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "k1-rv");
+    let feldman_commitments = qualify_all(&storage_arg, &shamir_xs, &key_ids, &deal_outputs);
+
+    let reveal = |party_idx: usize, feldman_commitment: &[Point], reveals, ret_code| {
+        let cmd = format!("dkg gjkr reveal {}", key_ids[party_idx]);
+        let input = json!({
+            "feldman_commitments": { shamir_xs[2].to_string(): feldman_commitment },
+            "reveals": reveals,
+        });
+        run(&storage_arg, &cmd, input, ret_code)
+    };
+
+    // the deals of an honest dealer stay secret
+    for party_idx in 0..3 {
+        reveal(party_idx, &feldman_commitments[&shamir_xs[2]], json!({}), 1);
+    }
+
+    // the dealer #2 adds `r * (X - x_1)` to its polynomial in the exponent: its Feldman
+    // commitment fails the check of every party but the party #1
+    let x_1 = shamir_xs[1].restore::<F>(curve).expect("x");
+    let mut commitment = feldman_commitments[&shamir_xs[2]]
+        .iter()
+        .map(|p| p.restore::<G>(curve).expect("commitment"))
+        .collect::<Vec<_>>();
+    commitment[0] -= G::generator() * x_1;
+    commitment[1] += G::generator();
+    let commitment =
+        commitment.into_iter().map(|p| Point::from_value(curve, p)).collect::<Vec<_>>();
+
+    reveal(1, &commitment, json!({}), 1);
+    let revealed: HashMap<Scalar, serde_json::Value> =
+        reveal(0, &commitment, json!({}), 0).stdout_as_yaml().expect("io:de");
+    let deal = revealed[&shamir_xs[2]].clone();
+
+    // the deal revealed by the party #0 proves the Feldman commitment invalid, unless passed off
+    // as the deal of another party
+    let forged = json!({ shamir_xs[2].to_string(): { shamir_xs[2].to_string(): deal.clone() } });
+    reveal(1, &commitment, forged, 1);
+    let proof = json!({ shamir_xs[2].to_string(): { shamir_xs[0].to_string(): deal } });
+    reveal(1, &commitment, proof, 0);
+}
+
+#[test]
+fn aggregate_keeps_the_session_if_the_key_exists_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, "k1-taken");
+    let feldman_commitments = qualify_all(&storage_arg, &shamir_xs, &key_ids, &deal_outputs);
+
+    // the key-id gets taken while the session is in progress
+    run(&storage_arg, &format!("keys gen --curve {} {}", curve, key_ids[0]), json!(null), 0);
+    let cmd = format!("dkg gjkr aggregate {}", key_ids[0]);
+    let input = json!({ "feldman_commitments": feldman_commitments, "reveals": {} });
+    run(&storage_arg, &cmd, &input, 1);
+
+    // the session survives: once the key-id is freed, the share is aggregated
+    run(&storage_arg, &format!("keys rm {}", key_ids[0]), json!(null), 0);
+    let public_key = aggregate_all(&storage_arg, &key_ids, &feldman_commitments, json!({}));
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids, &public_key);
+}
+
+#[test]
+fn repeated_x_is_refused_in_memory() {
+    type F = k256::Scalar;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let x = |idx: u64| Scalar::from_value(curve, F::from(idx));
+    let deal = |shamir_xs: &[Scalar], ret_code| {
+        let input = json!({ "threshold": 2, "this": 0, "shamir_xs": shamir_xs });
+        run(&storage_arg, "dkg gjkr deal --curve secp256k1 k1-rep:1", input, ret_code);
+    };
+
+    let upper_case = |idx: u64| {
+        let x = x(idx).to_string();
+        Scalar::from_hex(curve, x.split_once(':').expect("hex").1.to_uppercase())
+    };
+
+    // the holder #2 would get two shares, and `n` would be 3
+    deal(&[x(1), x(2), x(2)], 1);
+    // the same `x`, encoded differently
+    deal(&[x(1), x(0xab), upper_case(0xab)], 1);
+    deal(&[x(1), x(2), x(3)], 0);
+}
+
+fn run_2_of_3<F, G>(curve: CurveSelect, key_prefix: &str)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let (shamir_xs, key_ids, deal_outputs) = deal_2_of_3::<F>(&storage_arg, curve, key_prefix);
+
+    // nothing is known about the public key before the dealers are qualified
+    let cmd = format!("dkg gjkr aggregate {}", key_ids[0]);
+    run(&storage_arg, &cmd, json!({ "feldman_commitments": {} }), 1);
+
+    let feldman_commitments = qualify_all(&storage_arg, &shamir_xs, &key_ids, &deal_outputs);
+    let public_key = aggregate_all(&storage_arg, &key_ids, &feldman_commitments, json!({}));
+
+    assert_eq!(
+        public_key.restore::<G>(curve).expect("public_key"),
+        public_key_of::<G>(curve, &feldman_commitments)
+    );
+    assert_2_of_3_key::<F, G>(&storage_arg, curve, &key_ids, &public_key);
+}
+
+fn deal_2_of_3<F: PrimeField>(
+    storage_arg: &str,
+    curve: CurveSelect,
+    key_prefix: &str,
+) -> (Vec<Scalar>, Vec<String>, Vec<DealOutput>) {
+    dkg_deal_2_of_3::<F, _>(storage_arg, curve, "gjkr", key_prefix, json!({}))
+}
+
+/// The input of `complain` and `qualify` of the party `party_idx`.
+fn received(
+    shamir_xs: &[Scalar],
+    deal_outputs: &[DealOutput],
+    party_idx: usize,
+) -> serde_json::Value {
+    dkg_received(shamir_xs, deal_outputs, party_idx, &[("commitment", "commitments")])
+}
+
+/// Qualify all the dealers: the Feldman commitments, keyed by the `x` of the dealer.
+fn qualify_all(
+    storage_arg: &str,
+    shamir_xs: &[Scalar],
+    key_ids: &[String],
+    deal_outputs: &[DealOutput],
+) -> HashMap<Scalar, Vec<Point>> {
+    (0..shamir_xs.len())
+        .map(|party_idx| {
+            let cmd = format!("dkg gjkr qualify {}", key_ids[party_idx]);
+            let input = received(shamir_xs, deal_outputs, party_idx);
+            let output: QualifyOutput =
+                run(storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+            assert!(output.disqualified.is_empty());
+            let feldman_commitment = output.feldman_commitment.expect("feldman_commitment");
+            (shamir_xs[party_idx].clone(), feldman_commitment)
+        })
+        .collect()
+}
+
+/// Aggregate the key-shares `key_ids`: the public key they agree on.
+fn aggregate_all(
+    storage_arg: &str,
+    key_ids: &[String],
+    feldman_commitments: &HashMap<Scalar, Vec<Point>>,
+    reveals: serde_json::Value,
+) -> Point {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("dkg gjkr aggregate {}", key_id);
+            let input = json!({ "feldman_commitments": feldman_commitments, "reveals": reveals });
+            let output: AggregateOutput =
+                run(storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+            output.public_key
+        })
+        .reduce(|left, right| {
+            assert_eq!(left, right);
+            left
+        })
+        .expect("zero parties?")
+}
+
+fn public_key_of<G: Group + GroupEncoding>(
+    curve: CurveSelect,
+    feldman_commitments: &HashMap<Scalar, Vec<Point>>,
+) -> G {
+    feldman_commitments
+        .values()
+        .map(|c| c[0].restore::<G>(curve).expect("commitment"))
+        .fold(G::identity(), |acc, c| acc + c)
+}
@@ -0,0 +1,918 @@
+use std::collections::HashMap;
+
+use digest::Digest;
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use serde::Deserialize;
+use serde_json::json;
+
+use cli_storage::{RawTable, Storage, LEGACY_VERSION};
+use common_interop::curve_select::CurveSelect;
+use common_interop::types::{Point, Scalar, SecretScalar};
+
+use shamir_sss::LagrangeCoefficientAt;
+
+use crate::cli;
+use crate::data::{FullKey, Key, S4Share, Threshold, S4};
+use crate::tests::cli_utils::{args, import_2_of_3, in_memory, key_ids_2_of_3, run};
+use crate::tests::io_utils::TestIO;
+
+#[derive(Debug, Deserialize)]
+struct DealOutput {
+    commitment: Vec<Point>,
+    deals: HashMap<Scalar, Scalar>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshDealOutput {
+    commitment: Vec<Point>,
+    deals: HashMap<Scalar, Scalar>,
+    verification_share: Point,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshAggregateOutput {
+    public_key: Point,
+    verification_shares: HashMap<Scalar, Point>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepairDealOutput {
+    public_share: Point,
+    deltas: HashMap<Scalar, Scalar>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepairSumOutput {
+    sigma: Scalar,
+}
+
+#[derive(Debug, Deserialize)]
+struct AggregateOutput {
+    public_key: Point,
+}
+
+#[test]
+fn refresh_secp256k1_in_memory() {
+    refresh_2_of_3::<k256::Scalar, k256::ProjectivePoint>(CurveSelect::Secp256k1, "k1")
+}
+
+#[test]
+fn refresh_ed25519_in_memory() {
+    refresh_2_of_3::<curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        CurveSelect::Ed25519,
+        "ed",
+    )
+}
+
+#[test]
+fn refresh_ristretto25519_in_memory() {
+    refresh_2_of_3::<curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint>(
+        CurveSelect::Ristretto25519,
+        "ri",
+    )
+}
+
+#[test]
+fn refresh_rejects_invalid_deal_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3("ri-inv");
+    let (shamir_xs, _) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let old_shares = export_all(&storage_arg, &key_ids);
+
+    let mut deal_outputs = deal_all(&storage_arg, &shamir_xs, &key_ids);
+
+    // the holder #2 corrupts its deal for the holder #0
+    let deal = deal_outputs[2].deals.get_mut(&shamir_xs[0]).expect("deal");
+    *deal = Scalar::from_value(curve, deal.restore::<F>(curve).expect("deal") + F::ONE);
+
+    let cmd = format!("s4 refresh aggregate {}", key_ids[0]);
+    run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 0), 1);
+
+    // the holder #2 deals a non-zero secret, trying to shift the public key
+    let commitment = &mut deal_outputs[2].commitment;
+    commitment[0] = Point::from_value(curve, G::generator());
+
+    let cmd = format!("s4 refresh aggregate {}", key_ids[1]);
+    run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 1), 1);
+
+    // the holder #2 deals no polynomial at all
+    deal_outputs[2].commitment.clear();
+    let zero = Scalar::from_value(curve, F::ZERO);
+    deal_outputs[2].deals.insert(shamir_xs[1].clone(), zero);
+    run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 1), 1);
+
+    // the holder #1 is honest, but claims a key-share of another key
+    let mut deal_outputs = deal_all_again(&storage_arg, &shamir_xs, &key_ids);
+    deal_outputs[1].verification_share = Point::from_value(curve, G::generator());
+    let cmd = format!("s4 refresh aggregate {}", key_ids[0]);
+    run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, 0), 1);
+
+    // the key-shares are left intact, and the sessions can be restarted
+    let shares = export_all(&storage_arg, &key_ids);
+    for (old, new) in old_shares.iter().zip(&shares) {
+        assert_eq!(old.y, new.y);
+    }
+    for key_id in key_ids.iter() {
+        run(&storage_arg, &format!("s4 refresh reset {}", key_id), (), 0);
+        run(&storage_arg, &format!("s4 refresh reset {}", key_id), (), 1);
+    }
+    deal_all(&storage_arg, &shamir_xs, &key_ids);
+}
+
+#[test]
+fn refresh_requires_all_the_holders_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3("k1-all");
+    let (shamir_xs, _) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let cmd = format!("s4 refresh deal {}", key_ids[0]);
+
+    // the holder #2 is left out
+    run(&storage_arg, &cmd, json!({ "shamir_xs": shamir_xs[..2] }), 1);
+
+    // the holder #1 is listed twice instead of the holder #2
+    let twice = [&shamir_xs[0], &shamir_xs[1], &shamir_xs[1]];
+    run(&storage_arg, &cmd, json!({ "shamir_xs": twice }), 1);
+    let twice = [&shamir_xs[0], &shamir_xs[1], &upper_case(curve, &shamir_xs[1])];
+    run(&storage_arg, &cmd, json!({ "shamir_xs": twice }), 1);
+
+    // the zero `x` is the secret itself
+    let zero = Scalar::from_value(curve, F::ZERO);
+    let with_zero = [&shamir_xs[0], &shamir_xs[1], &shamir_xs[2], &zero];
+    run(&storage_arg, &cmd, json!({ "shamir_xs": with_zero }), 1);
+
+    run(&storage_arg, &cmd, json!({ "shamir_xs": shamir_xs }), 0);
+
+    // this holder's `x` encoded differently is still this holder's
+    run(&storage_arg, &format!("s4 refresh reset {}", key_ids[0]), (), 0);
+    let upper_x = upper_case(curve, &shamir_xs[0]);
+    let input = json!({ "shamir_xs": [&shamir_xs[2], &shamir_xs[1], &upper_x] });
+    let output: RefreshDealOutput =
+        run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+    assert_eq!(output.deals.len(), 2);
+    assert!(output.deals.contains_key(&shamir_xs[1]) && output.deals.contains_key(&shamir_xs[2]));
+}
+
+fn refresh_2_of_3<F, G>(curve: CurveSelect, key_prefix: &str)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3(key_prefix);
+    let (shamir_xs, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let old_shares = export_all(&storage_arg, &key_ids);
+
+    // nothing to aggregate before the deal
+    run(&storage_arg, &format!("s4 refresh aggregate {}", key_ids[0]), json!({}), 1);
+
+    let deal_outputs = deal_all(&storage_arg, &shamir_xs, &key_ids);
+    run(
+        &storage_arg,
+        &format!("s4 refresh deal {}", key_ids[0]),
+        json!({ "shamir_xs": shamir_xs }),
+        1,
+    );
+
+    let outputs = key_ids
+        .iter()
+        .enumerate()
+        .map(|(party_idx, key_id)| {
+            let cmd = format!("s4 refresh aggregate {}", key_id);
+            let output: RefreshAggregateOutput =
+                run(&storage_arg, &cmd, received(&shamir_xs, &deal_outputs, party_idx), 0)
+                    .stdout_as_yaml()
+                    .expect("io:de");
+            assert_eq!(output.public_key, public_key);
+            output
+        })
+        .collect::<Vec<_>>();
+
+    let new_shares = export_all(&storage_arg, &key_ids);
+    for output in outputs.iter() {
+        for new in new_shares.iter() {
+            let y = new.y.restore::<F>(curve).expect("y");
+            let verification_share = Point::from_value(curve, G::generator() * y);
+            assert_eq!(output.verification_shares[&new.x], verification_share);
+        }
+    }
+    for (old, new) in old_shares.iter().zip(&new_shares) {
+        assert_eq!(old.public_key, new.public_key);
+        assert_eq!(old.x, new.x);
+        assert_ne!(old.y, new.y);
+    }
+
+    let public_key = public_key.restore::<G>(curve).expect("public_key");
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        assert_eq!(
+            G::generator() * secret_of::<F>(curve, &[&new_shares[i], &new_shares[j]]),
+            public_key
+        );
+
+        // the old key-shares can't be combined with the new ones
+        assert_ne!(
+            G::generator() * secret_of::<F>(curve, &[&old_shares[i], &new_shares[j]]),
+            public_key
+        );
+    }
+}
+
+#[test]
+fn reshare_secp256k1_in_memory() {
+    reshare_2_of_3_to_3_of_5::<k256::Scalar, k256::ProjectivePoint>(CurveSelect::Secp256k1, "k1")
+}
+
+#[test]
+fn reshare_ed25519_in_memory() {
+    reshare_2_of_3_to_3_of_5::<curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        CurveSelect::Ed25519,
+        "ed",
+    )
+}
+
+#[test]
+fn reshare_ristretto25519_in_memory() {
+    reshare_2_of_3_to_3_of_5::<curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint>(
+        CurveSelect::Ristretto25519,
+        "ri",
+    )
+}
+
+#[test]
+fn reshare_rejects_invalid_deals_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let old_key_ids = key_ids_2_of_3("ri-inv");
+    let (old_xs, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &old_key_ids);
+    let new_xs = new_shamir_xs::<F>(curve, 3);
+    let threshold = Threshold { t: 2, n: 3 };
+
+    // a single old holder can't deal: its key-share is not enough to recover the secret
+    let input =
+        json!({ "old_shamir_xs": old_xs[..1], "threshold": threshold, "new_shamir_xs": new_xs });
+    run(&storage_arg, &format!("s4 reshare deal {}", old_key_ids[0]), input, 1);
+
+    // the old holder #0 counted twice, the zero `x`, a threshold not of the new holders
+    let cmd = format!("s4 reshare deal {}", old_key_ids[0]);
+    let twice = [&old_xs[0], &old_xs[0]];
+    let input = json!({ "old_shamir_xs": twice, "threshold": threshold, "new_shamir_xs": new_xs });
+    run(&storage_arg, &cmd, input, 1);
+    let twice = [old_xs[0].clone(), upper_case(curve, &old_xs[0])];
+    let input = json!({ "old_shamir_xs": twice, "threshold": threshold, "new_shamir_xs": new_xs });
+    run(&storage_arg, &cmd, input, 1);
+    let twice = [&new_xs[0], &new_xs[1], &upper_case(curve, &new_xs[1])];
+    let input =
+        json!({ "old_shamir_xs": old_xs[..2], "threshold": threshold, "new_shamir_xs": twice });
+    run(&storage_arg, &cmd, input, 1);
+    let zero = Scalar::from_value(curve, F::ZERO);
+    let with_zero = [&new_xs[0], &new_xs[1], &zero];
+    let input =
+        json!({ "old_shamir_xs": old_xs[..2], "threshold": threshold, "new_shamir_xs": with_zero });
+    run(&storage_arg, &cmd, input, 1);
+    let input = json!({ "old_shamir_xs": old_xs[..2], "threshold": threshold, "new_shamir_xs": new_xs[..2] });
+    run(&storage_arg, &cmd, input, 1);
+
+    let input =
+        json!({ "old_shamir_xs": old_xs[..2], "threshold": threshold, "new_shamir_xs": new_xs });
+    let mut deal_outputs = old_key_ids[..2]
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("s4 reshare deal {}", key_id);
+            run(&storage_arg, &cmd, &input, 0)
+                .stdout_as_yaml::<DealOutput>()
+                .expect("io:de")
+        })
+        .collect::<Vec<_>>();
+
+    // the old holder #1 weighs its key-share as if all the three old holders took part
+    let input = json!({ "old_shamir_xs": old_xs, "threshold": threshold, "new_shamir_xs": new_xs });
+    let cmd = format!("s4 reshare deal {}", old_key_ids[1]);
+    let misweighted: DealOutput =
+        run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+
+    let mut misweighted_outputs = vec![deal_outputs.remove(0), misweighted];
+    let input =
+        reshare_input(&public_key, threshold, &old_xs[..2], &misweighted_outputs, &new_xs[0]);
+    run(&storage_arg, "s4 reshare aggregate -c ristretto25519 new:1", input, 1);
+
+    // the old holder #0 corrupts its deal for the new holder #0
+    let deal = misweighted_outputs[0].deals.get_mut(&new_xs[0]).expect("deal");
+    *deal = Scalar::from_value(curve, deal.restore::<F>(curve).expect("deal") + F::ONE);
+    deal_outputs.insert(0, misweighted_outputs.remove(0));
+
+    let input = reshare_input(&public_key, threshold, &old_xs[..2], &deal_outputs, &new_xs[0]);
+    run(&storage_arg, "s4 reshare aggregate -c ristretto25519 new:1", input, 1);
+
+    // the other new holders are not affected
+    let input = reshare_input(&public_key, threshold, &old_xs[..2], &deal_outputs, &new_xs[1]);
+    run(&storage_arg, "s4 reshare aggregate -c ristretto25519 new:2", input.clone(), 0);
+    run(&storage_arg, "s4 reshare aggregate -c ristretto25519 new:2", input, 1);
+}
+
+fn reshare_2_of_3_to_3_of_5<F, G>(curve: CurveSelect, key_prefix: &str)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let old_key_ids = key_ids_2_of_3(key_prefix);
+    let (old_xs, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &old_key_ids);
+    let new_xs = new_shamir_xs::<F>(curve, 5);
+    let threshold = Threshold { t: 3, n: 5 };
+
+    // any two of the old holders can deal
+    let dealers = [0, 2];
+    let old_xs = dealers.map(|i| old_xs[i].clone());
+    let input = json!({ "old_shamir_xs": old_xs, "threshold": threshold, "new_shamir_xs": new_xs });
+    let deal_outputs = dealers
+        .iter()
+        .map(|&i| {
+            let cmd = format!("s4 reshare deal {}", old_key_ids[i]);
+            run(&storage_arg, &cmd, &input, 0)
+                .stdout_as_yaml::<DealOutput>()
+                .expect("io:de")
+        })
+        .collect::<Vec<_>>();
+
+    let new_key_ids =
+        (0..5).map(|idx| format!("{}-new:{}", key_prefix, idx + 1)).collect::<Vec<_>>();
+    for (new_x, key_id) in new_xs.iter().zip(&new_key_ids) {
+        let cmd = format!("s4 reshare aggregate -c {} {}", curve, key_id);
+        let input = reshare_input(&public_key, threshold, &old_xs, &deal_outputs, new_x);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.public_key, public_key);
+    }
+
+    let new_shares = export_all(&storage_arg, &new_key_ids);
+    for s4_share in new_shares.iter() {
+        assert_eq!(s4_share.threshold, threshold);
+        assert_eq!(s4_share.public_key, public_key);
+    }
+
+    let public_key = public_key.restore::<G>(curve).expect("public_key");
+    for i in 0..5 {
+        let [j, k] = [(i + 1) % 5, (i + 3) % 5];
+        let secret = secret_of::<F>(curve, &[&new_shares[i], &new_shares[j], &new_shares[k]]);
+        assert_eq!(G::generator() * secret, public_key);
+
+        // the new threshold is three
+        let secret = secret_of::<F>(curve, &[&new_shares[i], &new_shares[j]]);
+        assert_ne!(G::generator() * secret, public_key);
+    }
+}
+
+#[test]
+fn combine_secp256k1_in_memory() {
+    combine_2_of_3::<k256::Scalar, k256::ProjectivePoint>(CurveSelect::Secp256k1, "k1")
+}
+
+#[test]
+fn combine_ed25519_in_memory() {
+    combine_2_of_3::<curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        CurveSelect::Ed25519,
+        "ed",
+    )
+}
+
+#[test]
+fn combine_ristretto25519_in_memory() {
+    combine_2_of_3::<curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint>(
+        CurveSelect::Ristretto25519,
+        "ri",
+    )
+}
+
+#[test]
+fn combine_rejects_invalid_shares_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3("ri-a");
+    import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let other_key_ids = key_ids_2_of_3("ri-b");
+    import_2_of_3::<F, G>(&storage_arg, curve, &other_key_ids);
+
+    // not enough key-shares
+    run(&storage_arg, &format!("s4 combine {}", key_ids[0]), (), 1);
+    // the same key-share twice
+    run(&storage_arg, &format!("s4 combine {} {}", key_ids[0], key_ids[0]), (), 1);
+    // the key-shares of different keys
+    run(&storage_arg, &format!("s4 combine {} {}", key_ids[0], other_key_ids[1]), (), 1);
+    // no such key
+    run(&storage_arg, &format!("s4 combine {} ri-c:1", key_ids[0]), (), 1);
+
+    // the same key-share twice, its `x` encoded differently
+    let s4_share = export_all(&storage_arg, &key_ids[..1]).remove(0);
+    let twice = S4Share { x: upper_case(curve, &s4_share.x), ..s4_share.clone() };
+    let input = [s4_share, twice].into_iter().map(Key::S4Share).collect::<Vec<_>>();
+    run(&storage_arg, "s4 combine --key-id ri-a", input, 1);
+
+    // a corrupted key-share
+    let mut s4_shares = export_all(&storage_arg, &key_ids);
+    s4_shares[1].y =
+        SecretScalar::from_value(curve, s4_shares[1].y.restore::<F>(curve).expect("y") + F::ONE);
+    let input = s4_shares.into_iter().map(Key::S4Share).collect::<Vec<_>>();
+    run(&storage_arg, "s4 combine --key-id ri-a", input, 1);
+    run(&storage_arg, "keys export ri-a", (), 1);
+}
+
+#[test]
+fn gen_2_of_3_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let secret = <F as ff::Field>::random(rand::rngs::OsRng);
+    let full_key = Key::FullKey(FullKey { curve, value: SecretScalar::from_value(curve, secret) });
+    run(&storage_arg, "keys import ri", full_key, 0);
+
+    let cmd = format!("{}s4 gen --key-id ri --threshold 3-of-2 ri-s4", storage_arg);
+    assert!(cli::Cli::create_safe(args(cmd)).is_err());
+    run(&storage_arg, "s4 gen --key-id ri --threshold 2-of-3 ri-s4", (), 0);
+
+    let s4: S4 = run(&storage_arg, "s4 export ri-s4", (), 0).stdout_as_yaml().expect("io:de");
+    assert_eq!(s4.threshold, Threshold { t: 2, n: 3 });
+    assert_eq!(s4.polynomial.len(), 2);
+
+    let key_ids = (1..=3).map(|idx| format!("ri:{}", idx)).collect::<Vec<_>>();
+    for (idx, key_id) in key_ids.iter().enumerate() {
+        let x = Scalar::from_value(curve, F::from(idx as u64 + 1));
+        run(&storage_arg, &format!("s4 issue-share --key-id {} ri-s4 {}", key_id, x), (), 0);
+    }
+    let s4_shares = export_all(&storage_arg, &key_ids);
+    for s4_share in s4_shares.iter() {
+        assert_eq!(s4_share.threshold, Threshold { t: 2, n: 3 });
+    }
+
+    // any two of the key-shares are enough
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        assert_eq!(secret_of::<F>(curve, &[&s4_shares[i], &s4_shares[j]]), secret);
+        let cmd = format!("s4 combine {} {}", key_ids[i], key_ids[j]);
+        run(&storage_arg, &cmd, (), 0);
+    }
+    run(&storage_arg, &format!("s4 combine {}", key_ids[0]), (), 1);
+
+    // no more than `n` key-shares, each of a non-zero `x`: the same one can be issued again
+    let x = Scalar::from_value(curve, F::from(4u64));
+    run(&storage_arg, &format!("s4 issue-share --key-id ri:4 ri-s4 {}", x), (), 1);
+    let x = Scalar::from_value(curve, F::from(1u64));
+    run(&storage_arg, &format!("s4 issue-share --key-id ri:1-again ri-s4 {}", x), (), 0);
+    run(&storage_arg, &format!("s4 issue-share --key-id ri:1 ri-s4 {}", x), (), 1);
+    run(&storage_arg, &format!("s4 issue-share --key-id ri:1-b ri-s4-none {}", x), (), 1);
+    let x = Scalar::from_value(curve, F::ZERO);
+    run(&storage_arg, "s4 rm --s4-id ri-s4", (), 0);
+    run(&storage_arg, "s4 gen --key-id ri --threshold 2-of-3 ri-s4", (), 0);
+    run(&storage_arg, &format!("s4 issue-share --key-id ri:0 ri-s4 {}", x), (), 1);
+}
+
+#[test]
+fn legacy_threshold_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_path = in_memory();
+    let storage_arg = format!("--storage-path {} ", storage_path);
+
+    let scalar = |value: u64| Scalar::from_value(curve, F::from(value));
+    let point = |value: u64| Point::from_value(curve, G::generator() * F::from(value));
+
+    {
+        let storage = Storage::open(&storage_path).expect("storage");
+        // the tables named after the types, the records without a version
+        let tab_keys = RawTable::open(&storage, "rabbit_hole_cli_alice::data::Key").expect("keys");
+        let tab_s4 = RawTable::open(&storage, "rabbit_hole_cli_alice::data::S4").expect("s4");
+
+        // a scheme: no threshold, `t` is the count of the coefficients
+        let legacy = json!({ "curve": curve, "polynomial": [scalar(5), scalar(7)] });
+        tab_s4.insert("ri-s4", LEGACY_VERSION, &legacy).expect("insert");
+        // a key-share issued by that scheme: the degree of the polynomial
+        let legacy = json!({ "s4_share": {
+            "curve": curve, "threshold": 1, "public_key": point(5), "x": scalar(1), "y": scalar(12),
+        } });
+        tab_keys.insert("ri-s4:1", LEGACY_VERSION, &legacy).expect("insert");
+        // a key-share of the DKG: the count of the key-shares needed
+        let legacy = json!({ "s4_share": {
+            "curve": curve, "threshold": 2, "public_key": point(3), "x": scalar(1), "y": scalar(4),
+        } });
+        tab_keys.insert("ri:1", LEGACY_VERSION, &legacy).expect("insert");
+    }
+
+    // refused until migrated
+    let io = TestIO::from_empty_input();
+    let cli =
+        cli::Cli::create_safe(args(format!("{}keys export ri:1", storage_arg))).expect("args");
+    let err = cli::run(&cli, rand::rngs::OsRng, &io).expect_err("legacy threshold");
+    assert!(err.to_string().contains("alice storage migrate"), "{}", err);
+
+    // the count of the key-shares is not recorded
+    let io = run(&storage_arg, "storage migrate", (), 1);
+    assert!(io.stderr_as_string().contains("--scheme ri-s4=2-of-N"));
+    assert!(io.stderr_as_string().contains("--key ri:1=T-of-N"));
+    run(&storage_arg, "storage migrate --scheme ri-s4=2-of-3", (), 1);
+    run(&storage_arg, "storage migrate --scheme ri-s4=3-of-3 --key ri:1=2-of-3", (), 1);
+
+    let cmd = "storage migrate --dry-run --scheme ri-s4=2-of-3 --key ri:1=2-of-4";
+    let report: serde_json::Value = run(&storage_arg, cmd, (), 0).stdout_as_yaml().expect("io:de");
+    let thresholds = &report["explicit-thresholds"];
+    assert_eq!(thresholds["keys"]["ri-s4:1"], "2-of-3");
+    assert_eq!(thresholds["keys"]["ri:1"], "2-of-4");
+    assert_eq!(thresholds["s4/schemes"]["ri-s4"], "2-of-3");
+    assert_eq!(report["stable-table-ids"]["rabbit_hole_cli_alice::data::Key"], "keys");
+    assert_eq!(report["record-versions"]["keys"], 2);
+    let cli = cli::Cli::create_safe(args(format!("{}s4 export ri-s4", storage_arg))).expect("args");
+    assert!(cli::run(&cli, rand::rngs::OsRng, &io).is_err());
+
+    let cmd = "storage migrate --scheme ri-s4=2-of-3 --key ri:1=2-of-4";
+    run(&storage_arg, cmd, (), 0);
+    let s4_shares = export_all(&storage_arg, &["ri-s4:1".into(), "ri:1".into()]);
+    assert_eq!(s4_shares[0].threshold, Threshold { t: 2, n: 3 });
+    assert_eq!(s4_shares[1].threshold, Threshold { t: 2, n: 4 });
+    let s4: S4 = run(&storage_arg, "s4 export ri-s4", (), 0).stdout_as_yaml().expect("io:de");
+    assert_eq!(s4.threshold, Threshold { t: 2, n: 3 });
+    assert_eq!(s4.issued, vec![scalar(1)]);
+
+    // the key-share issued before the migration counts
+    run(&storage_arg, &format!("s4 issue-share --key-id ri-s4:2 ri-s4 {}", scalar(2)), (), 0);
+    run(&storage_arg, &format!("s4 issue-share --key-id ri-s4:3 ri-s4 {}", scalar(3)), (), 0);
+    run(&storage_arg, &format!("s4 issue-share --key-id ri-s4:4 ri-s4 {}", scalar(4)), (), 1);
+
+    // nothing left to migrate
+    let report: HashMap<String, serde_json::Value> =
+        run(&storage_arg, "storage migrate", (), 0).stdout_as_yaml().expect("io:de");
+    assert!(report.is_empty(), "{:?}", report);
+
+    // the threshold does not match the polynomial
+    let invalid = json!({
+        "curve": curve,
+        "threshold": Threshold { t: 2, n: 3 },
+        "polynomial": [scalar(1), scalar(2), scalar(3)],
+    });
+    run(&storage_arg, "s4 import ri-s4-invalid", invalid, 1);
+}
+
+#[test]
+fn invalid_threshold_is_refused_in_memory() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    let curve = CurveSelect::Secp256k1;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let scalar = |value: u64| Scalar::from_value(curve, F::from(value));
+    let point = |value: u64| Point::from_value(curve, G::generator() * F::from(value));
+
+    for (t, n) in [(0, 3), (3, 2)] {
+        let key = json!({ "s4_share": {
+            "curve": curve, "threshold": { "t": t, "n": n },
+            "public_key": point(5), "x": scalar(1), "y": scalar(12),
+        } });
+        let io = TestIO::from_yaml_stdin(key).expect("make io");
+        let cli =
+            cli::Cli::create_safe(args(format!("{}keys import ri:1", storage_arg))).expect("args");
+        let err = cli::run(&cli, rand::rngs::OsRng, &io).expect_err("invalid threshold");
+        assert!(err.to_string().contains("Invalid threshold"), "{}", err);
+    }
+    run(&storage_arg, "keys export ri:1", (), 1);
+}
+
+fn combine_2_of_3<F, G>(curve: CurveSelect, key_prefix: &str)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3(key_prefix);
+    let (_, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let public_key = public_key.restore::<G>(curve).expect("public_key");
+
+    let value_of = |key: Key| {
+        let Key::FullKey(full_key) = key else { panic!("not a full-key") };
+        assert_eq!(full_key.curve, curve);
+        full_key.value.restore::<F>(curve).expect("value")
+    };
+
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        let cmd = format!("s4 combine {} {}", key_ids[i], key_ids[j]);
+        let key: Key = run(&storage_arg, &cmd, (), 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(G::generator() * value_of(key), public_key);
+    }
+
+    // the exported key-shares, stored as a full-key
+    let input = export_all(&storage_arg, &key_ids)
+        .into_iter()
+        .map(Key::S4Share)
+        .collect::<Vec<_>>();
+    let cmd = format!("s4 combine --key-id {}", key_prefix);
+    run(&storage_arg, &cmd, input.clone(), 0);
+    run(&storage_arg, &cmd, input, 1);
+
+    let key: Key = run(&storage_arg, &format!("keys export {}", key_prefix), (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(G::generator() * value_of(key), public_key);
+}
+
+#[test]
+fn repair_secp256k1_in_memory() {
+    repair_2_of_3::<k256::Scalar, k256::ProjectivePoint>(CurveSelect::Secp256k1, "k1")
+}
+
+#[test]
+fn repair_ed25519_in_memory() {
+    repair_2_of_3::<curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        CurveSelect::Ed25519,
+        "ed",
+    )
+}
+
+#[test]
+fn repair_ristretto25519_in_memory() {
+    repair_2_of_3::<curve25519::scalar::Scalar, curve25519::ristretto::RistrettoPoint>(
+        CurveSelect::Ristretto25519,
+        "ri",
+    )
+}
+
+#[test]
+fn repair_rejects_invalid_sigmas_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::ristretto::RistrettoPoint;
+    let curve = CurveSelect::Ristretto25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3("ri-inv");
+    let (shamir_xs, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+
+    // the holder of the lost key-share can't help, and a single helper is not enough
+    let input = json!({ "helpers_xs": shamir_xs, "lost_x": shamir_xs[0] });
+    run(&storage_arg, &format!("s4 repair deal {}", key_ids[1]), input, 1);
+    let input = json!({ "helpers_xs": shamir_xs[1..2], "lost_x": shamir_xs[0] });
+    run(&storage_arg, &format!("s4 repair deal {}", key_ids[1]), input, 1);
+
+    // a helper counted twice, the zero `x` as a helper or as the lost one
+    let twice = [&shamir_xs[1], &shamir_xs[1]];
+    let input = json!({ "helpers_xs": twice, "lost_x": shamir_xs[0] });
+    run(&storage_arg, &format!("s4 repair deal {}", key_ids[1]), input, 1);
+    let zero = Scalar::from_value(curve, F::ZERO);
+    let with_zero = [&shamir_xs[1], &zero];
+    let input = json!({ "helpers_xs": with_zero, "lost_x": shamir_xs[0] });
+    run(&storage_arg, &format!("s4 repair deal {}", key_ids[1]), input, 1);
+    let input = json!({ "helpers_xs": shamir_xs[1..], "lost_x": zero });
+    run(&storage_arg, &format!("s4 repair deal {}", key_ids[1]), input, 1);
+
+    let (public_shares, mut sigmas) =
+        repair_helpers(&storage_arg, &shamir_xs[1..], &key_ids[1..], &shamir_xs[0]);
+
+    // the helper #1 lies about its public share
+    let mut forged_shares = public_shares.clone();
+    forged_shares.insert(shamir_xs[1].clone(), Point::from_value(curve, G::generator()));
+    let input = repair_input(&public_key, &shamir_xs[0], &forged_shares, &sigmas);
+    run(&storage_arg, "s4 repair recover -c ristretto25519 ri-inv:0", input, 1);
+
+    // the helper #1 corrupts its sigma
+    let sigma = sigmas.get_mut(&shamir_xs[1]).expect("sigma");
+    *sigma = Scalar::from_value(curve, sigma.restore::<F>(curve).expect("sigma") + F::ONE);
+    let input = repair_input(&public_key, &shamir_xs[0], &public_shares, &sigmas);
+    run(&storage_arg, "s4 repair recover -c ristretto25519 ri-inv:0", input, 1);
+
+    // the helper #2 counted twice, its `x` encoded differently
+    let (x, upper_x) = (&shamir_xs[2], upper_case(curve, &shamir_xs[2]));
+    let public_shares = HashMap::from([
+        (x.clone(), public_shares[x].clone()),
+        (upper_x.clone(), public_shares[x].clone()),
+    ]);
+    let sigmas = HashMap::from([(x.clone(), sigmas[x].clone()), (upper_x, sigmas[x].clone())]);
+    let input = repair_input(&public_key, &shamir_xs[0], &public_shares, &sigmas);
+    run(&storage_arg, "s4 repair recover -c ristretto25519 ri-inv:0", input, 1);
+
+    run(&storage_arg, "keys export ri-inv:0", (), 1);
+}
+
+fn repair_2_of_3<F, G>(curve: CurveSelect, key_prefix: &str)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let key_ids = key_ids_2_of_3(key_prefix);
+    let (shamir_xs, public_key) = import_2_of_3::<F, G>(&storage_arg, curve, &key_ids);
+    let old_shares = export_all(&storage_arg, &key_ids);
+
+    for lost in 0..3 {
+        run(&storage_arg, &format!("keys rm {}", key_ids[lost]), (), 0);
+
+        let helpers = [(lost + 1) % 3, (lost + 2) % 3];
+        let helpers_xs = helpers.map(|i| shamir_xs[i].clone());
+        let helpers_key_ids = helpers.map(|i| key_ids[i].clone());
+        let (public_shares, sigmas) =
+            repair_helpers(&storage_arg, &helpers_xs, &helpers_key_ids, &shamir_xs[lost]);
+
+        let cmd = format!("s4 repair recover -c {} {}", curve, key_ids[lost]);
+        let input = repair_input(&public_key, &shamir_xs[lost], &public_shares, &sigmas);
+        let output: AggregateOutput =
+            run(&storage_arg, &cmd, input, 0).stdout_as_yaml().expect("io:de");
+        assert_eq!(output.public_key, public_key);
+    }
+
+    for (old, new) in old_shares.iter().zip(export_all(&storage_arg, &key_ids)) {
+        assert_eq!(old.curve, new.curve);
+        assert_eq!(old.threshold, new.threshold);
+        assert_eq!(old.public_key, new.public_key);
+        assert_eq!(old.x, new.x);
+        assert_eq!(old.y, new.y);
+    }
+}
+
+/// Run `deal` and `sum` of the helpers: their public shares and sigmas, keyed by their `x`.
+fn repair_helpers(
+    storage_arg: &str,
+    helpers_xs: &[Scalar],
+    helpers_key_ids: &[String],
+    lost_x: &Scalar,
+) -> (HashMap<Scalar, Point>, HashMap<Scalar, Scalar>) {
+    let input = json!({ "helpers_xs": helpers_xs, "lost_x": lost_x });
+    let deal_outputs = helpers_key_ids
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("s4 repair deal {}", key_id);
+            run(storage_arg, &cmd, &input, 0)
+                .stdout_as_yaml::<RepairDealOutput>()
+                .expect("io:de")
+        })
+        .collect::<Vec<_>>();
+
+    let mut public_shares = HashMap::new();
+    let mut sigmas = HashMap::new();
+    for ((x, key_id), deal_output) in helpers_xs.iter().zip(helpers_key_ids).zip(&deal_outputs) {
+        let deltas = helpers_xs
+            .iter()
+            .zip(&deal_outputs)
+            .map(|(from, o)| (from.clone(), o.deltas[x].clone()))
+            .collect::<HashMap<_, _>>();
+        let cmd = format!("s4 repair sum {}", key_id);
+        let output: RepairSumOutput = run(storage_arg, &cmd, json!({ "deltas": deltas }), 0)
+            .stdout_as_yaml()
+            .expect("io:de");
+
+        public_shares.insert(x.clone(), deal_output.public_share.clone());
+        sigmas.insert(x.clone(), output.sigma);
+    }
+
+    (public_shares, sigmas)
+}
+
+fn repair_input(
+    public_key: &Point,
+    lost_x: &Scalar,
+    public_shares: &HashMap<Scalar, Point>,
+    sigmas: &HashMap<Scalar, Scalar>,
+) -> serde_json::Value {
+    json!({
+        "public_key": public_key,
+        "threshold": Threshold { t: 2, n: 3 },
+        "shamir_x": lost_x,
+        "public_shares": public_shares,
+        "sigmas": sigmas,
+    })
+}
+
+fn new_shamir_xs<F: PrimeField>(curve: CurveSelect, count: usize) -> Vec<Scalar> {
+    (0..count)
+        .map(|idx| {
+            utils::bytes_to_scalar::<F>(sha3::Sha3_256::digest(format!("new:{}", idx)).as_ref())
+        })
+        .map(|x| Scalar::from_value(curve, x))
+        .collect()
+}
+
+/// The input of `reshare aggregate` of the new holder `new_x`.
+fn reshare_input(
+    public_key: &Point,
+    threshold: Threshold,
+    old_xs: &[Scalar],
+    deal_outputs: &[DealOutput],
+    new_x: &Scalar,
+) -> serde_json::Value {
+    let commitments = old_xs
+        .iter()
+        .zip(deal_outputs)
+        .map(|(x, o)| (x.clone(), o.commitment.clone()))
+        .collect::<HashMap<_, _>>();
+    let deals = old_xs
+        .iter()
+        .zip(deal_outputs)
+        .map(|(x, o)| (x.clone(), o.deals[new_x].clone()))
+        .collect::<HashMap<_, _>>();
+    json!({
+        "public_key": public_key,
+        "threshold": threshold,
+        "shamir_x": new_x,
+        "commitments": commitments,
+        "deals": deals,
+    })
+}
+
+fn deal_all(storage_arg: &str, shamir_xs: &[Scalar], key_ids: &[String]) -> Vec<RefreshDealOutput> {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("s4 refresh deal {}", key_id);
+            run(storage_arg, &cmd, json!({ "shamir_xs": shamir_xs }), 0)
+                .stdout_as_yaml()
+                .expect("io:de")
+        })
+        .collect()
+}
+
+/// The same `x`, its hex in the upper case.
+fn upper_case(curve: CurveSelect, x: &Scalar) -> Scalar {
+    let x = x.to_string();
+    let (_, hex) = x.split_once(':').expect("hex");
+    let upper_x = Scalar::from_hex(curve, hex.to_uppercase());
+    assert_ne!(upper_x.to_string(), x);
+    upper_x
+}
+
+fn export_all(storage_arg: &str, key_ids: &[String]) -> Vec<S4Share> {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            let cmd = format!("keys export {}", key_id);
+            let key: Key = run(storage_arg, &cmd, (), 0).stdout_as_yaml().expect("io:de");
+            let Key::S4Share(s4_share) = key else { panic!("not an s4-share") };
+            s4_share
+        })
+        .collect()
+}
+
+/// Reset the refresh sessions of `key_ids` and deal anew.
+fn deal_all_again(
+    storage_arg: &str,
+    shamir_xs: &[Scalar],
+    key_ids: &[String],
+) -> Vec<RefreshDealOutput> {
+    for key_id in key_ids.iter() {
+        run(storage_arg, &format!("s4 refresh reset {}", key_id), (), 0);
+    }
+    deal_all(storage_arg, shamir_xs, key_ids)
+}
+
+/// The input of `aggregate` of the holder `party_idx`.
+fn received(
+    shamir_xs: &[Scalar],
+    deal_outputs: &[RefreshDealOutput],
+    party_idx: usize,
+) -> serde_json::Value {
+    let this_party_x = &shamir_xs[party_idx];
+    let others = (0..shamir_xs.len()).filter(|&i| i != party_idx);
+    let commitments = others
+        .clone()
+        .map(|i| (shamir_xs[i].clone(), deal_outputs[i].commitment.clone()))
+        .collect::<HashMap<_, _>>();
+    let deals = others
+        .clone()
+        .map(|i| (shamir_xs[i].clone(), deal_outputs[i].deals[this_party_x].clone()))
+        .collect::<HashMap<_, _>>();
+    let verification_shares = others
+        .map(|i| (shamir_xs[i].clone(), deal_outputs[i].verification_share.clone()))
+        .collect::<HashMap<_, _>>();
+    json!({ "commitments": commitments, "deals": deals, "verification_shares": verification_shares })
+}
+
+/// The secret interpolated over the `s4_shares`.
+fn secret_of<F: PrimeField>(curve: CurveSelect, s4_shares: &[&S4Share]) -> F {
+    let xs = s4_shares
+        .iter()
+        .map(|s| s.x.restore::<F>(curve).expect("x"))
+        .collect::<Vec<_>>();
+    let ys = s4_shares.iter().map(|s| s.y.restore::<F>(curve).expect("y"));
+    ys.enumerate().map(|(k, y)| y * xs.lagrange_coefficient_at(k, F::ZERO)).sum()
+}
@@ -0,0 +1,388 @@
+use digest::Digest;
+use elliptic_curve::point::DecompressPoint;
+use elliptic_curve::subtle::Choice;
+use ff::{Field, PrimeField};
+use group::Group;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
+use common_interop::transcript::{Input, Transcript};
+use common_interop::types::{Point, Scalar, SecretScalar};
+
+use crate::data::{FullKey, Key};
+use crate::tests::cli_utils::{args, in_memory, run};
+use crate::tests::io_utils::TestIO;
+use crate::{cli, ecdsa, transcript};
+
+type F = k256::Scalar;
+type G = k256::ProjectivePoint;
+
+const CURVE: CurveSelect = CurveSelect::Secp256k1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EcdsaSignature {
+    y: Point,
+    r: Scalar,
+    s: Scalar,
+    recovery_id: u8,
+}
+
+/// The widely used secp256k1 vectors for RFC 6979 with HMAC-SHA-256.
+const ECDSA_RFC6979_VECTORS: &[(u64, &str, &str, &str)] = &[
+    (
+        1,
+        "Satoshi Nakamoto",
+        "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d8",
+        "2442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5",
+    ),
+    (
+        1,
+        "All those moments will be lost in time, like tears in rain. Time to die...",
+        "8600dbd41e348fe5c9465ab92d23e3db8b98b873beecd930736488696438cb6b",
+        "547fe64427496db33bf66019dacbf0039c04199abb0122918601db38a72cfc21",
+    ),
+];
+
+#[test]
+fn sign_ecdsa_rfc6979_vectors() {
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    for (idx, (x, message, r, s)) in ECDSA_RFC6979_VECTORS.iter().enumerate() {
+        let key_id = format!("k1-{}", idx);
+        import_full_key(&storage_arg, &key_id, F::from(*x));
+
+        let transcript = text_transcript(HashFunctionSelect::Sha2_256, message);
+        let signature = sign_ecdsa(&storage_arg, &key_id, &transcript, None);
+
+        assert_eq!(signature.r.to_string(), format!("secp256k1:{}", r));
+        assert_eq!(signature.s.to_string(), format!("secp256k1:{}", s));
+    }
+}
+
+#[test]
+fn sign_ecdsa_and_verify() {
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let mut rng = rand::rngs::OsRng;
+
+    for hash_function in [HashFunctionSelect::Sha2_256, HashFunctionSelect::Sha3_256] {
+        let x = F::random(&mut rng);
+        let key_id = format!("k1-{}", hash_function);
+        import_full_key(&storage_arg, &key_id, x);
+
+        let transcript = text_transcript(hash_function, "Hello there!");
+        let m =
+            ecdsa::digest_to_scalar::<F>(&transcript::produce_digest(&transcript).expect("digest"));
+
+        let instance_key = F::random(&mut rng);
+        for instance_key in [None, Some(instance_key)] {
+            let signature = sign_ecdsa(&storage_arg, &key_id, &transcript, instance_key);
+            let y = signature.y.restore::<G>(CURVE).expect("y");
+            let r = signature.r.restore::<F>(CURVE).expect("r");
+            let s = signature.s.restore::<F>(CURVE).expect("s");
+
+            assert_eq!(y, G::GENERATOR * x);
+            if let Some(k) = instance_key {
+                assert_eq!(r, ecdsa::r_x::<F, G>(&(G::GENERATOR * k)));
+            }
+            assert_eq!(recover(&m, &r, &s, signature.recovery_id), y);
+
+            let input = Mapping::from_iter([
+                ("transcript".into(), serde_yaml::to_value(&transcript).expect("ser")),
+                ("signature".into(), serde_yaml::to_value(&signature).expect("ser")),
+            ]);
+            let is_valid: bool = run(&storage_arg, "verify ecdsa -c secp256k1 --low-s", input, 0)
+                .stdout_as_yaml()
+                .expect("io:de");
+            assert!(is_valid);
+        }
+    }
+}
+
+/// The test vectors from RFC 8032 (section 7.1): `(secret_key, public_key, message, signature)`.
+const ED25519_RFC8032_VECTORS: &[(&str, &str, &str, &str)] = &[
+    (
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        "",
+        "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+         5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+    ),
+    (
+        "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+        "72",
+        "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+         085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+    ),
+    (
+        "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+        "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+        "af82",
+        "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+         18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+    ),
+];
+
+#[derive(Debug, Deserialize)]
+struct Ed25519Signature {
+    y: Point,
+    signature: String,
+}
+
+#[test]
+fn sign_ed25519_rfc8032_vectors() {
+    type F = curve25519::scalar::Scalar;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    for (idx, (secret_key, public_key, message, signature)) in
+        ED25519_RFC8032_VECTORS.iter().enumerate()
+    {
+        let expanded = sha2::Sha512::digest(hex::decode(secret_key).expect("hex"));
+        let mut x = [0u8; 32];
+        x.copy_from_slice(&expanded[..32]);
+        x[0] &= 0xf8;
+        x[31] &= 0x7f;
+        x[31] |= 0x40;
+        let x = F::from_bytes_mod_order(x);
+        let message = hex::decode(message).expect("hex");
+        let k = F::from_bytes_mod_order_wide(
+            &sha2::Sha512::new()
+                .chain_update(&expanded[32..])
+                .chain_update(&message)
+                .finalize()
+                .into(),
+        );
+
+        let key_id = format!("ed-{}", idx);
+        let key = Key::FullKey(FullKey {
+            curve: CurveSelect::Ed25519,
+            value: SecretScalar::from_value(CurveSelect::Ed25519, x),
+        });
+        run(&storage_arg, &format!("keys import {}", key_id), key, 0);
+
+        let input = Mapping::from_iter([
+            (
+                "message".into(),
+                serde_yaml::to_value(vec![Input::Hex(hex::encode(&message))]).expect("ser"),
+            ),
+            (
+                "instance_key".into(),
+                serde_yaml::to_value(Scalar::from_value(CurveSelect::Ed25519, k)).expect("ser"),
+            ),
+        ]);
+        let output: Ed25519Signature =
+            run(&storage_arg, &format!("sign schnorr --rfc8032 -k {}", key_id), input, 0)
+                .stdout_as_yaml()
+                .expect("io:de");
+
+        assert_eq!(output.y.to_string(), format!("ed25519:{}", public_key));
+        assert_eq!(&output.signature, signature);
+    }
+}
+
+#[test]
+fn sign_ed25519_deterministic() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::edwards::EdwardsPoint;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let x = F::random(&mut rand::rngs::OsRng);
+    let key = Key::FullKey(FullKey {
+        curve: CurveSelect::Ed25519,
+        value: SecretScalar::from_value(CurveSelect::Ed25519, x),
+    });
+    run(&storage_arg, "keys import ed", key, 0);
+
+    let input = Mapping::from_iter([(
+        "message".into(),
+        serde_yaml::to_value(vec![Input::Text("Hello there!".into())]).expect("ser"),
+    )]);
+    let signatures = [(); 2].map(|_| {
+        let output: Ed25519Signature =
+            run(&storage_arg, "sign schnorr --rfc8032 -k ed", input.clone(), 0)
+                .stdout_as_yaml()
+                .expect("io:de");
+        output.signature
+    });
+    assert_eq!(signatures[0], signatures[1]);
+
+    let signature = hex::decode(&signatures[0]).expect("hex");
+    assert!(verify_ed25519(&(G::generator() * x), b"Hello there!", &signature));
+}
+
+#[test]
+fn sign_schnorr_rejects_invalid_transcript() {
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_full_key(&storage_arg, "k1", F::random(&mut rand::rngs::OsRng));
+
+    let transcript = Transcript {
+        hash_function: HashFunctionSelect::Sha2_256,
+        input: vec![Input::Hex("not a hex".into())],
+    };
+    let input = Mapping::from_iter([(
+        "transcript".into(),
+        serde_yaml::to_value(&transcript).expect("ser"),
+    )]);
+    let io = TestIO::from_yaml_stdin(input).expect("make io");
+    let cli = cli::Cli::create_safe(args(format!("{}sign schnorr -k k1", storage_arg)))
+        .expect("args error");
+    assert!(cli::run(&cli, rand::rngs::OsRng, &io).is_err());
+}
+
+/// Verification as per RFC 8032 (section 5.1.7), independent of the code under test.
+pub fn verify_ed25519(
+    public_key: &curve25519::edwards::EdwardsPoint,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    use curve25519::edwards::CompressedEdwardsY;
+    use curve25519::scalar::Scalar as F;
+
+    let Ok(r_bytes) = <[u8; 32]>::try_from(&signature[..32]) else { return false };
+    let Ok(s_bytes) = <[u8; 32]>::try_from(&signature[32..]) else { return false };
+    let Some(r) = CompressedEdwardsY(r_bytes).decompress() else { return false };
+    let Some(s) = Option::<F>::from(F::from_canonical_bytes(s_bytes)) else { return false };
+
+    let k = F::from_bytes_mod_order_wide(
+        &sha2::Sha512::new()
+            .chain_update(r_bytes)
+            .chain_update(public_key.compress().as_bytes())
+            .chain_update(message)
+            .finalize()
+            .into(),
+    );
+
+    curve25519::edwards::EdwardsPoint::generator() * s == r + public_key * k
+}
+
+/// The test vectors from BIP-340: `(secret_key, public_key, aux_rand, message, signature)`.
+const BIP340_VECTORS: &[(&str, &str, &str, &str, &str)] = &[
+    (
+        "0000000000000000000000000000000000000000000000000000000000000003",
+        "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+         25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+    ),
+    (
+        "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+        "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+        "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341\
+         8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+    ),
+    (
+        "c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9",
+        "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8",
+        "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906",
+        "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c",
+        "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1b\
+         ab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7",
+    ),
+    (
+        "0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710",
+        "25d1dff95105f5253c4022f628a996ad3a0d95fbf21d468a1b33f8c160d8f517",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "7eb0509757e246f19449885651611cb965ecc1a187dd51b64fda1edc9637d5ec\
+         97582b9cb13db3933705b32ba982af5af25fd78881ebb32771fc5922efc66ea3",
+    ),
+];
+
+#[derive(Debug, Deserialize)]
+struct Bip340Signature {
+    public_key: String,
+    signature: String,
+}
+
+#[test]
+fn sign_bip340_vectors() {
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    for (idx, (secret_key, public_key, aux_rand, message, signature)) in
+        BIP340_VECTORS.iter().enumerate()
+    {
+        let key_id = format!("k1-{}", idx);
+        let key = Key::FullKey(FullKey {
+            curve: CURVE,
+            value: format!("secp256k1:{}", secret_key).parse::<Scalar>().expect("scalar").into(),
+        });
+        run(&storage_arg, &format!("keys import {}", key_id), key, 0);
+
+        let message = serde_yaml::to_value(vec![Input::Hex(message.to_string())]).expect("ser");
+        let input = Mapping::from_iter([
+            ("message".into(), message.clone()),
+            ("aux_rand".into(), aux_rand.to_string().into()),
+        ]);
+        let output: Bip340Signature =
+            run(&storage_arg, &format!("sign schnorr -k {} --bip340", key_id), input, 0)
+                .stdout_as_yaml()
+                .expect("io:de");
+
+        assert_eq!(&output.public_key, public_key);
+        assert_eq!(&output.signature, signature);
+
+        let mut tampered = hex::decode(signature).expect("hex");
+        tampered[63] ^= 1;
+        for (signature, expected) in [(signature.to_string(), true), (hex::encode(tampered), false)]
+        {
+            let input = Mapping::from_iter([
+                ("message".into(), message.clone()),
+                ("public_key".into(), public_key.to_string().into()),
+                ("signature".into(), signature.into()),
+            ]);
+            let is_valid: bool =
+                run(&storage_arg, "verify schnorr -c secp256k1 --bip340", input, 0)
+                    .stdout_as_yaml()
+                    .expect("io:de");
+            assert_eq!(is_valid, expected);
+        }
+    }
+}
+
+fn recover(m: &F, r: &F, s: &F, recovery_id: u8) -> G {
+    assert!(recovery_id & 0b10 == 0, "r is expected to be less than the group order");
+
+    let r_point = k256::AffinePoint::decompress(&r.to_repr(), Choice::from(recovery_id & 0b01))
+        .expect("decompress");
+    let r_inv = r.invert().expect("r-inv");
+
+    (G::from(r_point) * s - G::GENERATOR * m) * r_inv
+}
+
+fn text_transcript(hash_function: HashFunctionSelect, text: &str) -> Transcript {
+    Transcript { hash_function, input: vec![Input::Text(text.into())] }
+}
+
+fn import_full_key(storage_arg: &str, key_id: &str, x: F) {
+    let key = Key::FullKey(FullKey { curve: CURVE, value: SecretScalar::from_value(CURVE, x) });
+    run(storage_arg, &format!("keys import {}", key_id), key, 0);
+}
+
+fn sign_ecdsa(
+    storage_arg: &str,
+    key_id: &str,
+    transcript: &Transcript,
+    instance_key: Option<F>,
+) -> EcdsaSignature {
+    let mut input =
+        Mapping::from_iter([("transcript".into(), serde_yaml::to_value(transcript).expect("ser"))]);
+    if let Some(k) = instance_key {
+        input.insert(
+            "instance_key".into(),
+            serde_yaml::to_value(Scalar::from_value(CURVE, k)).expect("ser"),
+        );
+    }
+
+    run(storage_arg, &format!("sign ecdsa -k {}", key_id), input, 0)
+        .stdout_as_yaml()
+        .expect("io:de")
+}
@@ -0,0 +1,328 @@
+use std::path::Path;
+
+use cli_storage::{RawTable, Storage, Table, LEGACY_VERSION};
+use serde_json::json;
+
+use crate::data::Key;
+use crate::tests::cli_utils::{args, run};
+use crate::tests::io_utils::TestIO;
+use crate::{cli, RetCode};
+
+const KDF_ARGS: &str = "--kdf-memory 64 --kdf-iterations 1";
+
+#[test]
+fn encrypted_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let storage_path = tmp.path().join("storage");
+    let storage_path = storage_path.to_str().expect("to-str");
+    let passphrase_file = write_passphrase(tmp.path(), "passphrase", "correct horse\n");
+    let storage_arg =
+        format!("--storage-path {} --passphrase-file {} ", storage_path, passphrase_file);
+
+    run(&storage_arg, &format!("storage init --encrypted {}", KDF_ARGS), (), 0);
+    run(&storage_arg, &format!("storage init --encrypted {}", KDF_ARGS), (), 1);
+    run(&storage_arg, "keys gen -c ristretto25519 k1", (), 0);
+    let Key::FullKey(full_key) =
+        run(&storage_arg, "keys export k1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+
+    // nothing in the files reveals the key
+    let value = full_key.value.expose_secret().to_string();
+    let (_, value) = value.split_once(':').expect("hex");
+    assert!(!contains(tmp.path(), value.as_bytes()));
+
+    // locked
+    assert!(Storage::open(storage_path).is_err());
+    let wrong_file = write_passphrase(tmp.path(), "wrong", "wrong horse");
+    let wrong_arg = format!("--storage-path {} --passphrase-file {} ", storage_path, wrong_file);
+    assert!(try_run(&wrong_arg, "keys export k1").is_err());
+
+    // a new passphrase: the old one no longer unlocks the storage
+    let new_file = write_passphrase(tmp.path(), "new", "battery staple");
+    let cmd = format!("storage passphrase --new-passphrase-file {} {}", new_file, KDF_ARGS);
+    run(&storage_arg, &cmd, (), 0);
+    assert!(try_run(&storage_arg, "keys export k1").is_err());
+    let new_arg = format!("--storage-path {} --passphrase-file {} ", storage_path, new_file);
+    let Key::FullKey(exported) =
+        run(&new_arg, "keys export k1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+    assert_eq!(exported.value, full_key.value);
+}
+
+#[test]
+fn encrypt_existing_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let storage_path = tmp.path().join("storage");
+    let storage_path = storage_path.to_str().expect("to-str");
+    let passphrase_file = write_passphrase(tmp.path(), "passphrase", "correct horse");
+    let plain_arg = format!("--storage-path {} ", storage_path);
+    let storage_arg = format!("{}--passphrase-file {} ", plain_arg, passphrase_file);
+
+    run(&plain_arg, "storage init", (), 0);
+    run(&plain_arg, "storage passphrase", (), 1);
+    run(&plain_arg, "keys gen -c secp256k1 k1", (), 0);
+    let Key::FullKey(full_key) =
+        run(&plain_arg, "keys export k1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+
+    let empty_file = write_passphrase(tmp.path(), "empty", "\n");
+    let empty_arg = format!("{}--passphrase-file {} ", plain_arg, empty_file);
+    assert!(try_run(&empty_arg, &format!("storage init --encrypted {}", KDF_ARGS)).is_err());
+    assert!(!Storage::open(storage_path).expect("storage").is_encrypted());
+
+    run(&storage_arg, &format!("storage init --encrypted {}", KDF_ARGS), (), 0);
+    assert!(Storage::open(storage_path).is_err());
+    let Key::FullKey(exported) =
+        run(&storage_arg, "keys export k1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+    assert_eq!(exported.value, full_key.value);
+}
+
+#[test]
+fn migrate_tables_and_versions_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let storage_path = tmp.path().to_str().expect("to-str");
+    let storage_arg = format!("--storage-path {} ", storage_path);
+
+    let full_key = |value: &str| json!({ "full_key": { "curve": "secp256k1", "value": format!("secp256k1:{:0>64}", value) } });
+    {
+        let storage = Storage::open(storage_path).expect("storage");
+        // a record without a version in the table of its stable identifier
+        let tab_keys = RawTable::open(&storage, "keys").expect("keys");
+        tab_keys.insert("k1", LEGACY_VERSION, &full_key("01")).expect("insert");
+        tab_keys.insert("k2", LEGACY_VERSION, &full_key("02")).expect("insert");
+        // and in the table named after the type
+        let tab_legacy =
+            RawTable::open(&storage, "rabbit_hole_cli_alice::data::Key").expect("legacy");
+        tab_legacy.insert("k2", LEGACY_VERSION, &full_key("02")).expect("insert");
+        tab_legacy.insert("k3", LEGACY_VERSION, &full_key("03")).expect("insert");
+    }
+
+    // the tables are to be moved
+    let err = try_run(&storage_arg, "keys list").expect_err("not migrated");
+    assert!(err.to_string().contains("alice storage migrate"), "{}", err);
+
+    // the same id in both tables
+    let io = run(&storage_arg, "storage migrate", (), 1);
+    assert!(io.stderr_as_string().contains("\"k2\""), "{}", io.stderr_as_string());
+    {
+        let storage = Storage::open(storage_path).expect("storage");
+        RawTable::open(&storage, "keys").expect("keys").remove("k2").expect("remove");
+    }
+
+    let report: serde_json::Value = run(&storage_arg, "storage migrate --dry-run", (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(report["stable-table-ids"]["rabbit_hole_cli_alice::data::Key"], "keys");
+    assert_eq!(report["record-versions"]["keys"], 3);
+    assert!(try_run(&storage_arg, "keys list").is_err());
+
+    run(&storage_arg, "storage migrate", (), 0);
+    for (key_id, value) in [("k1", "01"), ("k2", "02"), ("k3", "03")] {
+        let Key::FullKey(exported) = run(&storage_arg, &format!("keys export {}", key_id), (), 0)
+            .stdout_as_yaml()
+            .expect("io:de")
+        else {
+            panic!("full-key expected")
+        };
+        assert!(exported.value.expose_secret().to_string().ends_with(value));
+    }
+
+    let storage = Storage::open(storage_path).expect("storage");
+    assert_eq!(storage.table_names().expect("table-names"), vec!["keys".to_owned()]);
+    let versions = RawTable::open(&storage, "keys")
+        .expect("keys")
+        .select("")
+        .map(|item| item.expect("item").1)
+        .collect::<Vec<_>>();
+    assert_eq!(versions, vec![1, 1, 1]);
+}
+
+#[test]
+fn backup_and_restore_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let source_arg = format!("--storage-path {} ", tmp.path().join("source").to_str().unwrap());
+    let backup_file = write_passphrase(tmp.path(), "backup-passphrase", "correct horse");
+    let archive = tmp.path().join("alice.backup");
+    let archive = archive.to_str().expect("to-str");
+    let backup_arg = format!("--backup-passphrase-file {}", backup_file);
+
+    run(&source_arg, "keys gen -c secp256k1 key-1", (), 0);
+    run(&source_arg, "keys gen -c ristretto25519 key-2", (), 0);
+    let Key::FullKey(full_key) =
+        run(&source_arg, "keys export key-1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+    {
+        let storage = Storage::open(tmp.path().join("source").to_str().unwrap()).expect("storage");
+        RawTable::open(&storage, "tss/frost/nonces")
+            .expect("nonces")
+            .insert("key-1:nonce", 1, &json!({ "d": "-", "e": "-" }))
+            .expect("insert");
+    }
+
+    let cmd = format!("storage backup --encrypt --output {} {} {}", archive, backup_arg, KDF_ARGS);
+    run(&source_arg, &cmd, (), 0);
+    let value = full_key.value.expose_secret().to_string();
+    let (_, value) = value.split_once(':').expect("hex");
+    let data = std::fs::read(archive).expect("read");
+    assert!(!data.windows(value.len()).any(|w| w == value.as_bytes()));
+
+    // tampered, or the wrong passphrase
+    let wrong_file = write_passphrase(tmp.path(), "wrong", "wrong horse");
+    let target_arg = format!("--storage-path {} ", tmp.path().join("target").to_str().unwrap());
+    let restore = format!("storage restore --input {} ", archive);
+    let cmd = format!("{}--backup-passphrase-file {}", restore, wrong_file);
+    assert!(try_run(&target_arg, &cmd).is_err());
+    let mut tampered: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(archive).expect("read")).expect("json");
+    tampered["created_at"] = json!(0);
+    let tampered_file = tmp.path().join("tampered.backup");
+    std::fs::write(&tampered_file, serde_json::to_vec(&tampered).expect("json")).expect("write");
+    let cmd = format!(
+        "storage restore --input {} {}",
+        tampered_file.to_str().expect("to-str"),
+        backup_arg
+    );
+    assert!(try_run(&target_arg, &cmd).is_err());
+    // the cost of the KDF is bounded before anything is authenticated
+    tampered["kdf"]["memory_kib"] = json!(u32::MAX);
+    std::fs::write(&tampered_file, serde_json::to_vec(&tampered).expect("json")).expect("write");
+    let error = try_run(&target_arg, &cmd).expect_err("restored");
+    assert!(error.to_string().contains("exceed the maximum"), "{}", error);
+
+    // the nonces are skipped
+    let report: serde_json::Value = run(&target_arg, &format!("{}{}", restore, backup_arg), (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(report, json!({ "keys": 2 }));
+    let Key::FullKey(exported) =
+        run(&target_arg, "keys export key-1", (), 0).stdout_as_yaml().expect("io:de")
+    else {
+        panic!("full-key expected")
+    };
+    assert_eq!(exported.value, full_key.value);
+
+    // the records in place are left intact
+    let io = run(&target_arg, &format!("{}{}", restore, backup_arg), (), 1);
+    assert!(io.stderr_as_string().contains("key-1"), "{}", io.stderr_as_string());
+    let cmd = format!("{}--table tss/frost/nonces {}", restore, backup_arg);
+    run(&target_arg, &cmd, (), 1);
+    let cmd =
+        format!("{}--table tss/frost/nonces --with-nonces --overwrite {}", restore, backup_arg);
+    let report: serde_json::Value = run(&target_arg, &cmd, (), 0).stdout_as_yaml().expect("io:de");
+    assert_eq!(report, json!({ "tss/frost/nonces": 1 }));
+
+    // by the prefix of the key-ids
+    let partial_arg = format!("--storage-path {} ", tmp.path().join("partial").to_str().unwrap());
+    let cmd = format!("{}--table keys --prefix key-2 {}", restore, backup_arg);
+    let report: serde_json::Value = run(&partial_arg, &cmd, (), 0).stdout_as_yaml().expect("io:de");
+    assert_eq!(report, json!({ "keys": 1 }));
+    run(&partial_arg, "keys export key-1", (), 1);
+    run(&partial_arg, "keys export key-2", (), 0);
+}
+
+#[test]
+fn transaction_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let storage = Storage::open(tmp.path().to_str().expect("to-str")).expect("storage");
+    let tab_keys = Table::<Key>::open(&storage).expect("keys");
+    let tab_raw = RawTable::open(&storage, "raw").expect("raw");
+    let key: Key = serde_json::from_value(json!({ "full_key": { "curve": "secp256k1", "value": format!("secp256k1:{:0>64}", "01") } })).expect("key");
+
+    // rejected: rolled back
+    let rejected = storage
+        .transaction(&[&tab_keys, &tab_raw], |tx| {
+            assert!(tx.table(&tab_keys)?.insert_new("k1", &key)?);
+            tx.raw_table(&tab_raw)?.insert("r1", 1, &json!({}))?;
+            Ok(Err::<(), _>("rejected"))
+        })
+        .expect("transaction");
+    assert_eq!(rejected, Err("rejected"));
+    assert!(tab_keys.get("k1").expect("get").is_none());
+    assert!(!tab_raw.contains("r1").expect("contains"));
+
+    // failed: rolled back too
+    let failed = storage.transaction(&[&tab_keys, &tab_raw], |tx| {
+        tx.table(&tab_keys)?.insert("k1", &key)?;
+        Err::<Result<(), ()>, _>("failed".into())
+    });
+    assert_eq!(failed.expect_err("failed").to_string(), "failed");
+    assert!(tab_keys.get("k1").expect("get").is_none());
+
+    // a table not in the transaction
+    let outside = storage.transaction(&[&tab_keys], |tx| {
+        tx.raw_table(&tab_raw)?;
+        Ok(Ok::<_, ()>(()))
+    });
+    assert!(outside.is_err());
+
+    let committed = storage
+        .transaction(&[&tab_keys, &tab_raw], |tx| {
+            let tx_keys = tx.table(&tab_keys)?;
+            assert!(tx_keys.insert_new("k1", &key)?);
+            assert!(!tx_keys.insert_new("k1", &key)?);
+            assert!(tx_keys.get("k1")?.is_some());
+            tx.raw_table(&tab_raw)?.insert("r1", 1, &json!({}))?;
+            Ok(Ok::<_, ()>(()))
+        })
+        .expect("transaction");
+    assert_eq!(committed, Ok(()));
+    assert!(tab_keys.get("k1").expect("get").is_some());
+    assert!(tab_raw.contains("r1").expect("contains"));
+}
+
+#[test]
+fn storage_backend_in_tmp_dir() {
+    let tmp = tempfile::tempdir().expect("Tempdir");
+    let storage_path = tmp.path().join("storage");
+    let storage_path = storage_path.to_str().expect("to-str");
+
+    let dir_arg = format!("--storage-backend dir --storage-path {} ", storage_path);
+    run(&dir_arg, "keys gen -c secp256k1 k1", (), 0);
+    // a file per record
+    assert!(tmp.path().join("storage/storage-dir/keys/k1").is_file());
+    assert!(!tmp.path().join("storage/storage-sled.db").exists());
+
+    let uri_arg = format!("--storage-path dir://{} ", storage_path);
+    run(&uri_arg, "keys export k1", (), 0);
+    let conflicting_arg = format!("--storage-backend sled {}", uri_arg);
+    assert!(try_run(&conflicting_arg, "keys export k1").is_err());
+
+    // the default backend is another storage
+    let sled_arg = format!("--storage-path {} ", storage_path);
+    run(&sled_arg, "keys export k1", (), 1);
+}
+
+fn write_passphrase(dir: &Path, name: &str, passphrase: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, passphrase).expect("write");
+    path.to_str().expect("to-str").to_owned()
+}
+
+fn contains(dir: &Path, needle: &[u8]) -> bool {
+    std::fs::read_dir(dir).expect("read-dir").any(|entry| {
+        let path = entry.expect("entry").path();
+        if path.is_dir() {
+            contains(&path, needle)
+        } else {
+            let data = std::fs::read(&path).expect("read");
+            data.windows(needle.len()).any(|w| w == needle)
+        }
+    })
+}
+
+fn try_run(storage_arg: &str, cmd: &str) -> Result<RetCode, crate::AnyError> {
+    let io = TestIO::from_empty_input();
+    let cli = cli::Cli::create_safe(args(format!("{}{}", storage_arg, cmd))).expect("args error");
+    cli::run(&cli, rand::rngs::OsRng, &io)
+}
@@ -0,0 +1,2 @@
+mod test_dkls;
+mod test_frost;
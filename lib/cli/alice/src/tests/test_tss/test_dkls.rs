@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::hash_function_select::HashFunctionSelect;
+use common_interop::transcript::{Input, Transcript};
+use common_interop::types::{Point, Scalar};
+
+use crate::tests::cli_utils::{args, import_2_of_3, in_memory, key_ids_2_of_3, run};
+use crate::tests::io_utils::TestIO;
+use crate::{cli, ecdsa, transcript};
+
+type F = k256::Scalar;
+type G = k256::ProjectivePoint;
+
+const CURVE: CurveSelect = CurveSelect::Secp256k1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Signature {
+    y: Point,
+    r: Scalar,
+    s: Scalar,
+}
+
+#[test]
+fn sign_2_of_3_in_memory() {
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let mut rng = rand::rngs::OsRng;
+
+    let key_ids = key_ids_2_of_3("k1");
+    let (_, public_key) = import_2_of_3::<F, G>(&storage_arg, CURVE, &key_ids);
+    let public_key = public_key.restore::<G>(CURVE).expect("public key");
+
+    let transcript = Transcript {
+        hash_function: HashFunctionSelect::Sha2_256,
+        input: vec![Input::Text("Hello there!".into())],
+    };
+    let transcript_value = serde_yaml::to_value(&transcript).expect("ser");
+
+    for (aggregator, cosigner) in [(1, 2), (3, 1)] {
+        let session = format!("s-{}-{}", aggregator, cosigner);
+        let aggregator = format!("-k k1:{}", aggregator);
+        let cosigner = format!("-k k1:{}", cosigner);
+
+        let offer: Value = run(
+            &storage_arg,
+            &format!("tss dkls cosigner offer {} -h sha3-256 {}", cosigner, session),
+            Value::Null,
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        let choice: Value = run(
+            &storage_arg,
+            &format!("tss dkls aggregator choose {} -h sha3-256 {}", aggregator, session),
+            offer,
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        let reply: Value = run(
+            &storage_arg,
+            &format!("tss dkls cosigner reply {} {}", cosigner, session),
+            choice,
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        run(
+            &storage_arg,
+            &format!("tss dkls aggregator finalize {} {}", aggregator, session),
+            reply,
+            0,
+        );
+
+        let shares: Value = run(
+            &storage_arg,
+            &format!("tss dkls aggregator sign {} {}", aggregator, session),
+            Mapping::from_iter([("transcript".into(), transcript_value.clone())]),
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        let Value::Mapping(mut shares) = shares else { panic!("not a mapping") };
+        shares.insert("transcript".into(), transcript_value.clone());
+
+        let signature: Signature = run(
+            &storage_arg,
+            &format!("tss dkls cosigner sign {} {}", cosigner, session),
+            shares.clone(),
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+
+        let m =
+            ecdsa::digest_to_scalar::<F>(&transcript::produce_digest(&transcript).expect("digest"));
+        assert_eq!(signature.y.restore::<G>(CURVE).expect("y"), public_key);
+        assert!(ecdsa::verify::<F, G>(
+            &public_key,
+            &m,
+            &signature.r.restore(CURVE).expect("r"),
+            &signature.s.restore(CURVE).expect("s"),
+        ));
+
+        let digest = hex::encode(transcript::produce_digest(&transcript).expect("digest"));
+        assert!(verify_ecdsa(
+            &storage_arg,
+            "",
+            ("transcript", transcript_value.clone()),
+            &signature
+        ));
+        assert!(verify_ecdsa(&storage_arg, "", ("digest", digest.clone().into()), &signature));
+
+        let s = signature.s.restore::<F>(CURVE).expect("s");
+        let negated = Signature { s: Scalar::from_value(CURVE, -s), ..signature.clone() };
+        assert!(verify_ecdsa(&storage_arg, "", ("digest", digest.clone().into()), &negated));
+        assert!(verify_ecdsa(
+            &storage_arg,
+            "--low-s",
+            ("digest", digest.clone().into()),
+            &signature
+        ));
+        assert!(!verify_ecdsa(&storage_arg, "--low-s", ("digest", digest.into()), &negated));
+
+        let io = TestIO::from_yaml_stdin(shares).expect("make io");
+        let cli = cli::Cli::create_safe(args(format!(
+            "{}tss dkls cosigner sign {} {}",
+            storage_arg, cosigner, session
+        )))
+        .expect("args error");
+        assert_eq!(cli::run(&cli, &mut rng, &io).expect("cli-run"), 1, "presignature reused");
+    }
+}
+
+fn verify_ecdsa(
+    storage_arg: &str,
+    flags: &str,
+    message: (&str, Value),
+    signature: &Signature,
+) -> bool {
+    let input = Mapping::from_iter([
+        (message.0.into(), message.1),
+        ("signature".into(), serde_yaml::to_value(signature).expect("ser")),
+    ]);
+    run(storage_arg, &format!("verify ecdsa -c secp256k1 {}", flags), input, 0)
+        .stdout_as_yaml()
+        .expect("io:de")
+}
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+
+use ff::{Field, PrimeField};
+use group::{Group, GroupEncoding};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+
+use common_interop::curve_select::CurveSelect;
+use common_interop::transcript::Input;
+use common_interop::types::{Point, Scalar};
+
+use crate::data::Key;
+use crate::tests::cli_utils::{args, import_2_of_3, in_memory, run};
+use crate::tests::io_utils::{BrokenStdout, TestIO};
+use crate::tests::test_sign::verify_ed25519;
+use crate::{cli, RetCode};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Shard {
+    y: Point,
+    r: Point,
+    z: Scalar,
+}
+
+#[derive(Debug, Deserialize)]
+struct Signature {
+    y: Point,
+    public_key: String,
+    signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Report {
+    complaints: HashMap<Scalar, String>,
+    honest: Vec<Scalar>,
+    can_retry: Option<bool>,
+}
+
+const MESSAGE: &str = "Hello there!";
+
+#[test]
+fn sign_rfc8032_2_of_3_in_memory() {
+    type G = curve25519::edwards::EdwardsPoint;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let public_key = import_key_shares::<G>(&storage_arg, CurveSelect::Ed25519, "ed");
+
+    for signers in [[0, 1], [2, 0]] {
+        let signature =
+            sign_and_aggregate(&storage_arg, CurveSelect::Ed25519, "ed", "--rfc8032", signers);
+
+        assert_eq!(signature.y.restore::<G>(CurveSelect::Ed25519).expect("y"), public_key);
+        assert_eq!(signature.public_key, hex::encode(public_key.to_bytes()));
+        assert!(verify_ed25519(
+            &public_key,
+            MESSAGE.as_bytes(),
+            &hex::decode(signature.signature).expect("hex")
+        ));
+    }
+}
+
+#[test]
+fn sign_bip340_2_of_3_in_memory() {
+    type G = k256::ProjectivePoint;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    // different keys, so that both parities of the public key are covered
+    for key_idx in 0..4 {
+        let prefix = format!("k1-{}", key_idx);
+        let public_key = import_key_shares::<G>(&storage_arg, CurveSelect::Secp256k1, &prefix);
+
+        for signers in [[0, 1], [2, 0], [1, 2]] {
+            let signature = sign_and_aggregate(
+                &storage_arg,
+                CurveSelect::Secp256k1,
+                &prefix,
+                "--bip340",
+                signers,
+            );
+
+            let y = signature.y.restore::<G>(CurveSelect::Secp256k1).expect("y");
+            assert!(y == public_key || y == -public_key);
+            assert_eq!(signature.public_key, hex::encode(&public_key.to_bytes()[1..]));
+
+            let input = Mapping::from_iter([
+                ("message".into(), message()),
+                ("public_key".into(), signature.public_key.into()),
+                ("signature".into(), signature.signature.into()),
+            ]);
+            let is_valid: bool =
+                run(&storage_arg, "verify schnorr -c secp256k1 --bip340", input, 0)
+                    .stdout_as_yaml()
+                    .expect("io:de");
+            assert!(is_valid);
+        }
+    }
+}
+
+#[test]
+fn aggregate_reports_invalid_shards_in_memory() {
+    type F = curve25519::scalar::Scalar;
+    type G = curve25519::edwards::EdwardsPoint;
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_key_shares::<G>(&storage_arg, CURVE, "ed");
+
+    let (signers, shards) = sign(&storage_arg, "ed", "--rfc8032", &[0, 2]);
+    let mut shamir_xs = shards.keys().cloned().collect::<Vec<_>>();
+    shamir_xs.sort();
+
+    let with_invalid_z = |mut shards: HashMap<Scalar, Shard>, shamir_x: &Scalar| {
+        let shard = shards.get_mut(shamir_x).expect("shard");
+        shard.z = Scalar::from_value(CURVE, shard.z.restore::<F>(CURVE).expect("z") + F::ONE);
+        shards
+    };
+    let with_invalid_r = |mut shards: HashMap<Scalar, Shard>, shamir_x: &Scalar| {
+        let shard = shards.get_mut(shamir_x).expect("shard");
+        shard.r =
+            Point::from_value(CURVE, shard.r.restore::<G>(CURVE).expect("r") + G::generator());
+        shards
+    };
+    let with_invalid_y = |mut shards: HashMap<Scalar, Shard>, shamir_x: &Scalar| {
+        let shard = shards.get_mut(shamir_x).expect("shard");
+        shard.y =
+            Point::from_value(CURVE, shard.y.restore::<G>(CURVE).expect("y") + G::generator());
+        shards
+    };
+
+    let flags = "-c ed25519 -h sha3-256 --rfc8032";
+    let _: Signature = aggregate(&storage_arg, "ed", flags, &signers, shards.clone(), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+
+    // the reported `y` is checked against the trusted verification share: the signer misreporting
+    // it is blamed, rather than the honest ones
+    let report: Report = aggregate(
+        &storage_arg,
+        "ed",
+        flags,
+        &signers,
+        with_invalid_y(shards.clone(), &shamir_xs[0]),
+        1,
+    )
+    .stdout_as_yaml()
+    .expect("io:de");
+    assert_eq!(
+        report.complaints,
+        HashMap::from_iter([(shamir_xs[0].clone(), "verification_share_mismatch".to_owned())])
+    );
+    assert_eq!(report.honest, shamir_xs[1..]);
+
+    let report: Report = aggregate(
+        &storage_arg,
+        "ed",
+        flags,
+        &signers,
+        with_invalid_r(shards.clone(), &shamir_xs[1]),
+        1,
+    )
+    .stdout_as_yaml()
+    .expect("io:de");
+    assert_eq!(
+        report.complaints,
+        HashMap::from_iter([(shamir_xs[1].clone(), "commitment_mismatch".to_owned())])
+    );
+    assert_eq!(report.honest, shamir_xs[..1]);
+    assert_eq!(report.can_retry, None);
+
+    let tampered = with_invalid_r(with_invalid_z(shards.clone(), &shamir_xs[0]), &shamir_xs[1]);
+    let report: Report =
+        aggregate(&storage_arg, "ed", &format!("{} -t 2", flags), &signers, tampered, 1)
+            .stdout_as_yaml()
+            .expect("io:de");
+    assert_eq!(
+        report.complaints,
+        HashMap::from_iter([
+            (shamir_xs[0].clone(), "invalid_response".to_owned()),
+            (shamir_xs[1].clone(), "commitment_mismatch".to_owned()),
+        ])
+    );
+    assert!(report.honest.is_empty());
+    assert_eq!(report.can_retry, Some(false));
+
+    for (threshold, can_retry) in [(1, true), (2, false)] {
+        let report: Report = aggregate(
+            &storage_arg,
+            "ed",
+            &format!("{} -t {}", flags, threshold),
+            &signers,
+            with_invalid_z(shards.clone(), &shamir_xs[1]),
+            1,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        assert_eq!(report.honest, shamir_xs[..1]);
+        assert_eq!(report.can_retry, Some(can_retry));
+    }
+
+    // the shards are those of the signers, all of them
+    let mut missing = shards.clone();
+    missing.remove(&shamir_xs[0]);
+    aggregate(&storage_arg, "ed", flags, &signers, missing, 1);
+    let (other_signers, _) = sign(&storage_arg, "ed", "--rfc8032", &[0, 1]);
+    aggregate(&storage_arg, "ed", flags, &other_signers, shards, 1);
+}
+
+#[test]
+fn coordinator_2_of_3_in_memory() {
+    type G = curve25519::edwards::EdwardsPoint;
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    let public_key = import_key_shares::<G>(&storage_arg, CURVE, "ed");
+    let coordinator = "tss frost coordinator";
+
+    let mut start_input = trusted_input(&storage_arg, "ed");
+    start_input.insert("message".into(), message());
+    run(
+        &storage_arg,
+        &format!("{} start -c ed25519 -h sha3-256 --rfc8032 -t 2 s-1", coordinator),
+        &start_input,
+        0,
+    );
+    run(
+        &storage_arg,
+        &format!("{} start -c ed25519 -h sha3-256 --rfc8032 s-1", coordinator),
+        &start_input,
+        1,
+    );
+    run(&storage_arg, &format!("{} sign-request s-1", coordinator), (), 1);
+
+    let mut commitments = HashMap::new();
+    for idx in [0, 2] {
+        let io = run(&storage_arg, &format!("tss frost prepare -k ed:{} -c 1", idx), (), 0);
+        let [commitment]: [(Point, Point); 1] = io.stdout_as_yaml().expect("io:de");
+        let key: Key = run(&storage_arg, &format!("keys export ed:{}", idx), (), 0)
+            .stdout_as_yaml()
+            .expect("io:de");
+        let Key::S4Share(s4_share) = key else { panic!("not an S4-share") };
+
+        let input = HashMap::from([(s4_share.x.clone(), commitment.clone())]);
+        run(&storage_arg, &format!("{} add-commitments s-1", coordinator), &input, 0);
+        run(&storage_arg, &format!("{} add-commitments s-1", coordinator), input, 1);
+        commitments.insert(idx, (s4_share.x, commitment));
+    }
+
+    run(&storage_arg, &format!("{} add-shard s-1", coordinator), (), 1);
+    run(&storage_arg, &format!("{} finish s-1", coordinator), (), 1);
+
+    let request: serde_yaml::Value =
+        run(&storage_arg, &format!("{} sign-request s-1", coordinator), (), 0)
+            .stdout_as_yaml()
+            .expect("io:de");
+    run(
+        &storage_arg,
+        &format!("{} add-commitments s-1", coordinator),
+        HashMap::from([commitments[&0].clone()]),
+        1,
+    );
+
+    for idx in [0, 2] {
+        let shamir_x = &commitments[&idx].0;
+        let shard: Shard = run(
+            &storage_arg,
+            &format!("tss frost sign -k ed:{} -h sha3-256 --rfc8032", idx),
+            request[&serde_yaml::to_value(shamir_x).expect("ser")].clone(),
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+
+        // the shards are checked as they are added
+        let tampered = Shard {
+            y: Point::from_value(CURVE, shard.y.restore::<G>(CURVE).expect("y") + G::generator()),
+            ..shard.clone()
+        };
+        let tampered = HashMap::from([(shamir_x.clone(), tampered)]);
+        let io = run(&storage_arg, &format!("{} add-shard s-1", coordinator), &tampered, 1);
+        assert!(io.stderr_as_string().contains("verification_share_mismatch"));
+
+        let input = HashMap::from([(shamir_x.clone(), shard)]);
+
+        if idx == 0 {
+            run(&storage_arg, &format!("{} finish s-1", coordinator), (), 1);
+        }
+        run(&storage_arg, &format!("{} add-shard s-1", coordinator), &input, 0);
+        run(&storage_arg, &format!("{} add-shard s-1", coordinator), &input, 1);
+    }
+
+    // the signature can not be written out: the session is kept to be finished again
+    let io = TestIO::from_yaml_stdin(()).expect("make io");
+    let cli = cli::Cli::create_safe(args(format!("{}{} finish s-1", storage_arg, coordinator)))
+        .expect("args error");
+    assert!(cli::run(&cli, rand::rngs::OsRng, BrokenStdout(&io)).is_err());
+
+    let signature: Signature = run(&storage_arg, &format!("{} finish s-1", coordinator), (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(signature.y.restore::<G>(CURVE).expect("y"), public_key);
+    assert!(verify_ed25519(
+        &public_key,
+        MESSAGE.as_bytes(),
+        &hex::decode(signature.signature).expect("hex")
+    ));
+
+    run(&storage_arg, &format!("{} finish s-1", coordinator), (), 1);
+}
+
+#[test]
+fn sign_burns_nonces_before_the_shard_is_emitted_in_memory() {
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_key_shares::<curve25519::edwards::EdwardsPoint>(&storage_arg, CURVE, "ed");
+    let input = sign_input(&storage_arg, "ed", &[0, 1], "");
+
+    // the shard can not be written out: the nonces should be gone nonetheless
+    let io = TestIO::from_yaml_stdin(&input).expect("make io");
+    let cli = cli::Cli::create_safe(args(format!(
+        "{}tss frost sign -k ed:0 -h sha3-256 --rfc8032",
+        storage_arg
+    )))
+    .expect("args error");
+    assert!(cli::run(&cli, rand::rngs::OsRng, BrokenStdout(&io)).is_err());
+
+    assert_eq!(count(&storage_arg, "ed:0"), 0);
+    assert_eq!(count(&storage_arg, "ed:1"), 1);
+    run_with_error(&storage_arg, "tss frost sign -k ed:0 -h sha3-256 --rfc8032", &input);
+}
+
+#[test]
+fn sign_rejects_repeated_or_zero_x_before_burning_nonces_in_memory() {
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_key_shares::<curve25519::edwards::EdwardsPoint>(&storage_arg, CURVE, "ed");
+
+    // both commitments are of the key-share `0`, under the same `x`
+    let input = sign_input(&storage_arg, "ed", &[0, 0], "");
+    run_with_error(&storage_arg, "tss frost sign -k ed:0 -h sha3-256 --rfc8032", &input);
+    assert_eq!(count(&storage_arg, "ed:0"), 2);
+
+    let mut input = sign_input(&storage_arg, "ed", &[0, 1], "");
+    let signers = input.get_mut("signers").expect("signers");
+    let zero = Scalar::from_value(CURVE, curve25519::scalar::Scalar::ZERO);
+    signers[1][0] = serde_yaml::to_value(zero).expect("ser");
+    run_with_error(&storage_arg, "tss frost sign -k ed:0 -h sha3-256 --rfc8032", &input);
+    assert_eq!(count(&storage_arg, "ed:0"), 3);
+}
+
+#[test]
+fn nonces_pool_and_expiry_in_memory() {
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_key_shares::<curve25519::edwards::EdwardsPoint>(&storage_arg, CURVE, "ed");
+
+    run(&storage_arg, "tss frost nonces set-max-pool ed:9 4", (), 1);
+    run(&storage_arg, "tss frost nonces set-max-pool ed:0 4", (), 0);
+
+    // the limit is kept with the key rather than given on each invocation
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 3", (), 0);
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 2", (), 1);
+    assert_eq!(count(&storage_arg, "ed:0"), 3);
+    run(&storage_arg, "tss frost prepare -k ed:1 -c 5", (), 0);
+    assert_eq!(count(&storage_arg, "ed:1"), 5);
+    run(&storage_arg, "tss frost nonces purge -k ed:1 --all", (), 0);
+    assert_eq!(count(&storage_arg, "ed:1"), 0);
+
+    #[derive(Deserialize)]
+    struct Entry {
+        created_at: u64,
+        expires_at: Option<u64>,
+    }
+
+    let expired: [(Point, Point); 1] =
+        run(&storage_arg, "tss frost prepare -k ed:0 -c 1 --ttl 0", (), 0)
+            .stdout_as_yaml()
+            .expect("io:de");
+    let entries: Vec<Entry> = run(&storage_arg, "tss frost nonces list -k ed:0", (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|entry| entry.created_at > 0));
+    assert_eq!(entries.iter().filter(|entry| entry.expires_at.is_some()).count(), 1);
+
+    // an expired commitment can not be used, but it is burnt all the same
+    let input = sign_input(&storage_arg, "ed", &[1], &format!("{}-{}", expired[0].0, expired[0].1));
+    run_with_error(&storage_arg, "tss frost sign -k ed:0 -h sha3-256 --rfc8032", &input);
+    assert_eq!(count(&storage_arg, "ed:0"), 3);
+
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 1 --ttl 0", (), 0);
+    let purged: usize = run(&storage_arg, "tss frost nonces purge -k ed:0", (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(purged, 1);
+    assert_eq!(count(&storage_arg, "ed:0"), 3);
+
+    // the expired nonces are pruned before the pool is checked
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 1 --ttl 0", (), 0);
+    assert_eq!(count(&storage_arg, "ed:0"), 4);
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 1 --ttl 3600", (), 0);
+    assert_eq!(count(&storage_arg, "ed:0"), 4);
+
+    let purged: usize = run(&storage_arg, "tss frost nonces purge -k ed:0 --all", (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    assert_eq!(purged, 4);
+    assert_eq!(count(&storage_arg, "ed:0"), 0);
+}
+
+#[test]
+fn nonces_of_key_ids_sharing_a_prefix_in_memory() {
+    const CURVE: CurveSelect = CurveSelect::Ed25519;
+
+    let storage_arg = format!("--storage-path {} ", in_memory());
+
+    import_key_shares::<curve25519::edwards::EdwardsPoint>(&storage_arg, CURVE, "ed");
+    import_key_shares::<curve25519::edwards::EdwardsPoint>(&storage_arg, CURVE, "ed:0[x");
+
+    run(&storage_arg, "tss frost prepare -k ed:0 -c 2", (), 0);
+    run(&storage_arg, "tss frost prepare -k ed:0[x:0 -c 3", (), 0);
+    assert_eq!(count(&storage_arg, "ed:0"), 2);
+    assert_eq!(count(&storage_arg, "ed:0[x:0"), 3);
+
+    run(&storage_arg, "tss frost nonces purge -k ed:0 --all", (), 0);
+    assert_eq!(count(&storage_arg, "ed:0"), 0);
+    assert_eq!(count(&storage_arg, "ed:0[x:0"), 3);
+}
+
+fn message() -> serde_yaml::Value {
+    serde_yaml::to_value(vec![Input::Text(MESSAGE.into())]).expect("ser")
+}
+
+/// Import the key-shares `<prefix>:0..3` of a random 2-of-3 key: the public key.
+fn import_key_shares<G>(storage_arg: &str, curve: CurveSelect, prefix: &str) -> G
+where
+    G: Group + GroupEncoding,
+    G::Scalar: PrimeField,
+{
+    let key_ids = (0..3).map(|idx| format!("{}:{}", prefix, idx)).collect::<Vec<_>>();
+    let (_, public_key) = import_2_of_3::<G::Scalar, G>(storage_arg, curve, &key_ids);
+    public_key.restore(curve).expect("public key")
+}
+
+fn sign_and_aggregate(
+    storage_arg: &str,
+    curve: CurveSelect,
+    prefix: &str,
+    flag: &str,
+    signers: [usize; 2],
+) -> Signature {
+    let (signers, shards) = sign(storage_arg, prefix, flag, &signers);
+    let flags = format!("-c {} -h sha3-256 {}", curve, flag);
+    aggregate(storage_arg, prefix, &flags, &signers, shards, 0)
+        .stdout_as_yaml()
+        .expect("io:de")
+}
+
+fn sign(
+    storage_arg: &str,
+    prefix: &str,
+    flag: &str,
+    signers: &[usize],
+) -> (serde_yaml::Value, HashMap<Scalar, Shard>) {
+    let commitments = signers
+        .iter()
+        .map(|idx| {
+            let io =
+                run(storage_arg, &format!("tss frost prepare -k {}:{} -c 1", prefix, idx), (), 0);
+            let [commitment]: [(Point, Point); 1] = io.stdout_as_yaml().expect("io:de");
+            commitment
+        })
+        .collect::<Vec<_>>();
+    let shamir_xs = signers
+        .iter()
+        .map(|idx| {
+            let key: Key = run(storage_arg, &format!("keys export {}:{}", prefix, idx), (), 0)
+                .stdout_as_yaml()
+                .expect("io:de");
+            let Key::S4Share(s4_share) = key else { panic!("not an S4-share") };
+            s4_share.x
+        })
+        .collect::<Vec<_>>();
+    let signers_value = serde_yaml::to_value(
+        shamir_xs
+            .iter()
+            .zip(&commitments)
+            .map(|(x, (cd, ce))| (x, cd, ce))
+            .collect::<Vec<_>>(),
+    )
+    .expect("ser");
+
+    let mut shards = HashMap::new();
+    for (idx, shamir_x) in signers.iter().zip(shamir_xs) {
+        let input = Mapping::from_iter([
+            ("message".into(), message()),
+            ("signers".into(), signers_value.clone()),
+        ]);
+        let shard: Shard = run(
+            storage_arg,
+            &format!("tss frost sign -k {}:{} -h sha3-256 {}", prefix, idx, flag),
+            input,
+            0,
+        )
+        .stdout_as_yaml()
+        .expect("io:de");
+        shards.insert(shamir_x, shard);
+    }
+
+    (signers_value, shards)
+}
+
+fn aggregate(
+    storage_arg: &str,
+    prefix: &str,
+    flags: &str,
+    signers: &serde_yaml::Value,
+    shards: HashMap<Scalar, Shard>,
+    ret_code: RetCode,
+) -> TestIO {
+    let mut input = trusted_input(storage_arg, prefix);
+    input.insert("message".into(), message());
+    input.insert("signers".into(), signers.clone());
+    input.insert("shards".into(), serde_yaml::to_value(shards).expect("ser"));
+    run(storage_arg, &format!("tss frost aggregate {}", flags), input, ret_code)
+}
+
+/// The `public_key` and the `verification_shares` of the key-shares `0..3`.
+fn trusted_input(storage_arg: &str, prefix: &str) -> Mapping {
+    #[derive(Deserialize)]
+    struct VerificationShare {
+        x: Scalar,
+        public_key: Point,
+        verification_share: Point,
+    }
+
+    let shares = (0..3)
+        .map(|idx| {
+            run(storage_arg, &format!("tss frost verification-share -k {}:{}", prefix, idx), (), 0)
+                .stdout_as_yaml::<VerificationShare>()
+                .expect("io:de")
+        })
+        .collect::<Vec<_>>();
+    let verification_shares = shares
+        .iter()
+        .map(|share| (share.x.clone(), share.verification_share.clone()))
+        .collect::<HashMap<_, _>>();
+
+    Mapping::from_iter([
+        ("public_key".into(), serde_yaml::to_value(&shares[0].public_key).expect("ser")),
+        ("verification_shares".into(), serde_yaml::to_value(verification_shares).expect("ser")),
+    ])
+}
+
+/// The input of `sign` with fresh commitments of the `signers`, plus the commitment `own` of the
+/// key-share `0` if given.
+fn sign_input(storage_arg: &str, prefix: &str, signers: &[usize], own: &str) -> Mapping {
+    let mut signers_value = signers
+        .iter()
+        .map(|idx| {
+            let io =
+                run(storage_arg, &format!("tss frost prepare -k {}:{} -c 1", prefix, idx), (), 0);
+            let [(cd, ce)]: [(Point, Point); 1] = io.stdout_as_yaml().expect("io:de");
+            (shamir_x(storage_arg, &format!("{}:{}", prefix, idx)), cd, ce)
+        })
+        .collect::<Vec<_>>();
+    if let Some((cd, ce)) = own.split_once('-') {
+        let own_x = shamir_x(storage_arg, &format!("{}:0", prefix));
+        signers_value.push((own_x, cd.parse().expect("cd"), ce.parse().expect("ce")));
+    }
+
+    Mapping::from_iter([
+        ("message".into(), message()),
+        ("signers".into(), serde_yaml::to_value(signers_value).expect("ser")),
+    ])
+}
+
+fn shamir_x(storage_arg: &str, key_id: &str) -> Scalar {
+    let key: Key = run(storage_arg, &format!("keys export {}", key_id), (), 0)
+        .stdout_as_yaml()
+        .expect("io:de");
+    let Key::S4Share(s4_share) = key else { panic!("not an S4-share") };
+    s4_share.x
+}
+
+fn count(storage_arg: &str, key_id: &str) -> usize {
+    run(storage_arg, &format!("tss frost nonces count -k {}", key_id), (), 0)
+        .stdout_as_yaml()
+        .expect("io:de")
+}
+
+fn run_with_error(storage_arg: &str, cmd: &str, input: impl Serialize) {
+    let io = TestIO::from_yaml_stdin(input).expect("make io");
+    let cli = cli::Cli::create_safe(args(format!("{}{}", storage_arg, cmd))).expect("args error");
+    assert!(cli::run(&cli, rand::rngs::OsRng, &io).is_err(), "{}", cmd);
+}
@@ -38,3 +38,36 @@ where
 
     Ok(utils::bytes_to_scalar(hasher.finalize().as_ref()))
 }
+
+pub fn produce_digest(t: &Transcript) -> Result<Vec<u8>, AnyError> {
+    specialize_call!(produce_digest_1, (t), t.hash_function,
+        [
+            (HashFunctionSelect::Sha3_256 => sha3::Sha3_256),
+            (HashFunctionSelect::Sha2_256 => sha2::Sha256),
+        ],
+    )
+    .ok_or(format!("unsupported hash-function: {}", t.hash_function))?
+}
+
+fn produce_digest_1<H>(t: &Transcript) -> Result<Vec<u8>, AnyError>
+where
+    H: Digest,
+{
+    Ok(H::digest(produce_message(&t.input)?).to_vec())
+}
+
+/// Concatenates the inputs into a plain message. The inputs may not refer to points.
+pub fn produce_message(input: &[Input]) -> Result<Vec<u8>, AnyError> {
+    let mut message = vec![];
+
+    for input in input.iter() {
+        match input {
+            Input::Hex(h) => message.extend(hex::decode(h.as_str())?),
+            Input::Text(t) => message.extend(t.as_bytes()),
+            Input::Point(p) =>
+                return Err(format!("the message may not refer to a point: {:?}", p).into()),
+        }
+    }
+
+    Ok(message)
+}
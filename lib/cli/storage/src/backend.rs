@@ -0,0 +1,97 @@
+use std::any::Any;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use lockfile::Lockfile;
+
+use crate::AnyError;
+
+mod directory;
+mod memory;
+mod sled_db;
+
+/// The records of a tree: in the order of their keys.
+pub(crate) type Entries<'a> = Box<dyn Iterator<Item = Result<(String, Vec<u8>), AnyError>> + 'a>;
+
+/// The body of a transaction: run over the views of the trees.
+pub(crate) type TxBody<'a> = dyn Fn(&[&dyn TxTree]) -> Result<(), AnyError> + 'a;
+
+/// Where the trees are kept.
+pub(crate) trait Backend: Send + Sync + fmt::Debug {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn Tree>, AnyError>;
+
+    /// The tree of the storage itself (e.g. the key-slot): not one of the `tree_names`.
+    fn default_tree(&self) -> Result<Arc<dyn Tree>, AnyError>;
+
+    fn tree_names(&self) -> Result<Vec<String>, AnyError>;
+
+    fn drop_tree(&self, name: &str) -> Result<bool, AnyError>;
+
+    fn flush(&self) -> Result<(), AnyError>;
+
+    /// Run `f` over the views of the `trees` (opened by this backend): either all its writes are
+    /// applied, or (if it fails) none.
+    ///
+    /// `f` might be run more than once: the errors of the views should be returned as they are.
+    fn transaction(&self, trees: &[Arc<dyn Tree>], f: &TxBody<'_>) -> Result<(), AnyError>;
+}
+
+pub(crate) trait Tree: Send + Sync + fmt::Debug {
+    fn name(&self) -> &str;
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError>;
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError>;
+
+    /// Insert the value unless the `key` is already taken: `false` if it is.
+    fn insert_new(&self, key: &str, value: Vec<u8>) -> Result<bool, AnyError>;
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError>;
+
+    fn scan_prefix(&self, prefix: &str) -> Entries<'_>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// A tree within a transaction.
+pub(crate) trait TxTree {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError>;
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError>;
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError>;
+}
+
+/// The backend at the `location`: `sled://PATH` (or just `PATH`), `dir://PATH`, or `mem://NAME`;
+/// along with the lock of the files (if any) held by this process.
+pub(crate) fn open(location: &str) -> Result<(Arc<dyn Backend>, Option<Lockfile>), AnyError> {
+    let (scheme, rest) = location.split_once("://").unwrap_or(("sled", location));
+    match scheme {
+        "sled" => {
+            let path: PathBuf = rest.parse()?;
+            let lockfile = Lockfile::create_with_parents(path.join("storage-sled.lock"))?;
+            let backend = sled_db::SledDb::open(&path.join("storage-sled.db"))?;
+            Ok((Arc::new(backend), Some(lockfile)))
+        },
+        "dir" => {
+            let path: PathBuf = rest.parse()?;
+            let lockfile = Lockfile::create_with_parents(path.join("storage-dir.lock"))?;
+            let backend = directory::Directory::open(path.join("storage-dir"))?;
+            Ok((Arc::new(backend), Some(lockfile)))
+        },
+        "mem" => Ok((Arc::new(memory::Memory::open(rest)), None)),
+        _ => Err(format!("Unsupported storage backend: {:?}", scheme).into()),
+    }
+}
+
+fn downcast<T: 'static>(trees: &[Arc<dyn Tree>]) -> Result<Vec<&T>, AnyError> {
+    trees
+        .iter()
+        .map(|tree| {
+            tree.as_any().downcast_ref::<T>().ok_or_else(|| {
+                format!("The table {:?} is of another storage backend", tree.name()).into()
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,330 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use super::{downcast, Backend, Entries, Tree, TxBody, TxTree};
+use crate::AnyError;
+
+const DEFAULT_TREE: &str = "__directory__default";
+
+/// The writes of a transaction, kept until they all are applied.
+const JOURNAL: &str = ".journal";
+
+/// A directory per tree, a file per record: named after the tree and the key (the bytes other
+/// than `[A-Za-z0-9-_.:@+=,]` are %-encoded, so are the leading dots).
+#[derive(Debug)]
+pub(crate) struct Directory {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+#[derive(Debug)]
+struct DirTree {
+    directory: Arc<Directory>,
+    name: String,
+    path: PathBuf,
+}
+
+struct DirTxTree<'a> {
+    tree: &'a DirTree,
+    writes: RefCell<BTreeMap<String, Option<Vec<u8>>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    tree: String,
+    key: String,
+    value: Option<String>,
+}
+
+impl Directory {
+    /// The directory: the transaction interrupted (if any) is completed.
+    pub(crate) fn open(path: PathBuf) -> Result<Arc<Self>, AnyError> {
+        fs::create_dir_all(&path)?;
+        let directory = Arc::new(Self { path, lock: Mutex::new(()) });
+
+        let journal_path = directory.path.join(JOURNAL);
+        match fs::read(&journal_path) {
+            Ok(journal) => {
+                directory.apply(&serde_json::from_slice::<Vec<JournalEntry>>(&journal)?)?;
+                fs::remove_file(journal_path)?;
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(directory)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().expect("poisoned")
+    }
+
+    fn tree_path(&self, name: &str) -> PathBuf {
+        self.path.join(encode(name))
+    }
+
+    fn apply(&self, journal: &[JournalEntry]) -> Result<(), AnyError> {
+        for JournalEntry { tree, key, value } in journal {
+            let tree_path = self.tree_path(tree);
+            match value {
+                Some(value) => {
+                    fs::create_dir_all(&tree_path)?;
+                    write_file(&tree_path.join(encode(key)), &hex::decode(value)?)?;
+                },
+                None => remove_file(&tree_path.join(encode(key)))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Backend for Arc<Directory> {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn Tree>, AnyError> {
+        let path = self.tree_path(name);
+        fs::create_dir_all(&path)?;
+        Ok(Arc::new(DirTree { directory: self.clone(), name: name.to_owned(), path }))
+    }
+
+    fn default_tree(&self) -> Result<Arc<dyn Tree>, AnyError> {
+        self.open_tree(DEFAULT_TREE)
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, AnyError> {
+        let mut names = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else { continue };
+            if file_name.starts_with('.') || !entry.file_type()?.is_dir() {
+                continue
+            }
+            let name = decode(file_name)?;
+            if name != DEFAULT_TREE {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn drop_tree(&self, name: &str) -> Result<bool, AnyError> {
+        let _lock = self.lock();
+        match fs::remove_dir_all(self.tree_path(name)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn flush(&self) -> Result<(), AnyError> {
+        Ok(())
+    }
+
+    fn transaction(&self, trees: &[Arc<dyn Tree>], f: &TxBody<'_>) -> Result<(), AnyError> {
+        let trees = downcast::<DirTree>(trees)?;
+
+        let _lock = self.lock();
+        let views = trees
+            .iter()
+            .map(|tree| DirTxTree { tree, writes: Default::default() })
+            .collect::<Vec<_>>();
+        f(&views.iter().map(|view| view as &dyn TxTree).collect::<Vec<_>>())?;
+
+        let journal = views
+            .into_iter()
+            .flat_map(|view| {
+                let tree = view.tree.name.clone();
+                view.writes.into_inner().into_iter().map(move |(key, value)| JournalEntry {
+                    tree: tree.clone(),
+                    key,
+                    value: value.map(hex::encode),
+                })
+            })
+            .collect::<Vec<_>>();
+        if journal.is_empty() {
+            return Ok(())
+        }
+
+        // once the journal is written, the transaction is complete: if interrupted, it is
+        // applied when the directory is opened next time
+        let journal_path = self.path.join(JOURNAL);
+        write_file(&journal_path, &serde_json::to_vec(&journal)?)?;
+        self.apply(&journal)?;
+        fs::remove_file(journal_path)?;
+
+        Ok(())
+    }
+}
+
+impl DirTree {
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.path.join(encode(key))
+    }
+}
+
+impl Tree for DirTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        read_file(&self.record_path(key))
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        let _lock = self.directory.lock();
+        let path = self.record_path(key);
+        let previous = read_file(&path)?;
+        fs::create_dir_all(&self.path)?;
+        write_file(&path, &value)?;
+        Ok(previous)
+    }
+
+    fn insert_new(&self, key: &str, value: Vec<u8>) -> Result<bool, AnyError> {
+        let _lock = self.directory.lock();
+        let path = self.record_path(key);
+        if path.exists() {
+            return Ok(false)
+        }
+        fs::create_dir_all(&self.path)?;
+        write_file(&path, &value)?;
+        Ok(true)
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        let _lock = self.directory.lock();
+        let path = self.record_path(key);
+        let previous = read_file(&path)?;
+        remove_file(&path)?;
+        Ok(previous)
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Entries<'_> {
+        let keys = match fs::read_dir(&self.path) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    let file_name = match entry {
+                        Ok(entry) => entry.file_name(),
+                        Err(err) => return Some(Err(err.into())),
+                    };
+                    let file_name = file_name.to_str()?;
+                    if file_name.starts_with('.') {
+                        return None
+                    }
+                    Some(decode(file_name))
+                })
+                .collect::<Result<Vec<_>, AnyError>>(),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(err.into()),
+        };
+        let mut keys = match keys {
+            Ok(keys) => keys,
+            Err(err) => return Box::new(std::iter::once(Err(err))),
+        };
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+
+        Box::new(keys.into_iter().filter_map(|key| match read_file(&self.record_path(&key)) {
+            Ok(Some(value)) => Some(Ok((key, value))),
+            // removed meanwhile
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<'a> TxTree for DirTxTree<'a> {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        match self.writes.borrow().get(key) {
+            Some(value) => Ok(value.clone()),
+            None => self.tree.get(key),
+        }
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        let previous = self.get(key)?;
+        self.writes.borrow_mut().insert(key.to_owned(), Some(value));
+        Ok(previous)
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        let previous = self.get(key)?;
+        self.writes.borrow_mut().insert(key.to_owned(), None);
+        Ok(previous)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, AnyError> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Write into a temporary file first: the file is either intact or replaced as a whole.
+fn write_file(path: &Path, data: &[u8]) -> Result<(), AnyError> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).ok_or("invalid path")?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+fn remove_file(path: &Path) -> Result<(), AnyError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn encode(name: &str) -> String {
+    if name.is_empty() {
+        return "%".to_owned()
+    }
+    let mut encoded = String::with_capacity(name.len());
+    for (idx, byte) in name.bytes().enumerate() {
+        if byte.is_ascii_alphanumeric() || b"-_:@+=,".contains(&byte) || (byte == b'.' && idx > 0) {
+            encoded.push(byte as char);
+        } else {
+            write!(encoded, "%{:02X}", byte).expect("write to string");
+        }
+    }
+    encoded
+}
+
+fn decode(encoded: &str) -> Result<String, AnyError> {
+    if encoded == "%" {
+        return Ok(String::new())
+    }
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).ok_or_else(|| format!("Invalid file name: {:?}", encoded))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex)?, 16)?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
@@ -0,0 +1,146 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use super::{downcast, Backend, Entries, Tree, TxBody, TxTree};
+use crate::AnyError;
+
+const DEFAULT_TREE: &str = "__memory__default";
+
+type Records = BTreeMap<String, Vec<u8>>;
+
+/// The trees kept in memory: the storages of the same name share them, as long as the process
+/// lasts.
+#[derive(Debug, Default)]
+pub(crate) struct Memory {
+    trees: Mutex<BTreeMap<String, Records>>,
+}
+
+#[derive(Debug)]
+struct MemoryTree {
+    memory: Arc<Memory>,
+    name: String,
+}
+
+struct MemoryTxTree {
+    records: RefCell<Records>,
+}
+
+impl Memory {
+    pub(crate) fn open(name: &str) -> Arc<Self> {
+        static MEMORIES: OnceLock<Mutex<HashMap<String, Arc<Memory>>>> = OnceLock::new();
+
+        let mut memories = MEMORIES.get_or_init(Default::default).lock().expect("poisoned");
+        memories.entry(name.to_owned()).or_default().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Records>> {
+        self.trees.lock().expect("poisoned")
+    }
+}
+
+impl Backend for Arc<Memory> {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn Tree>, AnyError> {
+        self.lock().entry(name.to_owned()).or_default();
+        Ok(Arc::new(MemoryTree { memory: self.clone(), name: name.to_owned() }))
+    }
+
+    fn default_tree(&self) -> Result<Arc<dyn Tree>, AnyError> {
+        self.open_tree(DEFAULT_TREE)
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, AnyError> {
+        Ok(self.lock().keys().filter(|name| *name != DEFAULT_TREE).cloned().collect())
+    }
+
+    fn drop_tree(&self, name: &str) -> Result<bool, AnyError> {
+        Ok(self.lock().remove(name).is_some())
+    }
+
+    fn flush(&self) -> Result<(), AnyError> {
+        Ok(())
+    }
+
+    fn transaction(&self, trees: &[Arc<dyn Tree>], f: &TxBody<'_>) -> Result<(), AnyError> {
+        let trees = downcast::<MemoryTree>(trees)?;
+
+        // the trees are locked throughout: `f` works on their copies, kept if it succeeds
+        let mut locked = self.lock();
+        let views = trees
+            .iter()
+            .map(|tree| MemoryTxTree {
+                records: RefCell::new(locked.get(&tree.name).cloned().unwrap_or_default()),
+            })
+            .collect::<Vec<_>>();
+        f(&views.iter().map(|view| view as &dyn TxTree).collect::<Vec<_>>())?;
+
+        for (tree, view) in trees.iter().zip(views) {
+            locked.insert(tree.name.clone(), view.records.into_inner());
+        }
+        Ok(())
+    }
+}
+
+impl MemoryTree {
+    fn with<R>(&self, f: impl FnOnce(&mut Records) -> R) -> R {
+        f(self.memory.lock().entry(self.name.clone()).or_default())
+    }
+}
+
+impl Tree for MemoryTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.with(|records| records.get(key).cloned()))
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.with(|records| records.insert(key.to_owned(), value)))
+    }
+
+    fn insert_new(&self, key: &str, value: Vec<u8>) -> Result<bool, AnyError> {
+        Ok(self.with(|records| {
+            if records.contains_key(key) {
+                return false
+            }
+            records.insert(key.to_owned(), value);
+            true
+        }))
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.with(|records| records.remove(key)))
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Entries<'_> {
+        let entries = self.with(|records| {
+            records
+                .range(prefix.to_owned()..)
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .map(|(key, value)| Ok((key.clone(), value.clone())))
+                .collect::<Vec<_>>()
+        });
+        Box::new(entries.into_iter())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl TxTree for MemoryTxTree {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.records.borrow().get(key).cloned())
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.records.borrow_mut().insert(key.to_owned(), value))
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.records.borrow_mut().remove(key))
+    }
+}
@@ -0,0 +1,155 @@
+use std::any::Any;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sled::transaction::{
+    ConflictableTransactionError, TransactionError, TransactionalTree, UnabortableTransactionError,
+};
+use sled::Transactional;
+
+use super::{downcast, Backend, Entries, Tree, TxBody, TxTree};
+use crate::AnyError;
+
+/// How many times the database is opened while its lock is still held within this process.
+const OPEN_ATTEMPTS: u32 = 50;
+
+#[derive(Debug)]
+pub(crate) struct SledDb {
+    db: sled::Db,
+}
+
+#[derive(Debug)]
+struct SledTree {
+    name: String,
+    tree: sled::Tree,
+}
+
+struct SledTxTree<'a> {
+    view: &'a TransactionalTree,
+}
+
+impl SledDb {
+    /// The lock-file of the storage is held, so the database is not in use by another process;
+    /// yet it might still be locked for a while by this one, if it has just been closed (sled
+    /// releases the lock from its background threads).
+    pub(crate) fn open(path: &Path) -> Result<Self, AnyError> {
+        let mut attempt = 1;
+        loop {
+            match sled::open(path) {
+                Ok(db) => return Ok(Self { db }),
+                Err(sled::Error::Io(err))
+                    if err.kind() == ErrorKind::WouldBlock && attempt < OPEN_ATTEMPTS =>
+                {
+                    std::thread::sleep(Duration::from_millis(10 * u64::from(attempt)));
+                    attempt += 1;
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Backend for SledDb {
+    fn open_tree(&self, name: &str) -> Result<Arc<dyn Tree>, AnyError> {
+        let tree = self.db.open_tree(name)?;
+        Ok(Arc::new(SledTree { name: name.to_owned(), tree }))
+    }
+
+    fn default_tree(&self) -> Result<Arc<dyn Tree>, AnyError> {
+        let name = String::from_utf8(self.db.name().to_vec())?;
+        Ok(Arc::new(SledTree { name, tree: (*self.db).clone() }))
+    }
+
+    fn tree_names(&self) -> Result<Vec<String>, AnyError> {
+        let default_tree_name = self.db.name();
+        self.db
+            .tree_names()
+            .into_iter()
+            .filter(|name| *name != default_tree_name)
+            .map(|name| Ok(String::from_utf8(name.to_vec())?))
+            .collect()
+    }
+
+    fn drop_tree(&self, name: &str) -> Result<bool, AnyError> {
+        Ok(self.db.drop_tree(name)?)
+    }
+
+    fn flush(&self) -> Result<(), AnyError> {
+        tracing::debug!("about to flush");
+        let bytes_flushed = self.db.flush()?;
+        tracing::debug!("flushed {} bytes", bytes_flushed);
+        Ok(())
+    }
+
+    fn transaction(&self, trees: &[Arc<dyn Tree>], f: &TxBody<'_>) -> Result<(), AnyError> {
+        let trees = downcast::<SledTree>(trees)?
+            .into_iter()
+            .map(|t| t.tree.clone())
+            .collect::<Vec<_>>();
+
+        let result = trees.as_slice().transaction(|views| {
+            let views = views.iter().map(|view| SledTxTree { view }).collect::<Vec<_>>();
+            let views = views.iter().map(|view| view as &dyn TxTree).collect::<Vec<_>>();
+            // the conflicts are to be retried, not to abort the transaction
+            f(&views).map_err(|err| match err.downcast::<UnabortableTransactionError>() {
+                Ok(err) => ConflictableTransactionError::from(*err),
+                Err(err) => ConflictableTransactionError::Abort(err),
+            })
+        });
+        match result {
+            Ok(()) => Ok(()),
+            Err(TransactionError::Abort(err)) => Err(err),
+            Err(TransactionError::Storage(err)) => Err(err.into()),
+        }
+    }
+}
+
+impl Tree for SledTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.tree.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.tree.insert(key, value)?.map(|value| value.to_vec()))
+    }
+
+    fn insert_new(&self, key: &str, value: Vec<u8>) -> Result<bool, AnyError> {
+        let swapped = self.tree.compare_and_swap(key, None::<&[u8]>, Some(value))?;
+        Ok(swapped.is_ok())
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.tree.remove(key)?.map(|value| value.to_vec()))
+    }
+
+    fn scan_prefix(&self, prefix: &str) -> Entries<'_> {
+        Box::new(self.tree.scan_prefix(prefix).map(|result| {
+            let (key, value) = result?;
+            Ok((String::from_utf8(key.to_vec())?, value.to_vec()))
+        }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<'a> TxTree for SledTxTree<'a> {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.view.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.view.insert(key, value)?.map(|value| value.to_vec()))
+    }
+
+    fn remove(&self, key: &str) -> Result<Option<Vec<u8>>, AnyError> {
+        Ok(self.view.remove(key)?.map(|value| value.to_vec()))
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::cipher::{derive_key, open, seal, SALT_SIZE};
+use crate::{AnyError, KdfParams, RawTable, Storage};
+
+const FORMAT: &str = "rabbit-hole/alice-backup";
+const VERSION: u32 = 1;
+
+const CONTENTS_AAD: &[u8] = b"rabbit-hole/cli-storage/backup-contents";
+const MAC_AAD: &[u8] = b"rabbit-hole/cli-storage/backup-mac";
+
+/// The records of the tables, keyed by the table and the id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Backup {
+    pub tables: BTreeMap<String, BTreeMap<String, BackupRecord>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupRecord {
+    pub version: u32,
+    pub record: Value,
+}
+
+/// The archive of a backup: the contents (encrypted, or not) and the MAC over the header and the
+/// contents, both under a key derived from the passphrase of the backup.
+#[derive(Debug, Serialize, Deserialize)]
+struct Archive {
+    #[serde(flatten)]
+    header: Header,
+    contents: String,
+    mac: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    created_at: u64,
+    kdf: KdfParams,
+    salt: String,
+    encrypted: bool,
+}
+
+impl Backup {
+    /// The records of all the tables of the `storage`.
+    pub fn of(storage: &Storage) -> Result<Self, AnyError> {
+        let mut tables = BTreeMap::new();
+        for name in storage.table_names()? {
+            let records = RawTable::open(storage, &name)?
+                .select("")
+                .map(|item| {
+                    item.map(|(id, version, record)| (id, BackupRecord { version, record }))
+                })
+                .collect::<Result<BTreeMap<_, _>, AnyError>>()?;
+            if !records.is_empty() {
+                tables.insert(name, records);
+            }
+        }
+        Ok(Self { tables })
+    }
+
+    /// The archive of the backup: the contents are in plain text unless `encrypted`.
+    pub fn seal(
+        &self,
+        passphrase: &str,
+        encrypted: bool,
+        kdf: KdfParams,
+        created_at: u64,
+    ) -> Result<Vec<u8>, AnyError> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(passphrase, &salt, kdf)?;
+
+        let header = Header {
+            format: FORMAT.to_owned(),
+            version: VERSION,
+            created_at,
+            kdf,
+            salt: hex::encode(salt),
+            encrypted,
+        };
+        let header_json = serde_json::to_vec(&header)?;
+
+        let contents = Zeroizing::new(serde_json::to_string(&self)?);
+        let contents = if encrypted {
+            let aad = [CONTENTS_AAD, header_json.as_ref()].concat();
+            hex::encode(seal(&key, &aad, contents.as_bytes())?)
+        } else {
+            contents.to_string()
+        };
+        let mac = hex::encode(seal(&key, &mac_aad(&header_json, &contents), &[])?);
+
+        let archive = Archive { header, contents, mac };
+        Ok(serde_json::to_vec_pretty(&archive)?)
+    }
+
+    /// The backup in the archive: refused if the archive is not intact (or the passphrase wrong).
+    pub fn open(archive: &[u8], passphrase: &str) -> Result<Self, AnyError> {
+        let Archive { header, contents, mac } = serde_json::from_slice(archive)?;
+        if header.format != FORMAT || header.version != VERSION {
+            return Err(format!(
+                "Unsupported backup [format: {:?}; version: {}]",
+                header.format, header.version
+            )
+            .into())
+        }
+        let header_json = serde_json::to_vec(&header)?;
+        let key = derive_key(passphrase, &hex::decode(&header.salt)?, header.kdf)?;
+
+        open(&key, &mac_aad(&header_json, &contents), &hex::decode(mac)?)
+            .map_err(|_| "The backup is corrupt, or the passphrase is wrong")?;
+
+        let backup = if header.encrypted {
+            let aad = [CONTENTS_AAD, header_json.as_ref()].concat();
+            let contents = open(&key, &aad, &hex::decode(&contents)?)
+                .map_err(|_| "The backup is corrupt, or the passphrase is wrong")?;
+            serde_json::from_slice(&contents)?
+        } else {
+            serde_json::from_str(&contents)?
+        };
+        Ok(backup)
+    }
+}
+
+/// `MAC_AAD || header || 0 || contents`
+fn mac_aad(header_json: &[u8], contents: &str) -> Vec<u8> {
+    [MAC_AAD, header_json, &[0], contents.as_bytes()].concat()
+}
@@ -0,0 +1,153 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::AnyError;
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 24;
+pub(crate) const SALT_SIZE: usize = 16;
+
+const KEY_SLOT_AAD: &[u8] = b"rabbit-hole/cli-storage/key-slot";
+
+/// The cost of deriving the key that wraps the data-key from the passphrase (Argon2id).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+}
+
+impl KdfParams {
+    /// The costliest parameters accepted: those of an archive or of a key-slot are read before
+    /// anything is authenticated, so they can't be trusted to be sane.
+    pub const MAX: Self = Self { memory_kib: 1024 * 1024, iterations: 16 };
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self { memory_kib: 64 * 1024, iterations: 3 }
+    }
+}
+
+/// The data-key, wrapped with a key derived from the passphrase.
+///
+/// Changing the passphrase rewraps the data-key: the entries are left intact.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct KeySlot {
+    kdf: KdfParams,
+    salt: String,
+    wrapped_key: String,
+}
+
+/// The entries are sealed with XChaCha20-Poly1305; the table and the id of an entry are its
+/// associated data, so an entry can't be moved to another place undetected.
+pub(crate) struct Cipher {
+    key: Zeroizing<[u8; KEY_SIZE]>,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").field("key", &"<redacted>").finish()
+    }
+}
+
+impl Cipher {
+    pub(crate) fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        OsRng.fill_bytes(key.as_mut());
+        Self { key }
+    }
+
+    pub(crate) fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AnyError> {
+        seal(&self.key, aad, plaintext)
+    }
+
+    pub(crate) fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, AnyError> {
+        open(&self.key, aad, sealed).map_err(|_| "Failed to decrypt the entry".into())
+    }
+}
+
+impl KeySlot {
+    pub(crate) fn wrap(
+        cipher: &Cipher,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, AnyError> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        let kek = derive_key(passphrase, &salt, kdf)?;
+        let wrapped_key = seal(&kek, KEY_SLOT_AAD, cipher.key.as_ref())?;
+
+        Ok(Self { kdf, salt: hex::encode(salt), wrapped_key: hex::encode(wrapped_key) })
+    }
+
+    pub(crate) fn unwrap(&self, passphrase: &str) -> Result<Cipher, AnyError> {
+        let salt = hex::decode(&self.salt)?;
+        let wrapped_key = hex::decode(&self.wrapped_key)?;
+
+        let kek = derive_key(passphrase, &salt, self.kdf)?;
+        let key = open(&kek, KEY_SLOT_AAD, &wrapped_key).map_err(|_| "Wrong passphrase")?;
+        let key = Zeroizing::new(<[u8; KEY_SIZE]>::try_from(key.as_slice())?);
+
+        Ok(Cipher { key })
+    }
+}
+
+pub(crate) fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, AnyError> {
+    if kdf.memory_kib > KdfParams::MAX.memory_kib || kdf.iterations > KdfParams::MAX.iterations {
+        return Err(format!(
+            "The KDF parameters exceed the maximum [memory: {} KiB; iterations: {}]",
+            KdfParams::MAX.memory_kib,
+            KdfParams::MAX.iterations
+        )
+        .into())
+    }
+    let params = Params::new(kdf.memory_kib, kdf.iterations, 1, Some(KEY_SIZE))
+        .map_err(|e| format!("Invalid KDF parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| format!("Failed to derive the key: {}", e))?;
+    Ok(key)
+}
+
+/// `nonce || ciphertext`
+pub(crate) fn seal(
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, AnyError> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = XChaCha20Poly1305::new(&Key::from(*key))
+        .encrypt(&XNonce::from(nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| "Failed to encrypt")?;
+
+    Ok([nonce.as_ref(), ciphertext.as_ref()].concat())
+}
+
+pub(crate) fn open(
+    key: &[u8; KEY_SIZE],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, chacha20poly1305::aead::Error> {
+    if sealed.len() < NONCE_SIZE {
+        return Err(chacha20poly1305::aead::Error)
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    let nonce = <[u8; NONCE_SIZE]>::try_from(nonce).expect("split at NONCE_SIZE");
+
+    XChaCha20Poly1305::new(&Key::from(*key))
+        .decrypt(&XNonce::from(nonce), Payload { msg: ciphertext, aad })
+        .map(Zeroizing::new)
+}
@@ -1,7 +1,24 @@
 pub type AnyError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
+mod backend;
+
+mod backup;
+pub use backup::{Backup, BackupRecord};
+
+mod cipher;
+pub use cipher::KdfParams;
+
 mod storage;
-pub use storage::Storage;
+pub use storage::{Storage, LEGACY_VERSION};
 
 mod table;
-pub use table::Table;
+pub use table::{Record, Table};
+
+mod raw_table;
+pub use raw_table::RawTable;
+
+mod transaction;
+pub use transaction::{AnyTable, Transaction, TxRawTable, TxTable};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::backend::Tree;
+use crate::{AnyError, Storage};
+
+/// A table accessed by its name, the records of any version: for the migrations.
+#[derive(Debug, Clone)]
+pub struct RawTable {
+    pub(crate) storage: Storage,
+    pub(crate) tree: Arc<dyn Tree>,
+}
+
+impl RawTable {
+    pub fn open(storage: impl Into<Storage>, name: &str) -> Result<Self, AnyError> {
+        let storage = storage.into();
+        let tree = storage.backend.open_tree(name)?;
+        Ok(Self { storage, tree })
+    }
+
+    pub fn name(&self) -> String {
+        self.tree.name().to_owned()
+    }
+
+    /// The records along with their versions.
+    pub fn select(
+        &self,
+        prefix: &str,
+    ) -> impl Iterator<Item = Result<(String, u32, Value), AnyError>> + '_ {
+        self.tree.scan_prefix(prefix).map(|result| {
+            result.and_then(|(key, value)| {
+                let (version, record) =
+                    self.storage.deserialize(self.tree.name(), &key, None, value)?;
+
+                Ok((key, version, record))
+            })
+        })
+    }
+
+    pub fn contains(&self, id: &str) -> Result<bool, AnyError> {
+        Ok(self.tree.get(id)?.is_some())
+    }
+
+    pub fn insert(&self, id: &str, version: u32, record: &Value) -> Result<(), AnyError> {
+        let data = self.storage.serialize(self.tree.name(), id, version, record)?;
+        self.tree.insert(id, data)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool, AnyError> {
+        Ok(self.tree.remove(id)?.is_some())
+    }
+}
@@ -1,25 +1,47 @@
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use lockfile::Lockfile;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
 
-use crate::AnyError;
+use crate::backend::{self, Backend};
+use crate::cipher::{Cipher, KeySlot};
+use crate::{AnyError, KdfParams};
+
+/// The key of the key-slot in the default tree: present if the storage is encrypted.
+const KEY_SLOT: &str = "encryption";
+
+/// The version of the records stored before they had a version.
+pub const LEGACY_VERSION: u32 = 0;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<R> {
+    version: u32,
+    record: R,
+}
+
+#[derive(Deserialize)]
+struct Versioned {
+    #[serde(default)]
+    version: u32,
+}
 
 #[derive(Debug, Clone)]
 pub struct Storage {
-    pub(crate) sled_db: sled::Db,
+    pub(crate) backend: Arc<dyn Backend>,
+    cipher: Option<Arc<Cipher>>,
 
-    // mind the drop order: keep it the last element in the struct
-    lockfile: Arc<Lockfile>,
+    // held as long as the storage is open; mind the drop order: keep it the last element in the
+    // struct
+    _lockfile: Option<Arc<Lockfile>>,
 }
 
 impl Drop for Storage {
     fn drop(&mut self) {
         // if this is the last clone of that storage
-        if Arc::get_mut(&mut self.lockfile).is_some() {
-            let _ = self.sled_db.flush();
+        if Arc::get_mut(&mut self.backend).is_some() {
+            let _ = self.backend.flush();
         }
     }
 }
@@ -31,38 +53,160 @@ impl<'a> From<&'a Storage> for Storage {
 }
 
 impl Storage {
-    pub fn open(arg: &str) -> Result<Self, AnyError> {
-        let path: PathBuf = arg.parse()?;
-        let sled_path = path.join("storage-sled.db");
-        let lock_path = path.join("storage-sled.lock");
+    /// Open the storage at the `location` (see [`Storage::open_with`]): it should not be encrypted.
+    pub fn open(location: &str) -> Result<Self, AnyError> {
+        Self::open_with(location, || Err("The storage is encrypted: no passphrase given".into()))
+    }
+
+    /// Open the storage at the `location`: the `passphrase` is asked for if the storage is
+    /// encrypted.
+    ///
+    /// The location is `sled://PATH` (or just `PATH`), `dir://PATH` (a file per record), or
+    /// `mem://NAME` (in memory, as long as the process lasts).
+    pub fn open_with(
+        location: &str,
+        passphrase: impl FnOnce() -> Result<Zeroizing<String>, AnyError>,
+    ) -> Result<Self, AnyError> {
+        let (backend, lockfile) = backend::open(location)?;
+
+        let cipher = match backend.default_tree()?.get(KEY_SLOT)? {
+            None => None,
+            Some(key_slot) => {
+                let key_slot: KeySlot = serde_json::from_slice(&key_slot)?;
+                Some(Arc::new(key_slot.unwrap(&passphrase()?)?))
+            },
+        };
+
+        Ok(Self { backend, cipher, _lockfile: lockfile.map(Arc::new) })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Encrypt the entries already stored and those stored from now on.
+    ///
+    /// The other clones of this storage are not affected: drop them beforehand.
+    pub fn encrypt(&mut self, passphrase: &str, kdf: KdfParams) -> Result<(), AnyError> {
+        if self.is_encrypted() {
+            return Err("The storage is already encrypted".into())
+        }
+        let cipher = Cipher::generate();
+        let key_slot = serde_json::to_vec(&KeySlot::wrap(&cipher, passphrase, kdf)?)?;
+
+        let mut trees = vec![self.backend.default_tree()?];
+        let mut sealed = vec![vec![]];
+        for name in self.table_names()? {
+            let tree = self.backend.open_tree(&name)?;
+            let mut entries = vec![];
+            for item in tree.scan_prefix("") {
+                let (id, value) = item?;
+                let value = cipher.seal(&aad(&name, &id), &value)?;
+                entries.push((id, value));
+            }
+            trees.push(tree);
+            sealed.push(entries);
+        }
+
+        self.backend
+            .transaction(&trees, &|views| {
+                views[0].insert(KEY_SLOT, key_slot.clone())?;
+                for (view, entries) in views.iter().zip(sealed.iter()).skip(1) {
+                    for (id, value) in entries {
+                        view.insert(id, value.clone())?;
+                    }
+                }
+                Ok(())
+            })
+            .map_err(|e| format!("Failed to encrypt the storage: {}", e))?;
 
-        let lockfile = Lockfile::create_with_parents(lock_path)?;
+        self.cipher = Some(Arc::new(cipher));
+        self.flush()
+    }
+
+    /// Change the passphrase of an encrypted storage.
+    pub fn change_passphrase(&self, passphrase: &str, kdf: KdfParams) -> Result<(), AnyError> {
+        let cipher = self.cipher.as_ref().ok_or("The storage is not encrypted")?;
+        let key_slot = serde_json::to_vec(&KeySlot::wrap(cipher, passphrase, kdf)?)?;
+        self.backend.default_tree()?.insert(KEY_SLOT, key_slot)?;
+        self.flush()
+    }
 
-        let sled_db = sled::open(sled_path)?;
+    /// The names of the tables in the storage.
+    pub fn table_names(&self) -> Result<Vec<String>, AnyError> {
+        self.backend.tree_names()
+    }
 
-        Ok(Self { sled_db, lockfile: Arc::new(lockfile) })
+    /// Remove the table along with its records: `false` if there is no such table.
+    pub fn drop_table(&self, name: &str) -> Result<bool, AnyError> {
+        self.backend.drop_tree(name)
     }
 
     pub fn flush(&self) -> Result<(), AnyError> {
-        tracing::debug!("about to flush");
-        let bytes_flushed = self.sled_db.flush()?;
-        tracing::debug!("flushed {} bytes", bytes_flushed);
-        Ok(())
+        self.backend.flush()
     }
 
-    pub(crate) fn serialize<S>(&self, item: &S) -> Result<Vec<u8>, AnyError>
+    /// The record in its envelope (along with its `version`), sealed if the storage is encrypted.
+    ///
+    /// The records of the version [`LEGACY_VERSION`] are stored bare, as they were before the
+    /// envelope.
+    pub(crate) fn serialize<S>(
+        &self,
+        tree_name: &str,
+        id: &str,
+        version: u32,
+        item: &S,
+    ) -> Result<Vec<u8>, AnyError>
     where
         S: Serialize,
     {
-        let json = serde_json::to_vec(item)?;
-        Ok(json)
+        let json = Zeroizing::new(if version == LEGACY_VERSION {
+            serde_json::to_vec(item)?
+        } else {
+            serde_json::to_vec(&Envelope { version, record: item })?
+        });
+        match self.cipher.as_ref() {
+            None => Ok(json.to_vec()),
+            Some(cipher) => cipher.seal(&aad(tree_name, id), &json),
+        }
     }
 
-    pub(crate) fn deserialize<D>(&self, data: impl AsRef<[u8]>) -> Result<D, AnyError>
+    /// The version of the record, and the record (unless the version is not the `expected` one).
+    pub(crate) fn deserialize<D>(
+        &self,
+        tree_name: &str,
+        id: &str,
+        expected: Option<u32>,
+        data: impl AsRef<[u8]>,
+    ) -> Result<(u32, D), AnyError>
     where
         D: DeserializeOwned,
     {
-        let item = serde_json::from_slice(data.as_ref())?;
-        Ok(item)
+        let json = match self.cipher.as_ref() {
+            None => Zeroizing::new(data.as_ref().to_vec()),
+            Some(cipher) => cipher.open(&aad(tree_name, id), data.as_ref())?,
+        };
+
+        let Versioned { version } = serde_json::from_slice(&json)?;
+        if let Some(expected) = expected.filter(|expected| *expected != version) {
+            return Err(format!(
+                "The record {:?} in {:?} is of the version {} (expected: {}): run `alice storage \
+                 migrate`",
+                id, tree_name, version, expected,
+            )
+            .into())
+        }
+
+        let record = if version == LEGACY_VERSION {
+            serde_json::from_slice(&json)?
+        } else {
+            serde_json::from_slice::<Envelope<D>>(&json)?.record
+        };
+        Ok((version, record))
     }
 }
+
+/// `table-name || 0 || id`
+fn aad(tree_name: &str, id: &str) -> Vec<u8> {
+    [tree_name.as_bytes(), &[0], id.as_bytes()].concat()
+}
@@ -1,24 +1,35 @@
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 use common_interop::curve_select::CurveSelect;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::backend::Tree;
 use crate::{AnyError, Storage};
 
+/// The records of a table.
+pub trait Record {
+    /// The identifier of the table: stable, whatever the name of the type.
+    const TABLE: &'static str;
+
+    /// The version of the schema of the records: bumped along with a step of `alice storage
+    /// migrate`.
+    const VERSION: u32;
+}
+
 #[derive(Debug, Clone)]
 pub struct Table<N, T = N> {
     pub(crate) storage: Storage,
-    pub(crate) tree: sled::Tree,
+    pub(crate) tree: Arc<dyn Tree>,
 
     pub(crate) _pd: PhantomData<(N, T)>,
 }
 
-impl<N, T> Table<N, T> {
+impl<N: Record, T> Table<N, T> {
     pub fn open(storage: impl Into<Storage>) -> Result<Self, AnyError> {
         let storage = storage.into();
-        let tree_name = tree_name::<N>();
-        let tree = storage.sled_db.open_tree(tree_name)?;
+        let tree = storage.backend.open_tree(N::TABLE)?;
         let table = Self { storage, tree, _pd: Default::default() };
 
         Ok(table)
@@ -30,8 +41,8 @@ impl<N, T> Table<N, T> {
     ) -> Result<Self, AnyError> {
         let storage = storage.into();
 
-        let tree_name = tree_name_for_curve::<N>(curve);
-        let tree = storage.sled_db.open_tree(tree_name)?;
+        let tree_name = format!("{}/{}", curve, N::TABLE);
+        let tree = storage.backend.open_tree(&tree_name)?;
         let table = Self { storage, tree, _pd: Default::default() };
 
         Ok(table)
@@ -42,8 +53,8 @@ impl<N, T> Table<N, T> {
         T: Serialize,
         T: DeserializeOwned,
     {
-        let json_opt = self.tree.remove(id)?;
-        let entry_opt = json_opt.map(|json| self.storage.deserialize(&json)).transpose()?;
+        let data_opt = self.tree.remove(id)?;
+        let entry_opt = data_opt.map(|data| self.decode(id, &data)).transpose()?;
 
         Ok(entry_opt)
     }
@@ -53,19 +64,28 @@ impl<N, T> Table<N, T> {
         T: Serialize,
         T: DeserializeOwned,
     {
-        let json = self.storage.serialize(entry)?;
-        let json_opt = self.tree.insert(id, json)?;
-        let entry_opt = json_opt.map(|json| self.storage.deserialize(&json)).transpose()?;
+        let data = self.encode(id, entry)?;
+        let data_opt = self.tree.insert(id, data)?;
+        let entry_opt = data_opt.map(|data| self.decode(id, &data)).transpose()?;
 
         Ok(entry_opt)
     }
 
+    /// Insert the entry unless the `id` is already taken: `false` if it is (the table is intact).
+    pub fn insert_new(&self, id: &str, entry: &T) -> Result<bool, AnyError>
+    where
+        T: Serialize,
+    {
+        let data = self.encode(id, entry)?;
+        self.tree.insert_new(id, data)
+    }
+
     pub fn get(&self, key_id: &str) -> Result<Option<T>, AnyError>
     where
         T: DeserializeOwned,
     {
-        let Some(json) = self.tree.get(key_id)? else {return Ok(None)};
-        let entry = self.storage.deserialize(json)?;
+        let Some(data) = self.tree.get(key_id)? else { return Ok(None) };
+        let entry = self.decode(key_id, data)?;
         Ok(Some(entry))
     }
 
@@ -74,9 +94,8 @@ impl<N, T> Table<N, T> {
         T: DeserializeOwned,
     {
         self.tree.scan_prefix(prefix).map(|result| {
-            result.map_err(AnyError::from).and_then(|(key, value)| {
-                let key = String::from_utf8(key.as_ref().to_owned())?;
-                let value: T = self.storage.deserialize(value.as_ref())?;
+            result.and_then(|(key, value)| {
+                let value: T = self.decode(&key, value)?;
 
                 Ok((key, value))
             })
@@ -84,24 +103,28 @@ impl<N, T> Table<N, T> {
     }
 
     pub fn dump(&self) -> Result<(), AnyError> {
-        eprintln!("Dumping: {:?}", std::str::from_utf8(self.tree.name().as_ref()));
-        for k in self.tree.iter() {
+        eprintln!("Dumping: {:?}", self.tree.name());
+        for k in self.tree.scan_prefix("") {
             let (k, v) = k?;
 
-            eprintln!(
-                "{:?} -> {:?}",
-                std::str::from_utf8(k.as_ref()),
-                std::str::from_utf8(v.as_ref()),
-            );
+            eprintln!("{:?} -> {:?}", k, std::str::from_utf8(v.as_ref()));
         }
         eprintln!("Done!");
         Ok(())
     }
-}
 
-fn tree_name_for_curve<N>(curve: CurveSelect) -> String {
-    format!("{}/{}", curve, std::any::type_name::<N>())
-}
-fn tree_name<N>() -> String {
-    std::any::type_name::<N>().to_owned()
+    fn encode(&self, id: &str, entry: &T) -> Result<Vec<u8>, AnyError>
+    where
+        T: Serialize,
+    {
+        self.storage.serialize(self.tree.name(), id, N::VERSION, entry)
+    }
+
+    fn decode(&self, id: &str, data: impl AsRef<[u8]>) -> Result<T, AnyError>
+    where
+        T: DeserializeOwned,
+    {
+        let (_, entry) = self.storage.deserialize(self.tree.name(), id, Some(N::VERSION), data)?;
+        Ok(entry)
+    }
 }
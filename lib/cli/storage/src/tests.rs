@@ -0,0 +1,339 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tempfile::TempDir;
+use zeroize::Zeroizing;
+
+use crate::{AnyError, KdfParams, RawTable, Record, Storage, Table};
+
+/// The same suite, run against each of the backends.
+macro_rules! conformance {
+    ($($backend:ident),*) => {$(
+        mod $backend {
+            #[test]
+            fn insert_get_remove() {
+                super::insert_get_remove(super::Location::$backend())
+            }
+
+            #[test]
+            fn select_by_prefix() {
+                super::select_by_prefix(super::Location::$backend())
+            }
+
+            #[test]
+            fn table_names_and_drop_table() {
+                super::table_names_and_drop_table(super::Location::$backend())
+            }
+
+            #[test]
+            fn records_outlive_the_storage() {
+                super::records_outlive_the_storage(super::Location::$backend())
+            }
+
+            #[test]
+            fn transaction_commit_reject_fail() {
+                super::transaction_commit_reject_fail(super::Location::$backend())
+            }
+
+            #[test]
+            fn encryption() {
+                super::encryption(super::Location::$backend())
+            }
+
+            #[test]
+            fn raw_table_versions() {
+                super::raw_table_versions(super::Location::$backend())
+            }
+
+            #[test]
+            fn odd_names_and_ids() {
+                super::odd_names_and_ids(super::Location::$backend())
+            }
+        }
+    )*};
+}
+
+conformance!(sled, dir, mem);
+
+const PASSPHRASE: &str = "correct horse battery staple";
+const KDF: KdfParams = KdfParams { memory_kib: 64, iterations: 1 };
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Note {
+    text: String,
+}
+
+impl Record for Note {
+    const TABLE: &'static str = "tests/notes";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Tag {
+    text: String,
+}
+
+impl Record for Tag {
+    const TABLE: &'static str = "tests/tags";
+    const VERSION: u32 = 1;
+}
+
+struct Location {
+    location: String,
+    _tmp: Option<TempDir>,
+}
+
+impl Location {
+    fn sled() -> Self {
+        Self::in_tmp_dir("sled")
+    }
+
+    fn dir() -> Self {
+        Self::in_tmp_dir("dir")
+    }
+
+    fn mem() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let location = format!("mem://storage-test-{}", NEXT.fetch_add(1, Ordering::Relaxed));
+        Self { location, _tmp: None }
+    }
+
+    fn in_tmp_dir(scheme: &str) -> Self {
+        let tmp = tempfile::tempdir().expect("Tempdir");
+        let location = format!("{}://{}", scheme, tmp.path().to_str().expect("to-str"));
+        Self { location, _tmp: Some(tmp) }
+    }
+
+    fn open(&self) -> Storage {
+        Storage::open(&self.location).expect("open")
+    }
+
+    fn open_with(&self, passphrase: &str) -> Result<Storage, AnyError> {
+        Storage::open_with(&self.location, || Ok(Zeroizing::new(passphrase.to_owned())))
+    }
+}
+
+fn note(text: &str) -> Note {
+    Note { text: text.to_owned() }
+}
+
+fn tag(text: &str) -> Tag {
+    Tag { text: text.to_owned() }
+}
+
+fn insert_get_remove(location: Location) {
+    let storage = location.open();
+    let notes = Table::<Note>::open(&storage).expect("notes");
+
+    assert_eq!(notes.get("a").expect("get"), None);
+    assert_eq!(notes.insert("a", &note("one")).expect("insert"), None);
+    assert_eq!(notes.get("a").expect("get"), Some(note("one")));
+    assert_eq!(notes.insert("a", &note("two")).expect("insert"), Some(note("one")));
+
+    assert!(!notes.insert_new("a", &note("three")).expect("insert-new"));
+    assert_eq!(notes.get("a").expect("get"), Some(note("two")));
+    assert!(notes.insert_new("b", &note("three")).expect("insert-new"));
+    assert_eq!(notes.get("b").expect("get"), Some(note("three")));
+
+    assert_eq!(notes.remove("a").expect("remove"), Some(note("two")));
+    assert_eq!(notes.remove("a").expect("remove"), None);
+    assert_eq!(notes.get("a").expect("get"), None);
+}
+
+fn select_by_prefix(location: Location) {
+    let storage = location.open();
+    let notes = Table::<Note>::open(&storage).expect("notes");
+    for id in ["b/2", "a/1", "b/1", "c", "b/10"] {
+        notes.insert(id, &note(id)).expect("insert");
+    }
+
+    let select = |prefix| {
+        notes
+            .select(prefix)
+            .map(|item| item.map(|(id, note)| (id, note.text)))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("select")
+    };
+    assert_eq!(
+        select("b/"),
+        [("b/1", "b/1"), ("b/10", "b/10"), ("b/2", "b/2")]
+            .map(|(id, text)| (id.to_owned(), text.to_owned()))
+    );
+    assert_eq!(
+        select("").into_iter().map(|(id, _)| id).collect::<Vec<_>>(),
+        ["a/1", "b/1", "b/10", "b/2", "c"]
+    );
+    assert!(select("d").is_empty());
+}
+
+fn table_names_and_drop_table(location: Location) {
+    let storage = location.open();
+    let notes = Table::<Note>::open(&storage).expect("notes");
+    let tags = Table::<Tag>::open(&storage).expect("tags");
+    notes.insert("a", &note("one")).expect("insert");
+    tags.insert("a", &tag("one")).expect("insert");
+
+    let mut names = storage.table_names().expect("table-names");
+    names.sort();
+    assert_eq!(names, [Note::TABLE, Tag::TABLE]);
+
+    assert!(storage.drop_table(Tag::TABLE).expect("drop"));
+    assert!(!storage.drop_table(Tag::TABLE).expect("drop"));
+    assert_eq!(storage.table_names().expect("table-names"), [Note::TABLE]);
+
+    let tags = Table::<Tag>::open(&storage).expect("tags");
+    assert_eq!(tags.get("a").expect("get"), None);
+    assert_eq!(notes.get("a").expect("get"), Some(note("one")));
+}
+
+fn records_outlive_the_storage(location: Location) {
+    {
+        let storage = location.open();
+        let notes = Table::<Note>::open(&storage).expect("notes");
+        notes.insert("a", &note("one")).expect("insert");
+    }
+    {
+        let storage = location.open();
+        let notes = Table::<Note>::open(&storage).expect("notes");
+        assert_eq!(notes.get("a").expect("get"), Some(note("one")));
+        assert_eq!(storage.table_names().expect("table-names"), [Note::TABLE]);
+    }
+}
+
+fn transaction_commit_reject_fail(location: Location) {
+    let storage = location.open();
+    let notes = Table::<Note>::open(&storage).expect("notes");
+    let tags = Table::<Tag>::open(&storage).expect("tags");
+    notes.insert("a", &note("one")).expect("insert");
+
+    // committed: the writes to both the tables are applied
+    let committed = storage
+        .transaction::<_, String>(&[&notes, &tags], |tx| {
+            let tx_notes = tx.table(&notes)?;
+            let previous = tx_notes.remove("a")?;
+            tx_notes.insert("b", &note("two"))?;
+            tx.table(&tags)?.insert("a", &tag("one"))?;
+            Ok(Ok(previous))
+        })
+        .expect("transaction");
+    assert_eq!(committed, Ok(Some(note("one"))));
+    assert_eq!(notes.get("a").expect("get"), None);
+    assert_eq!(notes.get("b").expect("get"), Some(note("two")));
+    assert_eq!(tags.get("a").expect("get"), Some(tag("one")));
+
+    // rejected: the writes are rolled back, the rejection returned
+    let rejected = storage
+        .transaction::<(), _>(&[&notes, &tags], |tx| {
+            tx.table(&notes)?.insert("c", &note("three"))?;
+            let inserted = tx.table(&tags)?.insert_new("a", &tag("two"))?;
+            assert!(!inserted);
+            Ok(Err("Tag already exists"))
+        })
+        .expect("transaction");
+    assert_eq!(rejected, Err("Tag already exists"));
+    assert_eq!(notes.get("c").expect("get"), None);
+    assert_eq!(tags.get("a").expect("get"), Some(tag("one")));
+
+    // failed: the writes are rolled back, the error returned
+    let failed = storage.transaction::<(), ()>(&[&notes], |tx| {
+        tx.table(&notes)?.insert("c", &note("three"))?;
+        Err("failed".into())
+    });
+    assert_eq!(failed.expect_err("failed").to_string(), "failed");
+    assert_eq!(notes.get("c").expect("get"), None);
+
+    // the tables are to be listed
+    let outside = storage.transaction::<(), ()>(&[&notes], |tx| {
+        tx.table(&tags)?;
+        Ok(Ok(()))
+    });
+    assert!(outside.expect_err("not in the transaction").to_string().contains(Tag::TABLE));
+}
+
+fn encryption(location: Location) {
+    {
+        let mut storage = location.open();
+        Table::<Note>::open(&storage)
+            .expect("notes")
+            .insert("a", &note("before"))
+            .expect("insert");
+        storage.encrypt(PASSPHRASE, KDF).expect("encrypt");
+        assert!(storage.encrypt(PASSPHRASE, KDF).is_err());
+
+        let notes = Table::<Note>::open(&storage).expect("notes");
+        notes.insert("b", &note("after")).expect("insert");
+        assert_eq!(notes.get("a").expect("get"), Some(note("before")));
+
+        // neither is stored in the clear
+        let tree = storage.backend.open_tree(Note::TABLE).expect("tree");
+        for item in tree.scan_prefix("") {
+            let (_, data) = item.expect("record");
+            let data = String::from_utf8_lossy(&data);
+            assert!(!data.contains("before") && !data.contains("after"));
+        }
+    }
+
+    assert!(Storage::open(&location.location).is_err());
+    assert!(location.open_with("wrong").is_err());
+
+    let storage = location.open_with(PASSPHRASE).expect("open");
+    assert!(storage.is_encrypted());
+    let notes = Table::<Note>::open(&storage).expect("notes");
+    assert_eq!(notes.get("a").expect("get"), Some(note("before")));
+    assert_eq!(notes.get("b").expect("get"), Some(note("after")));
+}
+
+fn raw_table_versions(location: Location) {
+    let storage = location.open();
+    let raw = RawTable::open(&storage, Note::TABLE).expect("raw");
+    raw.insert("legacy", 0, &json!({ "text": "zero" })).expect("insert");
+    raw.insert("current", Note::VERSION, &json!({ "text": "one" })).expect("insert");
+    raw.insert("future", 2, &json!({ "words": ["two"] })).expect("insert");
+
+    let records = raw.select("").collect::<Result<Vec<_>, _>>().expect("select");
+    assert_eq!(
+        records,
+        [
+            ("current".to_owned(), 1, json!({ "text": "one" })),
+            ("future".to_owned(), 2, json!({ "words": ["two"] })),
+            ("legacy".to_owned(), 0, json!({ "text": "zero" })),
+        ]
+    );
+    assert!(raw.contains("legacy").expect("contains"));
+
+    let notes = Table::<Note>::open(&storage).expect("notes");
+    assert_eq!(notes.get("current").expect("get"), Some(note("one")));
+    for id in ["legacy", "future"] {
+        let err = notes.get(id).expect_err("of another version");
+        assert!(err.to_string().contains("alice storage migrate"));
+    }
+
+    assert!(raw.remove("legacy").expect("remove"));
+    assert!(!raw.contains("legacy").expect("contains"));
+}
+
+fn odd_names_and_ids(location: Location) {
+    const NAME: &str = ".hidden/a b%c/ü";
+    let ids = ["", ".", "..", ".journal", "%", "%41", "a/../b", "ü 1", "a\0b"];
+    {
+        let storage = location.open();
+        let raw = RawTable::open(&storage, NAME).expect("raw");
+        for (idx, id) in ids.iter().enumerate() {
+            raw.insert(id, 1, &json!(idx)).expect("insert");
+        }
+    }
+
+    let storage = location.open();
+    assert_eq!(storage.table_names().expect("table-names"), [NAME]);
+
+    let raw = RawTable::open(&storage, NAME).expect("raw");
+    let mut expected = ids
+        .iter()
+        .enumerate()
+        .map(|(idx, id)| (id.to_string(), 1, json!(idx)))
+        .collect::<Vec<_>>();
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
+    let records = raw.select("").collect::<Result<Vec<_>, _>>().expect("select");
+    assert_eq!(records, expected);
+}
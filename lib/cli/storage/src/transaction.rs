@@ -0,0 +1,189 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::backend::{Tree, TxTree};
+use crate::{AnyError, RawTable, Record, Storage, Table};
+
+/// A table that can take part in a transaction.
+pub trait AnyTable {
+    fn name(&self) -> String;
+}
+
+/// The tables taking part in a transaction: the records are read and written by the keys (there
+/// are no scans within a transaction).
+pub struct Transaction<'a> {
+    storage: &'a Storage,
+    trees: &'a [Arc<dyn Tree>],
+    views: &'a [&'a dyn TxTree],
+}
+
+pub struct TxTable<'a, N, T = N> {
+    storage: &'a Storage,
+    tree: &'a dyn Tree,
+    view: &'a dyn TxTree,
+
+    _pd: PhantomData<(N, T)>,
+}
+
+pub struct TxRawTable<'a> {
+    storage: &'a Storage,
+    tree: &'a dyn Tree,
+    view: &'a dyn TxTree,
+}
+
+/// The transaction is rejected by the caller: rolled back, and the rejection returned as it is.
+#[derive(Debug)]
+struct Rejected;
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The transaction is rejected")
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+impl Storage {
+    /// Run `f` over the `tables` atomically: either all its writes are applied, or none.
+    ///
+    /// The writes are rolled back if `f` fails, and also if it rejects the transaction (returns
+    /// `Ok(Err(_))`): the rejection is returned to the caller as it is.
+    ///
+    /// `f` is run again if the transaction conflicts with another one, so it should have no other
+    /// effects (nor access the storage otherwise); the errors of the tables in the transaction
+    /// should be returned as they are.
+    pub fn transaction<R, E>(
+        &self,
+        tables: &[&dyn AnyTable],
+        f: impl Fn(&Transaction<'_>) -> Result<Result<R, E>, AnyError>,
+    ) -> Result<Result<R, E>, AnyError> {
+        let trees = tables
+            .iter()
+            .map(|table| self.backend.open_tree(&table.name()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let outcome = RefCell::new(None);
+        let result = self.backend.transaction(&trees, &|views| {
+            let tx = Transaction { storage: self, trees: &trees, views };
+            let ret = f(&tx)?;
+            let rejected = ret.is_err();
+            *outcome.borrow_mut() = Some(ret);
+            if rejected {
+                Err(Rejected.into())
+            } else {
+                Ok(())
+            }
+        });
+        match result {
+            Ok(()) => Ok(outcome.into_inner().expect("the outcome of the transaction")),
+            Err(err) if err.is::<Rejected>() =>
+                Ok(outcome.into_inner().expect("the outcome of the transaction")),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<'a> Transaction<'a> {
+    pub fn table<N: Record, T>(&self, table: &Table<N, T>) -> Result<TxTable<'a, N, T>, AnyError> {
+        let (tree, view) = self.view(table.tree.name())?;
+        Ok(TxTable { storage: self.storage, tree, view, _pd: Default::default() })
+    }
+
+    pub fn raw_table(&self, table: &RawTable) -> Result<TxRawTable<'a>, AnyError> {
+        let (tree, view) = self.view(table.tree.name())?;
+        Ok(TxRawTable { storage: self.storage, tree, view })
+    }
+
+    fn view(&self, name: &str) -> Result<(&'a dyn Tree, &'a dyn TxTree), AnyError> {
+        self.trees
+            .iter()
+            .zip(self.views)
+            .find(|(tree, _)| tree.name() == name)
+            .map(|(tree, view)| (tree.as_ref(), *view))
+            .ok_or_else(|| format!("The table {:?} is not in the transaction", name).into())
+    }
+}
+
+impl<'a, N: Record, T> TxTable<'a, N, T> {
+    pub fn get(&self, id: &str) -> Result<Option<T>, AnyError>
+    where
+        T: DeserializeOwned,
+    {
+        let Some(data) = self.view.get(id)? else { return Ok(None) };
+        let entry = self.decode(id, &data)?;
+        Ok(Some(entry))
+    }
+
+    pub fn insert(&self, id: &str, entry: &T) -> Result<Option<T>, AnyError>
+    where
+        T: Serialize,
+        T: DeserializeOwned,
+    {
+        let data = self.storage.serialize(self.tree.name(), id, N::VERSION, entry)?;
+        let data_opt = self.view.insert(id, data)?;
+        data_opt.map(|data| self.decode(id, &data)).transpose()
+    }
+
+    /// Insert the entry unless the `id` is already taken: `false` if it is (the table is intact).
+    pub fn insert_new(&self, id: &str, entry: &T) -> Result<bool, AnyError>
+    where
+        T: Serialize,
+    {
+        if self.view.get(id)?.is_some() {
+            return Ok(false)
+        }
+        let data = self.storage.serialize(self.tree.name(), id, N::VERSION, entry)?;
+        self.view.insert(id, data)?;
+        Ok(true)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<Option<T>, AnyError>
+    where
+        T: DeserializeOwned,
+    {
+        let data_opt = self.view.remove(id)?;
+        data_opt.map(|data| self.decode(id, &data)).transpose()
+    }
+
+    fn decode(&self, id: &str, data: &[u8]) -> Result<T, AnyError>
+    where
+        T: DeserializeOwned,
+    {
+        let (_, entry) = self.storage.deserialize(self.tree.name(), id, Some(N::VERSION), data)?;
+        Ok(entry)
+    }
+}
+
+impl<'a> TxRawTable<'a> {
+    pub fn contains(&self, id: &str) -> Result<bool, AnyError> {
+        Ok(self.view.get(id)?.is_some())
+    }
+
+    pub fn insert(&self, id: &str, version: u32, record: &Value) -> Result<(), AnyError> {
+        let data = self.storage.serialize(self.tree.name(), id, version, record)?;
+        self.view.insert(id, data)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool, AnyError> {
+        Ok(self.view.remove(id)?.is_some())
+    }
+}
+
+impl<N, T> AnyTable for Table<N, T> {
+    fn name(&self) -> String {
+        self.tree.name().to_owned()
+    }
+}
+
+impl AnyTable for RawTable {
+    fn name(&self) -> String {
+        RawTable::name(self)
+    }
+}
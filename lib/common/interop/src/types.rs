@@ -2,12 +2,17 @@ use crate::curve_select::CurveSelect;
 
 mod impl_point;
 mod impl_scalar;
+mod impl_secret_scalar;
 
 mod impl_kv;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Scalar(CurveSelect, String);
 
+/// A [`Scalar`] that is a secret: wiped on drop, and redacted by `Debug`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretScalar(Scalar);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Point(CurveSelect, String);
 
@@ -0,0 +1,117 @@
+use core::fmt;
+
+use ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::curve_select::CurveSelect;
+use crate::AnyError;
+
+use super::{Scalar, SecretScalar};
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretScalar({}:<redacted>)", self.0 .0)
+    }
+}
+
+impl Zeroize for SecretScalar {
+    fn zeroize(&mut self) {
+        self.0 .1.zeroize();
+    }
+}
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+impl ZeroizeOnDrop for SecretScalar {}
+
+impl Serialize for SecretScalar {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Zeroizing::new(self.0.to_string()).serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for SecretScalar {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = Zeroizing::new(String::deserialize(deserializer)?);
+        s.parse::<Scalar>().map(Self).map_err(<D::Error as serde::de::Error>::custom)
+    }
+}
+
+impl SecretScalar {
+    pub fn curve(&self) -> CurveSelect {
+        self.0 .0
+    }
+
+    pub fn restore<F: PrimeField>(&self, curve: CurveSelect) -> Result<F, AnyError> {
+        if self.0 .0 != curve {
+            return Err("invalid curve".into())
+        }
+
+        let mut repr: F::Repr = Default::default();
+        hex::decode_to_slice(self.0 .1.as_str(), repr.as_mut())?;
+        let out = Option::<F>::from(F::from_repr(repr));
+        repr.as_mut().zeroize();
+        out.ok_or_else(|| "invalid scalar".into())
+    }
+    pub fn from_value<F: PrimeField>(curve: CurveSelect, value: F) -> Self {
+        let mut repr = value.to_repr();
+        let hex = hex::encode(repr.as_ref());
+        repr.as_mut().zeroize();
+        Self(Scalar(curve, hex))
+    }
+
+    /// The secret, as a plain [`Scalar`] (e.g. to hand it to the owner of the key).
+    pub fn expose_secret(&self) -> &Scalar {
+        &self.0
+    }
+}
+
+impl From<Scalar> for SecretScalar {
+    fn from(value: Scalar) -> Self {
+        Self(value)
+    }
+}
+
+#[test]
+fn test_debug_is_redacted() {
+    let secret = SecretScalar::from_value(CurveSelect::Secp256k1, k256::Scalar::from(42u64));
+    let hex = secret.expose_secret().to_string();
+    let (_, hex) = hex.split_once(':').expect("hex");
+
+    let debug = format!("{:?}", secret);
+    assert!(!debug.contains(hex), "{}", debug);
+    assert!(debug.contains("<redacted>"), "{}", debug);
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Holder {
+        y: SecretScalar,
+    }
+    let debug = format!("{:?}", Holder { y: secret.clone() });
+    assert!(!debug.contains(hex), "{}", debug);
+}
+
+#[test]
+fn test_serde_secret() {
+    let v1 = SecretScalar::from_value(CurveSelect::Secp256k1, k256::Scalar::from(42u64));
+    let s = serde_yaml::to_string(&v1).expect("ser");
+    assert_eq!(s.trim(), v1.expose_secret().to_string());
+    let v2: SecretScalar = serde_yaml::from_str(&s).expect("de");
+    assert_eq!(v1, v2);
+    assert_eq!(v2.restore::<k256::Scalar>(CurveSelect::Secp256k1).expect("restore"), 42u64.into());
+}
+
+#[test]
+fn test_zeroize() {
+    let mut secret = SecretScalar::from_value(CurveSelect::Secp256k1, k256::Scalar::from(42u64));
+    secret.zeroize();
+    assert!(secret.expose_secret().to_string().ends_with(':'));
+}
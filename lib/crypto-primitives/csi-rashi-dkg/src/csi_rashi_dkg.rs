@@ -1,46 +1,158 @@
-use feldman_vsss::{CommitmentInitFromScheme, CommitmentVerifyShare};
+use feldman_vsss::CommitmentVerifyShare;
 use ff::{Field, PrimeField};
 use group::Group;
 use rand::RngCore;
 
-use shamir_sss::{SchemeInitFromSecret, SchemeIssueShare};
-
 #[derive(Debug)]
 #[cfg_attr(feature = "std-error", derive(thiserror::Error))]
-pub enum Error {}
+pub enum Error {
+    /// The share dealt by a qualified dealer does not match its commitment: it should have been
+    /// complained about, and replaced with the share revealed in the response.
+    #[cfg_attr(feature = "std-error", error("Invalid share dealt by the dealer #{0}"))]
+    InvalidShare(usize),
+    /// The proof of knowledge of the secret of a qualified dealer is invalid.
+    #[cfg_attr(feature = "std-error", error("Invalid proof of the dealer #{0}"))]
+    InvalidProof(usize),
+    #[cfg_attr(feature = "std-error", error("No qualified dealers"))]
+    NoQualifiedDealers,
+    /// The threshold (the length of the commitment) is zero, or exceeds the count of the parties.
+    #[cfg_attr(feature = "std-error", error("Invalid threshold: {0}"))]
+    InvalidThreshold(usize),
+    /// The `x` of a party is zero: its share would be the secret.
+    #[cfg_attr(feature = "std-error", error("The `x` of the party #{0} is zero"))]
+    ZeroShamirX(usize),
+}
 
-pub fn deal<F, G, const MAX_THRESHOLD: usize>(
+/// Deal the `secret` with a polynomial of `vss_commitment.len()` coefficients (the threshold):
+/// the commitment is written into `vss_commitment`, the shares into `shamir_ys`. Produces the proof
+/// `(s, r)` of the knowledge of the `secret`.
+///
+/// The challenge of the proof is produced by `produce_challenge(y, r)` from the committed
+/// `y = g * secret` and `r`; it is expected to be bound to the identity of the dealer and to the
+/// session, so that the proof can't be replayed by another dealer.
+pub fn deal<F, G>(
     mut rng: impl RngCore,
-    threshold: usize,
     secret: &F,
     shamir_xs: &[F],
     shamir_ys: &mut [F],
-) -> Result<[G; MAX_THRESHOLD], Error>
+    vss_commitment: &mut [G],
+    produce_challenge: impl FnOnce(&G, &G) -> F,
+) -> Result<(F, G), Error>
 where
     F: PrimeField,
     G: Group<Scalar = F>,
 {
     assert_eq!(shamir_xs.len(), shamir_ys.len());
-    assert!(threshold <= MAX_THRESHOLD);
 
-    let mut ss = [F::ZERO; MAX_THRESHOLD];
-    (&mut ss[0..threshold]).init_from_secret(secret, &mut rng);
+    let threshold = vss_commitment.len();
+    if threshold == 0 || threshold > shamir_xs.len() {
+        return Err(Error::InvalidThreshold(threshold))
+    }
+    if let Some(party) = shamir_xs.iter().position(|x| bool::from(x.is_zero())) {
+        return Err(Error::ZeroShamirX(party))
+    }
 
-    let mut sc = [G::identity(); MAX_THRESHOLD];
-    sc.init_from_scheme(&ss);
+    // the coefficients are drawn from the highest one down to the secret, and added into the
+    // shares as they are (by the Horner's rule): the polynomial is not kept anywhere
+    let g = G::generator();
+    shamir_ys.fill(F::ZERO);
+    for (k, c) in vss_commitment.iter_mut().enumerate().rev() {
+        let a = if k == 0 { *secret } else { F::random(&mut rng) };
+        *c = g * a;
+        shamir_xs.iter().zip(shamir_ys.iter_mut()).for_each(|(x, y)| *y = *y * x + a);
+    }
 
-    shamir_xs.iter().copied().zip(shamir_ys.iter_mut()).for_each(|(x, y)| {
-        *y = ss.issue_share(x);
-    });
+    let k = F::random(&mut rng);
+    let c = produce_challenge(&vss_commitment[0], &(g * k));
+    let proof = schnorr_proof::prove(g, secret, &k, c);
 
-    Ok(sc)
+    Ok(proof)
 }
 
-pub fn aggregate<F, G>(
+/// Same as [`deal`], with the commitment of `threshold` points allocated.
+#[cfg(feature = "alloc")]
+pub fn deal_vec<F, G>(
+    rng: impl RngCore,
+    threshold: usize,
+    secret: &F,
+    shamir_xs: &[F],
+    shamir_ys: &mut [F],
+    produce_challenge: impl FnOnce(&G, &G) -> F,
+) -> Result<(alloc::vec::Vec<G>, (F, G)), Error>
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let mut vss_commitment = alloc::vec![G::identity(); threshold];
+    let proof = deal(rng, secret, shamir_xs, shamir_ys, &mut vss_commitment, produce_challenge)?;
+    Ok((vss_commitment, proof))
+}
+
+/// The check of the proof `(s, r)` of the knowledge of the secret committed in `vss_commitment`.
+///
+/// An empty `vss_commitment` commits to no secret: the proof is rejected.
+pub fn verify_proof<F, G>(
+    vss_commitment: &[G],
+    proof: &(F, G),
+    produce_challenge: impl FnOnce(&G, &G) -> F,
+) -> bool
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let Some(&y) = vss_commitment.first() else { return false };
+    let (s, r) = *proof;
+    let c = produce_challenge(&y, &r);
+    schnorr_proof::verify(G::generator(), y, c, s, r)
+}
+
+/// The complaint phase: `complaints[i]` is set if the share dealt by the `i`-th dealer does not
+/// match its commitment.
+pub fn complain<F, G>(
     vss_commitments: &[impl AsRef<[G]>],
     shamir_x: &F,
     shamir_ys: &[F],
     complaints: &mut [bool],
+) where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let parties_count = vss_commitments.len();
+    assert_eq!(parties_count, shamir_ys.len());
+    assert_eq!(parties_count, complaints.len());
+
+    for ((complaint, vss_commitment), shamir_y) in
+        complaints.iter_mut().zip(vss_commitments).zip(shamir_ys)
+    {
+        *complaint = !vss_commitment.as_ref().verify_share(shamir_x, shamir_y);
+    }
+}
+
+/// The check of the share revealed by an accused dealer in response to the complaint of the party
+/// `shamir_x`: the dealer is disqualified if it fails (or if the dealer does not respond at all).
+pub fn verify_response<F, G>(vss_commitment: &[G], shamir_x: &F, shamir_y: &F) -> bool
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    vss_commitment.verify_share(shamir_x, shamir_y)
+}
+
+/// The key-share and the public key produced by the dealers that are not `disqualified`.
+///
+/// The shares dealt by the qualified dealers are expected to be valid, with the disputed ones
+/// replaced by those revealed in the responses; the parties agreeing on the set of the
+/// disqualified dealers end up with the shares of the same key.
+///
+/// The proofs of the qualified dealers are checked with the challenges produced by
+/// `produce_challenge(dealer, y, r)`.
+pub fn aggregate<F, G>(
+    vss_commitments: &[impl AsRef<[G]>],
+    proofs: &[(F, G)],
+    shamir_x: &F,
+    shamir_ys: &[F],
+    disqualified: &[bool],
+    produce_challenge: impl Fn(usize, &G, &G) -> F,
 ) -> Result<(F, G), Error>
 where
     F: Field,
@@ -48,27 +160,36 @@ where
 {
     let parties_count = vss_commitments.len();
     assert_eq!(parties_count, shamir_ys.len());
-    assert_eq!(parties_count, complaints.len());
+    assert_eq!(parties_count, proofs.len());
+    assert_eq!(parties_count, disqualified.len());
 
-    let key_share = complaints
-        .iter_mut()
-        .zip(vss_commitments)
+    let mut key_share = F::ZERO;
+    let mut public_key = G::identity();
+    let mut qualified_count = 0;
+    for (dealer, vss_commitment, shamir_y) in vss_commitments
+        .iter()
         .zip(shamir_ys)
-        .map(|((complaint, vss_commitment), shamir_y)| {
-            (complaint, vss_commitment.as_ref(), shamir_y)
+        .zip(disqualified)
+        .enumerate()
+        .filter(|(_, (_, disqualified))| !**disqualified)
+        .map(|(dealer, ((vss_commitment, shamir_y), _))| {
+            (dealer, vss_commitment.as_ref(), shamir_y)
         })
-        .map(|(complaint, vss_commitment, shamir_y)| {
-            *complaint = !vss_commitment.verify_share(shamir_x, shamir_y);
-            *shamir_y
-        })
-        .reduce(core::ops::Add::add)
-        .expect("zero participants?");
+    {
+        if !verify_proof(vss_commitment, &proofs[dealer], |y, r| produce_challenge(dealer, y, r)) {
+            return Err(Error::InvalidProof(dealer))
+        }
+        if !vss_commitment.verify_share(shamir_x, shamir_y) {
+            return Err(Error::InvalidShare(dealer))
+        }
+        key_share += shamir_y;
+        public_key += vss_commitment[0];
+        qualified_count += 1;
+    }
 
-    let public_key = vss_commitments
-        .iter()
-        .map(|c| c.as_ref()[0])
-        .reduce(core::ops::Add::add)
-        .expect("zero participants?");
+    if qualified_count == 0 {
+        return Err(Error::NoQualifiedDealers)
+    }
 
     let out = (key_share, public_key);
     Ok(out)
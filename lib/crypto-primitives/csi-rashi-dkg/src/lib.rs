@@ -1,7 +1,12 @@
 #![no_std]
 
 mod csi_rashi_dkg;
-pub use csi_rashi_dkg::{aggregate, deal, Error};
+#[cfg(feature = "alloc")]
+pub use csi_rashi_dkg::deal_vec;
+pub use csi_rashi_dkg::{aggregate, complain, deal, verify_proof, verify_response, Error};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 #[cfg(feature = "std-error")]
 extern crate std;
@@ -12,6 +12,17 @@ pub enum Error {
     InvalidShard,
 }
 
+/// The check of the aggregation a shard has failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Complaint {
+    /// `y_i` does not match the verification share `g * s_i` of the signer.
+    VerificationShareMismatch,
+    /// `r_i` does not match the commitments `(D_i, E_i)` and the binding factor.
+    CommitmentMismatch,
+    /// `z_i` does not satisfy `g * z_i == r_i + (g * s_i) * (lambda_i * c)`.
+    InvalidResponse,
+}
+
 pub fn preprocess<F, G>(mut rng: impl RngCore, nonces: &mut [(F, F)], commitments: &mut [(G, G)])
 where
     F: PrimeField,
@@ -41,6 +52,68 @@ where
     F: PrimeField,
     G: Group<Scalar = F> + GroupEncoding,
     H: Digest,
+{
+    sign_normalized::<F, G, H>(
+        public_key,
+        participant_id,
+        shamir_y,
+        shamir_xs,
+        nonce,
+        commitments,
+        |_| false,
+        produce_challenge,
+    )
+}
+
+/// Same as [`sign`], but the public key and the group commitment are normalised: whichever of them
+/// `is_odd` holds for is negated, along with the key-share or the nonce respectively (e.g. the
+/// "even-Y" points of BIP-340).
+#[allow(clippy::too_many_arguments)]
+pub fn sign_normalized<F, G, H>(
+    public_key: &G,
+    participant_id: usize,
+    shamir_y: &F,
+    shamir_xs: &[F],
+    nonce: &(F, F),
+    commitments: &[(G, G)],
+    is_odd: impl Fn(&G) -> bool,
+    produce_challenge: impl Fn(&G, &G) -> F,
+) -> (G, G, F)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: Digest,
+{
+    sign_with(
+        public_key,
+        participant_id,
+        shamir_y,
+        shamir_xs,
+        nonce,
+        commitments,
+        |_, i| rho::<F, G, H>(&shamir_xs[i], commitments),
+        is_odd,
+        produce_challenge,
+    )
+}
+
+/// The signing round with the binding factor of each participant produced by `binding_factor`
+/// from the (normalised) public key and the participant's index.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sign_with<F, G>(
+    public_key: &G,
+    participant_id: usize,
+    shamir_y: &F,
+    shamir_xs: &[F],
+    nonce: &(F, F),
+    commitments: &[(G, G)],
+    binding_factor: impl Fn(&G, usize) -> F,
+    is_odd: impl Fn(&G) -> bool,
+    produce_challenge: impl Fn(&G, &G) -> F,
+) -> (G, G, F)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
 {
     assert!(participant_id < shamir_xs.len());
     assert_eq!(shamir_xs.len(), commitments.len());
@@ -48,25 +121,24 @@ where
     let g = G::generator();
 
     let i = participant_id;
-    let y = *public_key;
-
-    let s = shamir_y;
+    let (y, s) =
+        if is_odd(public_key) { (-*public_key, -*shamir_y) } else { (*public_key, *shamir_y) };
     let y_i = g * s;
 
     let d = nonce.0;
     let e = nonce.1;
-    let rho_i = rho::<F, G, H>(&shamir_xs[i], commitments);
+    let rho_i = binding_factor(&y, i);
     let k = d + e * rho_i;
-    let r_i = g * k;
 
     let r = (0..shamir_xs.len())
         .map(|i| {
-            let shamir_x = &shamir_xs[i];
-            let rho = rho::<F, G, H>(shamir_x, commitments);
+            let rho = binding_factor(&y, i);
             let (cd, ce) = commitments[i];
             cd + ce * rho
         })
         .sum::<G>();
+    let (r, k) = if is_odd(&r) { (-r, -k) } else { (r, k) };
+    let r_i = g * k;
 
     let c = produce_challenge(&y, &r);
 
@@ -78,50 +150,215 @@ where
 }
 
 pub fn aggregate<F, G, H>(
+    public_key: &G,
+    verification_shares: &[G],
     shards: &[(G, G, F)],
     shamir_xs: &[F],
     commitments: &[(G, G)],
-    complaints: &mut [bool],
+    complaints: &mut [Option<Complaint>],
     produce_challenge: impl Fn(&G, &G) -> F,
 ) -> Result<(G, G, F), Error>
 where
     F: PrimeField,
     G: Group<Scalar = F> + GroupEncoding,
     H: Digest,
+{
+    aggregate_normalized::<F, G, H>(
+        public_key,
+        verification_shares,
+        shards,
+        shamir_xs,
+        commitments,
+        complaints,
+        |_| false,
+        produce_challenge,
+    )
+}
+
+/// Aggregates the shards produced by [`sign_normalized`].
+#[allow(clippy::too_many_arguments)]
+pub fn aggregate_normalized<F, G, H>(
+    public_key: &G,
+    verification_shares: &[G],
+    shards: &[(G, G, F)],
+    shamir_xs: &[F],
+    commitments: &[(G, G)],
+    complaints: &mut [Option<Complaint>],
+    is_odd: impl Fn(&G) -> bool,
+    produce_challenge: impl Fn(&G, &G) -> F,
+) -> Result<(G, G, F), Error>
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: Digest,
+{
+    aggregate_with(
+        public_key,
+        verification_shares,
+        shards,
+        shamir_xs,
+        commitments,
+        complaints,
+        |_, i| rho::<F, G, H>(&shamir_xs[i], commitments),
+        is_odd,
+        produce_challenge,
+    )
+}
+
+/// Aggregates the shards produced by [`sign_with`].
+///
+/// The `public_key` and the `verification_shares` (`g * s_i` of each signer, in the order of
+/// `shamir_xs`) are trusted: the shards are checked against them rather than against the `y_i`
+/// reported by the signers, so that a signer can't shift the blame by misreporting its `y_i`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn aggregate_with<F, G>(
+    public_key: &G,
+    verification_shares: &[G],
+    shards: &[(G, G, F)],
+    shamir_xs: &[F],
+    commitments: &[(G, G)],
+    complaints: &mut [Option<Complaint>],
+    binding_factor: impl Fn(&G, usize) -> F,
+    is_odd: impl Fn(&G) -> bool,
+    produce_challenge: impl Fn(&G, &G) -> F,
+) -> Result<(G, G, F), Error>
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
 {
     assert_eq!(shards.len(), shamir_xs.len());
     assert_eq!(shards.len(), complaints.len());
     assert_eq!(shards.len(), commitments.len());
+    assert_eq!(shards.len(), verification_shares.len());
 
-    let g = G::generator();
+    let session = Session::new(public_key, commitments, &binding_factor, is_odd, produce_challenge);
 
-    let y = shards
-        .iter()
-        .enumerate()
-        .map(|(i, &(y_i, _, _))| y_i * shamir_xs.lagrange_coefficient_at(i, F::ZERO))
-        .sum::<G>();
-    let r = shards.iter().map(|(_, r_i, _)| r_i).sum::<G>();
-    let z = shards.iter().map(|(_, _, z_i)| z_i).sum::<F>();
+    for (i, complaint) in complaints.iter_mut().enumerate() {
+        let rho_i = binding_factor(&session.y, i);
+        *complaint = session.check_shard(
+            i,
+            &shards[i],
+            &verification_shares[i],
+            shamir_xs,
+            commitments,
+            rho_i,
+        );
+    }
 
-    let c = produce_challenge(&y, &r);
+    if complaints.iter().any(Option::is_some) {
+        Err(Error::InvalidShard)
+    } else {
+        let z = shards.iter().map(|(_, _, z_i)| z_i).sum::<F>();
+        Ok((session.y, session.r, z))
+    }
+}
 
-    for i in 0..shamir_xs.len() {
-        let (cd, ce) = commitments[i];
-        let lambda_i = shamir_xs.lagrange_coefficient_at(i, F::ZERO);
-        let rho_i = rho::<F, G, H>(&shamir_xs[i], commitments);
+/// Checks the shard of the participant `participant_id` produced by [`sign_normalized`] against
+/// its (trusted) `verification_share`, before the shards of the other participants are available.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_shard_normalized<F, G, H>(
+    public_key: &G,
+    verification_share: &G,
+    participant_id: usize,
+    shard: &(G, G, F),
+    shamir_xs: &[F],
+    commitments: &[(G, G)],
+    is_odd: impl Fn(&G) -> bool,
+    produce_challenge: impl Fn(&G, &G) -> F,
+) -> Result<(), Complaint>
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: Digest,
+{
+    assert!(participant_id < shamir_xs.len());
+    assert_eq!(shamir_xs.len(), commitments.len());
+
+    let binding_factor = |_: &G, i: usize| rho::<F, G, H>(&shamir_xs[i], commitments);
+    let session = Session::new(public_key, commitments, &binding_factor, is_odd, produce_challenge);
+    let rho_i = binding_factor(&session.y, participant_id);
+
+    match session.check_shard(
+        participant_id,
+        shard,
+        verification_share,
+        shamir_xs,
+        commitments,
+        rho_i,
+    ) {
+        Some(complaint) => Err(complaint),
+        None => Ok(()),
+    }
+}
+
+/// What the shards of a signing session are checked against: the normalised public key `y`, the
+/// normalised group commitment `r` and the challenge `c`.
+struct Session<F, G> {
+    y: G,
+    is_key_negated: bool,
+    r: G,
+    is_negated: bool,
+    c: F,
+}
+
+impl<F, G> Session<F, G>
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    fn new(
+        public_key: &G,
+        commitments: &[(G, G)],
+        binding_factor: impl Fn(&G, usize) -> F,
+        is_odd: impl Fn(&G) -> bool,
+        produce_challenge: impl Fn(&G, &G) -> F,
+    ) -> Self {
+        // the key-shares are negated by the signers along with the public key
+        let is_key_negated = is_odd(public_key);
+        let y = if is_key_negated { -*public_key } else { *public_key };
 
-        let (y_i, r_i, z_i) = shards[i];
+        // the group commitment is derived from the commitments rather than from the shards, so
+        // that an invalid `r_i` does not affect the verification of the other shards
+        let r = commitments
+            .iter()
+            .enumerate()
+            .map(|(i, &(cd, ce))| cd + ce * binding_factor(&y, i))
+            .sum::<G>();
+        let is_negated = is_odd(&r);
+        let r = if is_negated { -r } else { r };
 
-        let is_valid_r = r_i == cd + ce * rho_i;
-        let is_valid_z = (g * z_i) == (r_i + y_i * (lambda_i * c));
+        let c = produce_challenge(&y, &r);
 
-        complaints[i] = !(is_valid_r && is_valid_z);
+        Self { y, is_key_negated, r, is_negated, c }
     }
 
-    if complaints.iter().copied().any(core::convert::identity) {
-        Err(Error::InvalidShard)
-    } else {
-        Ok((y, r, z))
+    fn check_shard(
+        &self,
+        i: usize,
+        &(y_i, r_i, z_i): &(G, G, F),
+        verification_share: &G,
+        shamir_xs: &[F],
+        commitments: &[(G, G)],
+        rho_i: F,
+    ) -> Option<Complaint> {
+        let g = G::generator();
+        let (cd, ce) = commitments[i];
+        let lambda_i = shamir_xs.lagrange_coefficient_at(i, F::ZERO);
+
+        let y_i_expected =
+            if self.is_key_negated { -*verification_share } else { *verification_share };
+        let r_i_expected = cd + ce * rho_i;
+        let r_i_expected = if self.is_negated { -r_i_expected } else { r_i_expected };
+
+        if y_i != y_i_expected {
+            Some(Complaint::VerificationShareMismatch)
+        } else if r_i != r_i_expected {
+            Some(Complaint::CommitmentMismatch)
+        } else if g * z_i != r_i + y_i_expected * (lambda_i * self.c) {
+            Some(Complaint::InvalidResponse)
+        } else {
+            None
+        }
     }
 }
 
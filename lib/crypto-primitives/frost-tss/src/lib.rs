@@ -1,7 +1,12 @@
 #![no_std]
 
 mod frost_tss;
-pub use frost_tss::{aggregate, preprocess, sign, Error};
+pub use frost_tss::{
+    aggregate, aggregate_normalized, preprocess, sign, sign_normalized, verify_shard_normalized,
+    Complaint, Error,
+};
+
+pub mod rfc9591;
 
 #[cfg(feature = "std-error")]
 extern crate std;
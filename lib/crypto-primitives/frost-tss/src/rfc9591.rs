@@ -0,0 +1,275 @@
+//! FROST as specified in RFC 9591.
+//!
+//! The identifiers of the participants are their `shamir_x` values; the commitment list is
+//! encoded in the order in which the participants are given, so the callers are expected to sort
+//! them by identifier in ascending order (e.g. with [`utils::cmp_scalars`]).
+
+use digest::{Digest, Output};
+use ff::PrimeField;
+use group::{Group, GroupEncoding};
+use rand::RngCore;
+use sha2::{Sha256, Sha512};
+
+use crate::frost_tss::{aggregate_with, sign_with};
+use crate::{Complaint, Error};
+
+const TAG_RHO: &[u8] = b"rho";
+const TAG_CHAL: &[u8] = b"chal";
+const TAG_NONCE: &[u8] = b"nonce";
+const TAG_MSG: &[u8] = b"msg";
+const TAG_COM: &[u8] = b"com";
+
+pub trait Ciphersuite {
+    /// The `contextString` prefixed to the inputs of the hash functions.
+    const CONTEXT_STRING: &'static [u8];
+
+    /// The hash function behind `H4` and `H5`.
+    type Hash: Digest;
+
+    /// `H1`, `H2` and `H3` for the tags "rho", "chal" and "nonce" respectively: the concatenation
+    /// of the `parts` hashed to a scalar.
+    fn hash_to_scalar<F: PrimeField>(tag: &[u8], parts: &[&[u8]]) -> F;
+}
+
+/// FROST(Ed25519, SHA-512)
+pub struct Ed25519Sha512;
+
+/// FROST(ristretto255, SHA-512)
+pub struct Ristretto255Sha512;
+
+/// FROST(secp256k1, SHA-256)
+pub struct Secp256k1Sha256;
+
+impl Ciphersuite for Ed25519Sha512 {
+    const CONTEXT_STRING: &'static [u8] = b"FROST-ED25519-SHA512-v1";
+
+    type Hash = Sha512;
+
+    fn hash_to_scalar<F: PrimeField>(tag: &[u8], parts: &[&[u8]]) -> F {
+        // `H2` is the challenge of RFC 8032, hence no domain separation
+        let mut hasher = if tag == TAG_CHAL { Sha512::new() } else { tagged::<Self>(tag) };
+        parts.iter().for_each(|part| hasher.update(part));
+        utils::reduce_le(hasher.finalize().as_ref())
+    }
+}
+
+impl Ciphersuite for Ristretto255Sha512 {
+    const CONTEXT_STRING: &'static [u8] = b"FROST-RISTRETTO255-SHA512-v1";
+
+    type Hash = Sha512;
+
+    fn hash_to_scalar<F: PrimeField>(tag: &[u8], parts: &[&[u8]]) -> F {
+        let mut hasher = tagged::<Self>(tag);
+        parts.iter().for_each(|part| hasher.update(part));
+        utils::reduce_le(hasher.finalize().as_ref())
+    }
+}
+
+impl Ciphersuite for Secp256k1Sha256 {
+    const CONTEXT_STRING: &'static [u8] = b"FROST-secp256k1-SHA256-v1";
+
+    type Hash = Sha256;
+
+    /// `hash_to_field` of RFC 9380 with `expand_message_xmd` (SHA-256) and `L = 48`.
+    fn hash_to_scalar<F: PrimeField>(tag: &[u8], parts: &[&[u8]]) -> F {
+        const L: usize = 48;
+
+        let dst_len = [(Self::CONTEXT_STRING.len() + tag.len()) as u8];
+        let dst_prime = [Self::CONTEXT_STRING, tag, &dst_len];
+
+        let mut hasher = Sha256::new().chain_update([0; 64]);
+        parts.iter().for_each(|part| hasher.update(part));
+        hasher.update((L as u16).to_be_bytes());
+        hasher.update([0]);
+        dst_prime.iter().for_each(|part| hasher.update(part));
+        let b_0 = hasher.finalize();
+
+        // b_1 = H(b_0 || 1 || DST_prime), b_i = H(strxor(b_0, b_(i-1)) || i || DST_prime)
+        let mut b_i = Output::<Sha256>::default();
+        let mut uniform_bytes = [0u8; 2 * 32];
+        for (i, chunk) in uniform_bytes.chunks_mut(32).enumerate() {
+            b_i.iter_mut().zip(b_0.iter()).for_each(|(b, b_0)| *b ^= b_0);
+            let mut hasher = Sha256::new().chain_update(b_i).chain_update([i as u8 + 1]);
+            dst_prime.iter().for_each(|part| hasher.update(part));
+            b_i = hasher.finalize();
+            chunk.copy_from_slice(&b_i);
+        }
+
+        utils::reduce_be(&uniform_bytes[..L])
+    }
+}
+
+/// `nonce_generate`: the nonce derived from the `random_bytes` and the participant's key-share.
+pub fn nonce_generate<C, F>(random_bytes: &[u8; 32], shamir_y: &F) -> F
+where
+    C: Ciphersuite,
+    F: PrimeField,
+{
+    C::hash_to_scalar(TAG_NONCE, &[random_bytes, shamir_y.to_repr().as_ref()])
+}
+
+/// Same as [`crate::preprocess`], but the nonces are produced by [`nonce_generate`].
+pub fn preprocess<C, F, G>(
+    mut rng: impl RngCore,
+    shamir_y: &F,
+    nonces: &mut [(F, F)],
+    commitments: &mut [(G, G)],
+) where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    assert_eq!(nonces.len(), commitments.len());
+
+    let g = G::generator();
+    let mut random_bytes = [0u8; 32];
+    for ((d, e), (cd, ce)) in nonces.iter_mut().zip(commitments.iter_mut()) {
+        rng.fill_bytes(&mut random_bytes);
+        *d = nonce_generate::<C, F>(&random_bytes, shamir_y);
+        rng.fill_bytes(&mut random_bytes);
+        *e = nonce_generate::<C, F>(&random_bytes, shamir_y);
+        *cd = g * *d;
+        *ce = g * *e;
+    }
+}
+
+/// `compute_challenge`
+pub fn challenge<C, F, G>(public_key: &G, r: &G, message: &[u8]) -> F
+where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    C::hash_to_scalar(TAG_CHAL, &[r.to_bytes().as_ref(), public_key.to_bytes().as_ref(), message])
+}
+
+/// The binding factor of the participant `participant_id` (`compute_binding_factors`).
+pub fn binding_factor<C, F, G>(
+    public_key: &G,
+    participant_id: usize,
+    shamir_xs: &[F],
+    commitments: &[(G, G)],
+    message: &[u8],
+) -> F
+where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: GroupEncoding,
+{
+    BindingFactors::<C>::new(shamir_xs, commitments, message).get(
+        public_key,
+        participant_id,
+        shamir_xs,
+    )
+}
+
+/// `sign`: the signature share `(y_i, r_i, z_i)` of the participant `participant_id`, where `y_i`
+/// is its public key-share and `r_i` its commitment share.
+pub fn sign<C, F, G>(
+    public_key: &G,
+    participant_id: usize,
+    shamir_y: &F,
+    shamir_xs: &[F],
+    nonce: &(F, F),
+    commitments: &[(G, G)],
+    message: &[u8],
+) -> (G, G, F)
+where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let binding_factors = BindingFactors::<C>::new(shamir_xs, commitments, message);
+
+    sign_with(
+        public_key,
+        participant_id,
+        shamir_y,
+        shamir_xs,
+        nonce,
+        commitments,
+        |y, i| binding_factors.get(y, i, shamir_xs),
+        |_| false,
+        |y, r| challenge::<C, F, G>(y, r, message),
+    )
+}
+
+/// `aggregate` along with the verification of each of the signature shares produced by [`sign`]
+/// against the `verification_shares` of the signers (`verify_signature_share`).
+pub fn aggregate<C, F, G>(
+    public_key: &G,
+    verification_shares: &[G],
+    shards: &[(G, G, F)],
+    shamir_xs: &[F],
+    commitments: &[(G, G)],
+    complaints: &mut [Option<Complaint>],
+    message: &[u8],
+) -> Result<(G, G, F), Error>
+where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let binding_factors = BindingFactors::<C>::new(shamir_xs, commitments, message);
+
+    aggregate_with(
+        public_key,
+        verification_shares,
+        shards,
+        shamir_xs,
+        commitments,
+        complaints,
+        |y, i| binding_factors.get(y, i, shamir_xs),
+        |_| false,
+        |y, r| challenge::<C, F, G>(y, r, message),
+    )
+}
+
+/// `H4(msg)` and `H5(encode_group_commitment_list(commitment_list))`: the parts of the binding
+/// factor input that are the same for every participant.
+struct BindingFactors<C: Ciphersuite> {
+    msg_hash: Output<C::Hash>,
+    encoded_commitment_hash: Output<C::Hash>,
+}
+
+impl<C: Ciphersuite> BindingFactors<C> {
+    fn new<F, G>(shamir_xs: &[F], commitments: &[(G, G)], message: &[u8]) -> Self
+    where
+        F: PrimeField,
+        G: GroupEncoding,
+    {
+        assert_eq!(shamir_xs.len(), commitments.len());
+
+        let msg_hash = tagged::<C>(TAG_MSG).chain_update(message).finalize();
+
+        let mut hasher = tagged::<C>(TAG_COM);
+        for (shamir_x, (cd, ce)) in shamir_xs.iter().zip(commitments) {
+            hasher.update(shamir_x.to_repr());
+            hasher.update(cd.to_bytes());
+            hasher.update(ce.to_bytes());
+        }
+        let encoded_commitment_hash = hasher.finalize();
+
+        Self { msg_hash, encoded_commitment_hash }
+    }
+
+    fn get<F, G>(&self, public_key: &G, participant_id: usize, shamir_xs: &[F]) -> F
+    where
+        F: PrimeField,
+        G: GroupEncoding,
+    {
+        C::hash_to_scalar(
+            TAG_RHO,
+            &[
+                public_key.to_bytes().as_ref(),
+                self.msg_hash.as_ref(),
+                self.encoded_commitment_hash.as_ref(),
+                shamir_xs[participant_id].to_repr().as_ref(),
+            ],
+        )
+    }
+}
+
+fn tagged<C: Ciphersuite>(tag: &[u8]) -> C::Hash {
+    C::Hash::new().chain_update(C::CONTEXT_STRING).chain_update(tag)
+}
@@ -0,0 +1,12 @@
+#![no_std]
+
+mod pedersen_dkg;
+pub use pedersen_dkg::{aggregate, complain, deal, key_share, reconstruct, verify_share, Error};
+#[cfg(feature = "alloc")]
+pub use pedersen_dkg::{deal_vec, reconstruct_vec};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std-error")]
+extern crate std;
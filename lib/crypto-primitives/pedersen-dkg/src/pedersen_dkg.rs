@@ -0,0 +1,313 @@
+use feldman_vsss::CommitmentVerifyShare;
+use ff::{Field, PrimeField};
+use group::Group;
+use pedersen_vsss::CommitmentVerifySharePair;
+use rand::RngCore;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std-error", derive(thiserror::Error))]
+pub enum Error {
+    /// The share dealt by a qualified dealer does not match its Pedersen commitment: it should
+    /// have been complained about, and replaced with the share revealed in the response.
+    #[cfg_attr(feature = "std-error", error("Invalid share dealt by the dealer #{0}"))]
+    InvalidShare(usize),
+    /// The share dealt by a qualified dealer does not match its Feldman commitment: the secret of
+    /// the dealer should be reconstructed from the shares revealed by the parties.
+    #[cfg_attr(feature = "std-error", error("Invalid Feldman commitment of the dealer #{0}"))]
+    InvalidFeldmanCommitment(usize),
+    #[cfg_attr(feature = "std-error", error("Not enough valid shares to reconstruct"))]
+    NotEnoughShares,
+    #[cfg_attr(feature = "std-error", error("No qualified dealers"))]
+    NoQualifiedDealers,
+    /// The threshold (the length of the commitments) is zero, or exceeds the count of the
+    /// parties, or the commitments are of different lengths.
+    #[cfg_attr(feature = "std-error", error("Invalid threshold: {0}"))]
+    InvalidThreshold(usize),
+    /// The `x` of a party is zero: its share would be the secret.
+    #[cfg_attr(feature = "std-error", error("The `x` of the party #{0} is zero"))]
+    ZeroShamirX(usize),
+}
+
+/// Deal the `secret` with a polynomial of `threshold` coefficients: the shares are pairs
+/// `(y, y_blind)` of the secret polynomial and of the blinding one, written into `shamir_ys`.
+///
+/// Produces the Pedersen commitment `g * a_k + h * b_k`, to be published right away, and the
+/// Feldman commitment `g * a_k`, to be published only once the set of the qualified dealers is
+/// fixed: both are written into the buffers of `threshold` points.
+///
+/// Nobody is supposed to know the discrete logarithm of `h`, e.g. as derived by
+/// [`pedersen_vsss::second_generator`].
+pub fn deal<F, G>(
+    mut rng: impl RngCore,
+    secret: &F,
+    h: &G,
+    shamir_xs: &[F],
+    shamir_ys: &mut [(F, F)],
+    pedersen_commitment: &mut [G],
+    feldman_commitment: &mut [G],
+) -> Result<(), Error>
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    assert_eq!(shamir_xs.len(), shamir_ys.len());
+
+    let threshold = feldman_commitment.len();
+    if threshold == 0 || threshold > shamir_xs.len() || pedersen_commitment.len() != threshold {
+        return Err(Error::InvalidThreshold(threshold))
+    }
+    if let Some(party) = shamir_xs.iter().position(|x| bool::from(x.is_zero())) {
+        return Err(Error::ZeroShamirX(party))
+    }
+
+    // the coefficients are drawn from the highest ones down to the secrets, and added into the
+    // shares as they are (by the Horner's rule): the polynomials are not kept anywhere
+    let g = G::generator();
+    let blind = F::random(&mut rng);
+    shamir_ys.fill((F::ZERO, F::ZERO));
+    for (k, (pc, fc)) in pedersen_commitment.iter_mut().zip(feldman_commitment).enumerate().rev() {
+        let (a, b) =
+            if k == 0 { (*secret, blind) } else { (F::random(&mut rng), F::random(&mut rng)) };
+        *fc = g * a;
+        *pc = *fc + *h * b;
+        shamir_xs.iter().zip(shamir_ys.iter_mut()).for_each(|(x, (y, y_blind))| {
+            *y = *y * x + a;
+            *y_blind = *y_blind * x + b;
+        });
+    }
+
+    Ok(())
+}
+
+/// Same as [`deal`], with the commitments of `threshold` points allocated: `(pedersen, feldman)`.
+#[cfg(feature = "alloc")]
+pub fn deal_vec<F, G>(
+    rng: impl RngCore,
+    threshold: usize,
+    secret: &F,
+    h: &G,
+    shamir_xs: &[F],
+    shamir_ys: &mut [(F, F)],
+) -> Result<(alloc::vec::Vec<G>, alloc::vec::Vec<G>), Error>
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let mut pedersen_commitment = alloc::vec![G::identity(); threshold];
+    let mut feldman_commitment = alloc::vec![G::identity(); threshold];
+    deal(rng, secret, h, shamir_xs, shamir_ys, &mut pedersen_commitment, &mut feldman_commitment)?;
+    Ok((pedersen_commitment, feldman_commitment))
+}
+
+/// The check of the share `(y, y_blind)` of the party `shamir_x` against the Pedersen commitment
+/// of the dealer.
+pub fn verify_share<F, G>(h: &G, pedersen_commitment: &[G], shamir_x: &F, shamir_y: &(F, F)) -> bool
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let (y, y_blind) = shamir_y;
+    pedersen_commitment.verify_share_pair(h, shamir_x, y, y_blind)
+}
+
+/// The complaint phase: `complaints[i]` is set if the share dealt by the `i`-th dealer does not
+/// match its Pedersen commitment.
+pub fn complain<F, G>(
+    h: &G,
+    pedersen_commitments: &[impl AsRef<[G]>],
+    shamir_x: &F,
+    shamir_ys: &[(F, F)],
+    complaints: &mut [bool],
+) where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let parties_count = pedersen_commitments.len();
+    assert_eq!(parties_count, shamir_ys.len());
+    assert_eq!(parties_count, complaints.len());
+
+    for ((complaint, pedersen_commitment), shamir_y) in
+        complaints.iter_mut().zip(pedersen_commitments).zip(shamir_ys)
+    {
+        *complaint = !verify_share(h, pedersen_commitment.as_ref(), shamir_x, shamir_y);
+    }
+}
+
+/// The key-share produced by the dealers that are not `disqualified`.
+///
+/// The shares dealt by the qualified dealers are expected to be valid, with the disputed ones
+/// replaced by those revealed in the responses. The key-share is final at this point: the public
+/// key is only computed by [`aggregate`], once the Feldman commitments are revealed.
+pub fn key_share<F, G>(
+    h: &G,
+    pedersen_commitments: &[impl AsRef<[G]>],
+    shamir_x: &F,
+    shamir_ys: &[(F, F)],
+    disqualified: &[bool],
+) -> Result<F, Error>
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let parties_count = pedersen_commitments.len();
+    assert_eq!(parties_count, shamir_ys.len());
+    assert_eq!(parties_count, disqualified.len());
+
+    let mut key_share = F::ZERO;
+    let mut qualified_count = 0;
+    for (dealer, (pedersen_commitment, shamir_y)) in pedersen_commitments
+        .iter()
+        .zip(shamir_ys)
+        .enumerate()
+        .filter(|(dealer, _)| !disqualified[*dealer])
+    {
+        if !verify_share(h, pedersen_commitment.as_ref(), shamir_x, shamir_y) {
+            return Err(Error::InvalidShare(dealer))
+        }
+        key_share += shamir_y.0;
+        qualified_count += 1;
+    }
+
+    if qualified_count == 0 {
+        return Err(Error::NoQualifiedDealers)
+    }
+
+    Ok(key_share)
+}
+
+/// The key-share and the public key produced by the dealers that are not `disqualified`, checked
+/// against the Feldman commitments revealed by the qualified dealers.
+///
+/// A Feldman commitment of other than `threshold` points fails the check too: a longer one may
+/// still match the shares at every `x`, while moving the public key. The Feldman commitment of a
+/// dealer that fails the check is to be replaced with the one [`reconstruct`]ed from the shares
+/// revealed by the parties.
+pub fn aggregate<F, G>(
+    threshold: usize,
+    feldman_commitments: &[impl AsRef<[G]>],
+    shamir_x: &F,
+    shamir_ys: &[(F, F)],
+    disqualified: &[bool],
+) -> Result<(F, G), Error>
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let parties_count = feldman_commitments.len();
+    assert_eq!(parties_count, shamir_ys.len());
+    assert_eq!(parties_count, disqualified.len());
+
+    if threshold == 0 {
+        return Err(Error::InvalidThreshold(threshold))
+    }
+
+    let mut key_share = F::ZERO;
+    let mut public_key = G::identity();
+    let mut qualified_count = 0;
+    for (dealer, (feldman_commitment, (shamir_y, _))) in feldman_commitments
+        .iter()
+        .zip(shamir_ys)
+        .enumerate()
+        .filter(|(dealer, _)| !disqualified[*dealer])
+    {
+        let feldman_commitment = feldman_commitment.as_ref();
+        if feldman_commitment.len() != threshold ||
+            !feldman_commitment.verify_share(shamir_x, shamir_y)
+        {
+            return Err(Error::InvalidFeldmanCommitment(dealer))
+        }
+        key_share += shamir_y;
+        public_key += feldman_commitment[0];
+        qualified_count += 1;
+    }
+
+    if qualified_count == 0 {
+        return Err(Error::NoQualifiedDealers)
+    }
+
+    let out = (key_share, public_key);
+    Ok(out)
+}
+
+/// Reconstruct the Feldman commitment of a dealer, of `feldman_commitment.len()` points (the
+/// threshold), from the shares `(shamir_x, (y, y_blind))` revealed by the parties.
+///
+/// The shares that do not match the Pedersen commitment of the dealer are ignored, as well as
+/// the repeated ones; `threshold` valid shares are required, and are moved to the front of
+/// `shares`.
+pub fn reconstruct<F, G>(
+    h: &G,
+    pedersen_commitment: &[G],
+    shares: &mut [(F, (F, F))],
+    feldman_commitment: &mut [G],
+) -> Result<(), Error>
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let threshold = feldman_commitment.len();
+    if threshold == 0 {
+        return Err(Error::InvalidThreshold(threshold))
+    }
+
+    let mut count = 0;
+    for i in 0..shares.len() {
+        if count == threshold {
+            break
+        }
+        let (x, y) = &shares[i];
+        if shares[..count].iter().any(|(valid_x, _)| valid_x == x) ||
+            !verify_share(h, pedersen_commitment, x, y)
+        {
+            continue
+        }
+        shares.swap(count, i);
+        count += 1;
+    }
+    if count < threshold {
+        return Err(Error::NotEnoughShares)
+    }
+    let xs = || shares[..threshold].iter().map(|(x, _)| *x);
+
+    // the polynomial interpolated over the valid shares, in the exponent: first its Newton's
+    // divided differences...
+    let g = G::generator();
+    feldman_commitment
+        .iter_mut()
+        .zip(&shares[..threshold])
+        .for_each(|(c, (_, (y, _)))| *c = g * y);
+    for level in 1..threshold {
+        for (i, x) in xs().enumerate().skip(level).rev() {
+            let denominator = x - shares[i - level].0;
+            let factor = denominator.invert().expect("the `x` are distinct");
+            feldman_commitment[i] = (feldman_commitment[i] - feldman_commitment[i - 1]) * factor;
+        }
+    }
+    // ...then its coefficients
+    for (k, x) in xs().enumerate().take(threshold - 1).rev() {
+        for i in k..threshold - 1 {
+            feldman_commitment[i] -= feldman_commitment[i + 1] * x;
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as [`reconstruct`], with the Feldman commitment of `threshold` points allocated (and
+/// `shares` left intact).
+#[cfg(feature = "alloc")]
+pub fn reconstruct_vec<F, G>(
+    h: &G,
+    pedersen_commitment: &[G],
+    threshold: usize,
+    shares: &[(F, (F, F))],
+) -> Result<alloc::vec::Vec<G>, Error>
+where
+    F: Field,
+    G: Group<Scalar = F>,
+{
+    let mut shares = shares.to_vec();
+    let mut feldman_commitment = alloc::vec![G::identity(); threshold];
+    reconstruct(h, pedersen_commitment, &mut shares, &mut feldman_commitment)?;
+    Ok(feldman_commitment)
+}
@@ -0,0 +1,6 @@
+#![no_std]
+
+mod pedersen_vsss;
+pub use crate::pedersen_vsss::{CommitmentInitFromSchemes, CommitmentVerifySharePair};
+
+pub mod second_generator;
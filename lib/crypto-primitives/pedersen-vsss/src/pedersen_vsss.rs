@@ -0,0 +1,63 @@
+use ff::Field;
+use group::Group;
+
+pub trait CommitmentInitFromSchemes<G, F>: AsMut<[G]>
+where
+    G: Group<Scalar = F>,
+    F: Field,
+{
+    /// Commit to the `scheme` blinded with the `blinding_scheme`: `g * a_k + h * b_k`.
+    fn init_from_schemes(&mut self, h: &G, scheme: &[F], blinding_scheme: &[F]) {
+        let g = G::generator();
+
+        let commitments = self.as_mut();
+
+        assert!(scheme.len() <= commitments.len());
+        assert_eq!(scheme.len(), blinding_scheme.len());
+
+        commitments.iter_mut().for_each(|c| *c = G::identity());
+        commitments
+            .iter_mut()
+            .zip(scheme)
+            .zip(blinding_scheme)
+            .for_each(|((c, a), b)| *c = g * a + *h * b);
+    }
+}
+
+pub trait CommitmentVerifySharePair<G, F>: AsRef<[G]>
+where
+    G: Group<Scalar = F>,
+    F: Field,
+{
+    /// Check the share `y` of the scheme along with the share `y_blind` of the blinding scheme.
+    fn verify_share_pair(&self, h: &G, x: &F, y: &F, y_blind: &F) -> bool {
+        let g = G::generator();
+        let cs = self.as_ref();
+
+        let (c, _) = cs.iter().copied().fold((G::identity(), F::ONE), |(v, x_to_ith), c| {
+            let term = c * x_to_ith;
+            (v + term, x_to_ith * x)
+        });
+
+        let actual = c;
+        let expected = g * y + *h * y_blind;
+
+        expected == actual
+    }
+}
+
+impl<T, G, F> CommitmentInitFromSchemes<G, F> for T
+where
+    T: AsMut<[G]>,
+    G: Group<Scalar = F>,
+    F: Field,
+{
+}
+
+impl<T, G, F> CommitmentVerifySharePair<G, F> for T
+where
+    T: AsRef<[G]>,
+    G: Group<Scalar = F>,
+    F: Field,
+{
+}
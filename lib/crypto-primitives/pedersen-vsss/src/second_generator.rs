@@ -0,0 +1,83 @@
+//! The second generator `h` of the Pedersen commitments.
+//!
+//! The commitments are binding only as long as nobody knows the discrete logarithm of `h` with
+//! respect to `g`: `h` is hashed to the curve from a public per-curve domain separation tag, so
+//! it is nobody's choice. The encodings of `h` are constants, re-derived by the tests:
+//! - secp256k1: `secp256k1_XMD:SHA-256_SSWU_RO_` of RFC 9380, of the empty message;
+//! - ristretto255: `ristretto255_XMD:SHA-512_R255MAP_RO_` of RFC 9380, of the empty message;
+//! - edwards25519: `edwards25519_XMD:SHA-512_ELL2_RO_` of RFC 9380, of the empty message.
+//!
+//! The generators of secp256k1 are behind the `k256` feature, those of edwards25519 and
+//! ristretto255 behind the `curve25519` feature.
+
+use group::{Group, GroupEncoding};
+
+pub trait SecondGenerator {
+    /// The group `h` is in.
+    type Group: Group + GroupEncoding;
+
+    /// The domain separation tag `h` is hashed from.
+    const DST: &'static [u8];
+
+    /// The canonical encoding of `h`.
+    const ENCODED: &'static [u8];
+
+    /// `h`, decoded from [`Self::ENCODED`].
+    fn second_generator() -> Self::Group {
+        let mut repr = <Self::Group as GroupEncoding>::Repr::default();
+        repr.as_mut().copy_from_slice(Self::ENCODED);
+        Option::<Self::Group>::from(Self::Group::from_bytes(&repr))
+            .expect("`h` does not decode to a point")
+    }
+}
+
+/// secp256k1: `h` is in the compressed SEC1 encoding.
+#[cfg(feature = "k256")]
+pub struct Secp256k1;
+
+/// edwards25519: `h` is in the compressed encoding.
+#[cfg(feature = "curve25519")]
+pub struct Ed25519;
+
+/// ristretto255: `h` is in the canonical encoding.
+#[cfg(feature = "curve25519")]
+pub struct Ristretto255;
+
+#[cfg(feature = "k256")]
+impl SecondGenerator for Secp256k1 {
+    type Group = k256::ProjectivePoint;
+
+    const DST: &'static [u8] =
+        b"RABBIT-HOLE-PEDERSEN-VSSS-H-V01-CS01-with-secp256k1_XMD:SHA-256_SSWU_RO_";
+    const ENCODED: &'static [u8] = &[
+        0x02, 0x59, 0xe8, 0x65, 0x2c, 0x45, 0x17, 0x96, 0x03, 0x09, 0x8e, 0xb2, 0x20, 0x7a, 0x46,
+        0xab, 0x77, 0x7d, 0xf7, 0x16, 0x59, 0xb4, 0x83, 0x71, 0x7a, 0xd9, 0xb7, 0x44, 0x4d, 0xed,
+        0xa1, 0x34, 0x7c,
+    ];
+}
+
+#[cfg(feature = "curve25519")]
+impl SecondGenerator for Ed25519 {
+    type Group = curve25519::edwards::EdwardsPoint;
+
+    const DST: &'static [u8] =
+        b"RABBIT-HOLE-PEDERSEN-VSSS-H-V01-CS01-with-edwards25519_XMD:SHA-512_ELL2_RO_";
+    const ENCODED: &'static [u8] = &[
+        0x95, 0xf1, 0xe0, 0x2d, 0xef, 0x26, 0xd3, 0x16, 0x23, 0xe0, 0xfb, 0x4d, 0x71, 0x2f, 0xac,
+        0x5c, 0x75, 0xe0, 0xe1, 0x11, 0xd9, 0xc6, 0x7f, 0x3e, 0x32, 0x00, 0x66, 0xa8, 0x89, 0x78,
+        0x80, 0x0f,
+    ];
+}
+
+#[cfg(feature = "curve25519")]
+impl SecondGenerator for Ristretto255 {
+    type Group = curve25519::ristretto::RistrettoPoint;
+
+    const DST: &'static [u8] =
+        b"RABBIT-HOLE-PEDERSEN-VSSS-H-V01-CS01-with-ristretto255_XMD:SHA-512_R255MAP_RO_";
+    const ENCODED: &'static [u8] = &[
+        0x6e, 0x68, 0x92, 0x88, 0x7b, 0x26, 0x87, 0x33, 0x8c, 0x06, 0x76, 0x1e, 0x25, 0x09, 0xf9,
+        0xca, 0xcd, 0x0a, 0xf2, 0xd8, 0xc2, 0x23, 0xf8, 0x4a, 0x06, 0x5e, 0x55, 0xc5, 0xab, 0xe7,
+        0x76, 0x11,
+    ];
+}
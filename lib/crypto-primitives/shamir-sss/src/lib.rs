@@ -1,5 +1,12 @@
 #![no_std]
 
+mod repair;
 mod shamir_sss;
 
-pub use crate::shamir_sss::{LagrangeCoefficientAt, SchemeInitFromSecret, SchemeIssueShare};
+pub use crate::repair::{repair_deltas, repair_share, repair_sigma, Error as RepairError};
+pub use crate::shamir_sss::{
+    reconstruct_at, LagrangeCoefficientAt, SchemeInitFromSecret, SchemeIssueShare,
+};
+
+#[cfg(feature = "std-error")]
+extern crate std;
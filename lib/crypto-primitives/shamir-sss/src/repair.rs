@@ -0,0 +1,92 @@
+//! The repair of a lost share: the helpers, `threshold` holders of the other shares, recreate the
+//! share of the lost `x`, while none of them learns anything about it.
+//!
+//! Each helper splits its share weighted by the Lagrange coefficient at the lost `x` into random
+//! deltas, one for each helper (itself included). Each helper adds up the deltas it received into
+//! a sigma, sent to the holder of the lost share, which adds up the sigmas.
+
+use ff::{Field, PrimeField};
+use rand::RngCore;
+
+use crate::LagrangeCoefficientAt;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "std-error", derive(thiserror::Error))]
+pub enum Error {
+    #[cfg_attr(feature = "std-error", error("No helpers"))]
+    NoHelpers,
+    /// There should be a delta for each helper.
+    #[cfg_attr(
+        feature = "std-error",
+        error("Deltas count mismatch [helpers: {helpers}; deltas: {deltas}]")
+    )]
+    DeltasCountMismatch { helpers: usize, deltas: usize },
+    #[cfg_attr(feature = "std-error", error("The helper #{0} is out of range"))]
+    HelperOutOfRange(usize),
+    /// The `x` of the helpers should be distinct: the Lagrange coefficients are undefined
+    /// otherwise.
+    #[cfg_attr(feature = "std-error", error("The helper #{0} is repeated"))]
+    DuplicateHelper(usize),
+    /// The holder of the lost share can't help to repair it.
+    #[cfg_attr(feature = "std-error", error("The lost `x` is among the helpers"))]
+    LostIsHelper,
+}
+
+/// The deltas of the helper `helpers_xs[this]`, holding the share `y`: `deltas[j]` is sent to the
+/// helper `helpers_xs[j]`.
+pub fn repair_deltas<F>(
+    mut rng: impl RngCore,
+    helpers_xs: &[F],
+    this: usize,
+    y: &F,
+    lost_x: F,
+    deltas: &mut [F],
+) -> Result<(), Error>
+where
+    F: PrimeField,
+{
+    if helpers_xs.len() != deltas.len() {
+        return Err(Error::DeltasCountMismatch { helpers: helpers_xs.len(), deltas: deltas.len() })
+    }
+    if this >= helpers_xs.len() {
+        return Err(Error::HelperOutOfRange(this))
+    }
+    if helpers_xs.contains(&lost_x) {
+        return Err(Error::LostIsHelper)
+    }
+    if let Some(repeated) =
+        (1..helpers_xs.len()).find(|&j| helpers_xs[..j].contains(&helpers_xs[j]))
+    {
+        return Err(Error::DuplicateHelper(repeated))
+    }
+
+    let weighted = *y * helpers_xs.lagrange_coefficient_at(this, lost_x);
+
+    let (last, masks) = deltas.split_last_mut().ok_or(Error::NoHelpers)?;
+    masks.iter_mut().for_each(|d| *d = F::random(&mut rng));
+    *last = weighted - masks.iter().sum::<F>();
+
+    Ok(())
+}
+
+/// The sigma of a helper: the sum of the deltas received from all the helpers.
+pub fn repair_sigma<F>(deltas: &[F]) -> Result<F, Error>
+where
+    F: Field,
+{
+    if deltas.is_empty() {
+        return Err(Error::NoHelpers)
+    }
+    Ok(deltas.iter().sum())
+}
+
+/// The repaired share: the sum of the sigmas of all the helpers.
+pub fn repair_share<F>(sigmas: &[F]) -> Result<F, Error>
+where
+    F: Field,
+{
+    if sigmas.is_empty() {
+        return Err(Error::NoHelpers)
+    }
+    Ok(sigmas.iter().sum())
+}
@@ -51,6 +51,21 @@ where
     }
 }
 
+/// The value at `x` of the polynomial interpolated over the shares `(xs[i], ys[i])`: the secret,
+/// if `x` is zero and there are at least `threshold` shares.
+///
+/// `None` if the counts of `xs` and `ys` differ, or if an `x` is repeated.
+pub fn reconstruct_at<F>(xs: &[F], ys: &[F], x: F) -> Option<F>
+where
+    F: Field,
+{
+    if xs.len() != ys.len() || (1..xs.len()).any(|j| xs[..j].contains(&xs[j])) {
+        return None
+    }
+
+    Some(ys.iter().enumerate().map(|(i, y)| *y * xs.lagrange_coefficient_at(i, x)).sum())
+}
+
 impl<T, F> SchemeInitFromSecret<F> for T
 where
     T: AsMut<[F]>,
@@ -2,3 +2,9 @@
 
 mod bytes_to_scalar;
 pub use bytes_to_scalar::bytes_to_scalar;
+
+mod reduce;
+pub use reduce::{reduce_be, reduce_le};
+
+mod ordering;
+pub use ordering::cmp_scalars;
@@ -0,0 +1,18 @@
+use core::cmp::Ordering;
+
+use ff::PrimeField;
+
+/// Compares the canonical integer values of the field elements (regardless of the endianness of
+/// their representation).
+pub fn cmp_scalars<F>(a: &F, b: &F) -> Ordering
+where
+    F: PrimeField,
+{
+    let is_le = F::ONE.to_repr().as_ref().first() == Some(&1);
+    let (a, b) = (a.to_repr(), b.to_repr());
+    if is_le {
+        a.as_ref().iter().rev().cmp(b.as_ref().iter().rev())
+    } else {
+        a.as_ref().cmp(b.as_ref())
+    }
+}
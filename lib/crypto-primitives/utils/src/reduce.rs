@@ -0,0 +1,19 @@
+use ff::PrimeField;
+
+/// The little-endian integer `bytes`, reduced modulo the order of the field.
+pub fn reduce_le<F>(bytes: &[u8]) -> F
+where
+    F: PrimeField,
+{
+    let base = F::from(256);
+    bytes.iter().rev().fold(F::ZERO, |acc, b| acc * base + F::from(*b as u64))
+}
+
+/// The big-endian integer `bytes`, reduced modulo the order of the field.
+pub fn reduce_be<F>(bytes: &[u8]) -> F
+where
+    F: PrimeField,
+{
+    let base = F::from(256);
+    bytes.iter().fold(F::ZERO, |acc, b| acc * base + F::from(*b as u64))
+}
@@ -0,0 +1,328 @@
+//! The test vectors of RFC 9591 (appendix E).
+
+use ff::{Field, PrimeField};
+use group::{Group, GroupEncoding};
+
+use ::frost_tss::rfc9591::*;
+use ::frost_tss::Complaint;
+
+struct Vector {
+    group_secret_key: &'static str,
+    group_public_key: &'static str,
+    message: &'static str,
+    share_polynomial_coefficient: &'static str,
+    participants: [Participant; 2],
+    sig: &'static str,
+}
+
+struct Participant {
+    identifier: u64,
+    participant_share: &'static str,
+    hiding_nonce_randomness: &'static str,
+    binding_nonce_randomness: &'static str,
+    hiding_nonce: &'static str,
+    binding_nonce: &'static str,
+    hiding_nonce_commitment: &'static str,
+    binding_nonce_commitment: &'static str,
+    binding_factor: &'static str,
+    sig_share: &'static str,
+}
+
+const ED25519_SHA512: Vector = Vector {
+    group_secret_key: "7b1c33d3f5291d85de664833beb1ad469f7fb6025a0ec78b3a790c6e13a98304",
+    group_public_key: "15d21ccd7ee42959562fc8aa63224c8851fb3ec85a3faf66040d380fb9738673",
+    message: "74657374",
+    share_polynomial_coefficient:
+        "178199860edd8c62f5212ee91eff1295d0d670ab4ed4506866bae57e7030b204",
+    participants: [
+        Participant {
+            identifier: 1,
+            participant_share: "929dcc590407aae7d388761cddb0c0db6f5627aea8e217f4a033f2ec83d93509",
+            hiding_nonce_randomness:
+                "0fd2e39e111cdc266f6c0f4d0fd45c947761f1f5d3cb583dfcb9bbaf8d4c9fec",
+            binding_nonce_randomness:
+                "69cd85f631d5f7f2721ed5e40519b1366f340a87c2f6856363dbdcda348a7501",
+            hiding_nonce: "812d6104142944d5a55924de6d49940956206909f2acaeedecda2b726e630407",
+            binding_nonce: "b1110165fc2334149750b28dd813a39244f315cff14d4e89e6142f262ed83301",
+            hiding_nonce_commitment:
+                "b5aa8ab305882a6fc69cbee9327e5a45e54c08af61ae77cb8207be3d2ce13de3",
+            binding_nonce_commitment:
+                "67e98ab55aa310c3120418e5050c9cf76cf387cb20ac9e4b6fdb6f82a469f932",
+            binding_factor: "f2cb9d7dd9beff688da6fcc83fa89046b3479417f47f55600b106760eb3b5603",
+            sig_share: "001719ab5a53ee1a12095cd088fd149702c0720ce5fd2f29dbecf24b7281b603",
+        },
+        Participant {
+            identifier: 3,
+            participant_share: "d3cb090a075eb154e82fdb4b3cb507f110040905468bb9c46da8bdea643a9a02",
+            hiding_nonce_randomness:
+                "86d64a260059e495d0fb4fcc17ea3da7452391baa494d4b00321098ed2a0062f",
+            binding_nonce_randomness:
+                "13e6b25afb2eba51716a9a7d44130c0dbae0004a9ef8d7b5550c8a0e07c61775",
+            hiding_nonce: "c256de65476204095ebdc01bd11dc10e57b36bc96284595b8215222374f99c0e",
+            binding_nonce: "243d71944d929063bc51205714ae3c2218bd3451d0214dfb5aeec2a90c35180d",
+            hiding_nonce_commitment:
+                "cfbdb165bd8aad6eb79deb8d287bcc0ab6658ae57fdcc98ed12c0669e90aec91",
+            binding_nonce_commitment:
+                "7487bc41a6e712eea2f2af24681b58b1cf1da278ea11fe4e8b78398965f13552",
+            binding_factor: "b087686bf35a13f3dc78e780a34b0fe8a77fef1b9938c563f5573d71d8d7890f",
+            sig_share: "bd86125de990acc5e1f13781d8e32c03a9bbd4c53539bbc106058bfd14326007",
+        },
+    ],
+    sig: "36282629c383bb820a88b71cae937d41f2f2adfcc3d02e55507e2fb9e2dd3cbe\
+          bd9d2b0844e49ae0f3fa935161e1419aab7b47d21a37ebeae1f17d4987b3160b",
+};
+
+const RISTRETTO255_SHA512: Vector = Vector {
+    group_secret_key: "1b25a55e463cfd15cf14a5d3acc3d15053f08da49c8afcf3ab265f2ebc4f970b",
+    group_public_key: "e2a62f39eede11269e3bd5a7d97554f5ca384f9f6d3dd9c3c0d05083c7254f57",
+    message: "74657374",
+    share_polynomial_coefficient:
+        "410f8b744b19325891d73736923525a4f596c805d060dfb9c98009d34e3fec02",
+    participants: [
+        Participant {
+            identifier: 1,
+            participant_share: "5c3430d391552f6e60ecdc093ff9f6f4488756aa6cebdbad75a768010b8f830e",
+            hiding_nonce_randomness:
+                "f595a133b4d95c6e1f79887220c8b275ce6277e7f68a6640e1e7140f9be2fb5c",
+            binding_nonce_randomness:
+                "34dd1001360e3513cb37bebfabe7be4a32c5bb91ba19fbd4360d039111f0fbdc",
+            hiding_nonce: "214f2cabb86ed71427ea7ad4283b0fae26b6746c801ce824b83ceb2b99278c03",
+            binding_nonce: "c9b8f5e16770d15603f744f8694c44e335e8faef00dad182b8d7a34a62552f0c",
+            hiding_nonce_commitment:
+                "965def4d0958398391fc06d8c2d72932608b1e6255226de4fb8d972dac15fd57",
+            binding_nonce_commitment:
+                "ec5170920660820007ae9e1d363936659ef622f99879898db86e5bf1d5bf2a14",
+            binding_factor: "8967fd70fa06a58e5912603317fa94c77626395a695a0e4e4efc4476662eba0c",
+            sig_share: "9285f875923ce7e0c491a592e9ea1865ec1b823ead4854b48c8a46287749ee09",
+        },
+        Participant {
+            identifier: 3,
+            participant_share: "f17e505f0e2581c6acfe54d3846a622834b5e7b50cad9a2109a97ba7a80d5c04",
+            hiding_nonce_randomness:
+                "daa0cf42a32617786d390e0c7edfbf2efbd428037069357b5173ae61d6dd5d5e",
+            binding_nonce_randomness:
+                "b4387e72b2e4108ce4168931cc2c7fcce5f345a5297368952c18b5fc8473f050",
+            hiding_nonce: "3f7927872b0f9051dd98dd73eb2b91494173bbe0feb65a3e7e58d3e2318fa40f",
+            binding_nonce: "ffd79445fb8030f0a3ddd3861aa4b42b618759282bfe24f1f9304c7009728305",
+            hiding_nonce_commitment:
+                "480e06e3de182bf83489c45d7441879932fd7b434a26af41455756264fbd5d6e",
+            binding_nonce_commitment:
+                "3064746dfd3c1862ef58fc68c706da287dd925066865ceacc816b3a28c7b363b",
+            binding_factor: "f2c1bb7c33a10511158c2f1766a4a5fadf9f86f2a92692ed333128277cc31006",
+            sig_share: "7cb211fe0e3d59d25db6e36b3fb32344794139602a7b24f1ae0dc4e26ad7b908",
+        },
+    ],
+    sig: "fc45655fbc66bbffad654ea4ce5fdae253a49a64ace25d9adb62010dd9fb2555\
+          2164141787162e5b4cab915b4aa45d94655dbb9ed7c378a53b980a0be220a802",
+};
+
+const SECP256K1_SHA256: Vector = Vector {
+    group_secret_key: "0d004150d27c3bf2a42f312683d35fac7394b1e9e318249c1bfe7f0795a83114",
+    group_public_key: "02f37c34b66ced1fb51c34a90bdae006901f10625cc06c4f64663b0eae87d87b4f",
+    message: "74657374",
+    share_polynomial_coefficient:
+        "fbf85eadae3058ea14f19148bb72b45e4399c0b16028acaf0395c9b03c823579",
+    participants: [
+        Participant {
+            identifier: 1,
+            participant_share: "08f89ffe80ac94dcb920c26f3f46140bfc7f95b493f8310f5fc1ea2b01f4254c",
+            hiding_nonce_randomness:
+                "7ea5ed09af19f6ff21040c07ec2d2adbd35b759da5a401d4c99dd26b82391cb2",
+            binding_nonce_randomness:
+                "47acab018f116020c10cb9b9abdc7ac10aae1b48ca6e36dc15acb6ec9be5cdc5",
+            hiding_nonce: "841d3a6450d7580b4da83c8e618414d0f024391f2aeb511d7579224420aa81f0",
+            binding_nonce: "8d2624f532af631377f33cf44b5ac5f849067cae2eacb88680a31e77c79b5a80",
+            hiding_nonce_commitment:
+                "03c699af97d26bb4d3f05232ec5e1938c12f1e6ae97643c8f8f11c9820303f1904",
+            binding_nonce_commitment:
+                "02fa2aaccd51b948c9dc1a325d77226e98a5a3fe65fe9ba213761a60123040a45e",
+            binding_factor: "f5ce0e9e2148aac12c939d40299290c4008c20abd48cb403f0947110b10c3343",
+            sig_share: "75d3a3f832089f46946072a3a89a867267791b9adf64e1b238e8cc13f46356bc",
+        },
+        Participant {
+            identifier: 3,
+            participant_share: "00e95d59dd0d46b0e303e500b62b7ccb0e555d49f5b849f5e748c071da8c0dbc",
+            hiding_nonce_randomness:
+                "e9165dad654fc20a9e31ca6f32ac032ec327b551a50e8ac5cf25f5c4c9e20757",
+            binding_nonce_randomness:
+                "e9059a232598a0fba0e495a687580e624ab425337c3221246fb2c716905bc9e7",
+            hiding_nonce: "178d69aebbb44938a066f9efb311fb0b5dd4d188f9e7de944f287df0051afacd",
+            binding_nonce: "f1a1dea4332ee55b306be3f7fb87d497deef90065246f8097e8ffd9c6e125b15",
+            hiding_nonce_commitment:
+                "02cebd240c7c91934678046270b3af25586e9e0524fc41dda5c088b7b6daf40bd2",
+            binding_nonce_commitment:
+                "0327c802791e8a1be5ee40be4b26150b135f60174c22626bd6e7543cec489a3060",
+            binding_factor: "ab1e1c9c022c69d60e3333b8e91d946215a70349c1e4f0fe1f0b0c88206d3ee8",
+            sig_share: "2a7369a897f5f873c11865c1db3e1b69e6ec16e53edcb8aec69e6fc7a4980f70",
+        },
+    ],
+    sig: "022978685876e098d416066987db7873b87a6c5120d964864baadb83580262e01d\
+          a0470da0c9fe97ba5578d86583d8a1dc4e6532801e419a60ff873bdb98fb662c",
+};
+
+fn vector_impl<C, F, G>(vector: &Vector)
+where
+    C: Ciphersuite,
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let g = G::generator();
+    let message = hex::decode(vector.message).expect("hex");
+
+    let group_secret_key = scalar::<F>(vector.group_secret_key);
+    let coefficient = scalar::<F>(vector.share_polynomial_coefficient);
+    let public_key = g * group_secret_key;
+    assert_eq!(hex::encode(public_key.to_bytes()), vector.group_public_key);
+
+    let shamir_xs = vector.participants.each_ref().map(|p| F::from(p.identifier));
+    let shamir_ys = shamir_xs.map(|x| group_secret_key + coefficient * x);
+    let nonces: [_; 2] = core::array::from_fn(|i| {
+        let p = &vector.participants[i];
+        let randomness = |s| hex::decode(s).expect("hex").try_into().expect("32 bytes");
+        (
+            nonce_generate::<C, F>(&randomness(p.hiding_nonce_randomness), &shamir_ys[i]),
+            nonce_generate::<C, F>(&randomness(p.binding_nonce_randomness), &shamir_ys[i]),
+        )
+    });
+    let commitments = nonces.map(|(d, e)| (g * d, g * e));
+
+    for (i, p) in vector.participants.iter().enumerate() {
+        assert_eq!(hex::encode(shamir_ys[i].to_repr()), p.participant_share);
+        assert_eq!(hex::encode(nonces[i].0.to_repr()), p.hiding_nonce);
+        assert_eq!(hex::encode(nonces[i].1.to_repr()), p.binding_nonce);
+        assert_eq!(hex::encode(commitments[i].0.to_bytes()), p.hiding_nonce_commitment);
+        assert_eq!(hex::encode(commitments[i].1.to_bytes()), p.binding_nonce_commitment);
+
+        let rho = binding_factor::<C, F, G>(&public_key, i, &shamir_xs, &commitments, &message);
+        assert_eq!(hex::encode(rho.to_repr()), p.binding_factor);
+    }
+
+    let shards: [_; 2] = core::array::from_fn(|i| {
+        sign::<C, F, G>(
+            &public_key,
+            i,
+            &shamir_ys[i],
+            &shamir_xs,
+            &nonces[i],
+            &commitments,
+            &message,
+        )
+    });
+    for (i, p) in vector.participants.iter().enumerate() {
+        assert_eq!(hex::encode(shards[i].2.to_repr()), p.sig_share);
+    }
+
+    let verification_shares = shamir_ys.map(|s| g * s);
+    let aggregate = |shards: &[(G, G, F)], complaints: &mut [Option<Complaint>]| {
+        aggregate::<C, F, G>(
+            &public_key,
+            &verification_shares,
+            shards,
+            &shamir_xs,
+            &commitments,
+            complaints,
+            &message,
+        )
+    };
+
+    let mut complaints = [None; 2];
+    let (y, r, z) = aggregate(&shards, &mut complaints).expect("aggregate");
+    assert_eq!(y, public_key);
+    assert_eq!(hex::encode(r.to_bytes()) + &hex::encode(z.to_repr()), vector.sig);
+    assert_eq!(g * z, r + y * challenge::<C, F, G>(&y, &r, &message));
+
+    let mut tampered = shards;
+    tampered[0].1 += g;
+    tampered[1].2 += F::ONE;
+    assert!(aggregate(&tampered, &mut complaints).is_err());
+    assert_eq!(complaints, [Some(Complaint::CommitmentMismatch), Some(Complaint::InvalidResponse)]);
+
+    // a signer misreporting `y_i` is blamed, and only that signer
+    let mut tampered = shards;
+    tampered[1].0 += g;
+    assert!(aggregate(&tampered, &mut complaints).is_err());
+    assert_eq!(complaints, [None, Some(Complaint::VerificationShareMismatch)]);
+}
+
+fn scalar<F: PrimeField>(s: &str) -> F {
+    let mut repr = F::Repr::default();
+    repr.as_mut().copy_from_slice(&hex::decode(s).expect("hex"));
+    F::from_repr(repr).expect("scalar")
+}
+
+#[test]
+fn ed25519_sha512() {
+    vector_impl::<Ed25519Sha512, curve25519::scalar::Scalar, curve25519::edwards::EdwardsPoint>(
+        &ED25519_SHA512,
+    );
+}
+
+#[test]
+fn ristretto255_sha512() {
+    vector_impl::<
+        Ristretto255Sha512,
+        curve25519::scalar::Scalar,
+        curve25519::ristretto::RistrettoPoint,
+    >(&RISTRETTO255_SHA512);
+}
+
+#[test]
+fn secp256k1_sha256() {
+    vector_impl::<Secp256k1Sha256, k256::Scalar, k256::ProjectivePoint>(&SECP256K1_SHA256);
+}
+
+#[test]
+fn preprocess_and_sign() {
+    type F = k256::Scalar;
+    type G = k256::ProjectivePoint;
+    type C = Secp256k1Sha256;
+
+    let mut rng = rand::rngs::OsRng;
+    let g = G::GENERATOR;
+    let message = b"message";
+
+    let group_secret_key = F::random(&mut rng);
+    let coefficient = F::random(&mut rng);
+    let public_key = g * group_secret_key;
+
+    let shamir_xs = [2u64, 5, 7].map(F::from);
+    let shamir_ys = shamir_xs.map(|x| group_secret_key + coefficient * x);
+
+    let mut nonces = [(F::ZERO, F::ZERO); 3];
+    let mut commitments = [(G::IDENTITY, G::IDENTITY); 3];
+    for i in 0..3 {
+        preprocess::<C, F, G>(
+            &mut rng,
+            &shamir_ys[i],
+            &mut nonces[i..i + 1],
+            &mut commitments[i..i + 1],
+        );
+    }
+
+    let shards: [_; 3] = core::array::from_fn(|i| {
+        sign::<C, F, G>(
+            &public_key,
+            i,
+            &shamir_ys[i],
+            &shamir_xs,
+            &nonces[i],
+            &commitments,
+            message,
+        )
+    });
+    let verification_shares = shamir_ys.map(|s| g * s);
+    let mut complaints = [None; 3];
+    let (y, r, z) = aggregate::<C, F, G>(
+        &public_key,
+        &verification_shares,
+        &shards,
+        &shamir_xs,
+        &commitments,
+        &mut complaints,
+        message,
+    )
+    .expect("aggregate");
+
+    assert_eq!(y, public_key);
+    assert_eq!(g * z, r + y * challenge::<C, F, G>(&y, &r, message));
+}
@@ -1,8 +1,9 @@
+use digest::Digest;
 use ff::PrimeField;
-use group::Group;
+use group::{Group, GroupEncoding};
 use rand::RngCore;
 
-use ::csi_rashi_dkg::{aggregate, deal};
+use ::csi_rashi_dkg::{aggregate, complain, deal, deal_vec, verify_proof, verify_response, Error};
 use ::shamir_sss::LagrangeCoefficientAt;
 
 use super::*;
@@ -10,40 +11,258 @@ use super::*;
 fn basic_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
 where
     F: PrimeField,
-    G: Group<Scalar = F>,
+    G: Group<Scalar = F> + GroupEncoding,
 {
-    let g = G::generator();
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (vss_commitments, proofs, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &shamir_xs);
+
+    transpose(&mut shamir_ys);
+
+    let results: [_; PARTIES] = core::array::from_fn(|i| {
+        let shamir_x = &shamir_xs[i];
+        let shamir_ys = &shamir_ys[i][..];
+
+        let mut complaints = [false; PARTIES];
+        complain::<F, G>(&vss_commitments[..], shamir_x, shamir_ys, &mut complaints[..]);
+        assert!(complaints.iter().all(|complaint| !complaint));
+
+        aggregate::<F, G>(
+            &vss_commitments[..],
+            &proofs,
+            shamir_x,
+            shamir_ys,
+            &complaints[..],
+            |dealer, y, r| challenge(&shamir_xs[dealer], y, r),
+        )
+        .expect("aggregate")
+    });
+
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn disqualification_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    // the dealer #0 corrupts the share of the party #1, but reveals the valid one in response;
+    // the dealer #2 corrupts the share of the party #3, and fails to respond properly.
+    const HONEST_RESPONDER: usize = 0;
+    const DISHONEST_RESPONDER: usize = 2;
 
     let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (vss_commitments, proofs, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &shamir_xs);
+
+    let valid_response = shamir_ys[HONEST_RESPONDER][1];
+    shamir_ys[HONEST_RESPONDER][1] += F::ONE;
+    shamir_ys[DISHONEST_RESPONDER][3] += F::ONE;
+    let invalid_response = shamir_ys[DISHONEST_RESPONDER][3];
+
+    transpose(&mut shamir_ys);
+
+    // the complaint phase
+    let complaints: [[bool; PARTIES]; PARTIES] = core::array::from_fn(|i| {
+        let mut complaints = [false; PARTIES];
+        complain::<F, G>(&vss_commitments[..], &shamir_xs[i], &shamir_ys[i][..], &mut complaints);
+        complaints
+    });
+    for (accuser, complaints) in complaints.iter().enumerate() {
+        for (dealer, complaint) in complaints.iter().enumerate() {
+            let expected = (accuser, dealer) == (1, HONEST_RESPONDER) ||
+                (accuser, dealer) == (3, DISHONEST_RESPONDER);
+            assert_eq!(*complaint, expected);
+        }
+    }
+
+    // the responses are public: every party comes to the same set of the disqualified dealers
+    assert!(verify_response(&vss_commitments[HONEST_RESPONDER], &shamir_xs[1], &valid_response));
+    assert!(!verify_response(
+        &vss_commitments[DISHONEST_RESPONDER],
+        &shamir_xs[3],
+        &invalid_response
+    ));
+    let disqualified: [_; PARTIES] = core::array::from_fn(|dealer| dealer == DISHONEST_RESPONDER);
+
+    let results: [_; PARTIES] = core::array::from_fn(|i| {
+        let mut shamir_ys = shamir_ys[i];
+        if i == 1 {
+            assert!(matches!(
+                aggregate::<F, G>(
+                    &vss_commitments[..],
+                    &proofs,
+                    &shamir_xs[i],
+                    &shamir_ys,
+                    &disqualified,
+                    |dealer, y, r| challenge(&shamir_xs[dealer], y, r),
+                ),
+                Err(Error::InvalidShare(HONEST_RESPONDER))
+            ));
+            shamir_ys[HONEST_RESPONDER] = valid_response;
+        }
+
+        aggregate::<F, G>(
+            &vss_commitments[..],
+            &proofs,
+            &shamir_xs[i],
+            &shamir_ys,
+            &disqualified,
+            |dealer, y, r| challenge(&shamir_xs[dealer], y, r),
+        )
+        .expect("aggregate")
+    });
+
+    let expected_public_key = vss_commitments
+        .iter()
+        .enumerate()
+        .filter(|(dealer, _)| !disqualified[*dealer])
+        .map(|(_, vss_commitment)| vss_commitment[0])
+        .fold(G::identity(), |acc, c| acc + c);
+    assert_eq!(results[0].1, expected_public_key);
+
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn rogue_proof_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (mut vss_commitments, mut proofs, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &shamir_xs);
+
+    for (dealer, (vss_commitment, proof)) in vss_commitments.iter().zip(&proofs).enumerate() {
+        assert!(verify_proof(vss_commitment, proof, |y, r| challenge(&shamir_xs[dealer], y, r)));
+    }
+
+    // the dealer #1 replays the commitment and the proof of the dealer #0
+    vss_commitments[1] = vss_commitments[0];
+    proofs[1] = proofs[0];
+    shamir_ys[1] = shamir_ys[0];
+    assert!(!verify_proof(&vss_commitments[1], &proofs[1], |y, r| challenge(&shamir_xs[1], y, r)));
+
+    // the dealer #2 does not know its secret
+    proofs[2].0 += F::ONE;
+    assert!(!verify_proof(&vss_commitments[2], &proofs[2], |y, r| challenge(&shamir_xs[2], y, r)));
+
+    // an empty commitment commits to no secret
+    assert!(!verify_proof(&[] as &[G], &proofs[0], |y, r| challenge(&shamir_xs[0], y, r)));
+
+    transpose(&mut shamir_ys);
+
+    let aggregate = |i: usize, disqualified: &[bool; PARTIES]| {
+        aggregate::<F, G>(
+            &vss_commitments[..],
+            &proofs,
+            &shamir_xs[i],
+            &shamir_ys[i],
+            disqualified,
+            |dealer, y, r| challenge(&shamir_xs[dealer], y, r),
+        )
+    };
+
+    let mut disqualified = [false; PARTIES];
+    assert!(matches!(aggregate(0, &disqualified), Err(Error::InvalidProof(1))));
+    disqualified[1] = true;
+    assert!(matches!(aggregate(0, &disqualified), Err(Error::InvalidProof(2))));
+    disqualified[2] = true;
+
+    let results: [_; PARTIES] =
+        core::array::from_fn(|i| aggregate(i, &disqualified).expect("aggregate"));
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn invalid_threshold_impl<F, G, const PARTIES: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let mut shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let mut shamir_ys = [F::ZERO; PARTIES];
+    let secret = F::random(&mut rng);
+    let challenge = |y: &G, r: &G| challenge(&shamir_xs[0], y, r);
+
+    // no coefficients, or more than the parties
+    for threshold in [0, PARTIES + 1] {
+        assert!(matches!(
+            deal_vec::<F, G>(&mut rng, threshold, &secret, &shamir_xs, &mut shamir_ys, challenge),
+            Err(Error::InvalidThreshold(t)) if t == threshold
+        ));
+    }
+    let (vss_commitment, _) =
+        deal_vec::<F, G>(&mut rng, PARTIES, &secret, &shamir_xs, &mut shamir_ys, challenge)
+            .expect("deal");
+    assert_eq!(vss_commitment.len(), PARTIES);
+    assert_eq!(vss_commitment[0], G::generator() * secret);
+
+    // the share of a zero `x` would be the secret
+    shamir_xs[1] = F::ZERO;
+    let mut vss_commitment = [G::identity(); PARTIES];
+    assert!(matches!(
+        deal::<F, G>(&mut rng, &secret, &shamir_xs, &mut shamir_ys, &mut vss_commitment, |_, _| {
+            F::ONE
+        }),
+        Err(Error::ZeroShamirX(1))
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+fn deal_all<F, G, const PARTIES: usize, const THRESHOLD: usize>(
+    mut rng: impl RngCore,
+    shamir_xs: &[F; PARTIES],
+) -> ([[G; THRESHOLD]; PARTIES], [(F, G); PARTIES], [[F; PARTIES]; PARTIES])
+where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
     let mut shamir_ys = [[F::ZERO; PARTIES]; PARTIES];
+    let mut proofs = [(F::ZERO, G::identity()); PARTIES];
 
     let vss_commitments: [_; PARTIES] = core::array::from_fn(|dealer_id| {
         let y_0 = F::random(&mut rng);
-        deal::<F, G, THRESHOLD>(
+        let mut vss_commitment = [G::identity(); THRESHOLD];
+        proofs[dealer_id] = deal::<F, G>(
             &mut rng,
-            THRESHOLD,
             &y_0,
             &shamir_xs[..],
             &mut shamir_ys[dealer_id][..],
+            &mut vss_commitment,
+            |y, r| challenge(&shamir_xs[dealer_id], y, r),
         )
-        .expect("deal")
+        .expect("deal");
+        vss_commitment
     });
 
-    transpose(&mut shamir_ys);
+    (vss_commitments, proofs, shamir_ys)
+}
 
-    let results: [_; PARTIES] = core::array::from_fn(|i| {
-        let shamir_x = &shamir_xs[i];
-        let shamir_ys = &shamir_ys[i][..];
+/// The challenge of the proof of the dealer `shamir_x`.
+fn challenge<F: PrimeField, G: GroupEncoding>(shamir_x: &F, y: &G, r: &G) -> F {
+    ::utils::bytes_to_scalar(
+        sha3::Sha3_256::new()
+            .chain_update(shamir_x.to_repr())
+            .chain_update(y.to_bytes())
+            .chain_update(r.to_bytes())
+            .finalize()
+            .as_ref(),
+    )
+}
 
-        let mut complaints = [false; PARTIES];
-        aggregate::<F, G>(&vss_commitments[..], shamir_x, shamir_ys, &mut complaints[..])
-            .expect("aggregate")
-    });
+fn assert_same_key<F, G, const PARTIES: usize, const THRESHOLD: usize>(
+    shamir_xs: &[F; PARTIES],
+    results: &[(F, G); PARTIES],
+) where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+{
+    let g = G::generator();
 
-    let shamir_ys = results.map(|(y, _)| y);
+    let shamir_ys: [_; PARTIES] = core::array::from_fn(|i| results[i].0);
     let public_key = results
-        .into_iter()
-        .map(|(_, y)| y)
+        .iter()
+        .map(|(_, y)| *y)
         .reduce(|left, right| {
             assert_eq!(left, right);
             left
@@ -55,8 +274,8 @@ where
         let ys: [_; THRESHOLD] = core::array::from_fn(|j| shamir_ys[(i + j) % PARTIES]);
 
         let mut y = F::ZERO;
-        for j in 0..THRESHOLD {
-            y += ys[j] * xs.lagrange_coefficient_at(j, F::ZERO);
+        for (j, y_j) in ys.into_iter().enumerate() {
+            y += y_j * xs.lagrange_coefficient_at(j, F::ZERO);
         }
 
         assert_eq!(g * y, public_key);
@@ -84,3 +303,18 @@ fn transpose<T, const N: usize>(m: &mut [[T; N]; N]) {
 fn basic() {
     basic_impl::<Scalar, Point, 20, 5>(&mut rand::rngs::OsRng);
 }
+
+#[test]
+fn disqualification() {
+    disqualification_impl::<Scalar, Point, 7, 3>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn rogue_proof() {
+    rogue_proof_impl::<Scalar, Point, 5, 3>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn invalid_threshold() {
+    invalid_threshold_impl::<Scalar, Point, 3>(&mut rand::rngs::OsRng);
+}
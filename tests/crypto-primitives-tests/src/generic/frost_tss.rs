@@ -3,7 +3,7 @@ use ff::PrimeField;
 use group::{Group, GroupEncoding};
 use rand::RngCore;
 
-use ::shamir_sss::{SchemeInitFromSecret, SchemeIssueShare};
+use ::shamir_sss::{LagrangeCoefficientAt, SchemeInitFromSecret, SchemeIssueShare};
 
 use ::frost_tss::*;
 
@@ -16,8 +16,26 @@ fn basic_impl<
     const PARTIES: usize,
     const THRESHOLD: usize,
     const PREPROCESS_COUNT: usize,
+>(
+    rng: impl RngCore,
+) where
+    F: PrimeField,
+    G: Group<Scalar = F> + GroupEncoding,
+    H: Digest,
+{
+    normalized_impl::<F, G, H, PARTIES, THRESHOLD, PREPROCESS_COUNT>(rng, |_| false)
+}
+
+fn normalized_impl<
+    F,
+    G,
+    H,
+    const PARTIES: usize,
+    const THRESHOLD: usize,
+    const PREPROCESS_COUNT: usize,
 >(
     mut rng: impl RngCore,
+    is_odd: impl Fn(&G) -> bool,
 ) where
     F: PrimeField,
     G: Group<Scalar = F> + GroupEncoding,
@@ -76,31 +94,77 @@ fn basic_impl<
         };
 
         let shards: [_; THRESHOLD] = core::array::from_fn(|j| {
-            sign::<F, G, H>(
+            sign_normalized::<F, G, H>(
                 &public_key,
                 j,
                 &session_ys[j],
                 &session_xs,
                 &session_nonces[j],
                 &session_commitments,
+                &is_odd,
                 &produce_challenge,
             )
         });
 
-        let mut complaints = [false; THRESHOLD];
-        let (y, r, z) = aggregate::<F, G, H>(
+        let verification_shares = session_ys.map(|s| g * s);
+        let mut complaints = [None; THRESHOLD];
+        let (y, r, z) = aggregate_normalized::<F, G, H>(
+            &public_key,
+            &verification_shares,
             &shards,
             &session_xs,
             &session_commitments,
             &mut complaints,
+            &is_odd,
             &produce_challenge,
         )
         .expect("aggregate");
 
-        assert!(!complaints.into_iter().any(core::convert::identity));
+        assert!(complaints.iter().all(Option::is_none));
 
         assert_eq!(g * z, r + y * produce_challenge(&y, &r));
-        assert_eq!(y, public_key);
+        assert_eq!(y, if is_odd(&public_key) { -public_key } else { public_key });
+
+        if i > 0 {
+            continue
+        }
+
+        // the misreported `y_i` is caught even if `z_i` is consistent with it
+        let mut tampered = shards;
+        let lambda = session_xs.lagrange_coefficient_at(0, F::ZERO);
+        tampered[0].0 += g;
+        tampered[0].2 += lambda * produce_challenge(&y, &r);
+        assert!(aggregate_normalized::<F, G, H>(
+            &public_key,
+            &verification_shares,
+            &tampered,
+            &session_xs,
+            &session_commitments,
+            &mut complaints,
+            &is_odd,
+            &produce_challenge,
+        )
+        .is_err());
+        assert_eq!(complaints[0], Some(Complaint::VerificationShareMismatch));
+        assert!(complaints[1..].iter().all(Option::is_none));
+
+        // the shards can be checked one by one as well
+        let verify_shard = |j: usize, shard: &(G, G, F)| {
+            verify_shard_normalized::<F, G, H>(
+                &public_key,
+                &verification_shares[j],
+                j,
+                shard,
+                &session_xs,
+                &session_commitments,
+                &is_odd,
+                &produce_challenge,
+            )
+        };
+        for (j, shard) in shards.iter().enumerate() {
+            assert_eq!(verify_shard(j, shard), Ok(()));
+        }
+        assert_eq!(verify_shard(0, &tampered[0]), Err(Complaint::VerificationShareMismatch));
     }
 }
 
@@ -108,3 +172,11 @@ fn basic_impl<
 fn basic() {
     basic_impl::<Scalar, Point, sha3::Sha3_256, 10, 3, 1_000>(&mut rand::rngs::OsRng);
 }
+
+#[test]
+fn normalized() {
+    // any predicate will do: the signature should remain valid for the negated points
+    let is_odd =
+        |p: &Point| AsRef::<[u8]>::as_ref(&p.to_bytes()).iter().fold(0, |acc, b| acc ^ b) & 1 == 1;
+    normalized_impl::<Scalar, Point, sha3::Sha3_256, 10, 3, 1_000>(&mut rand::rngs::OsRng, is_odd);
+}
@@ -0,0 +1,452 @@
+use ff::PrimeField;
+use group::Group;
+use rand::RngCore;
+
+use ::feldman_vsss::CommitmentVerifyShare;
+use ::pedersen_dkg::{
+    aggregate, complain, deal, deal_vec, key_share, reconstruct, reconstruct_vec, verify_share,
+    Error,
+};
+use ::shamir_sss::LagrangeCoefficientAt;
+
+use super::*;
+
+fn basic_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let h = G::generator() * F::random(&mut rng);
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (pedersen_commitments, feldman_commitments, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &h, &shamir_xs);
+
+    transpose(&mut shamir_ys);
+
+    let disqualified = [false; PARTIES];
+    let results: [_; PARTIES] = core::array::from_fn(|i| {
+        let shamir_x = &shamir_xs[i];
+        let shamir_ys = &shamir_ys[i][..];
+
+        let mut complaints = [false; PARTIES];
+        complain::<F, G>(&h, &pedersen_commitments[..], shamir_x, shamir_ys, &mut complaints[..]);
+        assert!(complaints.iter().all(|complaint| !complaint));
+
+        let key_share =
+            key_share::<F, G>(&h, &pedersen_commitments[..], shamir_x, shamir_ys, &disqualified)
+                .expect("key_share");
+        let out = aggregate::<F, G>(
+            THRESHOLD,
+            &feldman_commitments[..],
+            shamir_x,
+            shamir_ys,
+            &disqualified,
+        )
+        .expect("aggregate");
+        assert_eq!(out.0, key_share);
+        out
+    });
+
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn disqualification_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    // the dealer #0 corrupts the share of the party #1, but reveals the valid one in response;
+    // the dealer #2 corrupts the share of the party #3, and fails to respond properly.
+    const HONEST_RESPONDER: usize = 0;
+    const DISHONEST_RESPONDER: usize = 2;
+
+    let h = G::generator() * F::random(&mut rng);
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (pedersen_commitments, feldman_commitments, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &h, &shamir_xs);
+
+    let valid_response = shamir_ys[HONEST_RESPONDER][1];
+    shamir_ys[HONEST_RESPONDER][1].1 += F::ONE;
+    shamir_ys[DISHONEST_RESPONDER][3].1 += F::ONE;
+    let invalid_response = shamir_ys[DISHONEST_RESPONDER][3];
+
+    transpose(&mut shamir_ys);
+
+    // the complaint phase
+    for i in 0..PARTIES {
+        let mut complaints = [false; PARTIES];
+        complain::<F, G>(
+            &h,
+            &pedersen_commitments[..],
+            &shamir_xs[i],
+            &shamir_ys[i][..],
+            &mut complaints,
+        );
+        for (dealer, complaint) in complaints.iter().enumerate() {
+            let expected =
+                (i, dealer) == (1, HONEST_RESPONDER) || (i, dealer) == (3, DISHONEST_RESPONDER);
+            assert_eq!(*complaint, expected);
+        }
+    }
+
+    // the responses are public: every party comes to the same set of the qualified dealers
+    assert!(verify_share(
+        &h,
+        &pedersen_commitments[HONEST_RESPONDER],
+        &shamir_xs[1],
+        &valid_response
+    ));
+    assert!(!verify_share(
+        &h,
+        &pedersen_commitments[DISHONEST_RESPONDER],
+        &shamir_xs[3],
+        &invalid_response
+    ));
+    let disqualified: [_; PARTIES] = core::array::from_fn(|dealer| dealer == DISHONEST_RESPONDER);
+
+    let results: [_; PARTIES] = core::array::from_fn(|i| {
+        let mut shamir_ys = shamir_ys[i];
+        if i == 1 {
+            assert!(matches!(
+                key_share::<F, G>(
+                    &h,
+                    &pedersen_commitments[..],
+                    &shamir_xs[i],
+                    &shamir_ys,
+                    &disqualified
+                ),
+                Err(Error::InvalidShare(HONEST_RESPONDER))
+            ));
+            shamir_ys[HONEST_RESPONDER] = valid_response;
+        }
+        key_share::<F, G>(&h, &pedersen_commitments[..], &shamir_xs[i], &shamir_ys, &disqualified)
+            .expect("key_share");
+
+        aggregate::<F, G>(
+            THRESHOLD,
+            &feldman_commitments[..],
+            &shamir_xs[i],
+            &shamir_ys,
+            &disqualified,
+        )
+        .expect("aggregate")
+    });
+
+    let expected_public_key = feldman_commitments
+        .iter()
+        .enumerate()
+        .filter(|(dealer, _)| !disqualified[*dealer])
+        .map(|(_, feldman_commitment)| feldman_commitment[0])
+        .fold(G::identity(), |acc, c| acc + c);
+    assert_eq!(results[0].1, expected_public_key);
+
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn reconstruction_impl<F, G, const PARTIES: usize, const THRESHOLD: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    // the dealer #1 reveals the Feldman commitment that does not match its Pedersen commitment
+    // (e.g. to bias the public key); the party #0 reveals an invalid share of the dealer #1.
+    const BIASING_DEALER: usize = 1;
+
+    let h = G::generator() * F::random(&mut rng);
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (pedersen_commitments, mut feldman_commitments, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, THRESHOLD>(&mut rng, &h, &shamir_xs);
+    let honest_feldman_commitment = feldman_commitments[BIASING_DEALER];
+    feldman_commitments[BIASING_DEALER][0] += G::random(&mut rng);
+
+    transpose(&mut shamir_ys);
+
+    let disqualified = [false; PARTIES];
+    for i in 0..PARTIES {
+        assert!(matches!(
+            aggregate::<F, G>(
+                THRESHOLD,
+                &feldman_commitments[..],
+                &shamir_xs[i],
+                &shamir_ys[i],
+                &disqualified
+            ),
+            Err(Error::InvalidFeldmanCommitment(BIASING_DEALER))
+        ));
+    }
+
+    let mut revealed: [_; PARTIES] =
+        core::array::from_fn(|i| (shamir_xs[i], shamir_ys[i][BIASING_DEALER]));
+    revealed[0].1 .0 += F::ONE;
+
+    let mut reconstructed = [G::identity(); THRESHOLD];
+    assert!(matches!(
+        reconstruct::<F, G>(
+            &h,
+            &pedersen_commitments[BIASING_DEALER],
+            &mut revealed[..THRESHOLD],
+            &mut reconstructed,
+        ),
+        Err(Error::NotEnoughShares)
+    ));
+    // the repeated shares count once
+    let mut repeated = [revealed[1]; PARTIES];
+    assert!(matches!(
+        reconstruct::<F, G>(
+            &h,
+            &pedersen_commitments[BIASING_DEALER],
+            &mut repeated,
+            &mut reconstructed
+        ),
+        Err(Error::NotEnoughShares)
+    ));
+    reconstruct::<F, G>(
+        &h,
+        &pedersen_commitments[BIASING_DEALER],
+        &mut revealed,
+        &mut reconstructed,
+    )
+    .expect("reconstruct");
+    assert_eq!(reconstructed, honest_feldman_commitment);
+    let reconstructed_vec =
+        reconstruct_vec::<F, G>(&h, &pedersen_commitments[BIASING_DEALER], THRESHOLD, &revealed)
+            .expect("reconstruct");
+    assert_eq!(reconstructed_vec, reconstructed);
+
+    feldman_commitments[BIASING_DEALER] = reconstructed;
+    let results: [_; PARTIES] = core::array::from_fn(|i| {
+        aggregate::<F, G>(
+            THRESHOLD,
+            &feldman_commitments[..],
+            &shamir_xs[i],
+            &shamir_ys[i],
+            &disqualified,
+        )
+        .expect("aggregate")
+    });
+
+    assert_same_key::<F, G, PARTIES, THRESHOLD>(&shamir_xs, &results);
+}
+
+fn over_long_feldman_commitment_impl<F, G, const PARTIES: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    // with `t == n`, the dealer #1 adds `r * (X - x_0) * ... * (X - x_{n-1})` to its polynomial in
+    // the exponent: the Feldman commitment of `n + 1` points still matches every share, but
+    // moves the public key.
+    const BIASING_DEALER: usize = 1;
+
+    let h = G::generator() * F::random(&mut rng);
+    let shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let (_, feldman_commitments, mut shamir_ys) =
+        deal_all::<F, G, PARTIES, PARTIES>(&mut rng, &h, &shamir_xs);
+
+    let mut vanishing = vec![F::ONE];
+    for x in shamir_xs.iter() {
+        vanishing.insert(0, F::ZERO);
+        for k in 0..vanishing.len() - 1 {
+            let shifted = vanishing[k + 1] * x;
+            vanishing[k] -= shifted;
+        }
+    }
+    let r = G::generator() * F::random(&mut rng);
+    let mut feldman_commitments = feldman_commitments.map(|c| c.to_vec());
+    feldman_commitments[BIASING_DEALER].push(G::identity());
+    feldman_commitments[BIASING_DEALER]
+        .iter_mut()
+        .zip(&vanishing)
+        .for_each(|(c, e)| *c += r * e);
+
+    transpose(&mut shamir_ys);
+
+    let disqualified = [false; PARTIES];
+    for i in 0..PARTIES {
+        assert!(feldman_commitments[BIASING_DEALER]
+            .verify_share(&shamir_xs[i], &shamir_ys[i][BIASING_DEALER].0));
+        assert!(matches!(
+            aggregate::<F, G>(
+                PARTIES,
+                &feldman_commitments[..],
+                &shamir_xs[i],
+                &shamir_ys[i],
+                &disqualified
+            ),
+            Err(Error::InvalidFeldmanCommitment(BIASING_DEALER))
+        ));
+    }
+
+    // the shorter one fails as well
+    feldman_commitments[BIASING_DEALER].truncate(PARTIES - 1);
+    assert!(matches!(
+        aggregate::<F, G>(
+            PARTIES,
+            &feldman_commitments[..],
+            &shamir_xs[0],
+            &shamir_ys[0],
+            &disqualified
+        ),
+        Err(Error::InvalidFeldmanCommitment(BIASING_DEALER))
+    ));
+}
+
+fn invalid_threshold_impl<F, G, const PARTIES: usize>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let h = G::generator() * F::random(&mut rng);
+    let mut shamir_xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+    let mut shamir_ys = [(F::ZERO, F::ZERO); PARTIES];
+    let secret = F::random(&mut rng);
+
+    // no coefficients, or more than the parties
+    for threshold in [0, PARTIES + 1] {
+        assert!(matches!(
+            deal_vec::<F, G>(&mut rng, threshold, &secret, &h, &shamir_xs, &mut shamir_ys),
+            Err(Error::InvalidThreshold(t)) if t == threshold
+        ));
+    }
+    // the commitments of different lengths
+    let mut pedersen_commitment = [G::identity(); PARTIES];
+    let mut feldman_commitment = [G::identity(); PARTIES];
+    assert!(matches!(
+        deal::<F, G>(
+            &mut rng,
+            &secret,
+            &h,
+            &shamir_xs,
+            &mut shamir_ys,
+            &mut pedersen_commitment,
+            &mut feldman_commitment[1..]
+        ),
+        Err(Error::InvalidThreshold(_))
+    ));
+    let (pedersen_commitment, feldman_commitment) =
+        deal_vec::<F, G>(&mut rng, PARTIES, &secret, &h, &shamir_xs, &mut shamir_ys).expect("deal");
+    assert_eq!(pedersen_commitment.len(), PARTIES);
+    assert_eq!(feldman_commitment[0], G::generator() * secret);
+
+    // nothing to reconstruct
+    let mut shares: [_; PARTIES] = core::array::from_fn(|i| (shamir_xs[i], shamir_ys[i]));
+    assert!(matches!(
+        reconstruct::<F, G>(&h, &pedersen_commitment, &mut shares, &mut []),
+        Err(Error::InvalidThreshold(0))
+    ));
+
+    // nothing to aggregate against
+    let feldman_commitments = [feldman_commitment];
+    assert!(matches!(
+        aggregate::<F, G>(0, &feldman_commitments, &shamir_xs[0], &shamir_ys[..1], &[false]),
+        Err(Error::InvalidThreshold(0))
+    ));
+
+    // the share of a zero `x` would be the secret
+    shamir_xs[1] = F::ZERO;
+    assert!(matches!(
+        deal_vec::<F, G>(&mut rng, PARTIES, &secret, &h, &shamir_xs, &mut shamir_ys),
+        Err(Error::ZeroShamirX(1))
+    ));
+}
+
+#[allow(clippy::type_complexity)]
+fn deal_all<F, G, const PARTIES: usize, const THRESHOLD: usize>(
+    mut rng: impl RngCore,
+    h: &G,
+    shamir_xs: &[F; PARTIES],
+) -> ([[G; THRESHOLD]; PARTIES], [[G; THRESHOLD]; PARTIES], [[(F, F); PARTIES]; PARTIES])
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let mut shamir_ys = [[(F::ZERO, F::ZERO); PARTIES]; PARTIES];
+    let mut feldman_commitments = [[G::identity(); THRESHOLD]; PARTIES];
+
+    let pedersen_commitments: [_; PARTIES] = core::array::from_fn(|dealer_id| {
+        let y_0 = F::random(&mut rng);
+        let mut pedersen_commitment = [G::identity(); THRESHOLD];
+        deal::<F, G>(
+            &mut rng,
+            &y_0,
+            h,
+            &shamir_xs[..],
+            &mut shamir_ys[dealer_id][..],
+            &mut pedersen_commitment,
+            &mut feldman_commitments[dealer_id],
+        )
+        .expect("deal");
+        pedersen_commitment
+    });
+
+    (pedersen_commitments, feldman_commitments, shamir_ys)
+}
+
+fn assert_same_key<F, G, const PARTIES: usize, const THRESHOLD: usize>(
+    shamir_xs: &[F; PARTIES],
+    results: &[(F, G); PARTIES],
+) where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    let g = G::generator();
+
+    let shamir_ys: [_; PARTIES] = core::array::from_fn(|i| results[i].0);
+    let public_key = results
+        .iter()
+        .map(|(_, y)| *y)
+        .reduce(|left, right| {
+            assert_eq!(left, right);
+            left
+        })
+        .expect("zero participants?");
+
+    for i in 0..PARTIES {
+        let xs: [_; THRESHOLD] = core::array::from_fn(|j| shamir_xs[(i + j) % PARTIES]);
+        let ys: [_; THRESHOLD] = core::array::from_fn(|j| shamir_ys[(i + j) % PARTIES]);
+
+        let y = ys
+            .iter()
+            .enumerate()
+            .map(|(j, y)| *y * xs.lagrange_coefficient_at(j, F::ZERO))
+            .sum::<F>();
+
+        assert_eq!(g * y, public_key);
+    }
+}
+
+fn transpose<T, const N: usize>(m: &mut [[T; N]; N]) {
+    for i in 0..N {
+        for j in 0..N {
+            if i < j {
+                let (has_i_row_at_i, has_j_row_at_0) = m.split_at_mut(j);
+                core::mem::swap(&mut has_i_row_at_i[i][j], &mut has_j_row_at_0[0][i]);
+            }
+        }
+    }
+}
+
+#[test]
+fn basic() {
+    basic_impl::<Scalar, Point, 20, 5>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn disqualification() {
+    disqualification_impl::<Scalar, Point, 7, 3>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn reconstruction() {
+    reconstruction_impl::<Scalar, Point, 5, 3>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn over_long_feldman_commitment() {
+    over_long_feldman_commitment_impl::<Scalar, Point, 4>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn invalid_threshold() {
+    invalid_threshold_impl::<Scalar, Point, 3>(&mut rand::rngs::OsRng);
+}
@@ -0,0 +1,60 @@
+use ff::PrimeField;
+use group::Group;
+use rand::RngCore;
+
+use ::pedersen_vsss::{CommitmentInitFromSchemes, CommitmentVerifySharePair};
+use ::shamir_sss::{SchemeInitFromSecret, SchemeIssueShare};
+
+use super::*;
+
+fn basic_impl<F, G>(mut rng: impl RngCore)
+where
+    F: PrimeField,
+    G: Group<Scalar = F>,
+{
+    const PARTIES: usize = 10;
+    const THRESHOLD: usize = 3;
+
+    let h = G::generator() * F::random(&mut rng);
+    let xs: [_; PARTIES] = core::array::from_fn(|_| F::random(&mut rng));
+
+    let ss: [_; PARTIES] = core::array::from_fn(|_| {
+        let mut s: [F; THRESHOLD] = Default::default();
+        s.init_from_secret(&F::random(&mut rng), &mut rng);
+        s
+    });
+    let bs: [_; PARTIES] = core::array::from_fn(|_| {
+        let mut b: [F; THRESHOLD] = Default::default();
+        b.init_from_secret(&F::random(&mut rng), &mut rng);
+        b
+    });
+    let cs: [_; PARTIES] = core::array::from_fn(|dealer_idx| {
+        let mut c = [G::identity(); THRESHOLD];
+        c.init_from_schemes(&h, ss[dealer_idx].as_ref(), bs[dealer_idx].as_ref());
+        c
+    });
+
+    for dealer_idx in 0..PARTIES {
+        let c = &cs[dealer_idx];
+
+        for recipient_idx in 0..PARTIES {
+            let y = ss[dealer_idx].issue_share(xs[recipient_idx]);
+            let y_blind = bs[dealer_idx].issue_share(xs[recipient_idx]);
+
+            for (r_i, x) in xs.iter().enumerate() {
+                let expecting_correct = r_i == recipient_idx;
+
+                assert_eq!(expecting_correct, c.verify_share_pair(&h, x, &y, &y_blind));
+            }
+
+            // neither of the shares is valid without the other
+            assert!(!c.verify_share_pair(&h, &xs[recipient_idx], &y, &(y_blind + F::ONE)));
+            assert!(!c.verify_share_pair(&h, &xs[recipient_idx], &(y + F::ONE), &y_blind));
+        }
+    }
+}
+
+#[test]
+fn basic() {
+    basic_impl::<Scalar, Point>(&mut rand::rngs::OsRng);
+}
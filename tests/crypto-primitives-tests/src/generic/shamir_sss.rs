@@ -37,7 +37,110 @@ where
     }
 }
 
+pub fn reconstruct_impl<F>(mut rng: impl RngCore)
+where
+    F: Field + PrimeField,
+{
+    const PARTIES: usize = 5;
+    const THRESHOLD: usize = 3;
+
+    let y_0 = F::random(&mut rng);
+
+    let mut scheme = [F::default(); THRESHOLD];
+    scheme.init_from_secret(&y_0, &mut rng);
+
+    let xs = std::iter::repeat_with(|| F::random(&mut rng)).take(PARTIES).collect::<Vec<_>>();
+    let ys = xs.iter().copied().map(|x| scheme.issue_share(x)).collect::<Vec<_>>();
+
+    assert_eq!(reconstruct_at(&xs, &ys, F::ZERO), Some(y_0));
+    assert_eq!(reconstruct_at(&xs[..THRESHOLD], &ys[..THRESHOLD], F::ZERO), Some(y_0));
+    assert_ne!(reconstruct_at(&xs[..THRESHOLD - 1], &ys[..THRESHOLD - 1], F::ZERO), Some(y_0));
+
+    // the missing shares are recovered as well
+    for k in THRESHOLD..PARTIES {
+        assert_eq!(reconstruct_at(&xs[..THRESHOLD], &ys[..THRESHOLD], xs[k]), Some(ys[k]));
+    }
+
+    // neither a repeated `x`, nor a missing `y`
+    let repeated = [xs[0], xs[1], xs[0]];
+    assert_eq!(reconstruct_at(&repeated, &ys[..THRESHOLD], F::ZERO), None);
+    assert_eq!(reconstruct_at(&xs[..THRESHOLD], &ys[..THRESHOLD - 1], F::ZERO), None);
+}
+
+pub fn repair_impl<F>(mut rng: impl RngCore)
+where
+    F: Field + PrimeField,
+{
+    const PARTIES: usize = 5;
+    const THRESHOLD: usize = 3;
+
+    let y_0 = F::random(&mut rng);
+
+    let mut scheme = [F::default(); THRESHOLD];
+    scheme.init_from_secret(&y_0, &mut rng);
+
+    let xs = std::iter::repeat_with(|| F::random(&mut rng)).take(PARTIES).collect::<Vec<_>>();
+    let ys = xs.iter().copied().map(|x| scheme.issue_share(x)).collect::<Vec<_>>();
+
+    for lost in 0..PARTIES {
+        let helpers = (1..=THRESHOLD).map(|k| (lost + k) % PARTIES).collect::<Vec<_>>();
+        let helpers_xs = helpers.iter().map(|&i| xs[i]).collect::<Vec<_>>();
+
+        // deltas[i][j]: from the helper #i to the helper #j
+        let deltas = helpers
+            .iter()
+            .enumerate()
+            .map(|(this, &i)| {
+                let mut deltas = [F::ZERO; THRESHOLD];
+                repair_deltas(&mut rng, &helpers_xs, this, &ys[i], xs[lost], &mut deltas)
+                    .expect("repair_deltas");
+                deltas
+            })
+            .collect::<Vec<_>>();
+        let sigmas = (0..THRESHOLD)
+            .map(|j| repair_sigma(&deltas.iter().map(|ds| ds[j]).collect::<Vec<_>>()))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("repair_sigma");
+
+        assert_eq!(repair_share(&sigmas).expect("repair_share"), ys[lost]);
+    }
+
+    let mut deltas = [F::ZERO; THRESHOLD];
+    let mut repair_deltas = |helpers_xs: &[F], this: usize, deltas: &mut [F]| {
+        repair_deltas(&mut rng, helpers_xs, this, &ys[0], xs[0], deltas)
+    };
+    assert!(matches!(
+        repair_deltas(&xs[1..=THRESHOLD], 0, &mut deltas[1..]),
+        Err(RepairError::DeltasCountMismatch { helpers: THRESHOLD, deltas: 2 })
+    ));
+    assert!(matches!(
+        repair_deltas(&xs[1..=THRESHOLD], THRESHOLD, &mut deltas),
+        Err(RepairError::HelperOutOfRange(THRESHOLD))
+    ));
+    assert!(matches!(
+        repair_deltas(&xs[..THRESHOLD], 1, &mut deltas),
+        Err(RepairError::LostIsHelper)
+    ));
+    assert!(matches!(
+        repair_deltas(&[xs[1], xs[2], xs[1]], 0, &mut deltas),
+        Err(RepairError::DuplicateHelper(2))
+    ));
+    assert!(matches!(repair_deltas(&[], 0, &mut []), Err(RepairError::HelperOutOfRange(0))));
+    assert!(matches!(repair_sigma::<F>(&[]), Err(RepairError::NoHelpers)));
+    assert!(matches!(repair_share::<F>(&[]), Err(RepairError::NoHelpers)));
+}
+
 #[test]
 fn basic() {
     basic_impl::<Scalar, Point>(&mut rand::rngs::OsRng);
 }
+
+#[test]
+fn repair() {
+    repair_impl::<Scalar>(&mut rand::rngs::OsRng);
+}
+
+#[test]
+fn reconstruct() {
+    reconstruct_impl::<Scalar>(&mut rand::rngs::OsRng);
+}
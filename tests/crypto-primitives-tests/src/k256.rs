@@ -10,9 +10,15 @@ mod shamir_sss;
 #[path = "generic/feldman_vsss.rs"]
 mod feldman_vsss;
 
+#[path = "generic/pedersen_vsss.rs"]
+mod pedersen_vsss;
+
 #[path = "generic/csi_rashi_dkg.rs"]
 mod csi_rashi_dkg;
 
+#[path = "generic/pedersen_dkg.rs"]
+mod pedersen_dkg;
+
 #[path = "generic/frost_tss.rs"]
 mod frost_tss;
 
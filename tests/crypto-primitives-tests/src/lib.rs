@@ -1,4 +1,6 @@
 #![cfg(test)]
+// the generic tests are included into the module of each curve they are run for
+#![allow(clippy::duplicate_mod)]
 
 mod curve25519_dalek_edwards;
 
@@ -7,3 +9,7 @@ mod curve25519_dalek_ristretto;
 mod k256;
 
 mod curve_debug;
+
+mod frost_rfc9591;
+
+mod pedersen_second_generator;
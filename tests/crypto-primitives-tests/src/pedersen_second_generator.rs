@@ -0,0 +1,156 @@
+use elliptic_curve::hash2curve::{ExpandMsg, ExpandMsgXmd, Expander, GroupDigest};
+use group::{Group, GroupEncoding};
+use sha2::{Sha256, Sha512};
+
+use ::pedersen_vsss::second_generator::{Ed25519, Ristretto255, SecondGenerator, Secp256k1};
+
+fn assert_second_generator<S: SecondGenerator>(derived: S::Group) {
+    let h = S::second_generator();
+    assert_eq!(h, derived);
+    assert_eq!(h.to_bytes().as_ref(), S::ENCODED);
+    assert!(!bool::from(h.is_identity()));
+    assert_ne!(h, S::Group::generator());
+}
+
+#[test]
+fn secp256k1() {
+    // RFC 9380, `secp256k1_XMD:SHA-256_SSWU_RO_`
+    let h = k256::Secp256k1::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[&[]], &[Secp256k1::DST])
+        .expect("hash-to-curve");
+    assert_second_generator::<Secp256k1>(h);
+}
+
+#[test]
+fn secp256k1_hash_to_curve_test_vector() {
+    // RFC 9380, J.8.1: `msg = ""`
+    let dst = b"QUUX-V01-CS02-with-secp256k1_XMD:SHA-256_SSWU_RO_";
+    let p = k256::Secp256k1::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[&[]], &[dst])
+        .expect("hash-to-curve");
+    assert_eq!(
+        hex::encode(p.to_bytes()),
+        "03c1cae290e291aee617ebaef1be6d73861479c48b841eaba9b7b5852ddfeb1346"
+    );
+}
+
+#[test]
+fn ristretto255() {
+    // RFC 9380, `ristretto255_XMD:SHA-512_R255MAP_RO_`
+    let mut uniform_bytes = [0u8; 64];
+    <ExpandMsgXmd<Sha512> as ExpandMsg>::expand_message(&[&[]], &[Ristretto255::DST], 64)
+        .expect("expand_message")
+        .fill_bytes(&mut uniform_bytes);
+    let h = curve25519::ristretto::RistrettoPoint::from_uniform_bytes(&uniform_bytes);
+    assert_second_generator::<Ristretto255>(h);
+}
+
+#[test]
+fn ed25519() {
+    // RFC 9380, `edwards25519_XMD:SHA-512_ELL2_RO_`
+    let h = edwards25519::hash_to_curve(Ed25519::DST);
+    assert!(h.is_torsion_free());
+    assert_second_generator::<Ed25519>(h);
+}
+
+#[test]
+fn ed25519_hash_to_curve_test_vector() {
+    // RFC 9380, J.5.1: `msg = ""`
+    let p = edwards25519::hash_to_curve(b"QUUX-V01-CS02-with-edwards25519_XMD:SHA-512_ELL2_RO_");
+
+    let x = hex::decode("3c3da6925a3c3c268448dcabb47ccde5439559d9599646a8260e47b1e4822fc6")
+        .expect("hex");
+    let mut y = hex::decode("09a6c8561a0b22bef63124c588ce4c62ea83a3c899763af26d795302e115dc21")
+        .expect("hex");
+
+    // the compressed encoding: `y`, little-endian, with the sign of `x` in the top bit
+    y.reverse();
+    y[31] |= (x[31] & 1) << 7;
+    assert_eq!(p.to_bytes().as_ref(), y);
+}
+
+/// `edwards25519_XMD:SHA-512_ELL2_RO_` of RFC 9380: the curve library lacks it.
+mod edwards25519 {
+    use elliptic_curve::bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+    use elliptic_curve::bigint::{Encoding, NonZero, U256, U384};
+    use elliptic_curve::hash2curve::{ExpandMsg, ExpandMsgXmd, Expander};
+    use group::GroupEncoding;
+    use sha2::Sha512;
+
+    type Fe = DynResidue<4>;
+    type G = curve25519::edwards::EdwardsPoint;
+
+    /// `p = 2^255 - 19`
+    const P: U256 =
+        U256::from_be_hex("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed");
+
+    /// `J` of curve25519: `v^2 = u^3 + J * u^2 + u`
+    const J: u64 = 486662;
+
+    pub fn hash_to_curve(dst: &[u8]) -> G {
+        let mut uniform_bytes = [0u8; 96];
+        <ExpandMsgXmd<Sha512> as ExpandMsg>::expand_message(&[&[]], &[dst], 96)
+            .expect("expand_message")
+            .fill_bytes(&mut uniform_bytes);
+        let (u0, u1) = uniform_bytes.split_at(48);
+
+        (map_to_curve(hash_to_field(u0)) + map_to_curve(hash_to_field(u1))).mul_by_cofactor()
+    }
+
+    fn hash_to_field(bytes: &[u8]) -> Fe {
+        let p = NonZero::new(P.resize::<{ U384::LIMBS }>()).expect("p is not zero");
+        fe(U384::from_be_slice(bytes).rem(&p).resize())
+    }
+
+    /// Elligator 2 to curve25519, then the rational map to edwards25519.
+    fn map_to_curve(u: Fe) -> G {
+        let zero = small(0);
+        let one = small(1);
+        let j = small(J);
+
+        let x1 = -j * inv0(one + small(2) * u.square());
+        let x1 = if x1 == zero { -j } else { x1 };
+        let gx = |x: Fe| x.square() * x + j * x.square() + x;
+        let x2 = -x1 - j;
+        let (s, t) = match sqrt(gx(x1)) {
+            Some(y) => (x1, if sgn0(y) { y } else { -y }),
+            None => {
+                let y = sqrt(gx(x2)).expect("either gx1 or gx2 is square");
+                (x2, if sgn0(y) { -y } else { y })
+            },
+        };
+
+        let c1 = sqrt(-small(J + 2)).expect("-486664 is square");
+        let c1 = if sgn0(c1) { -c1 } else { c1 };
+        let (v, w) = if t * (s + one) == zero {
+            (zero, one)
+        } else {
+            (c1 * s * inv0(t), (s - one) * inv0(s + one))
+        };
+
+        let mut repr = w.retrieve().to_le_bytes();
+        repr[31] |= (sgn0(v) as u8) << 7;
+        Option::<G>::from(G::from_bytes(&repr)).expect("on the curve")
+    }
+
+    fn fe(n: U256) -> Fe {
+        DynResidue::new(&n, DynResidueParams::new(&P))
+    }
+
+    fn small(n: u64) -> Fe {
+        fe(U256::from_u64(n))
+    }
+
+    fn inv0(a: Fe) -> Fe {
+        a.pow(&P.wrapping_sub(&U256::from_u8(2)))
+    }
+
+    fn sgn0(a: Fe) -> bool {
+        a.retrieve().to_le_bytes()[0] & 1 == 1
+    }
+
+    /// `p = 5 (mod 8)`: `a^((p + 3) / 8)` is a square root of either `a` or `-a`.
+    fn sqrt(a: Fe) -> Option<Fe> {
+        let candidate = a.pow(&P.wrapping_add(&U256::from_u8(3)).shr_vartime(3));
+        let sqrt_m1 = small(2).pow(&P.wrapping_sub(&U256::ONE).shr_vartime(2));
+        [candidate, candidate * sqrt_m1].into_iter().find(|root| root.square() == a)
+    }
+}